@@ -0,0 +1,111 @@
+use clap::Args;
+use dzip_core::format::Chunk;
+use dzip_core::reader::DzipReader;
+use dzip_core::{CompressionMethod, DzipError, Result, compress_data};
+use log::{info, warn};
+use serde::Serialize;
+use std::io::Cursor;
+use std::time::Instant;
+
+#[derive(Args, Debug, Clone)]
+pub struct BenchOptions {
+    /// Comma-separated compression methods to measure
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "zlib,gzip,bzip,lzma,xz,brotli"
+    )]
+    pub methods: Vec<CompressionMethod>,
+
+    /// Print the results as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Throughput and ratio of one method on the input.
+#[derive(Debug, Serialize)]
+struct BenchRow {
+    method: String,
+    input_bytes: u64,
+    output_bytes: u64,
+    ratio: f64,
+    compress_mb_s: f64,
+    decompress_mb_s: f64,
+}
+
+/// Megabytes (10^6 bytes) per second.
+fn throughput(bytes: usize, secs: f64) -> f64 {
+    bytes as f64 / 1_000_000.0 / secs.max(f64::EPSILON)
+}
+
+/// Compresses `input_path` in memory with each method, decodes it again through
+/// the regular chunk reader, and prints the results sorted by ratio (best first).
+pub fn bench_codecs(input_path: &str, options: &BenchOptions) -> Result<()> {
+    let data = std::fs::read(input_path)?;
+    info!("Benchmarking {} bytes from {}", data.len(), input_path);
+
+    let mut rows = Vec::with_capacity(options.methods.len());
+    for &method in &options.methods {
+        if method == CompressionMethod::Zero {
+            warn!("Skipping Zero, it discards the input");
+            continue;
+        }
+
+        let start = Instant::now();
+        let (flags, compressed) = compress_data(&data, method)?;
+        let compress_secs = start.elapsed().as_secs_f64();
+        if CompressionMethod::from_flags(flags) != Some(method) {
+            warn!("Skipping {:?}, it has no encoder", method);
+            continue;
+        }
+
+        let chunk = Chunk {
+            offset: 0,
+            compressed_length: compressed.len() as u32,
+            decompressed_length: data.len() as u32,
+            flags,
+            file: 0,
+        };
+        let start = Instant::now();
+        let decompressed = DzipReader::new(Cursor::new(&compressed)).read_chunk_data(&chunk)?;
+        let decompress_secs = start.elapsed().as_secs_f64();
+        if decompressed != data {
+            return Err(DzipError::Generic(format!(
+                "{:?} did not round-trip the input",
+                method
+            )));
+        }
+
+        rows.push(BenchRow {
+            method: format!("{:?}", method),
+            input_bytes: data.len() as u64,
+            output_bytes: compressed.len() as u64,
+            ratio: compressed.len() as f64 / data.len().max(1) as f64,
+            compress_mb_s: throughput(data.len(), compress_secs),
+            decompress_mb_s: throughput(data.len(), decompress_secs),
+        });
+    }
+    rows.sort_by(|a, b| a.ratio.total_cmp(&b.ratio));
+
+    if options.json {
+        let json = serde_json::to_string_pretty(&rows).map_err(std::io::Error::other)?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    println!(
+        "{:<8} | {:<10} | {:<7} | {:<14} | Decompress MB/s",
+        "Method", "Packed", "Ratio", "Compress MB/s"
+    );
+    println!(
+        "{:-<8}-+-{:-<10}-+-{:-<7}-+-{:-<14}-+-{:-<15}",
+        "", "", "", "", ""
+    );
+    for row in rows {
+        println!(
+            "{:<8} | {:<10} | {:<7.3} | {:<14.1} | {:.1}",
+            row.method, row.output_bytes, row.ratio, row.compress_mb_s, row.decompress_mb_s
+        );
+    }
+    Ok(())
+}
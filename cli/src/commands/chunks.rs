@@ -0,0 +1,230 @@
+use clap::Args;
+use dzip_core::format::{HEADER_CRC_TRAILER_SIZE, flag_names};
+use dzip_core::reader::{DzipReader, correct_chunk_sizes};
+use dzip_core::{DzipError, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct ChunksOptions {
+    /// Print the chunk table as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// Order the rows by path, offset or compressed size instead of by id
+    #[arg(long, value_enum)]
+    pub sort: Option<super::SortKey>,
+
+    /// Instead of the chunk table, list each volume with the files stored in it
+    #[arg(long, conflicts_with = "sort")]
+    pub list_volumes: bool,
+
+    /// Print only the first N rows, taken after --sort
+    #[arg(long, value_name = "N")]
+    pub limit: Option<usize>,
+
+    /// Offset of the archive in the input, for a self-extracting stub the
+    /// header cannot be found behind automatically
+    #[arg(long, value_name = "BYTES", value_parser = super::parse_size)]
+    pub archive_offset: Option<u64>,
+}
+
+/// A volume and the files with at least one chunk in it.
+#[derive(Debug, Serialize)]
+struct VolumeRow {
+    volume: u16,
+    name: String,
+    /// Compressed bytes of the chunks stored in this volume
+    packed: u64,
+    chunks: usize,
+    files: BTreeSet<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ChunkRow {
+    id: usize,
+    volume: u16,
+    offset: u32,
+    compressed_length: u32,
+    decompressed_length: u32,
+    flags: u16,
+    flag_names: Vec<String>,
+}
+
+/// Prints the chunk table of an archive without decompressing anything.
+///
+/// Compressed lengths are shown after `correct_chunk_sizes`, i.e. as used for extraction.
+pub fn list_chunks(input_path: &str, options: &ChunksOptions) -> Result<()> {
+    let (file, base) = super::open_archive(input_path, options.archive_offset)?;
+    let mut reader = DzipReader::new(file);
+    let header = reader.read_header()?;
+    // A corrupt header is reported but still listed, since that helps diagnose it
+    let trailer_size = match reader.verify_header_crc() {
+        Ok(header_crc) => header_crc.trailer_size(),
+        Err(e @ DzipError::HeaderCorrupt { .. }) => {
+            log::warn!("{}", e);
+            HEADER_CRC_TRAILER_SIZE
+        }
+        Err(e) => return Err(e),
+    };
+    // `--sort path` orders chunks by the first file that uses them
+    let owners = if options.sort == Some(super::SortKey::Path) {
+        let mut owners = vec![None; header.chunks.len()];
+        for (i, (_, chunk_ids)) in header.map.iter().enumerate() {
+            for &cid in chunk_ids {
+                if let Some(owner) = owners.get_mut(cid as usize).filter(|o| o.is_none()) {
+                    *owner = Some(header.file_path(i)?);
+                }
+            }
+        }
+        Some(owners)
+    } else {
+        None
+    };
+    // Paths of the files using each chunk, for --list-volumes
+    let mut chunk_files = vec![Vec::new(); header.chunks.len()];
+    if options.list_volumes {
+        for (i, (_, chunk_ids)) in header.map.iter().enumerate() {
+            for &cid in chunk_ids {
+                if let Some(files) = chunk_files.get_mut(cid as usize) {
+                    files.push(header.file_path(i)?);
+                }
+            }
+        }
+    }
+    let (volume_files, mut chunks) = (header.volume_files, header.chunks);
+
+    let input_base_dir = Path::new(input_path)
+        .parent()
+        .unwrap_or_else(|| Path::new("."));
+    let mut file_sizes = HashMap::new();
+    let archive_len = std::fs::metadata(input_path)?.len().saturating_sub(base);
+    file_sizes.insert(0u16, archive_len.saturating_sub(trailer_size));
+    for (i, name) in volume_files.iter().enumerate() {
+        if let Ok(meta) = std::fs::metadata(input_base_dir.join(name)) {
+            file_sizes.insert((i + 1) as u16, meta.len());
+        }
+    }
+    correct_chunk_sizes(&mut chunks, &file_sizes);
+
+    if options.list_volumes {
+        // Every listed volume appears, even if no chunk lives in it
+        let main_name = Path::new(input_path).file_name().map_or_else(
+            || input_path.to_string(),
+            |n| n.to_string_lossy().to_string(),
+        );
+        let mut volumes: BTreeMap<u16, VolumeRow> = std::iter::once(main_name)
+            .chain(volume_files.iter().cloned())
+            .enumerate()
+            .map(|(i, name)| {
+                let row = VolumeRow {
+                    volume: i as u16,
+                    name,
+                    packed: 0,
+                    chunks: 0,
+                    files: BTreeSet::new(),
+                };
+                (i as u16, row)
+            })
+            .collect();
+        for (chunk, files) in chunks.iter().zip(chunk_files) {
+            let row = volumes.entry(chunk.file).or_insert_with(|| VolumeRow {
+                volume: chunk.file,
+                name: "(not in volume list)".to_string(),
+                packed: 0,
+                chunks: 0,
+                files: BTreeSet::new(),
+            });
+            row.packed += chunk.compressed_length as u64;
+            row.chunks += 1;
+            row.files.extend(files);
+        }
+        let volumes: Vec<VolumeRow> = volumes
+            .into_values()
+            .take(options.limit.unwrap_or(usize::MAX))
+            .collect();
+
+        if options.json {
+            let json = serde_json::to_string_pretty(&volumes).map_err(std::io::Error::other)?;
+            println!("{}", json);
+            return Ok(());
+        }
+        for row in volumes {
+            println!(
+                "Volume {}: {} ({} bytes in {} chunks, {} files)",
+                row.volume,
+                row.name,
+                row.packed,
+                row.chunks,
+                row.files.len()
+            );
+            for file in &row.files {
+                println!("  {}", file);
+            }
+        }
+        return Ok(());
+    }
+
+    let mut rows: Vec<ChunkRow> = chunks
+        .iter()
+        .enumerate()
+        .map(|(id, chunk)| ChunkRow {
+            id,
+            volume: chunk.file,
+            offset: chunk.offset,
+            compressed_length: chunk.compressed_length,
+            decompressed_length: chunk.decompressed_length,
+            flags: chunk.flags,
+            flag_names: flag_names(chunk.flags),
+        })
+        .collect();
+
+    match options.sort {
+        None => {}
+        // Chunks no file uses sort last
+        Some(super::SortKey::Path) => {
+            let owners = owners.unwrap_or_default();
+            rows.sort_by_key(|row| {
+                let owner = owners.get(row.id).cloned().flatten();
+                (owner.is_none(), owner)
+            })
+        }
+        Some(super::SortKey::Offset) => rows.sort_by_key(|row| (row.volume, row.offset)),
+        Some(super::SortKey::Size) => {
+            rows.sort_by_key(|row| std::cmp::Reverse(row.compressed_length))
+        }
+    }
+
+    if let Some(limit) = options.limit {
+        rows.truncate(limit);
+    }
+
+    if options.json {
+        let json = serde_json::to_string_pretty(&rows).map_err(std::io::Error::other)?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    println!(
+        "{:<5} | {:<6} | {:<10} | {:<10} | {:<10} | Flags",
+        "Id", "Volume", "Offset", "Packed", "Size"
+    );
+    println!(
+        "{:-<5}-+-{:-<6}-+-{:-<10}-+-{:-<10}-+-{:-<10}-+-{:-<20}",
+        "", "", "", "", "", ""
+    );
+    for row in rows {
+        println!(
+            "{:<5} | {:<6} | {:<10} | {:<10} | {:<10} | {:#06x} {}",
+            row.id,
+            row.volume,
+            row.offset,
+            row.compressed_length,
+            row.decompressed_length,
+            row.flags,
+            row.flag_names.join("|")
+        );
+    }
+    Ok(())
+}
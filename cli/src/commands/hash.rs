@@ -0,0 +1,84 @@
+use clap::{Args, ValueEnum};
+use dzip_core::reader::DzipReader;
+use dzip_core::{DzipError, Result};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use xxhash_rust::xxh3::Xxh3;
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgo {
+    /// SHA-256, for manifests that need a cryptographic hash
+    Sha256,
+    /// 64-bit XXH3, much faster but not collision resistant
+    Xxh3,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct HashOptions {
+    /// Hash algorithm
+    #[arg(long, value_enum, default_value_t = HashAlgo::Sha256)]
+    pub algo: HashAlgo,
+
+    /// Also hash every auxiliary volume listed in the header
+    #[arg(long)]
+    pub volumes: bool,
+}
+
+/// Hashes everything `file` yields from its current position, in fixed-size blocks.
+fn hash_file(file: &mut File, algo: HashAlgo) -> Result<String> {
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut sha256 = Sha256::new();
+    let mut xxh3 = Xxh3::new();
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        match algo {
+            HashAlgo::Sha256 => sha256.update(&buf[..n]),
+            HashAlgo::Xxh3 => xxh3.update(&buf[..n]),
+        }
+    }
+    Ok(match algo {
+        HashAlgo::Sha256 => format!("{:x}", sha256.finalize()),
+        HashAlgo::Xxh3 => format!("{:016x}", xxh3.digest()),
+    })
+}
+
+/// Prints the hash of the archive's bytes, and with `--volumes` of each auxiliary
+/// volume, one `<digest>  <path>` line per file (the `sha256sum` layout).
+pub fn hash_archive(input_path: &str, options: &HashOptions) -> Result<()> {
+    let mut file = File::open(input_path)?;
+    let volume_files = if options.volumes {
+        // Read the volume list through the same handle, then rewind it for hashing
+        let header = DzipReader::new(&mut file).read_header()?;
+        header.volume_files
+    } else {
+        Vec::new()
+    };
+
+    file.seek(SeekFrom::Start(0))?;
+    println!("{}  {}", hash_file(&mut file, options.algo)?, input_path);
+
+    let base_dir = Path::new(input_path)
+        .parent()
+        .unwrap_or_else(|| Path::new("."));
+    for (i, name) in volume_files.iter().enumerate() {
+        let path = base_dir.join(name);
+        let mut volume = File::open(&path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => DzipError::MissingVolume {
+                volume: (i + 1) as u16,
+                name: name.clone(),
+            },
+            _ => DzipError::Io(e),
+        })?;
+        println!(
+            "{}  {}",
+            hash_file(&mut volume, options.algo)?,
+            path.display()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use clap::Args;
+use dzip_core::format::ArchiveHeader;
+use dzip_core::reader::DzipReader;
+use dzip_core::{DzipError, Endianness, Result};
+use serde::Serialize;
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct MetaOptions {
+    /// Print the header as JSON instead of Rust debug notation
+    #[arg(long)]
+    pub json: bool,
+
+    /// Character set of the file and directory names in the archive
+    /// (e.g. shift_jis, latin1); UTF-8 if unset
+    #[arg(long, value_name = "CHARSET", value_parser = super::parse_encoding)]
+    pub encoding: Option<&'static dzip_core::reader::Encoding>,
+
+    /// Offset of the archive in the input, for a self-extracting stub the
+    /// header cannot be found behind automatically
+    #[arg(long, value_name = "BYTES", value_parser = super::parse_size)]
+    pub archive_offset: Option<u64>,
+}
+
+/// Everything read from the header of volume 0, as stored.
+#[derive(Debug, Serialize)]
+struct ArchiveMeta {
+    /// Where the archive starts in the file (after a self-extracting stub)
+    archive_offset: u64,
+    endianness: Endianness,
+    /// CRC from the header CRC trailer, if present and valid
+    header_crc: Option<u32>,
+    #[serde(flatten)]
+    header: ArchiveHeader,
+}
+
+/// Dumps the parsed header of an archive: archive settings, string table,
+/// file to chunk map, chunk table, volume names and range settings.
+///
+/// Unlike `chunks`, nothing is corrected or decoded: chunk lengths and flags
+/// are printed as stored.
+pub fn dump_meta(input_path: &str, options: &MetaOptions) -> Result<()> {
+    let (file, base) = super::open_archive(input_path, options.archive_offset)?;
+    let mut reader = DzipReader::new(file);
+    if let Some(encoding) = options.encoding {
+        reader.set_encoding(encoding);
+    }
+    let header = reader.read_header()?;
+    // A corrupt header is still dumped, since that helps diagnose it
+    let header_crc = match reader.verify_header_crc() {
+        Ok(dzip_core::reader::HeaderCrc::Valid(crc)) => Some(crc),
+        Ok(dzip_core::reader::HeaderCrc::Absent) => None,
+        Err(e @ DzipError::HeaderCorrupt { .. }) => {
+            log::warn!("{}", e);
+            None
+        }
+        Err(e) => return Err(e),
+    };
+
+    let meta = ArchiveMeta {
+        archive_offset: base,
+        endianness: reader.endianness(),
+        header_crc,
+        header,
+    };
+    if options.json {
+        let json = serde_json::to_string_pretty(&meta).map_err(std::io::Error::other)?;
+        println!("{}", json);
+    } else {
+        println!("{:#?}", meta);
+    }
+    Ok(())
+}
@@ -1,3 +1,130 @@
+pub mod bench;
+pub mod chunks;
+pub mod hash;
+pub mod meta;
 pub mod pack;
+pub mod rename;
+pub mod repack;
+pub mod repair;
+pub mod selftest;
+pub mod tree;
 pub mod unpack;
 pub mod verify;
+
+use clap::ValueEnum;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+/// Row order for the `verify` and `chunks` tables. Rows keep their index
+/// column, and ties stay in index order.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// By archive path
+    Path,
+    /// By volume, then offset of the (first) chunk
+    Offset,
+    /// By compressed size, largest first
+    Size,
+}
+
+/// When the progress bars are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressMode {
+    /// Only when stderr is a terminal, so logs and pipes get no redraw noise
+    Auto,
+    /// Also when stderr is redirected (`--progress`)
+    Always,
+    /// Never (`--no-progress`)
+    Never,
+}
+
+static PROGRESS_MODE: OnceLock<ProgressMode> = OnceLock::new();
+
+/// Sets the progress mode for the rest of the run; `Auto` if never called.
+pub fn set_progress_mode(mode: ProgressMode) {
+    let _ = PROGRESS_MODE.set(mode);
+}
+
+/// Creates the progress bar shared by the long-running commands.
+///
+/// The bar is hidden when info logging is disabled (e.g. with `--quiet`), and
+/// by default when stderr is not a terminal (see `ProgressMode`).
+pub fn progress_bar(len: u64) -> ProgressBar {
+    styled_progress_bar(
+        len,
+        "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}",
+    )
+}
+
+/// Like `progress_bar`, for progress measured in bytes.
+pub fn byte_progress_bar(len: u64) -> ProgressBar {
+    styled_progress_bar(
+        len,
+        "[{elapsed_precise}] {bar:40.cyan/blue} {bytes}/{total_bytes} {msg}",
+    )
+}
+
+fn styled_progress_bar(len: u64, template: &str) -> ProgressBar {
+    if log::max_level() < log::LevelFilter::Info {
+        return ProgressBar::hidden();
+    }
+    let pb = match PROGRESS_MODE.get().copied().unwrap_or(ProgressMode::Auto) {
+        ProgressMode::Auto if std::io::stderr().is_terminal() => ProgressBar::new(len),
+        ProgressMode::Auto | ProgressMode::Never => return ProgressBar::hidden(),
+        // The default target stays silent off a terminal, so draw to stderr directly
+        ProgressMode::Always => ProgressBar::with_draw_target(
+            Some(len),
+            ProgressDrawTarget::term_like(Box::new(console::Term::stderr())),
+        ),
+    };
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(template)
+            .unwrap()
+            .progress_chars("=>-"),
+    );
+    pb
+}
+
+/// Opens the main volume of an archive, which may follow a self-extracting stub.
+///
+/// The archive starts at `archive_offset` if given, and otherwise where
+/// `locate_archive` finds it. Returns the file, positioned so that offset 0 is
+/// the start of the archive, and that start in the file.
+pub fn open_archive(
+    path: &str,
+    archive_offset: Option<u64>,
+) -> dzip_core::Result<(dzip_core::source::OffsetReader<std::fs::File>, u64)> {
+    let mut file = std::fs::File::open(path)?;
+    let base = match archive_offset {
+        Some(offset) => offset,
+        None => dzip_core::reader::locate_archive(&mut file)?,
+    };
+    if base > 0 {
+        log::info!("Archive starts at offset {} of {}", base, path);
+    }
+    Ok((dzip_core::source::OffsetReader::new(file, base)?, base))
+}
+
+/// Parses an `--encoding` label such as `shift_jis` or `latin1`.
+pub fn parse_encoding(
+    label: &str,
+) -> std::result::Result<&'static dzip_core::reader::Encoding, String> {
+    dzip_core::reader::string_encoding(label).map_err(|e| e.to_string())
+}
+
+/// Parses a byte count such as `65536`, `64K`, `700M` or `2G` (binary multiples).
+pub fn parse_size(value: &str) -> std::result::Result<u64, String> {
+    let (digits, multiplier) = match value.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&value[..i], 1 << 10),
+        Some((i, 'M' | 'm')) => (&value[..i], 1 << 20),
+        Some((i, 'G' | 'g')) => (&value[..i], 1 << 30),
+        _ => (value, 1),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid size '{}'", value))
+}
@@ -1,27 +1,755 @@
 use crate::config;
-use dzip_core::format::{ArchiveSettings, CHUNK_DZ, Chunk, ChunkSettings, RangeSettings};
-use dzip_core::{Result, compress_data};
-use indicatif::{ProgressBar, ProgressStyle};
-use log::{debug, info};
+use clap::{Args, ValueEnum};
+use dzip_core::format::{
+    ArchiveHeader, ArchiveSettings, CHUNK_COPYCOMP, CHUNK_DZ, Chunk, ChunkSettings, DTRZ_MAGIC,
+    HEADER_CRC_TRAILER_SIZE, RangeSettings,
+};
+use dzip_core::{
+    ArchivePlan, BrotliOptions, CompressionMethod, LzmaOptions, Result, compress_data,
+    compress_stream, compute_header_size,
+};
+use log::{debug, info, warn};
 use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
 use std::io::{Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
 
-pub fn pack_archive(input_path: &str, output_dir: &str) -> Result<()> {
-    let config_path = std::path::Path::new(input_path);
-    info!("Parsing config file: {}", config_path.display());
-    let mut config = config::parse_config(config_path)
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+#[derive(Args, Debug, Clone, Default)]
+pub struct PackOptions {
+    /// Print a summary of sizes, compression ratio and timings after packing
+    #[arg(long)]
+    pub stats: bool,
 
-    // If base_dir is "." (default), make it relative to the config file's directory
+    /// Print the pack summary as JSON on stdout
+    #[arg(long)]
+    pub stats_json: bool,
+
+    /// Split the archive into volumes of at most this many bytes (e.g. `700M`),
+    /// named after the main volume (`game.001`, `game.002`, ...). Replaces the
+    /// auxiliary volumes and per-file volume indices of the config.
+    #[arg(long, value_name = "BYTES", value_parser = parse_split_size)]
+    pub split_size: Option<u64>,
+
+    /// Append a CRC of the header to volume 0 so readers can detect a corrupt
+    /// chunk table
+    #[arg(long)]
+    pub header_crc: bool,
+
+    /// Resolve source files against this directory instead of the config's
+    /// `base_dir` (or the config file's directory)
+    #[arg(long, value_name = "DIR")]
+    pub base_dir: Option<std::path::PathBuf>,
+
+    /// Treat directories differing only in case (`Textures`, `textures`) as one,
+    /// keeping the first-seen spelling
+    #[arg(long)]
+    pub merge_case: bool,
+
+    /// Compress each file straight from disk into its volume instead of reading
+    /// it into memory first. Bounds memory use for large files, but compresses
+    /// one file at a time.
+    #[arg(long, conflicts_with = "split_size")]
+    pub streaming: bool,
+
+    /// Compress on at most this many threads (all cores by default); writing
+    /// the volumes is sequential either way
+    #[arg(long, value_name = "N", conflicts_with = "streaming")]
+    pub compression_threads: Option<std::num::NonZeroUsize>,
+
+    /// File name of the main volume, instead of the first entry of the config's
+    /// `archives`. Auxiliary volume names are unchanged.
+    #[arg(long, value_name = "NAME", value_parser = parse_output_name)]
+    pub output_name: Option<String>,
+
+    /// Pack the files listed in this file (one path per line, relative to the
+    /// list's directory or --base-dir; `#` starts a comment) instead of a config
+    #[arg(long, value_name = "PATH")]
+    pub file_list: Option<std::path::PathBuf>,
+
+    /// Compression method for the files of --file-list (Copy if unset)
+    #[arg(long, requires = "file_list")]
+    pub method: Option<CompressionMethod>,
+
+    /// Hex key to XOR the compressed chunk data with, for games that expect
+    /// obfuscated archives; overrides `xor_key` in the config's options
+    #[arg(long, value_name = "HEX")]
+    pub xor_key: Option<dzip_core::XorKey>,
+
+    /// Pack each of these configs into the output directory in parallel,
+    /// continuing past failures and summarizing the results at the end
+    #[arg(
+        long,
+        value_name = "CONFIG",
+        num_args = 1..,
+        conflicts_with_all = ["file_list", "output_name"]
+    )]
+    pub batch: Vec<std::path::PathBuf>,
+
+    /// Store a file uncompressed (Copy) unless compressing it saves at least
+    /// this fraction of its size, e.g. 0.05 for 5%; 0 only stores files that
+    /// compression would not shrink
+    #[arg(long, value_name = "FRACTION", value_parser = parse_min_ratio, conflicts_with = "streaming")]
+    pub min_ratio: Option<f64>,
+
+    /// Write the `directory` strings of the config verbatim, including
+    /// separators at their end (`textures\`), which are dropped by default
+    #[arg(long)]
+    pub keep_trailing_separators: bool,
+
+    /// Compress every file twice and fail if the two outputs differ, catching
+    /// encoders that are not deterministic within this build
+    #[arg(long, conflicts_with = "streaming")]
+    pub reproducible: bool,
+
+    /// Where chunk ids come from: numbered in pack order, or each file's
+    /// `chunk_id` in the config
+    #[arg(long, value_enum, default_value_t = ChunkIdPolicy::Auto)]
+    pub chunk_ids: ChunkIdPolicy,
+
+    /// Pack the files that can be read and compressed, skipping the others,
+    /// instead of stopping at the first failure; still exits with an error
+    /// listing the skipped files
+    #[arg(long, alias = "skip-missing", conflicts_with = "streaming")]
+    pub keep_going: bool,
+}
+
+/// How `pack` assigns chunk ids.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChunkIdPolicy {
+    /// File `i`, after the `priority` sort, gets chunk id `i`
+    #[default]
+    Auto,
+    /// Each file gets the `chunk_id` set in the config; the ids must be
+    /// unique and run from 0 up to the file count minus one
+    Config,
+}
+
+fn parse_split_size(value: &str) -> std::result::Result<u64, String> {
+    match super::parse_size(value)? {
+        0 => Err("split size must be greater than zero".to_string()),
+        size => Ok(size),
+    }
+}
+
+fn parse_min_ratio(value: &str) -> std::result::Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(ratio) if (0.0..1.0).contains(&ratio) => Ok(ratio),
+        _ => Err(format!("'{}' is not a fraction from 0 up to 1", value)),
+    }
+}
+
+/// Whether compressing `original_len` bytes to `compressed_len` saves less
+/// than `min_ratio` of the original, so the file is better stored.
+fn below_min_ratio(original_len: usize, compressed_len: usize, min_ratio: f64) -> bool {
+    compressed_len as f64 >= original_len as f64 * (1.0 - min_ratio)
+}
+
+fn parse_output_name(value: &str) -> std::result::Result<String, String> {
+    if value.is_empty() || value == "." || value == ".." || value.contains(['/', '\\']) {
+        return Err(format!(
+            "'{}' is not a file name (use -o to choose the output directory)",
+            value
+        ));
+    }
+    Ok(value.to_string())
+}
+
+/// Size totals for a group of packed files.
+#[derive(Debug, Default, Serialize)]
+pub struct SizeStats {
+    pub files: usize,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+}
+
+impl SizeStats {
+    fn add(&mut self, input_bytes: u64, output_bytes: u64) {
+        self.files += 1;
+        self.input_bytes += input_bytes;
+        self.output_bytes += output_bytes;
+    }
+
+    /// Packed size as a fraction of the input size.
+    fn ratio(&self) -> f64 {
+        if self.input_bytes == 0 {
+            1.0
+        } else {
+            self.output_bytes as f64 / self.input_bytes as f64
+        }
+    }
+}
+
+/// Summary of a pack run. Output sizes count chunk data only, not the header.
+#[derive(Debug, Default, Serialize)]
+pub struct PackStats {
+    #[serde(flatten)]
+    pub total: SizeStats,
+    pub ratio: f64,
+    /// Totals per codec actually used (after any fallback to Copy)
+    pub codecs: BTreeMap<String, SizeStats>,
+    pub compress_secs: f64,
+    pub write_secs: f64,
+}
+
+impl PackStats {
+    fn record(&mut self, flags: u16, input_bytes: u64, output_bytes: u64) {
+        let codec = CompressionMethod::from_flags(flags)
+            .map_or_else(|| format!("{:#x}", flags), |m| format!("{:?}", m));
+        self.total.add(input_bytes, output_bytes);
+        self.codecs
+            .entry(codec)
+            .or_default()
+            .add(input_bytes, output_bytes);
+    }
+
+    fn print(&self) {
+        println!(
+            "Packed {} files: {} -> {} bytes ({:.1}%)",
+            self.total.files,
+            self.total.input_bytes,
+            self.total.output_bytes,
+            self.ratio * 100.0
+        );
+        println!(
+            "{:<8} | {:<6} | {:<12} | {:<12} | Ratio",
+            "Codec", "Files", "Input", "Output"
+        );
+        println!(
+            "{:-<8}-+-{:-<6}-+-{:-<12}-+-{:-<12}-+-{:-<7}",
+            "", "", "", "", ""
+        );
+        for (codec, stats) in &self.codecs {
+            println!(
+                "{:<8} | {:<6} | {:<12} | {:<12} | {:.1}%",
+                codec,
+                stats.files,
+                stats.input_bytes,
+                stats.output_bytes,
+                stats.ratio() * 100.0
+            );
+        }
+        println!(
+            "Compression: {:.3}s, write: {:.3}s",
+            self.compress_secs, self.write_secs
+        );
+    }
+}
+
+/// Packs the files of the config at `input_path`, or of `--file-list` when given.
+///
+/// Layout: file `i` (after the `priority` sort) gets chunk id `i`, and each
+/// volume holds its chunks in id order, right after the header in volume 0.
+/// With `--chunk-ids config` the data keeps that order, but each file's chunk
+/// record takes the id from its `chunk_id`.
+/// The buffered and `--streaming` paths both follow this order, so they write
+/// identical archives; only the compression itself runs in parallel.
+pub fn pack_archive(
+    input_path: Option<&str>,
+    output_dir: &str,
+    options: &PackOptions,
+) -> Result<()> {
+    let (config_path, config) = match (&options.file_list, input_path) {
+        (Some(_), Some(_)) => {
+            return Err(dzip_core::DzipError::Generic(
+                "A config file cannot be combined with --file-list".to_string(),
+            ));
+        }
+        (Some(list), None) => {
+            info!("Reading file list: {}", list.display());
+            (
+                list.as_path(),
+                config::parse_file_list(list, options.method),
+            )
+        }
+        (None, Some(input)) => {
+            let path = std::path::Path::new(input);
+            info!("Parsing config file: {}", path.display());
+            (path, config::parse_config(path))
+        }
+        (None, None) => {
+            return Err(dzip_core::DzipError::Generic(
+                "Either a config file or --file-list is required".to_string(),
+            ));
+        }
+    };
+    // `{:#}` keeps the context chain, e.g. which file has invalid options
+    let mut config = config
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{:#}", e)))?;
+
+    // Precedence: --base-dir, then the config's base_dir, then the config file's
+    // directory when base_dir is left at "." (the default)
     #[allow(clippy::collapsible_if)]
-    if config.base_dir == std::path::Path::new(".") {
+    if let Some(base_dir) = &options.base_dir {
+        config.base_dir = base_dir.clone();
+    } else if config.base_dir == std::path::Path::new(".") {
         if let Some(parent) = config_path.parent().filter(|p| !p.as_os_str().is_empty()) {
             config.base_dir = parent.to_path_buf();
         }
     }
 
+    if let Some(name) = &options.output_name {
+        if config.archives[1..].contains(name) {
+            return Err(dzip_core::DzipError::Generic(format!(
+                "--output-name '{}' is also the name of an auxiliary volume",
+                name
+            )));
+        }
+        config.archives[0] = name.clone();
+    }
+
+    if config.files.is_empty() {
+        warn!("Config lists no files; writing an archive with an empty file table");
+    }
+
+    let xor_key = options
+        .xor_key
+        .clone()
+        .or_else(|| config.options.as_ref().and_then(|o| o.xor_key.clone()));
+    if xor_key.is_some() {
+        info!("Obfuscating chunk data with the XOR key");
+    }
+
+    // Everything below (string table, chunk ids, data offsets, split volumes)
+    // follows `files`, so sorting here puts high-priority files first. The sort
+    // is stable, which keeps config order when no priorities are set.
+    config
+        .files
+        .sort_by_key(|entry| entry.priority.unwrap_or(0));
+
+    let mut chunk_ids = match options.chunk_ids {
+        ChunkIdPolicy::Auto => {
+            if config.files.iter().any(|entry| entry.chunk_id.is_some()) {
+                warn!("Ignoring the chunk_id of the config's files (see --chunk-ids config)");
+            }
+            None
+        }
+        ChunkIdPolicy::Config => Some(config_chunk_ids(&config.files)?),
+    };
+
+    // One chunk per file, so the file count also bounds the chunk ids; these
+    // hold for the buffered and --streaming paths alike
+    check_count("files", config.files.len())?;
+    check_count("volumes", config.archives.len())?;
+
     std::fs::create_dir_all(output_dir)?;
 
+    // Rebuilt below if --keep-going drops files
+    let mut table = string_table(&config, options)?;
+
+    if config.archives.is_empty() {
+        return Err(
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "No archives specified").into(),
+        );
+    }
+
+    // --- Process Files and Write Chunks ---
+    let mut chunks = Vec::new();
+    let mut chunk_map = Vec::new(); // (dir_id, vec![chunk_id])
+
+    let mut stats = PackStats::default();
+    let mut skipped = Vec::new();
+    let archives;
+    let mut writers;
+    let compress_time;
+    let write_start;
+    if options.streaming {
+        // Each file is compressed straight from disk into its volume, one at a time
+        archives = config.archives.clone();
+        writers = open_volumes(
+            output_dir,
+            &archives,
+            header_size(&table.strings, config.files.len(), &archives[1..]),
+        )?;
+
+        info!("Compressing files into volumes...");
+        let compress_start = Instant::now();
+        let pb = super::progress_bar(config.files.len() as u64);
+        let default_lzma = LzmaOptions::default();
+        let default_brotli = BrotliOptions::default();
+        for (i, entry) in config.files.iter().enumerate() {
+            let full_path = config.base_dir.join(&entry.path);
+            debug!("Processing file {}: {}", i, full_path.display());
+            pb.set_message(format!("Compressing {}", entry.path.display()));
+
+            let mut input =
+                std::fs::File::open(&full_path).map_err(|e| read_error(&full_path, e))?;
+            let writer = volume_writer(&mut writers, entry.archive_file_index)?;
+            let offset = writer.stream_position()? as u32;
+            let lzma = entry.lzma.as_ref().unwrap_or(&default_lzma);
+            let brotli = entry.brotli.as_ref().unwrap_or(&default_brotli);
+            let (method, wrappers) = config.encoding(entry)?;
+            let mut xor_writer;
+            let writer: &mut dyn Write = match &xor_key {
+                Some(key) => {
+                    xor_writer = key.writer(writer);
+                    &mut xor_writer
+                }
+                None => writer,
+            };
+            let streamed = compress_stream(&mut input, writer, method, lzma, brotli)?;
+            let flags = streamed.flags | wrappers;
+            stats.record(flags, streamed.input_len, streamed.output_len);
+
+            chunk_map.push((table.file_dir_ids[i], vec![chunks.len() as u16]));
+            chunks.push(Chunk {
+                offset,
+                compressed_length: streamed.output_len as u32,
+                decompressed_length: streamed.input_len as u32,
+                flags,
+                file: entry.archive_file_index,
+            });
+            pb.inc(1);
+        }
+        pb.finish_with_message("Compression complete");
+        compress_time = compress_start.elapsed();
+        write_start = Instant::now();
+    } else {
+        info!("Compressing chunks in parallel...");
+        let compress_start = Instant::now();
+        let pb = super::progress_bar(config.files.len() as u64);
+        let stored_files = AtomicUsize::new(0);
+
+        let compress_files = || {
+            config
+                .files
+                .par_iter()
+                .enumerate()
+                .map(|(i, entry)| {
+                    let full_path = config.base_dir.join(&entry.path);
+                    debug!("Processing file {}: {}", i, full_path.display());
+                    pb.set_message(format!("Compressing {}", entry.path.display()));
+
+                    let raw_data =
+                        std::fs::read(&full_path).map_err(|e| read_error(&full_path, e))?;
+                    let original_len = raw_data.len();
+
+                    let (method, wrappers) = config.encoding(entry)?;
+                    let encode = || -> Result<(u16, Vec<u8>)> {
+                        Ok(match (method, &entry.lzma, &entry.brotli) {
+                            (CompressionMethod::Lzma, Some(lzma), _) => (
+                                dzip_core::format::CHUNK_LZMA,
+                                dzip_core::writer::compress_lzma(&raw_data, lzma)?,
+                            ),
+                            (CompressionMethod::Brotli, _, Some(brotli)) => (
+                                dzip_core::format::CHUNK_BROTLI,
+                                dzip_core::writer::compress_brotli(&raw_data, brotli)?,
+                            ),
+                            _ => compress_data(&raw_data, method)?,
+                        })
+                    };
+                    let (flags, mut compressed_data) = encode()?;
+                    let differs = |(again_flags, again): (u16, Vec<u8>)| {
+                        again_flags != flags || again[..] != compressed_data[..]
+                    };
+                    if options.reproducible && differs(encode()?) {
+                        return Err(dzip_core::DzipError::Generic(format!(
+                            "{:?} compressed {} differently on a second run; \
+                             the output is not reproducible",
+                            method,
+                            entry.path.display()
+                        )));
+                    }
+                    let flags = match options.min_ratio {
+                        Some(min_ratio)
+                            if !matches!(
+                                method,
+                                CompressionMethod::Copy | CompressionMethod::Zero
+                            ) && below_min_ratio(
+                                original_len,
+                                compressed_data.len(),
+                                min_ratio,
+                            ) =>
+                        {
+                            debug!(
+                                "Storing {}: {:?} only reached {} of {} bytes",
+                                entry.path.display(),
+                                method,
+                                compressed_data.len(),
+                                original_len
+                            );
+                            stored_files.fetch_add(1, Ordering::Relaxed);
+                            compressed_data = raw_data;
+                            CHUNK_COPYCOMP | wrappers
+                        }
+                        _ => flags | wrappers,
+                    };
+                    if let Some(key) = &xor_key {
+                        key.apply(&mut compressed_data);
+                    }
+
+                    pb.inc(1);
+                    Ok((
+                        entry.archive_file_index,
+                        compressed_data,
+                        original_len,
+                        flags,
+                    ))
+                })
+                .collect::<Vec<Result<_>>>()
+        };
+        // A dedicated pool bounds the CPU-heavy codecs without limiting the
+        // global pool used elsewhere
+        let results = match options.compression_threads {
+            Some(threads) => rayon::ThreadPoolBuilder::new()
+                .num_threads(threads.get())
+                .build()
+                .map_err(std::io::Error::other)?
+                .install(compress_files),
+            None => compress_files(),
+        };
+        pb.finish_with_message("Compression complete");
+
+        // With --keep-going, failed files are dropped and the rest packed as usual
+        let mut processed_files: Vec<(u16, Vec<u8>, usize, u16)> =
+            Vec::with_capacity(results.len());
+        let mut kept = Vec::with_capacity(results.len());
+        for (entry, result) in std::mem::take(&mut config.files).into_iter().zip(results) {
+            match result {
+                Ok(processed) => {
+                    processed_files.push(processed);
+                    kept.push(entry);
+                }
+                Err(e) if options.keep_going => {
+                    warn!("Skipping {}: {}", entry.path.display(), e);
+                    skipped.push(entry.path);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        config.files = kept;
+        if !skipped.is_empty() {
+            table = string_table(&config, options)?;
+            // The ids of the skipped files are closed up, keeping the order
+            if let Some(ids) = &mut chunk_ids {
+                let mut order: Vec<usize> = (0..config.files.len()).collect();
+                order.sort_by_key(|&i| config.files[i].chunk_id);
+                *ids = vec![0; order.len()];
+                for (id, i) in order.into_iter().enumerate() {
+                    ids[i] = id as u16;
+                }
+            }
+        }
+        compress_time = compress_start.elapsed();
+        let stored_files = stored_files.into_inner();
+        if stored_files > 0 {
+            info!(
+                "Stored {} file(s) uncompressed that compressed worse than --min-ratio",
+                stored_files
+            );
+        }
+
+        for (_, compressed_data, original_len, flags) in &processed_files {
+            stats.record(*flags, *original_len as u64, compressed_data.len() as u64);
+        }
+
+        // --- Assign Volumes ---
+        archives = match options.split_size {
+            Some(budget) => {
+                if config.archives.len() > 1 {
+                    warn!(
+                        "--split-size replaces the {} auxiliary volume(s) listed in the config",
+                        config.archives.len() - 1
+                    );
+                }
+                let sizes: Vec<u64> = processed_files
+                    .iter()
+                    .map(|(_, data, _, _)| data.len() as u64)
+                    .collect();
+                // The header CRC trailer follows the header in volume 0
+                let crc_size = if options.header_crc {
+                    HEADER_CRC_TRAILER_SIZE
+                } else {
+                    0
+                };
+                let (archives, volumes) = split_volumes(
+                    &config.archives[0],
+                    |aux| header_size(&table.strings, config.files.len(), aux) + crc_size,
+                    &sizes,
+                    budget,
+                )?;
+                for (file, volume) in processed_files.iter_mut().zip(volumes) {
+                    file.0 = volume;
+                }
+                info!(
+                    "Split into {} volume(s) of at most {} bytes",
+                    archives.len(),
+                    budget
+                );
+                archives
+            }
+            None => config.archives.clone(),
+        };
+
+        writers = open_volumes(
+            output_dir,
+            &archives,
+            header_size(&table.strings, config.files.len(), &archives[1..]),
+        )?;
+
+        // Sequential Write Phase: the chunks stay buffered until here, since
+        // the volumes and --min-ratio depend on their compressed sizes
+        info!("Writing compressed chunks to volumes...");
+        write_start = Instant::now();
+        for (i, (archive_id, compressed_data, original_len, flags)) in
+            processed_files.into_iter().enumerate()
+        {
+            let chunk_id = chunks.len() as u16;
+
+            let writer = volume_writer(&mut writers, archive_id)?;
+
+            let offset = writer.stream_position()? as u32;
+            writer.write_all(&compressed_data)?;
+
+            chunks.push(Chunk {
+                offset,
+                compressed_length: compressed_data.len() as u32,
+                decompressed_length: original_len as u32,
+                flags,
+                file: archive_id,
+            });
+
+            chunk_map.push((table.file_dir_ids[i], vec![chunk_id]));
+        }
+    }
+
+    // The data stays in pack order; only the chunk records move to the ids
+    // the config gave them
+    if let Some(ids) = &chunk_ids {
+        let mut records: Vec<(u16, Chunk)> = ids.iter().copied().zip(chunks).collect();
+        records.sort_by_key(|(id, _)| *id);
+        chunks = records.into_iter().map(|(_, chunk)| chunk).collect();
+        for ((_, file_chunks), &id) in chunk_map.iter_mut().zip(ids) {
+            *file_chunks = vec![id];
+        }
+    }
+
+    // The header CRC covers everything up to the end of the chunk table
+    let crc_header_len = header_size(&table.strings, config.files.len(), &[]);
+
+    // --- Write Header ---
+    info!("Writing header to Volume 0...");
+    let main_writer = writers
+        .get_mut(&0)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Volume 0 missing"))?;
+
+    main_writer.seek(SeekFrom::Start(0))?;
+
+    let has_dz = chunks.iter().any(|c| (c.flags & CHUNK_DZ) != 0);
+    let header = ArchiveHeader {
+        settings: ArchiveSettings {
+            header: DTRZ_MAGIC,
+            num_user_files: config.files.len() as u16,
+            num_directories: table.num_directories,
+            version: config.format_version()?.as_u8(),
+        },
+        strings: table.strings,
+        map: chunk_map,
+        chunk_settings: ChunkSettings {
+            num_archive_files: archives.len() as u16,
+            num_chunks: chunks.len() as u16,
+        },
+        chunks,
+        volume_files: archives[1..].to_vec(),
+        range_settings: has_dz.then(RangeSettings::default),
+    };
+    dzip_core::writer::DzipWriter::new(&mut *main_writer).write_header(&header)?;
+
+    if options.header_crc {
+        let main_writer = writers.get_mut(&0).expect("volume 0 is open");
+        let crc = dzip_core::writer::append_header_crc(main_writer, crc_header_len)?;
+        debug!("Header CRC: {:#010x}", crc);
+    }
+
+    let write_time = write_start.elapsed();
+    info!("Pack complete.");
+
+    if options.stats || options.stats_json {
+        stats.ratio = stats.total.ratio();
+        stats.compress_secs = compress_time.as_secs_f64();
+        stats.write_secs = write_time.as_secs_f64();
+        if options.stats_json {
+            let json = serde_json::to_string_pretty(&stats).map_err(std::io::Error::other)?;
+            println!("{}", json);
+        } else {
+            stats.print();
+        }
+    }
+    if !skipped.is_empty() {
+        let paths: Vec<String> = skipped.iter().map(|p| p.display().to_string()).collect();
+        return Err(dzip_core::DzipError::Generic(format!(
+            "Skipped {} file(s) that could not be packed: {}",
+            skipped.len(),
+            paths.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Chunk ids for `--chunk-ids config`: the `chunk_id` of every file, which
+/// must be set and unique and lie below the file count, so that the ids
+/// cover the chunk table without gaps.
+fn config_chunk_ids(files: &[config::FileEntry]) -> Result<Vec<u16>> {
+    let mut owners: Vec<Option<&std::path::Path>> = vec![None; files.len()];
+    for entry in files {
+        let path = &entry.path;
+        let Some(id) = entry.chunk_id else {
+            return Err(dzip_core::DzipError::Generic(format!(
+                "{} has no chunk_id, which --chunk-ids config requires",
+                path.display()
+            )));
+        };
+        match owners.get_mut(id as usize) {
+            None => {
+                return Err(dzip_core::DzipError::Generic(format!(
+                    "chunk_id {} of {} is out of range: with {} files the ids run from 0 to {}",
+                    id,
+                    path.display(),
+                    files.len(),
+                    files.len() - 1
+                )));
+            }
+            Some(Some(other)) => {
+                return Err(dzip_core::DzipError::Generic(format!(
+                    "{} and {} both have chunk_id {}",
+                    other.display(),
+                    path.display(),
+                    id
+                )));
+            }
+            Some(owner) => *owner = Some(path),
+        }
+    }
+    Ok(files.iter().filter_map(|entry| entry.chunk_id).collect())
+}
+
+/// Fails if `count` does not fit the 16-bit field the header stores it in.
+///
+/// Chunk id `0xFFFF` ends a file's chunk list, but with one chunk per file
+/// the highest id is `count - 1`, so `u16::MAX` files still fit.
+fn check_count(what: &str, count: usize) -> Result<()> {
+    if count > u16::MAX as usize {
+        return Err(dzip_core::DzipError::Generic(format!(
+            "{} {} exceed the format's limit of {}; split the files across several archives",
+            count,
+            what,
+            u16::MAX
+        )));
+    }
+    Ok(())
+}
+
+/// String table of an archive, and the directory of each file.
+struct StringTable {
+    /// File names, then the directories (without the implicit root)
+    strings: Vec<String>,
+    file_dir_ids: Vec<u16>,
+    num_directories: u16,
+}
+
+/// Builds the string table for the files of `config`, in their current order.
+fn string_table(config: &config::DzipConfig, options: &PackOptions) -> Result<StringTable> {
     // --- Prepare Metadata ---
     // 1. Strings: User Files + Unique Directories
     // Note: Dzip strings table contains filenames (basename) and directory paths.
@@ -49,245 +777,274 @@ pub fn pack_archive(input_path: &str, output_dir: &str) -> Result<()> {
     }
 
     // Collect Unique Directories and assign IDs
+    // IDs follow first-seen order over `files`, then `empty_dirs`. The map is
+    // only used for lookups and never iterated, so the same config always gives
+    // the same string table and therefore a byte-identical archive.
     let mut directories = Vec::new();
     let mut dir_map = std::collections::HashMap::new(); // path -> dir_id (1-based)
+    // With --merge-case, directories are looked up by their lowercase form
+    let dir_key = |dir: &str| {
+        if options.merge_case {
+            dir.to_lowercase()
+        } else {
+            dir.to_string()
+        }
+    };
+    let mut merged = std::collections::HashSet::new();
 
     // Directory ID 0 is Root.
     // We need to map each file to a dir_id.
     let mut file_dir_ids = Vec::new();
 
     for entry in &config.files {
-        let parent = entry.path.parent().unwrap_or(std::path::Path::new(""));
-        // Force Windows-style backslashes as requested using core utility
-        let parent_str = dzip_core::path::to_archive_format(parent);
+        let parent_str = match &entry.directory {
+            // Preserved from the original archive, separators and all
+            Some(dir) if options.keep_trailing_separators => dir.clone(),
+            // `textures\` and `textures` are one directory
+            Some(dir) => dzip_core::path::trim_trailing_separators(dir).to_string(),
+            None => {
+                let parent = entry.path.parent().unwrap_or(std::path::Path::new(""));
+                // Force Windows-style backslashes as requested using core utility
+                dzip_core::path::to_archive_format(parent)
+            }
+        };
 
         if parent_str.is_empty() || parent_str == "." {
             file_dir_ids.push(0u16);
         } else {
             // Check if known
-            if let Some(&id) = dir_map.get(&parent_str) {
+            if let Some(&id) = dir_map.get(&dir_key(&parent_str)) {
+                let existing = &directories[id as usize - 1];
+                if *existing != parent_str && merged.insert(parent_str.clone()) {
+                    warn!("Merging directory '{}' into '{}'", parent_str, existing);
+                }
                 file_dir_ids.push(id);
             } else {
                 // New directory
                 // Directories list stores paths.
                 directories.push(parent_str.clone());
                 let id = directories.len() as u16; // 1-based
-                dir_map.insert(parent_str, id);
+                dir_map.insert(dir_key(&parent_str), id);
                 file_dir_ids.push(id);
             }
         }
     }
 
-    let num_user_files = file_names.len() as u16;
+    // Directories without files of their own come after the file parents
+    for dir in &config.empty_dirs {
+        let path = dzip_core::path::resolve_relative_path(dir)?;
+        let dir_str = dzip_core::path::to_archive_format(&path);
+        if dir_str.is_empty() {
+            continue;
+        }
+        if let Some(&id) = dir_map.get(&dir_key(&dir_str)) {
+            let existing = &directories[id as usize - 1];
+            if *existing != dir_str && merged.insert(dir_str.clone()) {
+                warn!("Merging directory '{}' into '{}'", dir_str, existing);
+            }
+        } else {
+            directories.push(dir_str.clone());
+            dir_map.insert(dir_key(&dir_str), directories.len() as u16);
+        }
+    }
+
+    check_count("directories (counting the root)", directories.len() + 1)?;
     let num_directories = (directories.len() + 1) as u16; // +1 for Root?
     // Unpacker: `strings_count = num_user_files + num_directories - 1`.
     // So strings count = files + dirs.
     // Strings array = [Files..., Dirs...].
     // Root dir is NOT in strings.
 
-    let mut all_strings = file_names;
-    all_strings.extend(directories);
-
-    // --- Open Volumes ---
-    if config.archives.is_empty() {
-        return Err(
-            std::io::Error::new(std::io::ErrorKind::InvalidInput, "No archives specified").into(),
-        );
-    }
-
-    let mut writers = std::collections::HashMap::new();
-    for (i, name) in config.archives.iter().enumerate() {
-        let path = std::path::Path::new(output_dir).join(name);
-        info!("Opening volume {}: {}", i, path.display());
-        let f = std::fs::File::create(&path)?;
-        writers.insert(i as u16, f);
-    }
-
-    // --- Pre-calculate Header Size (Volume 0) ---
-    // Header (ArchiveSettings) = 4+2+2+1 = 9
-    // Strings = Sum(len+1)
-    // FileMap (ChunkMap) = NumFiles * (2 + NumChunksInFile*2 + 2)
-    // ChunkSettings = 2+2=4
-    // ChunkTable = NumChunks * 16
-    // Auxiliary File List = Sum(len+1) of archives[1..]
-
-    // Assuming 1 chunk per file
-    let num_chunks = num_user_files;
-
-    let mut header_size = 9;
-    for s in &all_strings {
-        header_size += s.len() as u64 + 1;
-    }
-    let file_map_size = (num_user_files as u64) * 6; // DirID(2) + ChunkID(2) + Term(2)
-    header_size += file_map_size;
-
-    header_size += 4; // ChunkSettings
-    let chunk_table_size = (num_chunks as u64) * 16;
-    header_size += chunk_table_size;
+    let mut strings = file_names;
+    strings.extend(directories);
+    Ok(StringTable {
+        strings,
+        file_dir_ids,
+        num_directories,
+    })
+}
 
-    // Add Volume List Size
-    if config.archives.len() > 1 {
-        for name in &config.archives[1..] {
-            header_size += name.len() as u64 + 1;
+/// Packs every config of `--batch` into `output_dir`, continuing past failures.
+///
+/// Configs are packed in parallel on the shared rayon pool. A config whose
+/// volume names are also used by an earlier config fails instead of racing it
+/// for the same files. Prints one line per config and fails if any did.
+pub fn pack_batch(
+    configs: &[std::path::PathBuf],
+    output_dir: &str,
+    options: &PackOptions,
+) -> Result<()> {
+    // Volume name -> first config writing it
+    let mut owners: HashMap<String, usize> = HashMap::new();
+    let mut conflicts: HashMap<usize, String> = HashMap::new();
+    for (i, path) in configs.iter().enumerate() {
+        // Unreadable configs fail below with the full error
+        let Ok(config) = config::parse_config(path) else {
+            continue;
+        };
+        for name in config.archives {
+            if let Some(&owner) = owners.get(&name) {
+                conflicts.entry(i).or_insert_with(|| {
+                    format!("writes {}, as does {}", name, configs[owner].display())
+                });
+            } else {
+                owners.insert(name, i);
+            }
         }
     }
 
-    // Should we add GlobalSettings size? Only if we use DZ compression.
-    // Config options might specify usage. For now assume minimal header.
-    // We will update this offset if needed.
-
-    // Seek Volume 0
-    if let Some(w) = writers.get_mut(&0) {
-        w.seek(SeekFrom::Start(header_size))?;
-    }
-
-    // --- Process Files and Write Chunks ---
-    let mut chunks = Vec::new();
-    let mut chunk_map = Vec::new(); // (dir_id, vec![chunk_id])
-
-    // Parallel Compression Phase
-    info!("Compressing chunks in parallel...");
-    let pb = ProgressBar::new(config.files.len() as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}")
-            .unwrap()
-            .progress_chars("=>-"),
-    );
-
-    let processed_files: Vec<(u16, Vec<u8>, usize, u16)> = config
-        .files
+    let results: Vec<Option<String>> = configs
         .par_iter()
         .enumerate()
-        .map(|(i, entry)| {
-            let full_path = config.base_dir.join(&entry.path);
-            debug!("Processing file {}: {}", i, full_path.display());
-            pb.set_message(format!("Compressing {}", entry.path.display()));
-
-            let raw_data = std::fs::read(&full_path).map_err(|e| {
-                dzip_core::DzipError::Io(std::io::Error::other(format!(
-                    "Failed to read {}: {}",
-                    full_path.display(),
-                    e
-                )))
-            })?;
-            let original_len = raw_data.len();
-
-            let method = entry.compression;
-            let (flags, compressed_data) = compress_data(&raw_data, method)?;
-
-            pb.inc(1);
-            Ok((
-                entry.archive_file_index,
-                compressed_data,
-                original_len,
-                flags,
-            ))
+        .map(|(i, path)| {
+            if let Some(conflict) = conflicts.get(&i) {
+                return Some(conflict.clone());
+            }
+            info!("Packing {}", path.display());
+            pack_archive(Some(&path.to_string_lossy()), output_dir, options)
+                .err()
+                .map(|e| e.to_string())
         })
-        .collect::<Result<Vec<_>>>()?;
-    pb.finish_with_message("Compression complete");
-
-    // Sequential Write Phase
-    info!("Writing compressed chunks to volumes...");
-    for (i, (archive_id, compressed_data, original_len, flags)) in
-        processed_files.into_iter().enumerate()
-    {
-        let chunk_id = chunks.len() as u16;
-
-        let writer = writers.get_mut(&archive_id).ok_or_else(|| {
-            std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                format!("Archive volume {} not found in config", archive_id),
-            )
-        })?;
+        .collect();
 
-        let offset = writer.stream_position()? as u32;
-        writer.write_all(&compressed_data)?;
-
-        chunks.push(Chunk {
-            offset,
-            compressed_length: compressed_data.len() as u32,
-            decompressed_length: original_len as u32,
-            flags,
-            file: archive_id,
-        });
-
-        chunk_map.push((file_dir_ids[i], vec![chunk_id]));
+    let width = configs
+        .iter()
+        .map(|path| path.display().to_string().len())
+        .max()
+        .unwrap_or(0)
+        .max("Config".len());
+    println!("{:<width$} | Result", "Config");
+    println!("{:-<width$}-+-{:-<20}", "", "");
+    for (path, failure) in configs.iter().zip(&results) {
+        match failure {
+            None => println!("{:<width$} | OK", path.display()),
+            Some(reason) => println!("{:<width$} | FAIL: {}", path.display(), reason),
+        }
     }
 
-    // --- Write Header ---
-    info!("Writing header to Volume 0...");
-    let main_writer = writers
-        .get_mut(&0)
-        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Volume 0 missing"))?;
+    let failed = results.iter().filter(|f| f.is_some()).count();
+    if failed > 0 {
+        return Err(dzip_core::DzipError::Generic(format!(
+            "{} of {} configs failed to pack",
+            failed,
+            configs.len()
+        )));
+    }
+    println!("Packed all {} configs.", configs.len());
+    Ok(())
+}
 
-    main_writer.seek(SeekFrom::Start(0))?;
+/// Error for a source file that cannot be read.
+fn read_error(path: &std::path::Path, e: std::io::Error) -> dzip_core::DzipError {
+    dzip_core::DzipError::Io(std::io::Error::other(format!(
+        "Failed to read {}: {}",
+        path.display(),
+        e
+    )))
+}
 
-    // We need DzipWriter
-    struct SimpleWriter<'a, W: Write + Seek>(&'a mut W);
-    impl<'a, W: Write + Seek> Write for SimpleWriter<'a, W> {
-        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-            self.0.write(buf)
-        }
-        fn flush(&mut self) -> std::io::Result<()> {
-            self.0.flush()
-        }
+/// Creates (truncating) every volume, with volume 0 positioned after the
+/// `header_len` bytes reserved for its header.
+fn open_volumes(
+    output_dir: &str,
+    archives: &[String],
+    header_len: u64,
+) -> Result<HashMap<u16, std::fs::File>> {
+    let mut writers = HashMap::new();
+    for (i, name) in archives.iter().enumerate() {
+        let path = std::path::Path::new(output_dir).join(name);
+        info!("Opening volume {}: {}", i, path.display());
+        // Readable too, so the header CRC can be computed from the written header
+        let f = std::fs::File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        writers.insert(i as u16, f);
     }
-    impl<'a, W: Write + Seek> Seek for SimpleWriter<'a, W> {
-        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
-            self.0.seek(pos)
-        }
+    if let Some(w) = writers.get_mut(&0) {
+        w.seek(SeekFrom::Start(header_len))?;
     }
+    Ok(writers)
+}
 
-    let mut dzip_writer = dzip_core::writer::DzipWriter::new(SimpleWriter(main_writer));
-
-    // ... rest of header writing ...
-
-    dzip_writer.write_archive_settings(&ArchiveSettings {
-        header: 0x5A525444, // DTRZ
-        num_user_files,
-        num_directories,
-        version: 0,
-    })?;
-
-    // ...
-
-    dzip_writer.write_strings(&all_strings)?;
-    dzip_writer.write_file_chunk_map(&chunk_map)?;
-
-    // ...
-
-    let num_archive_files = config.archives.len() as u16;
-
-    dzip_writer.write_chunk_settings(&ChunkSettings {
-        num_archive_files,
-        num_chunks: chunks.len() as u16,
-    })?;
+/// The open volume with index `id`.
+fn volume_writer(writers: &mut HashMap<u16, std::fs::File>, id: u16) -> Result<&mut std::fs::File> {
+    writers.get_mut(&id).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("Archive volume {} not found in config", id),
+        )
+        .into()
+    })
+}
 
-    dzip_writer.write_chunks(&chunks)?;
+/// Size of the header at the start of volume 0, with one chunk per file and no
+/// range settings (pack never writes DZ chunks).
+fn header_size(strings: &[String], num_files: usize, aux_volumes: &[String]) -> u64 {
+    compute_header_size(&ArchivePlan {
+        strings,
+        num_files,
+        num_chunks: num_files,
+        volume_files: aux_volumes,
+        range_settings: false,
+    })
+}
 
-    // Write Auxiliary File List
-    if config.archives.len() > 1 {
-        let aux_files = &config.archives[1..];
-        dzip_writer.write_strings(aux_files)?;
-    }
+/// Assigns chunks to volumes for `--split-size`.
+///
+/// Chunks are placed in order, starting a new volume whenever the next one would
+/// push the current volume past `budget`. Volume 0 also holds the header, whose
+/// size depends on the auxiliary volume names, so the assignment is repeated
+/// until the number of volumes is stable. A chunk larger than `budget` gets a
+/// volume of its own. Returns the volume names, starting with `main_name`, and
+/// the volume of each chunk.
+fn split_volumes(
+    main_name: &str,
+    header_size: impl Fn(&[String]) -> u64,
+    chunk_sizes: &[u64],
+    budget: u64,
+) -> Result<(Vec<String>, Vec<u16>)> {
+    let stem = std::path::Path::new(main_name)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let mut aux_volumes: Vec<String> = Vec::new();
+    loop {
+        let mut volumes = Vec::with_capacity(chunk_sizes.len());
+        let mut volume = 0usize;
+        let mut used = header_size(&aux_volumes);
+        for &size in chunk_sizes {
+            if used > 0 && used + size > budget {
+                volume += 1;
+                used = 0;
+            }
+            used += size;
+            volumes.push(volume);
+        }
 
-    let has_dz = chunks.iter().any(|c| (c.flags & CHUNK_DZ) != 0);
-    if has_dz {
-        dzip_writer.write_global_settings(&RangeSettings {
-            win_size: 0,
-            flags: 0,
-            offset_table_size: 0,
-            offset_tables: 0,
-            offset_contexts: 0,
-            ref_length_table_size: 0,
-            ref_length_tables: 0,
-            ref_offset_table_size: 0,
-            ref_offset_tables: 0,
-            big_min_match: 0,
-        })?;
+        if volume == aux_volumes.len() {
+            let oversized = chunk_sizes.iter().filter(|&&size| size > budget).count();
+            if oversized > 0 {
+                warn!(
+                    "{} file(s) are larger than the split size and get a volume of their own",
+                    oversized
+                );
+            }
+            let volumes = volumes.into_iter().map(|v| v as u16).collect();
+            let mut names = vec![main_name.to_string()];
+            names.extend(aux_volumes);
+            return Ok((names, volumes));
+        }
+        if volume >= u16::MAX as usize {
+            return Err(dzip_core::DzipError::Generic(format!(
+                "Split size {} needs more than {} volumes",
+                budget,
+                u16::MAX
+            )));
+        }
+        aux_volumes = (1..=volume).map(|i| format!("{}.{:03}", stem, i)).collect();
     }
-
-    info!("Pack complete.");
-    Ok(())
 }
@@ -0,0 +1,316 @@
+use crate::config;
+use clap::Args;
+use dzip_core::format::ArchiveHeader;
+use dzip_core::reader::{DzipReader, HeaderCrc};
+use dzip_core::writer::{DzipWriter, append_header_crc};
+use dzip_core::{DzipError, Endianness, Result};
+use log::info;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Args, Debug, Clone)]
+pub struct RenameOptions {
+    /// File with one `old_path=new_path` per line; `#` starts a comment. A
+    /// directory path renames everything below it.
+    #[arg(long, value_name = "PATH")]
+    pub map: PathBuf,
+}
+
+/// One line of the rename map, as path components.
+struct RenameRule {
+    line: usize,
+    old: Vec<String>,
+    new: Vec<String>,
+    used: bool,
+}
+
+/// An archive path as its components, split on either separator, without
+/// empty and `.` components.
+fn components(path: &str) -> Vec<String> {
+    path.split(['/', '\\'])
+        .filter(|c| !c.is_empty() && *c != ".")
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parses the `old_path=new_path` lines of a rename map.
+fn parse_rename_map(path: &Path) -> Result<Vec<RenameRule>> {
+    let content = config::read_text(path)
+        .map_err(|e| DzipError::Generic(format!("{}: {:#}", path.display(), e)))?;
+    let mut rules: Vec<RenameRule> = Vec::new();
+    for (line_no, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |reason: String| {
+            DzipError::Generic(format!("{}:{}: {}", path.display(), line_no + 1, reason))
+        };
+        let Some((old, new)) = line.split_once('=') else {
+            return Err(invalid("expected 'old_path=new_path'".to_string()));
+        };
+        let (old, new) = (old.trim(), new.trim());
+        dzip_core::path::resolve_relative_path(new).map_err(|e| invalid(e.to_string()))?;
+        let (old, new) = (components(old), components(new));
+        if old.is_empty() || new.is_empty() {
+            return Err(invalid("cannot rename to or from the root".to_string()));
+        }
+        if let Some(earlier) = rules.iter().find(|rule| rule.old == old) {
+            return Err(invalid(format!(
+                "'{}' is already renamed on line {}",
+                old.join("/"),
+                earlier.line
+            )));
+        }
+        rules.push(RenameRule {
+            line: line_no + 1,
+            old,
+            new,
+            used: false,
+        });
+    }
+    Ok(rules)
+}
+
+/// Applies the rule with the longest `old` that is `path` or a directory
+/// above it, if any.
+fn rename_path(rules: &mut [RenameRule], path: &[String]) -> Option<Vec<String>> {
+    let rule = rules
+        .iter_mut()
+        .filter(|rule| path.starts_with(&rule.old))
+        .max_by_key(|rule| rule.old.len())?;
+    rule.used = true;
+    let mut renamed = rule.new.clone();
+    renamed.extend_from_slice(&path[rule.old.len()..]);
+    Some(renamed)
+}
+
+/// Rewrites the string table and file map of `header` for `rules`.
+///
+/// Directories keep their stored strings unless renamed; renamed and new
+/// directories are written with `\`. A directory that only held renamed files
+/// is dropped, while directories that were empty to begin with are kept.
+fn rename_files(header: &mut ArchiveHeader, rules: &mut [RenameRule]) -> Result<()> {
+    let num_files = header.map.len();
+    let num_dirs = header.settings.num_directories.saturating_sub(1) as usize;
+    let old_dirs: Vec<String> = header.strings[num_files..num_files + num_dirs].to_vec();
+    let used_dirs: HashSet<u16> = header.map.iter().map(|(dir_id, _)| *dir_id).collect();
+
+    // New path of every file, as directory components and file name
+    let old_paths = (0..num_files)
+        .map(|i| header.file_path(i))
+        .collect::<Result<Vec<_>>>()?;
+    let mut paths = Vec::with_capacity(num_files);
+    for old_path in &old_paths {
+        let old = components(old_path);
+        let mut new = rename_path(rules, &old).unwrap_or(old);
+        let name = new.pop().unwrap_or_default();
+        if name.is_empty() {
+            return Err(DzipError::Generic(format!(
+                "'{}' does not end in a file name",
+                old_path
+            )));
+        }
+        paths.push((new, name));
+    }
+
+    // Directory strings in table order; keys are the components joined with `/`
+    let mut directories: Vec<String> = Vec::new();
+    let mut dir_ids: HashMap<String, u16> = HashMap::new();
+    let mut add_dir = |dir: &[String], stored: String| -> Result<u16> {
+        if dir.is_empty() {
+            return Ok(0);
+        }
+        if let Some(&id) = dir_ids.get(&dir.join("/")) {
+            return Ok(id);
+        }
+        directories.push(stored);
+        let id = u16::try_from(directories.len())
+            .ok()
+            .filter(|&id| id < u16::MAX)
+            .ok_or_else(|| DzipError::Generic("Too many directories".to_string()))?;
+        dir_ids.insert(dir.join("/"), id);
+        Ok(id)
+    };
+    let file_dirs: HashSet<String> = paths.iter().map(|(dir, _)| dir.join("/")).collect();
+    let mut empty_dirs = Vec::new();
+    for (i, stored) in old_dirs.iter().enumerate() {
+        let dir = components(stored);
+        let (dir, stored) = match rename_path(rules, &dir) {
+            Some(renamed) => {
+                let stored = renamed.join("\\");
+                (renamed, stored)
+            }
+            None => (dir, stored.clone()),
+        };
+        let was_used = used_dirs.contains(&((i + 1) as u16));
+        if !was_used || file_dirs.contains(&dir.join("/")) {
+            add_dir(&dir, stored)?;
+            if !file_dirs.contains(&dir.join("/")) {
+                empty_dirs.push(dir);
+            }
+        }
+    }
+    let mut map_dirs = Vec::with_capacity(num_files);
+    for (dir, _) in &paths {
+        map_dirs.push(add_dir(dir, dir.join("\\"))?);
+    }
+
+    if let Some(rule) = rules.iter().find(|rule| !rule.used) {
+        return Err(DzipError::PathNotFound(format!(
+            "{} (rename map line {})",
+            rule.old.join("/"),
+            rule.line
+        )));
+    }
+
+    // No two files may end up at one path, nor a file where a directory is,
+    // including the empty directories the archive keeps
+    let mut taken: HashMap<String, usize> = HashMap::new();
+    for (i, (dir, name)) in paths.iter().enumerate() {
+        let mut path = dir.clone();
+        path.push(name.clone());
+        if let Some(&other) = taken.get(&path.join("/")) {
+            return Err(DzipError::Generic(format!(
+                "Renaming would give '{}' and '{}' the same path '{}'",
+                old_paths[other],
+                old_paths[i],
+                path.join("/")
+            )));
+        }
+        taken.insert(path.join("/"), i);
+    }
+    for (dir, _) in &paths {
+        for end in 1..=dir.len() {
+            if let Some(&i) = taken.get(&dir[..end].join("/")) {
+                return Err(DzipError::Generic(format!(
+                    "Renaming would make '{}' a directory of other files",
+                    old_paths[i]
+                )));
+            }
+        }
+    }
+    for dir in &empty_dirs {
+        for end in 1..=dir.len() {
+            if let Some(&i) = taken.get(&dir[..end].join("/")) {
+                return Err(DzipError::Generic(format!(
+                    "Renaming would put '{}' in the way of the empty directory '{}'",
+                    old_paths[i],
+                    dir.join("/")
+                )));
+            }
+        }
+    }
+
+    header.settings.num_directories = (directories.len() + 1) as u16;
+    header.strings = paths.into_iter().map(|(_, name)| name).collect();
+    header.strings.extend(directories);
+    for ((dir_id, _), new_dir) in header.map.iter_mut().zip(map_dirs) {
+        *dir_id = new_dir;
+    }
+    Ok(())
+}
+
+/// Header bytes for `header`, and the length up to the end of the chunk table.
+fn encode_header(header: &ArchiveHeader, endianness: Endianness) -> Result<(Vec<u8>, u64)> {
+    let mut cursor = Cursor::new(Vec::new());
+    let mut writer = DzipWriter::with_endianness(&mut cursor, endianness);
+    writer.write_archive_settings(&header.settings)?;
+    writer.write_strings(&header.strings)?;
+    writer.write_file_chunk_map(&header.map)?;
+    writer.write_chunk_settings(&header.chunk_settings)?;
+    writer.write_chunks(&header.chunks)?;
+    let chunk_table_end = cursor.position();
+    DzipWriter::with_endianness(&mut cursor, endianness).write_strings(&header.volume_files)?;
+    if let Some(range_settings) = &header.range_settings {
+        DzipWriter::with_endianness(&mut cursor, endianness)
+            .write_global_settings(range_settings)?;
+    }
+    Ok((cursor.into_inner(), chunk_table_end))
+}
+
+/// Renames files and directories of an archive as listed in `--map`, rewriting
+/// only the header.
+///
+/// The chunk data of volume 0 is copied unchanged behind the new header, with
+/// the chunk offsets moved by the change in header size. Auxiliary volumes are
+/// copied next to `output_path` under their names, unless that is where they
+/// already are. A self-extracting stub in front of the archive is not copied.
+pub fn rename_archive(input_path: &str, output_path: &str, options: &RenameOptions) -> Result<()> {
+    let mut rules = parse_rename_map(&options.map)?;
+    let (file, base) = super::open_archive(input_path, None)?;
+    let mut reader = DzipReader::new(file);
+    let mut header = reader.read_header()?;
+    let header_end = reader.position()?;
+    let header_crc = reader.verify_header_crc()?;
+    let endianness = reader.endianness();
+    let (mut input, _) = super::open_archive(input_path, Some(base))?;
+    let data_end = input.seek(SeekFrom::End(0))? - header_crc.trailer_size();
+
+    rename_files(&mut header, &mut rules)?;
+
+    // Offsets only shift; the header size does not depend on them
+    let (encoded, _) = encode_header(&header, endianness)?;
+    let shift = encoded.len() as i64 - header_end as i64;
+    for chunk in header.chunks.iter_mut() {
+        if chunk.file == 0 && chunk.offset as u64 >= header_end {
+            chunk.offset = u32::try_from(chunk.offset as i64 + shift).map_err(|_| {
+                DzipError::Generic("Chunk offsets no longer fit in 32 bits".to_string())
+            })?;
+        }
+    }
+    let (encoded, chunk_table_end) = encode_header(&header, endianness)?;
+
+    let input_path = Path::new(input_path);
+    let output_path = Path::new(output_path);
+    if let (Ok(input), Ok(output)) = (input_path.canonicalize(), output_path.canonicalize())
+        && input == output
+    {
+        return Err(DzipError::Io(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("Refusing to overwrite input {}", input_path.display()),
+        )));
+    }
+    let output_base_dir = output_path.parent().unwrap_or_else(|| Path::new("."));
+    std::fs::create_dir_all(output_base_dir)?;
+
+    info!("Writing {}", output_path.display());
+    let mut output = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(output_path)?;
+    output.write_all(&encoded)?;
+    input.seek(SeekFrom::Start(header_end))?;
+    std::io::copy(
+        &mut (&mut input).take(data_end.saturating_sub(header_end)),
+        &mut output,
+    )?;
+    if header_crc != HeaderCrc::Absent {
+        append_header_crc(&mut output, chunk_table_end)?;
+    }
+    output.flush()?;
+
+    let input_base_dir = input_path.parent().unwrap_or_else(|| Path::new("."));
+    for name in &header.volume_files {
+        let (from, to) = (input_base_dir.join(name), output_base_dir.join(name));
+        if let (Ok(a), Ok(b)) = (from.canonicalize(), to.canonicalize())
+            && a == b
+        {
+            continue;
+        }
+        info!("Copying volume {}", name);
+        std::fs::copy(&from, &to)?;
+    }
+
+    info!(
+        "Applied {} rename(s); header {} -> {} bytes",
+        rules.len(),
+        header_end,
+        encoded.len()
+    );
+    Ok(())
+}
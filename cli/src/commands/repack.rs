@@ -0,0 +1,165 @@
+use clap::Args;
+use dzip_core::format::{CHUNK_ZERO, Chunk, UNKNOWN_FLAGS};
+use dzip_core::reader::HeaderCrc;
+use dzip_core::writer::{DzipWriter, append_header_crc};
+use dzip_core::{Archive, CompressionMethod, DzipError, Result, compress_into};
+use log::{debug, info, warn};
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Args, Debug, Clone)]
+pub struct RepackOptions {
+    /// Compression method for the new archive (e.g. lzma, zlib, bzip, copy)
+    #[arg(short, long)]
+    pub method: CompressionMethod,
+
+    /// Re-compress chunks that already use the target method
+    #[arg(long)]
+    pub force: bool,
+}
+
+/// Re-compresses every chunk of an archive with a new method.
+///
+/// The file list, directories, chunk map and volume layout are kept as they are;
+/// auxiliary volumes are written next to `output_path` under their original names.
+/// Chunks that cannot be decoded (e.g. DZ) are copied through unchanged.
+pub fn repack_archive(input_path: &str, output_path: &str, options: &RepackOptions) -> Result<()> {
+    if options.method == CompressionMethod::Zero {
+        return Err(DzipError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Zero is not a valid repack method, it would discard file contents",
+        )));
+    }
+
+    let input_path = Path::new(input_path);
+    let output_path = Path::new(output_path);
+    let input_base_dir = input_path.parent().unwrap_or_else(|| Path::new("."));
+    let output_base_dir = output_path.parent().unwrap_or_else(|| Path::new("."));
+
+    let mut archive = Archive::open(input_path)?;
+    let mut header = archive.header().clone();
+    // The header layout is kept, so a CRC trailer covers the same byte range
+    let crc_header_len = archive.chunk_table_end();
+    let header_crc = archive.header_crc();
+    let volume_files = header.volume_files.clone();
+    let chunks = std::mem::take(&mut header.chunks);
+
+    // --- Open output volumes, refusing to overwrite any input volume ---
+    let input_volumes: Vec<PathBuf> = std::iter::once(input_path.to_path_buf())
+        .chain(volume_files.iter().map(|name| input_base_dir.join(name)))
+        .filter_map(|p| p.canonicalize().ok())
+        .collect();
+    let output_volumes: Vec<PathBuf> = std::iter::once(output_path.to_path_buf())
+        .chain(volume_files.iter().map(|name| output_base_dir.join(name)))
+        .collect();
+
+    std::fs::create_dir_all(output_base_dir)?;
+    let mut writers = Vec::with_capacity(output_volumes.len());
+    for (i, path) in output_volumes.iter().enumerate() {
+        if let Ok(canonical) = path.canonicalize()
+            && input_volumes.contains(&canonical)
+        {
+            return Err(DzipError::Io(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("Refusing to overwrite input volume {}", path.display()),
+            )));
+        }
+        info!("Opening volume {}: {}", i, path.display());
+        // Readable too, so the header CRC can be computed from the written header
+        writers.push(
+            File::options()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)?,
+        );
+    }
+
+    let endianness = archive.endianness();
+    let mut write_header = |file: &mut File, chunks: &[Chunk]| -> Result<u64> {
+        file.seek(SeekFrom::Start(0))?;
+        header.chunks = chunks.to_vec();
+        DzipWriter::with_endianness(&mut *file, endianness).write_header(&header)?;
+        Ok(file.stream_position()?)
+    };
+
+    // The header size only depends on counts and strings, so write a provisional
+    // header now to reserve its space and rewrite it once the offsets are known.
+    let header_size = write_header(&mut writers[0], &chunks)?;
+
+    let pb = super::progress_bar(chunks.len() as u64);
+
+    let mut new_chunks = Vec::with_capacity(chunks.len());
+    let (mut recompressed, mut copied) = (0usize, 0usize);
+    for (chunk_id, chunk) in chunks.iter().enumerate() {
+        let writer = writers
+            .get_mut(chunk.file as usize)
+            .ok_or(DzipError::VolumeNotFound(chunk.file))?;
+        let offset = writer.stream_position()? as u32;
+
+        let already_target =
+            !options.force && CompressionMethod::from_flags(chunk.flags) == Some(options.method);
+        // Zero chunks carry no data, so there is nothing to gain by re-encoding them
+        let raw = if already_target || (chunk.flags & CHUNK_ZERO) != 0 {
+            debug!("Chunk {} copied without re-compression", chunk_id);
+            None
+        } else {
+            match archive.read_chunk(chunk_id as u16) {
+                Ok(raw) => Some(raw),
+                Err(DzipError::UnsupportedCompression(flags)) => {
+                    warn!(
+                        "Chunk {} uses unsupported compression (flags: {:#x}), copying as-is",
+                        chunk_id, flags
+                    );
+                    None
+                }
+                Err(e) => return Err(e),
+            }
+        };
+        // The encoder writes straight to the volume
+        let (flags, compressed_length) = match raw {
+            Some(raw) => {
+                recompressed += 1;
+                let (flags, written) = compress_into(raw.as_slice(), &mut *writer, options.method)?;
+                // Vendor-specific bits are not ours to drop
+                (flags | (chunk.flags & UNKNOWN_FLAGS), written)
+            }
+            None => {
+                copied += 1;
+                let data = archive.read_raw_chunk(chunk_id as u16)?;
+                writer.write_all(&data)?;
+                (chunk.flags, data.len() as u64)
+            }
+        };
+        new_chunks.push(Chunk {
+            offset,
+            compressed_length: compressed_length as u32,
+            decompressed_length: chunk.decompressed_length,
+            flags,
+            file: chunk.file,
+        });
+        pb.inc(1);
+    }
+    pb.finish_with_message("Repack complete");
+
+    if write_header(&mut writers[0], &new_chunks)? != header_size {
+        return Err(DzipError::InvalidHeader);
+    }
+    if header_crc != HeaderCrc::Absent {
+        append_header_crc(&mut writers[0], crc_header_len)?;
+    }
+    for writer in &mut writers {
+        writer.flush()?;
+    }
+
+    info!(
+        "Repacked {} chunks ({} re-compressed, {} copied) into {}",
+        new_chunks.len(),
+        recompressed,
+        copied,
+        output_path.display()
+    );
+    Ok(())
+}
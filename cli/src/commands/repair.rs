@@ -0,0 +1,230 @@
+use clap::Args;
+use dzip_core::format::*;
+use dzip_core::reader::{DzipReader, check_string_table};
+use dzip_core::repair::scan_chunks;
+use dzip_core::writer::DzipWriter;
+use dzip_core::{ArchivePlan, DzipError, Result, compute_header_size};
+use log::{info, warn};
+use std::fs::File;
+use std::io::{BufWriter, Cursor, Write};
+use std::path::Path;
+
+/// Size of `ArchiveSettings` on disk; scanning starts here if nothing else is readable.
+const ARCHIVE_SETTINGS_SIZE: usize = 9;
+
+#[derive(Args, Debug, Clone)]
+pub struct RepairOptions {
+    /// Offset in the input where chunk data begins, if the header is too damaged to tell
+    #[arg(long)]
+    pub data_offset: Option<u64>,
+}
+
+/// Directory id and chunk ids of every user file
+type FileMap = Vec<(u16, Vec<u16>)>;
+
+/// What could still be read from the damaged header.
+#[derive(Default)]
+struct Salvaged {
+    /// Settings, string table and file map, if they parse
+    names: Option<(ArchiveSettings, Vec<String>, FileMap)>,
+    /// Where scanning starts: right after the file map if it was read
+    scan_start: usize,
+    /// Start of the chunk data, if the rest of the header parsed as well
+    data_start: Option<usize>,
+}
+
+fn salvage_header(data: &[u8]) -> Salvaged {
+    let mut salvaged = Salvaged {
+        scan_start: ARCHIVE_SETTINGS_SIZE,
+        ..Default::default()
+    };
+    let mut reader = DzipReader::new(Cursor::new(data));
+    let names = (|| -> Result<_> {
+        let settings = reader.read_archive_settings()?;
+        let strings = reader.read_strings(settings.num_strings())?;
+        check_string_table(&strings, settings.num_user_files as usize)?;
+        let map = reader.read_file_chunk_map(settings.num_user_files as usize)?;
+        Ok((settings, strings, map))
+    })();
+    match names {
+        Ok(names) => {
+            salvaged.names = Some(names);
+            salvaged.scan_start = reader.position().unwrap_or(0) as usize;
+        }
+        Err(e) => {
+            warn!("String table is unreadable ({}); files will be numbered", e);
+            return salvaged;
+        }
+    }
+
+    let data_start = (|| -> Result<u64> {
+        let chunk_settings = reader.read_chunk_settings()?;
+        let chunks = reader.read_chunks(chunk_settings.num_chunks as usize)?;
+        reader.read_file_list(chunk_settings.num_archive_files.saturating_sub(1) as usize)?;
+        if chunks.iter().any(|c| (c.flags & CHUNK_DZ) != 0) {
+            reader.read_global_settings()?;
+        }
+        Ok(reader.position()?)
+    })();
+    salvaged.data_start = data_start.ok().map(|start| start as usize);
+    salvaged
+}
+
+/// Rebuilds a consistent single-volume archive from the chunk data of a damaged one.
+///
+/// The chunk table is re-derived by scanning volume 0 for codec streams (see
+/// `dzip_core::repair`). File names are kept if the string table is readable and
+/// lists one file per recovered chunk; otherwise every chunk becomes a numbered
+/// file in the root directory. Chunk data is copied unchanged.
+pub fn repair_archive(input_path: &str, output_path: &str, options: &RepairOptions) -> Result<()> {
+    let input_path = Path::new(input_path);
+    let output_path = Path::new(output_path);
+    if let (Ok(input), Ok(output)) = (input_path.canonicalize(), output_path.canonicalize())
+        && input == output
+    {
+        return Err(DzipError::Io(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("Refusing to overwrite input {}", input_path.display()),
+        )));
+    }
+
+    let data = std::fs::read(input_path)?;
+    let mut end = data.len();
+    if data.len() >= HEADER_CRC_TRAILER_SIZE as usize
+        && data[end - HEADER_CRC_TRAILER_SIZE as usize..].starts_with(&HEADER_CRC_MAGIC)
+    {
+        end -= HEADER_CRC_TRAILER_SIZE as usize;
+    }
+    let data = &data[..end];
+
+    let mut salvaged = salvage_header(data);
+    if let Some(offset) = options.data_offset {
+        if offset as usize > data.len() {
+            return Err(DzipError::InvalidSettings(format!(
+                "--data-offset {} is past the end of the input ({} bytes)",
+                offset,
+                data.len()
+            )));
+        }
+        salvaged.scan_start = offset as usize;
+        salvaged.data_start = Some(offset as usize);
+    }
+    let scan_start = salvaged.scan_start.min(data.len());
+    info!(
+        "Scanning {} from offset {}",
+        input_path.display(),
+        scan_start
+    );
+
+    let mut chunks = scan_chunks(data, scan_start);
+    // Leading bytes that are not a stream are the rest of the header, except for
+    // whatever follows the known start of the chunk data.
+    if let Some(first) = chunks.first_mut()
+        && first.flags == CHUNK_COPYCOMP
+        && first.offset as usize == scan_start
+    {
+        let first_end = first.offset as usize + first.compressed_length as usize;
+        match salvaged.data_start {
+            Some(start) if start >= scan_start && start < first_end => {
+                first.offset = start as u32;
+                first.compressed_length = (first_end - start) as u32;
+                first.decompressed_length = first.compressed_length;
+            }
+            _ => {
+                chunks.remove(0);
+            }
+        }
+    }
+    if chunks.is_empty() {
+        return Err(DzipError::Generic(
+            "No chunk data found in the input".to_string(),
+        ));
+    }
+    if chunks.len() > u16::MAX as usize {
+        return Err(DzipError::InvalidSettings(format!(
+            "{} chunks recovered; an archive holds at most {}",
+            chunks.len(),
+            u16::MAX
+        )));
+    }
+    let stored = chunks.iter().filter(|c| c.flags == CHUNK_COPYCOMP).count();
+    info!(
+        "Recovered {} chunks ({} compressed streams, {} stored ranges)",
+        chunks.len(),
+        chunks.len() - stored,
+        stored
+    );
+
+    let (num_directories, strings, map) = match salvaged.names {
+        Some((settings, strings, map)) if map.len() == chunks.len() => {
+            let map = map
+                .iter()
+                .enumerate()
+                .map(|(i, (dir_id, _))| (*dir_id, vec![i as u16]))
+                .collect();
+            (settings.num_directories, strings, map)
+        }
+        Some((_, _, map)) => {
+            warn!(
+                "The header lists {} files but {} chunks were recovered; files will be numbered",
+                map.len(),
+                chunks.len()
+            );
+            numbered_files(chunks.len())
+        }
+        None => numbered_files(chunks.len()),
+    };
+
+    let header_size = compute_header_size(&ArchivePlan {
+        strings: &strings,
+        num_files: map.len(),
+        num_chunks: chunks.len(),
+        volume_files: &[],
+        range_settings: false,
+    });
+    let mut offset = header_size as u32;
+    let mut new_chunks = chunks.clone();
+    for chunk in &mut new_chunks {
+        chunk.offset = offset;
+        offset += chunk.compressed_length;
+    }
+
+    let header = ArchiveHeader {
+        settings: ArchiveSettings {
+            header: DTRZ_MAGIC,
+            num_user_files: map.len() as u16,
+            num_directories,
+            version: 0,
+        },
+        strings,
+        map,
+        chunk_settings: ChunkSettings {
+            num_archive_files: 1,
+            num_chunks: new_chunks.len() as u16,
+        },
+        chunks: new_chunks,
+        volume_files: Vec::new(),
+        range_settings: None,
+    };
+
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut writer = BufWriter::new(File::create(output_path)?);
+    DzipWriter::new(&mut writer).write_header(&header)?;
+    for chunk in &chunks {
+        let start = chunk.offset as usize;
+        writer.write_all(&data[start..start + chunk.compressed_length as usize])?;
+    }
+    writer.flush()?;
+
+    info!("Wrote repaired archive to {}", output_path.display());
+    Ok(())
+}
+
+/// Names `chunk_0000.bin`, `chunk_0001.bin`, ... in the root directory, one chunk each.
+fn numbered_files(count: usize) -> (u16, Vec<String>, FileMap) {
+    let strings = (0..count).map(|i| format!("chunk_{:04}.bin", i)).collect();
+    let map = (0..count).map(|i| (0, vec![i as u16])).collect();
+    (1, strings, map)
+}
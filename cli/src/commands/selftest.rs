@@ -0,0 +1,221 @@
+use super::pack::{PackOptions, pack_archive};
+use super::unpack::{UnpackOptions, unpack_archive};
+use crate::config::{self, CONFIG_VERSION, DzipConfig, FileEntry};
+use clap::{Args, Parser};
+use dzip_core::{CompressionMethod, DzipError, Result};
+use log::info;
+use std::path::{Path, PathBuf};
+
+/// Codecs with an encoder whose output decodes back to the input (Zero discards it).
+const CODECS: [CompressionMethod; 7] = [
+    CompressionMethod::Copy,
+    CompressionMethod::Zlib,
+    CompressionMethod::Gzip,
+    CompressionMethod::Bzip,
+    CompressionMethod::Lzma,
+    CompressionMethod::Xz,
+    CompressionMethod::Brotli,
+];
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct SelftestOptions {
+    /// Keep the temporary directory with the generated files and archives
+    #[arg(long)]
+    pub keep: bool,
+}
+
+/// Parses the unpack options as the command line would, so the self-test runs
+/// with the same defaults as `dzip-cli unpack`.
+#[derive(Parser)]
+struct UnpackDefaults {
+    #[command(flatten)]
+    options: UnpackOptions,
+}
+
+/// Relative path and contents of every generated source file.
+fn sample_files() -> Vec<(&'static str, Vec<u8>)> {
+    // xorshift keeps the "binary" file incompressible and the same on every run
+    let mut state = 0x2545_F491_u32;
+    let noise = (0..65536)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+    vec![
+        ("readme.txt", b"dzip self-test\n".repeat(64)),
+        ("empty.bin", Vec::new()),
+        ("Data/levels.bin", noise),
+        ("Data/Sub/zeros.bin", vec![0; 4096]),
+        (
+            "Loc/strings_en.txt",
+            (0..200)
+                .map(|i| format!("menu.item{} = Item number {}\n", i, i))
+                .collect::<String>()
+                .into_bytes(),
+        ),
+    ]
+}
+
+/// Packs the sample tree with `method`, unpacks it, and checks the files and
+/// the generated config. Returns a description of the first mismatch.
+fn check_codec(work_dir: &Path, method: CompressionMethod) -> Result<Option<String>> {
+    let name = format!("{:?}", method).to_lowercase();
+    let codec_dir = work_dir.join(&name);
+    let src = codec_dir.join("src");
+    let files = sample_files();
+    for (path, data) in &files {
+        let path = src.join(path);
+        std::fs::create_dir_all(path.parent().unwrap_or(&src))?;
+        std::fs::write(path, data)?;
+    }
+
+    let config = DzipConfig {
+        config_version: CONFIG_VERSION,
+        archives: vec![format!("{}.dz", name)],
+        base_dir: PathBuf::from("."),
+        files: files
+            .iter()
+            .map(|(path, _)| FileEntry {
+                path: PathBuf::from(path),
+                archive_file_index: 0,
+                compression: Some(method.into()),
+                modifiers: String::new(),
+                directory: None,
+                lzma: None,
+                brotli: None,
+                priority: None,
+                chunk_id: None,
+            })
+            .collect(),
+        empty_dirs: Vec::new(),
+        options: None,
+    };
+    let config_path = src.join(format!("{}.toml", name));
+    let toml = toml::to_string_pretty(&config).map_err(std::io::Error::other)?;
+    std::fs::write(&config_path, toml)?;
+
+    let packed = codec_dir.join("packed");
+    let archive = packed.join(format!("{}.dz", name));
+    pack_archive(
+        config_path.to_str(),
+        &packed.to_string_lossy(),
+        &PackOptions::default(),
+    )?;
+
+    let unpacked = codec_dir.join("unpacked");
+    let unpack_options = UnpackDefaults::try_parse_from(["unpack"])
+        .map_err(std::io::Error::other)?
+        .options;
+    unpack_archive(
+        &archive.to_string_lossy(),
+        &unpacked.to_string_lossy(),
+        &unpack_options,
+    )?;
+
+    for (path, data) in &files {
+        match std::fs::read(unpacked.join(path)) {
+            Ok(actual) if actual == *data => {}
+            Ok(actual) => {
+                return Ok(Some(format!(
+                    "{} differs ({} bytes, expected {})",
+                    path,
+                    actual.len(),
+                    data.len()
+                )));
+            }
+            Err(e) => return Ok(Some(format!("{} was not extracted: {}", path, e))),
+        }
+    }
+
+    // The generated config must describe the same files and repack to the same bytes
+    let generated = unpacked.join(format!("{}.toml", name));
+    let generated_config = config::parse_config(&generated)
+        .map_err(|e| DzipError::Generic(format!("{}: {:#}", generated.display(), e)))?;
+    if generated_config.files.len() != files.len() {
+        return Ok(Some(format!(
+            "config lists {} files, expected {}",
+            generated_config.files.len(),
+            files.len()
+        )));
+    }
+    for entry in &generated_config.files {
+        let compression = generated_config.compression(entry);
+        if compression != method {
+            return Ok(Some(format!(
+                "config has {:?} for {}",
+                compression,
+                entry.path.display()
+            )));
+        }
+    }
+    let repacked = codec_dir.join("repacked");
+    pack_archive(
+        generated.to_str(),
+        &repacked.to_string_lossy(),
+        &PackOptions::default(),
+    )?;
+    if std::fs::read(&archive)? != std::fs::read(repacked.join(format!("{}.dz", name)))? {
+        return Ok(Some(
+            "repacking the generated config changed the archive".to_string(),
+        ));
+    }
+    Ok(None)
+}
+
+/// Round-trips a generated directory tree through pack and unpack with every
+/// codec that has an encoder, and prints PASS or FAIL per codec.
+///
+/// Fails if any codec fails, so it can gate an installation or a CI job.
+pub fn run_selftest(options: &SelftestOptions) -> Result<()> {
+    // Removed when dropped, so an early return cleans up too
+    let work_dir = tempfile::Builder::new()
+        .prefix("dzip-selftest-")
+        .tempdir()?;
+    info!("Running self-test in {}", work_dir.path().display());
+
+    // The inner pack and unpack runs only report warnings unless --verbose is set
+    let log_level = log::max_level();
+    if log_level == log::LevelFilter::Info {
+        log::set_max_level(log::LevelFilter::Warn);
+    }
+    let results: Vec<(CompressionMethod, Option<String>)> = CODECS
+        .iter()
+        .map(|&method| {
+            let failure = match check_codec(work_dir.path(), method) {
+                Ok(failure) => failure,
+                Err(e) => Some(e.to_string()),
+            };
+            (method, failure)
+        })
+        .collect();
+    log::set_max_level(log_level);
+
+    println!("{:<8} | Result", "Codec");
+    println!("{:-<8}-+-{:-<20}", "", "");
+    for (method, failure) in &results {
+        match failure {
+            None => println!("{:<8} | PASS", format!("{:?}", method)),
+            Some(reason) => println!("{:<8} | FAIL: {}", format!("{:?}", method), reason),
+        }
+    }
+
+    if options.keep {
+        println!("Kept {}", work_dir.keep().display());
+    } else {
+        work_dir.close()?;
+    }
+
+    let failed = results.iter().filter(|(_, f)| f.is_some()).count();
+    if failed > 0 {
+        return Err(DzipError::Generic(format!(
+            "Self-test failed for {} of {} codecs",
+            failed,
+            results.len()
+        )));
+    }
+    println!("All {} codecs passed.", results.len());
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use clap::Args;
+use dzip_core::Result;
+use dzip_core::reader::DzipReader;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct TreeOptions {
+    /// Show at most N levels below the root; deeper entries are counted in
+    /// the size of their directory
+    #[arg(long, value_name = "N")]
+    pub depth: Option<usize>,
+
+    /// Character set of the file and directory names in the archive
+    /// (e.g. shift_jis, latin1); UTF-8 if unset
+    #[arg(long, value_name = "CHARSET", value_parser = super::parse_encoding)]
+    pub encoding: Option<&'static dzip_core::reader::Encoding>,
+
+    /// Offset of the archive in the input, for a self-extracting stub the
+    /// header cannot be found behind automatically
+    #[arg(long, value_name = "BYTES", value_parser = super::parse_size)]
+    pub archive_offset: Option<u64>,
+}
+
+/// A directory or file of the prefix tree. A file whose path is also another
+/// file's directory is shown as the directory, with its size included.
+#[derive(Debug, Default)]
+struct Node {
+    /// Decompressed size of the file itself, or of everything below a directory
+    size: u64,
+    files: usize,
+    children: BTreeMap<String, Node>,
+}
+
+impl Node {
+    fn insert(&mut self, components: &[&str], size: u64) {
+        self.size += size;
+        self.files += 1;
+        if let [first, rest @ ..] = components {
+            self.children
+                .entry(first.to_string())
+                .or_default()
+                .insert(rest, size);
+        }
+    }
+
+    /// Appends the lines for the children, `depth` levels deep (unlimited if `None`).
+    fn render(&self, prefix: &str, depth: Option<usize>, lines: &mut Vec<String>) {
+        if depth == Some(0) {
+            return;
+        }
+        let mut children = self.children.iter().peekable();
+        while let Some((name, child)) = children.next() {
+            let last = children.peek().is_none();
+            let branch = if last { "└── " } else { "├── " };
+            lines.push(if child.children.is_empty() {
+                format!("{}{}{} ({} bytes)", prefix, branch, name, child.size)
+            } else {
+                format!(
+                    "{}{}{}/ ({} files, {} bytes)",
+                    prefix, branch, name, child.files, child.size
+                )
+            });
+            let indent = if last { "    " } else { "│   " };
+            child.render(
+                &format!("{}{}", prefix, indent),
+                depth.map(|d| d - 1),
+                lines,
+            );
+        }
+    }
+}
+
+/// Prints the directory hierarchy of an archive, with each file's decompressed
+/// size and the total size and file count of each directory.
+///
+/// Paths are split on both separators; empty and `.` components are dropped.
+/// Nothing is decompressed.
+pub fn print_tree(input_path: &str, options: &TreeOptions) -> Result<()> {
+    let (file, _) = super::open_archive(input_path, options.archive_offset)?;
+    let mut reader = DzipReader::new(file);
+    if let Some(encoding) = options.encoding {
+        reader.set_encoding(encoding);
+    }
+    let header = reader.read_header()?;
+
+    let mut root = Node::default();
+    for i in 0..header.map.len() {
+        let path = header.file_path(i)?;
+        let components: Vec<&str> = path
+            .split(['/', '\\'])
+            .filter(|c| !c.is_empty() && *c != ".")
+            .collect();
+        root.insert(&components, header.file_decompressed_size(i).unwrap_or(0));
+    }
+
+    let name = Path::new(input_path)
+        .file_name()
+        .map_or_else(|| input_path.into(), |n| n.to_string_lossy());
+    let mut lines = vec![format!(
+        "{} ({} files, {} bytes)",
+        name, root.files, root.size
+    )];
+    root.render("", options.depth, &mut lines);
+    for line in lines {
+        println!("{}", line);
+    }
+    Ok(())
+}
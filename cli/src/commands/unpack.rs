@@ -1,40 +1,625 @@
 use crate::config;
-use dzip_core::Result;
-use indicatif::{ProgressBar, ProgressStyle};
-use log::{debug, error, info, warn};
+use clap::{Args, ValueEnum};
+use dzip_core::format::{Chunk, UNKNOWN_FLAGS};
+use dzip_core::progress::ProgressEvent;
+use dzip_core::sink::{FileSystemSink, TarSink, UnpackSink, ZipSink};
+use dzip_core::source::{ArchiveSource, SharedFile};
+use dzip_core::unpack::{ChunkCache, ExtractStats, UnpackReport};
+use dzip_core::volume::SharedVolumes;
+use dzip_core::writer::{WRAPPER_FLAGS, method_for_flags};
+use dzip_core::{CompressionMethod, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use log::{debug, info, warn};
 use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 
-pub fn unpack_archive(input_path: &str, output_dir: &str) -> Result<()> {
-    let file = std::fs::File::open(input_path)?;
-    let mut reader = dzip_core::reader::DzipReader::new(file);
+#[derive(Args, Debug, Clone)]
+pub struct UnpackOptions {
+    /// Record each file's directory exactly as stored in the archive, so that
+    /// repacking the generated config reproduces identical directory strings
+    #[arg(long)]
+    pub preserve_separators: bool,
 
-    info!("Reading archive metadata...");
-    let settings = reader.read_archive_settings()?;
+    /// With --preserve-separators, also keep separators at the end of a
+    /// directory (`textures\`), which are dropped by default
+    #[arg(long, requires = "preserve_separators")]
+    pub keep_trailing_separators: bool,
 
-    // Determine string count (handling implicit root directory)
-    let strings_count = (settings.num_user_files + settings.num_directories - 1) as usize;
-    let strings = reader.read_strings(strings_count)?;
+    /// Separator between directories in the paths of the generated config.
+    /// Extracted files always use the OS separator
+    #[arg(long, value_enum, default_value_t = SeparatorStyle::Native)]
+    pub normalize_separators: SeparatorStyle,
 
-    let map = reader.read_file_chunk_map(settings.num_user_files as usize)?;
-    let chunk_settings = reader.read_chunk_settings()?;
-    let mut chunks = reader.read_chunks(chunk_settings.num_chunks as usize)?;
+    /// Only extract files whose archive path matches this glob (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    pub include: Vec<String>,
 
-    // Read file list (if multi-volume)
-    let num_other_volumes = if chunk_settings.num_archive_files > 0 {
-        chunk_settings.num_archive_files as usize - 1
-    } else {
-        0
+    /// Skip files whose archive path matches this glob (repeatable).
+    /// Exclusion takes precedence when a file matches both filters
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Only extract files whose first chunk uses this compression method
+    /// (repeatable, e.g. `--method dz --method lzma`)
+    #[arg(long, value_name = "METHOD")]
+    pub method: Vec<CompressionMethod>,
+
+    /// How to handle files that resolve to the same output path
+    #[arg(long, value_enum, default_value_t = CollisionPolicy::Overwrite)]
+    pub on_collision: CollisionPolicy,
+
+    /// Abort if chunks overlap or extend beyond their volume, instead of
+    /// extracting with corrected sizes
+    #[arg(long)]
+    pub strict_layout: bool,
+
+    /// Abort on files whose directory id is past the directory table, instead
+    /// of extracting them to the root
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Use each chunk's compressed length from the header as stored, instead of
+    /// correcting it against the next chunk and the end of the volume
+    #[arg(long)]
+    pub no_size_correction: bool,
+
+    /// Compare each volume's size on disk with the end of its chunk data and
+    /// report truncated volumes or volumes with extra data before extracting
+    #[arg(long)]
+    pub volume_size_report: bool,
+
+    /// Memory-map the archive volumes instead of reading them with file I/O
+    /// (falls back to file I/O if a volume cannot be mapped)
+    #[arg(long)]
+    pub mmap: bool,
+
+    /// Buffer size for reading the archive and writing extracted files, in bytes
+    /// (a power of two from 512 to 64M; K and M suffixes are accepted)
+    #[arg(long, value_name = "BYTES", value_parser = parse_buffer_size, default_value = "8K")]
+    pub buffer_size: usize,
+
+    /// Skip files that already exist in the output directory with the expected size,
+    /// e.g. to continue an interrupted unpack
+    #[arg(long)]
+    pub resume: bool,
+
+    /// With --resume, also decompress and hash files that look complete and
+    /// re-extract them if their contents differ
+    #[arg(long, requires = "resume")]
+    pub verify_existing: bool,
+
+    /// Extract only files whose chunks are all present in the volumes, skipping
+    /// and reporting the rest (e.g. for a truncated download)
+    #[arg(long, conflicts_with = "strict_layout")]
+    pub recover: bool,
+
+    /// Refuse to write through symlinks inside the output directory instead of
+    /// following them
+    #[arg(long)]
+    pub no_follow_symlinks: bool,
+
+    /// Whether to replace files that already exist in the output directory
+    #[arg(long, value_enum, default_value_t = OverwritePolicy::Always)]
+    pub overwrite: OverwritePolicy,
+
+    /// Write each file to `<name>.partial` and rename it into place once complete,
+    /// so an interrupted unpack leaves no truncated files
+    #[arg(long)]
+    pub atomic: bool,
+
+    /// With --atomic, keep the partial files of failed writes for diagnosis
+    #[arg(long, requires = "atomic")]
+    pub keep_partial: bool,
+
+    /// Write the extracted files into this `.tar` or `.zip` archive instead of
+    /// the output directory, which then only receives the config
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["resume", "atomic", "no_follow_symlinks", "overwrite"]
+    )]
+    pub to_archive: Option<PathBuf>,
+
+    /// Override a DZ range setting from the header, e.g. `win_size=18` (repeatable)
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_range_setting)]
+    pub range_setting: Vec<(String, u8)>,
+
+    /// Only write the config, without extracting any files
+    #[arg(
+        long,
+        conflicts_with_all = ["resume", "atomic", "to_archive", "overwrite", "no_follow_symlinks"]
+    )]
+    pub config_only: bool,
+
+    /// Character set of the file and directory names in the archive
+    /// (e.g. shift_jis, latin1); UTF-8 if unset
+    #[arg(long, value_name = "CHARSET", value_parser = super::parse_encoding)]
+    pub encoding: Option<&'static dzip_core::reader::Encoding>,
+
+    /// Hex key the chunk data of an obfuscated archive is XORed with; it is
+    /// recorded in the generated config so repacking obfuscates again
+    #[arg(long, value_name = "HEX")]
+    pub xor_key: Option<dzip_core::XorKey>,
+
+    /// Decode chunks shared by several files once, keeping up to BYTES of
+    /// decompressed data in memory (64M if no size is given)
+    #[arg(
+        long,
+        value_name = "BYTES",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "64M",
+        value_parser = super::parse_size
+    )]
+    pub cache_shared_chunks: Option<u64>,
+
+    /// Offset of the archive in the input, for a self-extracting stub the
+    /// header cannot be found behind automatically
+    #[arg(long, value_name = "BYTES", value_parser = super::parse_size)]
+    pub archive_offset: Option<u64>,
+}
+
+const MIN_BUFFER_SIZE: usize = 512;
+const MAX_BUFFER_SIZE: usize = 64 * 1024 * 1024;
+
+/// Parses a `--buffer-size` value such as `65536`, `64K` or `1M`.
+fn parse_buffer_size(value: &str) -> std::result::Result<usize, String> {
+    let size = super::parse_size(value)
+        .ok()
+        .and_then(|size| usize::try_from(size).ok())
+        .ok_or_else(|| format!("invalid buffer size '{}'", value))?;
+    if !size.is_power_of_two() || !(MIN_BUFFER_SIZE..=MAX_BUFFER_SIZE).contains(&size) {
+        return Err(format!(
+            "buffer size must be a power of two between {} and {} bytes",
+            MIN_BUFFER_SIZE, MAX_BUFFER_SIZE
+        ));
+    }
+    Ok(size)
+}
+
+/// Parses a `--range-setting` value such as `win_size=18`.
+fn parse_range_setting(value: &str) -> std::result::Result<(String, u8), String> {
+    let (key, number) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", value))?;
+    let number: u8 = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {} (expected 0-255)", number, key))?;
+    let key = key.trim().to_string();
+    dzip_core::format::RangeSettings::default()
+        .set(&key, number)
+        .map_err(|e| e.to_string())?;
+    Ok((key, number))
+}
+
+/// Separator written between directories in the generated config.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SeparatorStyle {
+    /// The OS separator (`\` on Windows, `/` elsewhere)
+    #[default]
+    Native,
+    /// Always `/`
+    Forward,
+    /// Always `\`, as stored in the archive
+    Back,
+}
+
+impl SeparatorStyle {
+    /// Joins the components of a relative path with this separator.
+    fn join(self, path: &Path) -> String {
+        let separator = match self {
+            SeparatorStyle::Native => std::path::MAIN_SEPARATOR_STR,
+            SeparatorStyle::Forward => "/",
+            SeparatorStyle::Back => "\\",
+        };
+        let parts: Vec<_> = path.iter().map(|part| part.to_string_lossy()).collect();
+        parts.join(separator)
+    }
+}
+
+/// Handling of archive entries that resolve to an already-used output path,
+/// or to a path another entry needs as a directory.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CollisionPolicy {
+    /// Keep the last entry with a given path
+    #[default]
+    Overwrite,
+    /// Keep the first entry with a given path
+    Skip,
+    /// Extract later entries under a numbered name (e.g. `file_1.txt`); a file
+    /// in the way of a directory is renamed instead
+    Rename,
+    /// Fail before extracting anything
+    Abort,
+}
+
+/// Handling of output files that already exist before extraction.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverwritePolicy {
+    /// Replace existing files
+    #[default]
+    Always,
+    /// Leave existing files untouched
+    Never,
+    /// Replace files older than the archived copy. Archives store no
+    /// modification times, so this currently behaves like `always`
+    IfNewer,
+}
+
+/// Where extracted files are written.
+enum Output {
+    Dir(FileSystemSink),
+    Tar(TarSink<BufWriter<File>>),
+    Zip(Box<ZipSink<BufWriter<File>>>),
+}
+
+impl Output {
+    fn open(output_dir: &str, options: &UnpackOptions) -> Result<Self> {
+        let Some(path) = &options.to_archive else {
+            return Ok(Output::Dir(
+                FileSystemSink::with_buffer_size(output_dir, options.buffer_size)
+                    .follow_symlinks(!options.no_follow_symlinks)
+                    .atomic(options.atomic)
+                    .keep_partial(options.keep_partial),
+            ));
+        };
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase());
+        let create = || -> Result<_> { Ok(BufWriter::new(File::create(path)?)) };
+        match extension.as_deref() {
+            Some("tar") => Ok(Output::Tar(TarSink::new(create()?))),
+            Some("zip") => Ok(Output::Zip(Box::new(ZipSink::new(create()?)))),
+            _ => Err(dzip_core::DzipError::Generic(format!(
+                "--to-archive needs a .tar or .zip file, got '{}'",
+                path.display()
+            ))),
+        }
+    }
+
+    fn sink(&self) -> &dyn UnpackSink {
+        match self {
+            Output::Dir(sink) => sink,
+            Output::Tar(sink) => sink,
+            Output::Zip(sink) => sink.as_ref(),
+        }
+    }
+
+    /// Completes an output archive; loose files need nothing more.
+    fn finish(self) -> Result<()> {
+        match self {
+            Output::Dir(_) => {}
+            Output::Tar(sink) => sink.finish()?.flush()?,
+            Output::Zip(sink) => sink.finish()?.flush()?,
+        }
+        Ok(())
+    }
+}
+
+/// What happened to a selected file.
+enum Extracted {
+    Written(ExtractStats),
+    /// Already complete from a previous run (`--resume`)
+    Resumed,
+    /// Existing file kept by `--overwrite never`
+    Kept,
+}
+
+/// A file selected for extraction, with its resolved output path.
+struct ExtractEntry<'a> {
+    index: usize,
+    archive_dir: String,
+    relative_path: PathBuf,
+    chunk_ids: &'a [u16],
+}
+
+/// Include/exclude glob filters matched against archive-relative paths using `/` separators.
+///
+/// A file is selected when it matches any include pattern (or none were given)
+/// and no exclude pattern.
+struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+impl PathFilter {
+    fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        Ok(Self {
+            include: Self::build(include)?,
+            exclude: Self::build(exclude)?,
+        })
+    }
+
+    fn build(patterns: &[String]) -> Result<Option<GlobSet>> {
+        if patterns.is_empty() {
+            return Ok(None);
+        }
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob = Glob::new(pattern).map_err(|e| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("Invalid glob '{}': {}", pattern, e),
+                )
+            })?;
+            builder.add(glob);
+        }
+        let set = builder
+            .build()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        Ok(Some(set))
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        let path = path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if self.exclude.as_ref().is_some_and(|set| set.is_match(&path)) {
+            return false;
+        }
+        self.include.as_ref().is_none_or(|set| set.is_match(&path))
+    }
+}
+
+/// Total decompressed size of a file's chunks, ignoring out-of-range chunk IDs.
+fn decompressed_size(chunks: &[dzip_core::Chunk], chunk_ids: &[u16]) -> u64 {
+    chunk_ids
+        .iter()
+        .filter_map(|&id| chunks.get(id as usize))
+        .map(|chunk| chunk.decompressed_length as u64)
+        .sum()
+}
+
+/// Checks whether `path` already holds the complete contents of a file, for `--resume`.
+///
+/// The size is compared against the sum of the chunks' decompressed lengths. With
+/// `verify`, the chunks are also decoded and their SHA-256 compared with the file's.
+fn is_extracted<R: std::io::Read + std::io::Seek>(
+    path: &Path,
+    reader: &mut dzip_core::reader::DzipReader<R>,
+    volumes: &mut dyn dzip_core::reader::VolumeSource,
+    chunks: &[dzip_core::Chunk],
+    chunk_ids: &[u16],
+    verify: bool,
+) -> Result<bool> {
+    let expected_size = decompressed_size(chunks, chunk_ids);
+    match std::fs::metadata(path) {
+        Ok(meta) if meta.is_file() && meta.len() == expected_size => {}
+        _ => return Ok(false),
+    }
+    if !verify {
+        return Ok(true);
+    }
+
+    let mut expected = Sha256::new();
+    let stats =
+        dzip_core::unpack::extract_chunks(reader, volumes, chunks, chunk_ids, &mut expected)?;
+    if stats.chunks_failed > 0 {
+        return Ok(false);
+    }
+    let mut actual = Sha256::new();
+    std::io::copy(&mut std::fs::File::open(path)?, &mut actual)?;
+    Ok(expected.finalize() == actual.finalize())
+}
+
+/// Volume and compression recorded in the config for a file, taken from its
+/// first chunk. Chunks with COMBUF, RANDOMACCESS or unknown bits keep their
+/// flags as a list, so that a repack writes them the same way.
+fn file_source(chunks: &[Chunk], chunk_ids: &[u16]) -> (u16, config::Compression) {
+    // Zero-length files (and files whose first chunk id is out of range) have
+    // nothing to decompress; store them as-is on repack
+    let Some(chunk) = chunk_ids.first().and_then(|&id| chunks.get(id as usize)) else {
+        return (0, CompressionMethod::Copy.into());
     };
-    let volume_files = reader.read_file_list(num_other_volumes)?;
+    if (chunk.flags & (WRAPPER_FLAGS | UNKNOWN_FLAGS)) != 0 && method_for_flags(chunk.flags).is_ok()
+    {
+        let names = dzip_core::format::flag_names(chunk.flags);
+        return (chunk.file, config::Compression::Flags(names));
+    }
+    let method = CompressionMethod::from_flags(chunk.flags).unwrap_or(CompressionMethod::Dz);
+    (chunk.file, method.into())
+}
+
+/// Directories that no file lives in directly and that pass `filter`; only the
+/// string table records them, so the config lists them as `empty_dirs`.
+fn unused_dirs(
+    header: &dzip_core::format::ArchiveHeader,
+    filter: &PathFilter,
+) -> Result<Vec<PathBuf>> {
+    let mut used_dirs = vec![false; header.settings.num_directories as usize];
+    for (dir_id, _) in &header.map {
+        if let Some(used) = used_dirs.get_mut(*dir_id as usize) {
+            *used = true;
+        }
+    }
+    let mut dirs = Vec::new();
+    for (dir_id, used) in used_dirs.iter().enumerate().skip(1) {
+        let Some(dir) = header.directory(dir_id as u16) else {
+            continue;
+        };
+        let path = dzip_core::path::resolve_relative_path(dir)?;
+        if *used || path.as_os_str().is_empty() || !filter.matches(&path) {
+            continue;
+        }
+        dirs.push(path);
+    }
+    Ok(dirs)
+}
+
+/// Returns `path` with a numeric suffix (`name_1.ext`, `name_2.ext`, ...) not present in `used`.
+fn unique_path(path: &Path, taken: impl Fn(&Path) -> bool) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|n| path.with_file_name(format!("{}_{}{}", stem, n, ext)))
+        .find(|candidate| !taken(candidate))
+        .expect("unbounded suffix search")
+}
+
+/// Resolves files whose path another file needs as a directory (`foo` and
+/// `foo/bar`), which would otherwise fail or lose data depending on which is
+/// extracted first. `overwrite` keeps the entry that comes last in the archive,
+/// `skip` the one that comes first, and `rename` moves the file out of the way.
+fn resolve_dir_collisions(
+    entries: &mut [Option<ExtractEntry>],
+    policy: CollisionPolicy,
+) -> Result<()> {
+    // Positions of the entries that need each directory
+    let mut dirs: HashMap<PathBuf, Vec<usize>> = HashMap::new();
+    for (pos, entry) in entries.iter().enumerate() {
+        let Some(entry) = entry else { continue };
+        for dir in entry.relative_path.ancestors().skip(1) {
+            if !dir.as_os_str().is_empty() {
+                dirs.entry(dir.to_path_buf()).or_default().push(pos);
+            }
+        }
+    }
+
+    for pos in 0..entries.len() {
+        let Some(entry) = &entries[pos] else { continue };
+        let Some(users) = dirs.get(&entry.relative_path) else {
+            continue;
+        };
+        let users: Vec<usize> = users
+            .iter()
+            .copied()
+            .filter(|&user| entries[user].is_some())
+            .collect();
+        let (Some(&first), Some(&last)) = (users.first(), users.last()) else {
+            continue;
+        };
+        let inner = entries[first].as_ref().expect("user entry is present");
+        let message = format!(
+            "File {} ('{}') is also the directory of file {} ('{}')",
+            entry.index,
+            entry.relative_path.display(),
+            inner.index,
+            inner.relative_path.display()
+        );
+        if policy == CollisionPolicy::Abort {
+            return Err(dzip_core::DzipError::Generic(message));
+        }
+        warn!("{} ({:?})", message, policy);
+
+        let file_index = entry.index;
+        let keep_file = match policy {
+            CollisionPolicy::Overwrite => {
+                file_index > entries[last].as_ref().expect("user entry is present").index
+            }
+            CollisionPolicy::Skip => file_index < inner.index,
+            CollisionPolicy::Rename => {
+                let path = entry.relative_path.clone();
+                let renamed = unique_path(&path, |candidate| {
+                    dirs.contains_key(candidate)
+                        || entries
+                            .iter()
+                            .flatten()
+                            .any(|other| other.relative_path == candidate)
+                });
+                info!("Extracting file {} as '{}'", file_index, renamed.display());
+                entries[pos]
+                    .as_mut()
+                    .expect("entry is present")
+                    .relative_path = renamed;
+                continue;
+            }
+            CollisionPolicy::Abort => unreachable!("handled above"),
+        };
+        if keep_file {
+            for user in users {
+                entries[user] = None;
+            }
+        } else {
+            entries[pos] = None;
+        }
+    }
+    Ok(())
+}
+
+/// Logs one line per volume for `--volume-size-report`; mismatches are warnings.
+fn report_volume_sizes(volumes: &[dzip_core::validate::VolumeSize], names: &[String]) {
+    use dzip_core::validate::VolumeSizeStatus;
+    for volume in volumes {
+        let name = names.get(volume.file as usize).map_or("?", String::as_str);
+        // A final chunk with a placeholder length only tells where the data starts
+        let bound = if volume.exact { "" } else { " or later" };
+        match (volume.status(), volume.actual) {
+            (VolumeSizeStatus::Missing, _) | (_, None) => warn!(
+                "Volume {} ({}) is missing; its chunk data ends at {}{}",
+                volume.file, name, volume.expected, bound
+            ),
+            (VolumeSizeStatus::Truncated, Some(actual)) => warn!(
+                "Volume {} ({}) is truncated: {} bytes, but its chunk data ends at {}{}",
+                volume.file, name, actual, volume.expected, bound
+            ),
+            (VolumeSizeStatus::ExtraData, Some(actual)) => warn!(
+                "Volume {} ({}) has {} bytes after its last chunk ({} bytes, chunk data ends at {})",
+                volume.file,
+                name,
+                actual - volume.expected,
+                actual,
+                volume.expected
+            ),
+            (VolumeSizeStatus::Ok, Some(actual)) => info!(
+                "Volume {} ({}): {} bytes, chunk data ends at {}{}",
+                volume.file, name, actual, volume.expected, bound
+            ),
+        }
+    }
+}
+
+pub fn unpack_archive(input_path: &str, output_dir: &str, options: &UnpackOptions) -> Result<()> {
+    let (file, base) = super::open_archive(input_path, options.archive_offset)?;
+    let mut reader = dzip_core::reader::DzipReader::with_buffer_size(file, options.buffer_size);
+    if let Some(encoding) = options.encoding {
+        reader.set_encoding(encoding);
+    }
+    if let Some(key) = &options.xor_key {
+        reader.set_xor_key(key.clone());
+    }
+
+    info!("Reading archive metadata...");
+    let mut header = reader.read_header()?;
+    if reader.endianness() == dzip_core::Endianness::Big {
+        info!("Archive header is big-endian");
+    }
+    debug!("Format version: {}", reader.format_version());
+    let header_crc = reader.verify_header_crc()?;
+    if let dzip_core::reader::HeaderCrc::Valid(crc) = header_crc {
+        debug!("Header CRC {:#010x} is valid", crc);
+    }
+
+    if !options.range_setting.is_empty() {
+        match header.range_settings.as_mut() {
+            Some(range_settings) => {
+                for (key, value) in &options.range_setting {
+                    range_settings.set(key, *value)?;
+                }
+                info!("Effective range settings: {:?}", range_settings);
+            }
+            None => warn!("Archive has no DZ chunks; --range-setting has no effect"),
+        }
+    }
+
+    if header.map.is_empty() {
+        info!("Archive contains no files; only the config will be written");
+    }
+    let volume_files = header.volume_files.clone();
     debug!(
         "Num archive files: {}, Volume List: {:?}",
-        chunk_settings.num_archive_files, volume_files
+        header.chunk_settings.num_archive_files, volume_files
     );
 
-    info!(
-        "Extracting {} files to '{}'...",
-        settings.num_user_files, output_dir
-    );
     std::fs::create_dir_all(output_dir)?;
 
     let mut archives_names = vec![
@@ -46,48 +631,57 @@ pub fn unpack_archive(input_path: &str, output_dir: &str) -> Result<()> {
     ];
     archives_names.extend(volume_files.clone());
 
-    use dzip_core::format::CHUNK_DZ;
-    let has_dz_chunks = chunks.iter().any(|c| (c.flags & CHUNK_DZ) != 0);
-
-    let global_options = if has_dz_chunks {
-        let settings = reader.read_global_settings()?;
-        Some(config::GlobalOptions {
-            win_size: settings.win_size,
-            offset_table_size: settings.offset_table_size,
-            offset_tables: settings.offset_tables,
-            offset_contexts: settings.offset_contexts,
-            ref_length_table_size: settings.ref_length_table_size,
-            ref_length_tables: settings.ref_length_tables,
-            ref_offset_table_size: settings.ref_offset_table_size,
-            ref_offset_tables: settings.ref_offset_tables,
-            big_min_match: settings.big_min_match,
-            ..config::GlobalOptions::default()
-        })
-    } else {
-        None
-    };
+    let mut global_options = header.range_settings.map(|settings| config::GlobalOptions {
+        win_size: settings.win_size,
+        offset_table_size: settings.offset_table_size,
+        offset_tables: settings.offset_tables,
+        offset_contexts: settings.offset_contexts,
+        ref_length_table_size: settings.ref_length_table_size,
+        ref_length_tables: settings.ref_length_tables,
+        ref_offset_table_size: settings.ref_offset_table_size,
+        ref_offset_tables: settings.ref_offset_tables,
+        big_min_match: settings.big_min_match,
+        ..config::GlobalOptions::default()
+    });
+    if let Some(key) = &options.xor_key {
+        global_options.get_or_insert_with(Default::default).xor_key = Some(key.clone());
+    }
 
     let mut pack_config = config::DzipConfig {
+        config_version: config::CONFIG_VERSION,
         archives: archives_names,
         base_dir: std::path::PathBuf::from("."),
         files: Vec::new(),
+        empty_dirs: Vec::new(),
         options: global_options,
     };
 
     // Prepare shared data for parallel execution
-    let settings_num_user_files = settings.num_user_files;
-    let volume_files_shared = volume_files.clone(); // Clone vec from manager
     let input_base_dir = std::path::Path::new(input_path)
         .parent()
         .unwrap_or_else(|| std::path::Path::new("."));
-    let input_base_dir_shared = input_base_dir.to_path_buf();
+    // Volumes are opened once and read with positional I/O from every thread
+    let (main_source, shared_volumes) = if options.mmap {
+        (
+            SharedFile::open_mapped(input_path)?.with_base(base)?,
+            SharedVolumes::open_mapped(input_base_dir, &volume_files),
+        )
+    } else {
+        (
+            SharedFile::open(input_path)?.with_base(base)?,
+            SharedVolumes::open(input_base_dir, &volume_files),
+        )
+    };
 
     // --- Chunk Size Correction ---
     // Some archives (like testnew.dz) have incorrect compressed_length headers (listing uncompressed size).
     // Validity check: compressed_length cannot exceed distance to next chunk or EOF.
     let mut file_sizes = std::collections::HashMap::new();
     if let Ok(meta) = std::fs::metadata(input_path) {
-        file_sizes.insert(0u16, meta.len());
+        file_sizes.insert(
+            0u16,
+            meta.len().saturating_sub(base) - header_crc.trailer_size(),
+        );
     }
     for (i, vol_name) in volume_files.iter().enumerate() {
         let path = input_base_dir.join(vol_name);
@@ -95,177 +689,425 @@ pub fn unpack_archive(input_path: &str, output_dir: &str) -> Result<()> {
             file_sizes.insert((i + 1) as u16, meta.len());
         }
     }
-    dzip_core::reader::correct_chunk_sizes(&mut chunks, &file_sizes);
+    for issue in dzip_core::validate::check_chunk_layout(&header.chunks, &file_sizes) {
+        if !issue.is_error() {
+            debug!("Chunk layout: {}", issue);
+        } else if options.strict_layout {
+            return Err(dzip_core::DzipError::Generic(format!(
+                "Invalid chunk layout: {}",
+                issue
+            )));
+        } else {
+            warn!("Chunk layout: {}", issue);
+        }
+    }
+    if options.volume_size_report {
+        report_volume_sizes(
+            &dzip_core::validate::check_volume_sizes(&header.chunks, &file_sizes),
+            &pack_config.archives,
+        );
+    }
+    // Must run before the correction, which clamps lengths to the volume size
+    let missing_chunks = dzip_core::validate::missing_chunks(&header.chunks, &file_sizes);
+    if !missing_chunks.is_empty() && !options.recover {
+        warn!(
+            "{} chunk(s) are missing from their volumes; the archive may be truncated (use --recover to extract only complete files)",
+            missing_chunks.len()
+        );
+    }
+    if options.no_size_correction {
+        info!("Size correction disabled; using the compressed lengths from the header");
+    } else {
+        dzip_core::reader::correct_chunk_sizes(&mut header.chunks, &file_sizes);
+    }
     // -----------------------------
+    let (map, chunks) = (&header.map, header.chunks.as_slice());
 
-    info!("Extracting {} files to '{}'...", map.len(), output_dir);
-    let pb = ProgressBar::new(map.len() as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}")
-            .unwrap()
-            .progress_chars("=>-"),
-    );
+    // Resolve every file's archive path up front so filters can be applied
+    // before the parallel extraction loop.
+    let filter = PathFilter::new(&options.include, &options.exclude)?;
+    let mut entries: Vec<Option<ExtractEntry>> = Vec::with_capacity(map.len());
+    let mut seen_paths: HashMap<PathBuf, usize> = HashMap::new();
+    let mut selected = 0;
+    let mut zero_length = 0;
+    let mut unrecoverable = 0;
+    for (i, (_, chunk_ids)) in map.iter().enumerate() {
+        // Keep the directory string as stored, so the config can reproduce it
+        let mut archive_dir = match header.file_directory(i) {
+            Ok(dir) => dir,
+            Err(e) if options.strict => return Err(e),
+            Err(e) => {
+                warn!("{}; extracting it to the root", e);
+                ""
+            }
+        };
+        if !options.keep_trailing_separators {
+            archive_dir = dzip_core::path::trim_trailing_separators(archive_dir);
+        }
+        let archive_dir = archive_dir.to_string();
+        // The archive path may mix `/` and `\`; resolve_file_path splits on both
+        let mut relative_path = dzip_core::path::resolve_file_path(&header.file_path(i)?)?;
+        if !filter.matches(&relative_path) {
+            debug!("Skipping filtered file: {}", relative_path.display());
+            continue;
+        }
+        if !options.method.is_empty() {
+            let method = chunk_ids
+                .first()
+                .and_then(|&id| chunks.get(id as usize))
+                .and_then(|chunk| CompressionMethod::from_flags(chunk.flags));
+            if !method.is_some_and(|method| options.method.contains(&method)) {
+                debug!(
+                    "Skipping file with method {:?}: {}",
+                    method,
+                    relative_path.display()
+                );
+                continue;
+            }
+        }
+        selected += 1;
+
+        if options.recover {
+            let missing = chunk_ids
+                .iter()
+                .filter(|&&id| {
+                    (id as usize) >= chunks.len() || missing_chunks.contains(&(id as usize))
+                })
+                .count();
+            if missing > 0 {
+                warn!(
+                    "Cannot recover '{}': {} of {} chunk(s) missing",
+                    relative_path.display(),
+                    missing,
+                    chunk_ids.len()
+                );
+                unrecoverable += 1;
+                continue;
+            }
+        }
 
-    // We need to collect file entries for config *after* parallel execution or use a mutex.
-    // Collecting results is better.
-    // Result type: (FileEntry, Vec<String>) where Vec<String> are log messages? No, just log directly or return errors.
-    // Actually, we need to generate `pack_config.files`.
-
-    let results: Vec<config::FileEntry> = map
-        .par_iter()
-        .enumerate()
-        .map(|(i, (dir_id, chunk_ids))| -> Result<config::FileEntry> {
-            pb.inc(1);
-            let file_name = &strings[i];
-
-            // Actually, we should construct the full path string first, then resolve it.
-            // But we have `relative_path_buf` which is built using `push`.
-            // If `dir_name` contains `\`, `push` treats it as a filename on Unix.
-            // So `relative_path_buf` might be "dir\subdir/filename" on Unix.
-
-            // We should append components carefully?
-            // Or just use string builder for the "archive path" and then resolve.
-
-            // Best approach:
-            // 1. Reconstruct the full "archive path string" (using / or \ as per archive, likely mixed)
-            // 2. Pass that string to `resolve_relative_path`
-
-            let mut full_archive_path = String::new();
-            if *dir_id > 0 {
-                // dir_id 0 is root.
-                let dir_index = settings_num_user_files as usize + (*dir_id as usize) - 1;
-                if dir_index < strings.len() {
-                    let dir_name = &strings[dir_index];
-                    full_archive_path.push_str(dir_name);
-                    // Ensure separator if missing
-                    if !full_archive_path.ends_with('/') && !full_archive_path.ends_with('\\') {
-                        full_archive_path.push('\\'); // Use archive default separator
-                    }
+        if let Some(&pos) = seen_paths.get(&relative_path) {
+            let previous: &ExtractEntry = entries[pos].as_ref().expect("seen entry is present");
+            warn!(
+                "Files {} and {} both resolve to '{}' ({:?})",
+                previous.index,
+                i,
+                relative_path.display(),
+                options.on_collision
+            );
+            match options.on_collision {
+                CollisionPolicy::Overwrite => entries[pos] = None,
+                CollisionPolicy::Skip => continue,
+                CollisionPolicy::Rename => {
+                    relative_path =
+                        unique_path(&relative_path, |path| seen_paths.contains_key(path));
+                    info!("Extracting file {} as '{}'", i, relative_path.display());
+                }
+                CollisionPolicy::Abort => {
+                    return Err(dzip_core::DzipError::Generic(format!(
+                        "Files {} and {} both resolve to '{}'",
+                        previous.index,
+                        i,
+                        relative_path.display()
+                    )));
                 }
             }
-            full_archive_path.push_str(file_name);
+        }
+
+        if chunk_ids.is_empty() {
+            zero_length += 1;
+        }
+        seen_paths.insert(relative_path.clone(), entries.len());
+        entries.push(Some(ExtractEntry {
+            index: i,
+            archive_dir,
+            relative_path,
+            chunk_ids,
+        }));
+    }
+    resolve_dir_collisions(&mut entries, options.on_collision)?;
+    let entries: Vec<ExtractEntry> = entries.into_iter().flatten().collect();
+    let mut report = UnpackReport {
+        files_skipped: map.len() - entries.len() - unrecoverable,
+        files_missing: unrecoverable,
+        ..UnpackReport::default()
+    };
+    if selected < map.len() {
+        info!(
+            "{} of {} files selected by include/exclude filters",
+            selected,
+            map.len()
+        );
+    }
+    if zero_length > 0 {
+        info!("{} file(s) have no chunks and will be empty", zero_length);
+    }
 
-            // Normalize path using dzip-core path handling (Platform Aware)
-            let sanitized_path = dzip_core::path::resolve_relative_path(&full_archive_path)?;
-            let full_out_path = std::path::Path::new(output_dir).join(&sanitized_path);
+    let file_entry = |path: PathBuf,
+                      archive_file_index: u16,
+                      compression: config::Compression,
+                      archive_dir: String| config::FileEntry {
+        path,
+        archive_file_index,
+        compression: Some(compression),
+        modifiers: String::new(),
+        directory: options.preserve_separators.then_some(archive_dir),
+        lzma: None,
+        brotli: None,
+        priority: None,
+        chunk_id: None,
+    };
+    let empty_dirs = unused_dirs(&header, &filter)?;
+    let config_dir = |path: &Path| options.normalize_separators.join(path);
+
+    if options.config_only {
+        info!("Writing the config for {} files...", entries.len());
+        for entry in entries {
+            let (archive_index, compression) = file_source(chunks, entry.chunk_ids);
+            pack_config.files.push(file_entry(
+                entry.relative_path,
+                archive_index,
+                compression,
+                entry.archive_dir,
+            ));
+        }
+        pack_config.empty_dirs = empty_dirs.iter().map(|dir| config_dir(dir)).collect();
+        return write_config(
+            input_path,
+            output_dir,
+            &pack_config,
+            options.normalize_separators,
+        );
+    }
 
-            // Sanity check: ensure it is still within output_dir?
-            // sanitize_path returns a relative path without `..` so joining it to output_dir is safe.
+    let output = Output::open(output_dir, options)?;
+    let sink = output.sink();
+    let destination = match &options.to_archive {
+        Some(path) => path.display().to_string(),
+        None => output_dir.to_string(),
+    };
+    info!("Extracting {} files to '{}'...", entries.len(), destination);
+    // Progress is tracked in decompressed bytes, fed by the core's chunk events
+    let total_bytes: u64 = entries
+        .iter()
+        .map(|entry| decompressed_size(chunks, entry.chunk_ids))
+        .sum();
+    let pb = super::byte_progress_bar(total_bytes);
+    let on_progress = |event: ProgressEvent<'_>| {
+        if let ProgressEvent::ChunkDecompressed { bytes, .. } = event {
+            pb.inc(bytes);
+        }
+    };
 
-            // Relative path for config
-            let relative_path = sanitized_path.clone();
+    let cache = options.cache_shared_chunks.map(|capacity| {
+        let cache = ChunkCache::new(entries.iter().map(|entry| entry.chunk_ids), capacity);
+        info!(
+            "Caching {} shared chunk(s) in up to {} bytes",
+            cache.shared_chunks(),
+            capacity
+        );
+        cache
+    });
 
-            // Use sanitized path for creation
-            if let Some(parent) = full_out_path.parent() {
-                std::fs::create_dir_all(parent)?;
-            }
+    if options.overwrite == OverwritePolicy::IfNewer {
+        warn!("Archives store no modification times; --overwrite if-newer replaces every file");
+    }
 
-            // info!("Extracting: {}", file_name); // Valid input, but too detailed for parallel log? PB shows progress.
+    let extract = |entry: ExtractEntry| -> Result<(config::FileEntry, Extracted)> {
+        let ExtractEntry {
+            index: _,
+            archive_dir,
+            relative_path,
+            chunk_ids,
+        } = entry;
 
-            let mut out_file = std::fs::File::create(&full_out_path)?;
+        // Thread-local readers over the shared volumes; DzipReader serves
+        // volume 0 chunks and the volume source serves the rest.
+        let mut volume_manager = shared_volumes.readers();
+        let mut reader = dzip_core::reader::DzipReader::with_buffer_size(
+            main_source.reader()?,
+            options.buffer_size,
+        );
+        if let Some(key) = &options.xor_key {
+            reader.set_xor_key(key.clone());
+        }
 
-            // Thread-local VolumeManager
-            let mut volume_manager = dzip_core::volume::FileSystemVolumeManager::new(
-                input_base_dir_shared.clone(),
-                volume_files_shared.clone(),
-            );
+        let (archive_index, compression) = file_source(chunks, chunk_ids);
 
-            // Also need local DzipReader for Main Volume (ID 0)
-            // But VolumeManager handles ID > 0.
-            // ID 0 chunks must be read from MAIN file.
-            // DzipReader::read_chunk_data_with_volumes handles this?
-            // "if chunk.file == 0 { self.read_chunk_data(chunk) }"
-            // So we need a DzipReader for `self`.
-            let main_file = std::fs::File::open(input_path).map_err(dzip_core::DzipError::Io)?;
-            let mut reader = dzip_core::reader::DzipReader::new(main_file);
-
-            // Determine compression from the first chunk
-            use dzip_core::CompressionMethod;
-            let mut compression = CompressionMethod::Dz; // Default
-            let mut archive_index = 0;
-            if let Some(&first_chunk_id) = chunk_ids.first() {
-                let chunk = &chunks[first_chunk_id as usize];
-                archive_index = chunk.file;
-
-                use dzip_core::format::*;
-                if (chunk.flags & CHUNK_ZLIB) != 0 {
-                    compression = CompressionMethod::Zlib;
-                } else if (chunk.flags & CHUNK_BZIP) != 0 {
-                    compression = CompressionMethod::Bzip;
-                } else if (chunk.flags & CHUNK_COPYCOMP) != 0 {
-                    compression = CompressionMethod::Copy;
-                } else if (chunk.flags & CHUNK_ZERO) != 0 {
-                    compression = CompressionMethod::Zero;
-                } else if (chunk.flags & CHUNK_MP3) != 0 {
-                    compression = CompressionMethod::Mp3;
-                } else if (chunk.flags & CHUNK_JPEG) != 0 {
-                    compression = CompressionMethod::Jpeg;
-                } else if (chunk.flags & CHUNK_LZMA) != 0 {
-                    compression = CompressionMethod::Lzma;
-                } else if (chunk.flags & CHUNK_DZ) != 0 {
-                    compression = CompressionMethod::Dz;
-                } else if (chunk.flags & CHUNK_COMBUF) != 0 {
-                    compression = CompressionMethod::Combuf;
-                } else if (chunk.flags & CHUNK_RANDOMACCESS) != 0 {
-                    compression = CompressionMethod::RandomAccess;
+        let outcome = if options.resume
+            && is_extracted(
+                &Path::new(output_dir).join(&relative_path),
+                &mut reader,
+                &mut volume_manager,
+                chunks,
+                chunk_ids,
+                options.verify_existing,
+            )? {
+            debug!("Already extracted: {}", relative_path.display());
+            pb.inc(decompressed_size(chunks, chunk_ids));
+            Extracted::Resumed
+        } else {
+            if options.overwrite == OverwritePolicy::Never
+                && Path::new(output_dir).join(&relative_path).exists()
+            {
+                debug!("Keeping existing file: {}", relative_path.display());
+                pb.inc(decompressed_size(chunks, chunk_ids));
+                return Ok((
+                    file_entry(relative_path, archive_index, compression, archive_dir),
+                    Extracted::Kept,
+                ));
+            }
+            // --recover decodes the whole file before creating it, so a file
+            // with a chunk that fails to decode is never written
+            let recovered = if options.recover {
+                let mut data = Vec::new();
+                let stats = dzip_core::unpack::extract_chunks_cached(
+                    &mut reader,
+                    &mut volume_manager,
+                    chunks,
+                    chunk_ids,
+                    &mut data,
+                    Some(&on_progress),
+                    cache.as_ref(),
+                )?;
+                if stats.chunks_failed > 0 {
+                    return Err(dzip_core::DzipError::Generic(format!(
+                        "{} of {} chunk(s) could not be decoded",
+                        stats.chunks_failed,
+                        chunk_ids.len()
+                    )));
                 }
+                Some((data, stats))
+            } else {
+                None
+            };
+            // resolve_file_path returns a relative path without `..`, so it stays inside the sink root.
+            if let Some(parent) = relative_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                sink.create_dir_all(parent)?;
             }
+            let mut out_file = sink.create_file(&relative_path)?;
+            let stats = match recovered {
+                Some((data, stats)) => {
+                    out_file.write_all(&data)?;
+                    stats
+                }
+                None => dzip_core::unpack::extract_chunks_cached(
+                    &mut reader,
+                    &mut volume_manager,
+                    chunks,
+                    chunk_ids,
+                    &mut out_file,
+                    Some(&on_progress),
+                    cache.as_ref(),
+                )?,
+            };
+            out_file.finish()?;
+            Extracted::Written(stats)
+        };
 
-            for &chunk_id in chunk_ids {
-                let chunk = &chunks[chunk_id as usize];
-                /*
-                debug!(
-                    "Chunk {} - Offset: {}, CompLen: {}, DecompLen: {}, File: {}, Flags: {:#x}",
-                    chunk_id,
-                    chunk.offset,
-                    chunk.compressed_length,
-                    chunk.decompressed_length,
-                    chunk.file,
-                    chunk.flags
-                );
-                */
-                match reader.read_chunk_data_with_volumes(chunk, &mut volume_manager) {
-                    Ok(data) => {
-                        use std::io::Write;
-                        out_file.write_all(&data)?;
-                    }
-                    Err(dzip_core::DzipError::UnsupportedCompression(flags)) => {
-                        warn!(
-                            "Skipping chunk {} due to unsupported compression (flags: {:#x})",
-                            chunk_id, flags
-                        );
-                    }
-                    Err(_e) => {
-                        error!("Error extracting chunk {}: {}", chunk_id, _e);
-                        // Continue? Or fail? Currently continue.
-                        continue;
-                    }
+        Ok((
+            file_entry(relative_path, archive_index, compression, archive_dir),
+            outcome,
+        ))
+    };
+
+    // Collect file entries for the config from the parallel results, in archive
+    // order. With --recover a file that fails is reported and left out.
+    let results: Vec<Option<(config::FileEntry, Extracted)>> = entries
+        .into_par_iter()
+        .map(|entry| {
+            let relative_path = entry.relative_path.clone();
+            match extract(entry) {
+                Err(e) if options.recover => {
+                    warn!("Cannot recover '{}': {}", relative_path.display(), e);
+                    Ok(None)
                 }
+                result => result.map(Some),
             }
-
-            Ok(config::FileEntry {
-                path: relative_path,
-                archive_file_index: archive_index,
-                compression,
-                modifiers: String::new(),
-            })
         })
-        .collect::<Result<Vec<config::FileEntry>>>()?;
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut kept = 0;
+    for result in results {
+        let Some((entry, outcome)) = result else {
+            report.files_missing += 1;
+            continue;
+        };
+        match outcome {
+            Extracted::Written(stats) => report.add_file(entry.path.clone(), stats),
+            Extracted::Resumed => report.add_existing(entry.path.clone()),
+            Extracted::Kept => {
+                report.files_skipped += 1;
+                kept += 1;
+            }
+        }
+        pack_config.files.push(entry);
+    }
+
+    for path in &empty_dirs {
+        debug!("Creating empty directory: {}", path.display());
+        sink.create_dir_all(path)?;
+        pack_config.empty_dirs.push(config_dir(path));
+    }
+
+    write_config(
+        input_path,
+        output_dir,
+        &pack_config,
+        options.normalize_separators,
+    )?;
+    output.finish()?;
 
-    pack_config.files = results;
+    pb.finish_with_message("Unpack complete");
+    info!(
+        "Unpacked {} files ({} bytes): {} complete, {} with failed chunks, {} skipped",
+        report.files.len(),
+        report.bytes_written,
+        report.files_ok,
+        report.files_partial,
+        report.files_skipped
+    );
+    if kept > 0 {
+        info!("{} existing file(s) were not overwritten", kept);
+    }
+    if report.files_existing > 0 {
+        info!(
+            "{} file(s) were already extracted and left in place",
+            report.files_existing
+        );
+    }
+    if !report.is_complete() {
+        warn!(
+            "{} chunk(s) could not be decoded and were left out",
+            report.chunks_failed
+        );
+    }
+    if report.files_missing > 0 {
+        return Err(dzip_core::DzipError::Generic(format!(
+            "{} file(s) could not be recovered and were not extracted",
+            report.files_missing
+        )));
+    }
+    Ok(())
+}
 
-    // Write config file
-    let input_name = std::path::Path::new(input_path)
+/// Writes `<input stem>.toml` to `output_dir`, with file paths joined by `separators`.
+fn write_config(
+    input_path: &str,
+    output_dir: &str,
+    pack_config: &config::DzipConfig,
+    separators: SeparatorStyle,
+) -> Result<()> {
+    let input_name = Path::new(input_path)
         .file_stem()
         .unwrap_or_default()
         .to_string_lossy();
-    let config_filename = format!("{}.toml", input_name);
-    let config_path = std::path::Path::new(output_dir).join(config_filename);
+    let config_path = Path::new(output_dir).join(format!("{}.toml", input_name));
+    let mut pack_config = pack_config.clone();
+    for entry in &mut pack_config.files {
+        entry.path = PathBuf::from(separators.join(&entry.path));
+    }
     let toml_string = toml::to_string_pretty(&pack_config).expect("Failed to serialize config");
     std::fs::write(config_path, toml_string)?;
-
-    pb.finish_with_message("Unpack complete");
-    info!("Unpack complete.");
     Ok(())
 }
@@ -1,52 +1,300 @@
-use dzip_core::Result;
-use log::error;
+use clap::Args;
+use dzip_core::format::{ArchiveHeader, CHUNK_ENTRY_SIZE, Chunk};
+use dzip_core::reader::HeaderCrc;
+use dzip_core::source::{ArchiveSource, OffsetReader, SharedFile};
+use dzip_core::volume::SharedVolumes;
+use dzip_core::writer::{DzipWriter, append_header_crc};
+use dzip_core::{DzipError, Endianness, Result};
+use log::{error, warn};
 use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
 
-pub fn verify_archive(input_path: &str) -> Result<()> {
-    // use dzip_core::format::*; // don't import everything, be explicit if needed, but here symbols are used
+#[derive(Args, Debug, Clone, Default)]
+pub struct VerifyOptions {
+    /// Check each file's SHA-256 against a `SHA256SUMS`-style file
+    /// (`<hex digest>  <archive path>` per line)
+    #[arg(long, value_name = "PATH")]
+    pub checksums_file: Option<String>,
 
-    let mut reader = dzip_core::reader::DzipReader::new(
-        std::fs::File::open(input_path).map_err(dzip_core::DzipError::Io)?,
-    );
+    /// Also print the decoder selected for each chunk from its flags
+    #[arg(long)]
+    pub explain: bool,
 
-    let settings = reader.read_archive_settings()?;
+    /// Use each chunk's compressed length from the header as stored, instead of
+    /// correcting it against the next chunk and the end of the volume
+    #[arg(long)]
+    pub no_size_correction: bool,
 
-    // Read strings (filenames + dirnames)
-    // Formula: num_user_files + num_directories - 1
-    let strings_count = (settings.num_user_files + settings.num_directories - 1) as usize;
-    let strings = reader.read_strings(strings_count)?;
+    /// Memory-map the archive volumes instead of reading them with file I/O
+    /// (falls back to file I/O if a volume cannot be mapped)
+    #[arg(long)]
+    pub mmap: bool,
 
-    // Read FileChunkMap
-    let map = reader.read_file_chunk_map(settings.num_user_files as usize)?;
+    /// Write a copy of volume 0 whose chunk table holds the corrected sizes, for
+    /// tools that do not correct them (chunk data and other volumes are unchanged)
+    #[arg(long, value_name = "OUT", conflicts_with = "no_size_correction")]
+    pub repair_sizes: Option<String>,
 
-    // We need chunk headers to get sizes
-    let chunk_settings = reader.read_chunk_settings()?;
-    let mut chunks = reader.read_chunks(chunk_settings.num_chunks as usize)?;
+    /// Order the rows by path, offset or compressed size instead of by index
+    #[arg(long, value_enum)]
+    pub sort: Option<super::SortKey>,
 
-    // Read Auxiliary Files (Volumes)
-    let num_volumes_expected = chunk_settings.num_archive_files.saturating_sub(1);
-    let volume_files = if num_volumes_expected > 0 {
-        reader.read_strings(num_volumes_expected as usize)?
-    } else {
-        Vec::new()
+    /// Character set of the file and directory names in the archive
+    /// (e.g. shift_jis, latin1); UTF-8 if unset
+    #[arg(long, value_name = "CHARSET", value_parser = super::parse_encoding)]
+    pub encoding: Option<&'static dzip_core::reader::Encoding>,
+
+    /// Hex key the chunk data of an obfuscated archive is XORed with
+    #[arg(long, value_name = "HEX")]
+    pub xor_key: Option<dzip_core::XorKey>,
+
+    /// Offset of the archive in the input, for a self-extracting stub the
+    /// header cannot be found behind automatically
+    #[arg(long, value_name = "BYTES", value_parser = super::parse_size)]
+    pub archive_offset: Option<u64>,
+
+    /// Decompress only this percentage of the files (e.g. `5%`), picked at
+    /// random, for a quick check; the header is still read in full
+    #[arg(long, value_name = "PERCENT", value_parser = parse_percent)]
+    pub sample: Option<f64>,
+
+    /// Seed for picking the --sample files, to check the same files again
+    #[arg(long, requires = "sample")]
+    pub seed: Option<u64>,
+
+    /// Verify only the first N files, taken after --sort and --sample
+    #[arg(long, value_name = "N")]
+    pub limit: Option<usize>,
+}
+
+fn parse_percent(value: &str) -> std::result::Result<f64, String> {
+    let number = value.strip_suffix('%').unwrap_or(value);
+    match number.parse::<f64>() {
+        Ok(percent) if percent > 0.0 && percent <= 100.0 => Ok(percent),
+        _ => Err(format!(
+            "'{}' is not a percentage above 0 and up to 100",
+            value
+        )),
+    }
+}
+
+/// Picks `ceil(count * percent / 100)` of `count` files (at least one) with a
+/// seeded shuffle. Returns whether each file is in the sample.
+fn sample_files(count: usize, percent: f64, seed: u64) -> Vec<bool> {
+    let size = ((count as f64 * percent / 100.0).ceil() as usize).clamp(1, count.max(1));
+    let mut indices: Vec<usize> = (0..count).collect();
+    // splitmix64: small, and the same sequence on every platform
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
     };
+    let mut sampled = vec![false; count];
+    for i in 0..size.min(count) {
+        let j = i + (next() % (count - i) as u64) as usize;
+        indices.swap(i, j);
+        sampled[indices[i]] = true;
+    }
+    sampled
+}
+
+/// The keys `--sort` orders a file by.
+struct SortKeys {
+    path: String,
+    /// Volume and offset of the first chunk
+    location: (u16, u32),
+    packed: u32,
+}
+
+/// Sort keys of user file `i`; files without chunks sort last by offset.
+fn sort_keys(header: &ArchiveHeader, i: usize) -> SortKeys {
+    let (dir_id, chunk_ids) = &header.map[i];
+    let mut path = header.directory(*dir_id).unwrap_or_default().to_string();
+    if !path.is_empty() && !path.ends_with('/') && !path.ends_with('\\') {
+        path.push('/');
+    }
+    path.push_str(&header.strings[i]);
+    // Out-of-range chunk ids are left out here and reported as FAIL
+    let file_chunks = chunk_ids
+        .iter()
+        .filter_map(|&cid| header.chunks.get(cid as usize));
+    let location = file_chunks
+        .clone()
+        .next()
+        .map_or((u16::MAX, u32::MAX), |chunk| (chunk.file, chunk.offset));
+    let packed = file_chunks.map(|chunk| chunk.compressed_length).sum();
+    SortKeys {
+        path,
+        location,
+        packed,
+    }
+}
+
+/// Where verify sends decoded bytes: into the hash if a checksum is expected
+/// and, for JPEG files, into a buffer for the marker check. Otherwise they are
+/// dropped, so memory does not grow with the chunk size.
+struct VerifySink<'a> {
+    hasher: Option<&'a mut Sha256>,
+    jpeg_data: Option<&'a mut Vec<u8>>,
+}
+
+impl Write for VerifySink<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.update(buf);
+        }
+        if let Some(jpeg_data) = self.jpeg_data.as_mut() {
+            jpeg_data.extend_from_slice(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Status and table line of a verified file.
+struct VerifyRow {
+    status: &'static str,
+    line: String,
+}
+
+/// Copies volume 0 to `output_path` and rewrites its chunk table with `chunks`.
+///
+/// The table keeps its size, so only its bytes change; a header CRC trailer is
+/// recomputed over the new table. A self-extracting stub before the archive
+/// (at `base`) is copied unchanged.
+fn write_corrected_copy(
+    input_path: &Path,
+    output_path: &Path,
+    base: u64,
+    chunks: &[Chunk],
+    chunk_table_end: u64,
+    endianness: Endianness,
+    header_crc: HeaderCrc,
+) -> Result<()> {
+    if let (Ok(input), Ok(output)) = (input_path.canonicalize(), output_path.canonicalize())
+        && input == output
+    {
+        return Err(DzipError::Io(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("Refusing to overwrite input {}", input_path.display()),
+        )));
+    }
+    std::fs::copy(input_path, output_path)?;
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(output_path)?;
+    if let HeaderCrc::Valid(_) = header_crc {
+        let len = file.metadata()?.len();
+        file.set_len(len - header_crc.trailer_size())?;
+    }
+    let mut file = OffsetReader::new(file, base)?;
+    let table_len = chunks.len() as u64 * CHUNK_ENTRY_SIZE;
+    file.seek(SeekFrom::Start(chunk_table_end - table_len))?;
+    DzipWriter::with_endianness(&mut file, endianness).write_chunks(chunks)?;
+    if let HeaderCrc::Valid(_) = header_crc {
+        append_header_crc(&mut file, chunk_table_end)?;
+    }
+    Ok(())
+}
+
+/// Parses a `sha256sum`-style checksum file into a map of archive path (with `/`
+/// separators) to lowercase hex digest.
+fn parse_checksums(path: &Path) -> Result<HashMap<String, String>> {
+    let content = std::fs::read_to_string(path)?;
+    let mut checksums = HashMap::new();
+    for (line_no, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((digest, name)) = line.split_once(char::is_whitespace) else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "{}:{}: expected '<digest>  <path>'",
+                    path.display(),
+                    line_no + 1
+                ),
+            )
+            .into());
+        };
+        // `*` marks binary mode in sha256sum output
+        let name = name.trim_start().trim_start_matches('*');
+        checksums.insert(normalize_archive_path(name), digest.to_ascii_lowercase());
+    }
+    Ok(checksums)
+}
+
+/// Normalizes an archive path for lookups: `/` separators, no leading `./` or `/`.
+fn normalize_archive_path(path: &str) -> String {
+    path.split(['/', '\\'])
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub fn verify_archive(input_path: &str, options: &VerifyOptions) -> Result<()> {
+    let checksums = match &options.checksums_file {
+        Some(path) => Some(parse_checksums(Path::new(path))?),
+        None => None,
+    };
+
+    // use dzip_core::format::*; // don't import everything, be explicit if needed, but here symbols are used
+
+    let (file, base) = super::open_archive(input_path, options.archive_offset)?;
+    let mut reader = dzip_core::reader::DzipReader::new(file);
+    if let Some(encoding) = options.encoding {
+        reader.set_encoding(encoding);
+    }
+
+    let mut header = reader.read_header()?;
+    println!("Format version: {}", reader.format_version());
+    if reader.endianness() == dzip_core::Endianness::Big {
+        println!("Archive header is big-endian.");
+    }
+    let header_crc = reader.verify_header_crc()?;
+    match header_crc {
+        HeaderCrc::Valid(crc) => println!("Header CRC: {:#010x} (valid)", crc),
+        HeaderCrc::Absent => println!("Header CRC: not present"),
+    }
+    let volume_files = header.volume_files.clone();
 
     // Prepare shared data for VolumeManager
     let input_base_dir = std::path::Path::new(input_path)
         .parent()
         .unwrap_or_else(|| std::path::Path::new("."));
-    let input_base_dir_shared = input_base_dir.to_path_buf();
-    let volume_files_shared = volume_files.clone();
-
-    // Use FileSystemVolumeManager
-    // Note: Creating one here just to read file sizes.
-    // Actually we don't need VolumeManager logic for size calc, can just iterate strings.
-    // But we need it for verification loop later.
+    // Volumes are opened once and shared by the verification threads
+    let (main_source, shared_volumes) = if options.mmap {
+        (
+            SharedFile::open_mapped(input_path)?.with_base(base)?,
+            SharedVolumes::open_mapped(input_base_dir, &volume_files),
+        )
+    } else {
+        (
+            SharedFile::open(input_path)?.with_base(base)?,
+            SharedVolumes::open(input_base_dir, &volume_files),
+        )
+    };
 
     // --- Chunk Size Correction ---
     let mut file_sizes = std::collections::HashMap::new();
     if let Ok(meta) = std::fs::metadata(input_path) {
-        file_sizes.insert(0u16, meta.len());
+        file_sizes.insert(
+            0u16,
+            meta.len().saturating_sub(base) - header_crc.trailer_size(),
+        );
     }
     for (i, vol_name) in volume_files.iter().enumerate() {
         let path = input_base_dir.join(vol_name);
@@ -55,50 +303,107 @@ pub fn verify_archive(input_path: &str) -> Result<()> {
         }
     }
 
-    dzip_core::reader::correct_chunk_sizes(&mut chunks, &file_sizes);
+    // Check the layout on the raw chunk table; size correction would hide overlaps
+    let layout_issues = dzip_core::validate::check_chunk_layout(&header.chunks, &file_sizes);
+    if options.no_size_correction {
+        println!("Size correction disabled; using the compressed lengths from the header.");
+    } else {
+        let stored = header.chunks.clone();
+        dzip_core::reader::correct_chunk_sizes(&mut header.chunks, &file_sizes);
+        if let Some(out) = &options.repair_sizes {
+            let changed = stored
+                .iter()
+                .zip(&header.chunks)
+                .filter(|(a, b)| a != b)
+                .count();
+            write_corrected_copy(
+                Path::new(input_path),
+                Path::new(out),
+                base,
+                &header.chunks,
+                reader.chunk_table_end().expect("chunk table was read"),
+                reader.endianness(),
+                header_crc,
+            )?;
+            println!("Wrote {} with {} corrected chunk length(s).", out, changed);
+        }
+    }
+    let (map, chunks) = (&header.map, header.chunks.as_slice());
+
+    let sampled = options.sample.map(|percent| {
+        let seed = options.seed.unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+        });
+        let sampled = sample_files(map.len(), percent, seed);
+        println!(
+            "Sampling {} of {} files ({}%, seed {}).",
+            sampled.iter().filter(|&&s| s).count(),
+            map.len(),
+            percent,
+            seed
+        );
+        sampled
+    });
 
     println!("Verifying archive integrity...");
 
     println!(
-        "{:<5} | {:<7} | {:<10} | {:<10} | {:<8} | Path",
+        "{:<5} | {:<9} | {:<10} | {:<10} | {:<8} | Path",
         "Idx", "Status", "Size", "Packed", "Method"
     );
     println!(
-        "{:-<5}-+-{:-<7}-+-{:-<10}-+-{:-<10}-+-{:-<8}-+-{:-<20}",
+        "{:-<5}-+-{:-<9}-+-{:-<10}-+-{:-<10}-+-{:-<8}-+-{:-<20}",
         "", "", "", "", "", ""
     );
 
+    // The sort keys come from the header, so the files are ordered (and cut
+    // to --limit) before anything is decompressed
+    let mut selected: Vec<(usize, SortKeys)> = (0..map.len())
+        .filter(|&i| sampled.as_ref().is_none_or(|sampled| sampled[i]))
+        .map(|i| (i, sort_keys(&header, i)))
+        .collect();
+    match options.sort {
+        None => {}
+        Some(super::SortKey::Path) => selected.sort_by(|a, b| a.1.path.cmp(&b.1.path)),
+        Some(super::SortKey::Offset) => selected.sort_by_key(|(_, keys)| keys.location),
+        Some(super::SortKey::Size) => {
+            selected.sort_by_key(|(_, keys)| std::cmp::Reverse(keys.packed))
+        }
+    }
+    if let Some(limit) = options.limit {
+        selected.truncate(limit);
+    }
+
     // Use parallel iterator to verify
-    // We need to collect results to print them in order (or we could print as we go if we didn't care about order, but table looks best ordered)
-    // Order is important for "Idx".
-
-    let results: Vec<String> = map
-        .par_iter()
-        .enumerate()
-        .map(|(i, (dir_id, chunk_ids))| -> Result<String> {
-            let file_name = &strings[i];
-
-            // Reconstruct path
-            let mut full_path = String::new();
-            if *dir_id > 0 {
-                let dir_index = settings.num_user_files as usize + (*dir_id as usize) - 1;
-                if let Some(dir_name) = strings.get(dir_index) {
-                    full_path.push_str(dir_name);
-                    if !full_path.ends_with('/') && !full_path.ends_with('\\') {
-                        full_path.push('/');
-                    }
-                }
-            }
-            full_path.push_str(file_name);
+    // Results are collected in the order chosen above, so the output does not
+    // depend on which file finishes first.
+    let results: Vec<VerifyRow> = selected
+        .into_par_iter()
+        .map(|(i, keys)| -> Result<VerifyRow> {
+            let SortKeys {
+                path: full_path,
+                packed,
+                ..
+            } = keys;
+            let chunk_ids = &map[i].1;
 
             // Calculate sizes
             let mut size = 0;
-            let mut packed = 0;
-            let mut method_str = "Unknown";
+            // Out-of-range chunk ids are left out here and reported as FAIL below
+            let file_chunks: Vec<&Chunk> = chunk_ids
+                .iter()
+                .filter_map(|&cid| chunks.get(cid as usize))
+                .collect();
+            let mut method_str = if chunk_ids.is_empty() {
+                "Empty"
+            } else {
+                "Unknown"
+            };
 
             use dzip_core::format::*;
-            if let Some(&first_chunk_id) = chunk_ids.first() {
-                let chunk = &chunks[first_chunk_id as usize];
+            if let Some(chunk) = file_chunks.first() {
                 // Determine method from first chunk
                 if (chunk.flags & CHUNK_ZLIB) != 0 {
                     method_str = "Zlib";
@@ -106,56 +411,205 @@ pub fn verify_archive(input_path: &str) -> Result<()> {
                     method_str = "Bzip";
                 } else if (chunk.flags & CHUNK_LZMA) != 0 {
                     method_str = "LZMA";
+                } else if (chunk.flags & CHUNK_XZ) != 0 {
+                    method_str = "XZ";
+                } else if (chunk.flags & CHUNK_GZIP) != 0 {
+                    method_str = "GZIP";
+                } else if (chunk.flags & CHUNK_BROTLI) != 0 {
+                    method_str = "Brotli";
                 } else if (chunk.flags & CHUNK_COPYCOMP) != 0 {
                     method_str = "Copy";
                 } else if (chunk.flags & CHUNK_ZERO) != 0 {
                     method_str = "Zero";
                 } else if (chunk.flags & CHUNK_DZ) != 0 {
                     method_str = "Dz";
+                } else if (chunk.flags & CHUNK_COMBUF) != 0 {
+                    method_str = "Combuf";
+                } else if (chunk.flags & CHUNK_MP3) != 0 {
+                    method_str = "Mp3";
+                } else if (chunk.flags & CHUNK_JPEG) != 0 {
+                    method_str = "Jpeg";
+                } else if (chunk.flags & CHUNK_RANDOMACCESS) != 0 {
+                    method_str = "Copy";
                 }
             }
 
             // Verify integrity
             // We need a local DzipReader and VolumeManager
-            let main_file = std::fs::File::open(input_path).map_err(dzip_core::DzipError::Io)?;
-            let mut local_reader = dzip_core::reader::DzipReader::new(main_file);
+            let mut local_reader = dzip_core::reader::DzipReader::new(main_source.reader()?);
+            if let Some(key) = &options.xor_key {
+                local_reader.set_xor_key(key.clone());
+            }
+            let mut volume_manager = shared_volumes.readers();
 
-            let mut volume_manager = dzip_core::volume::FileSystemVolumeManager::new(
-                input_base_dir_shared.clone(),
-                volume_files_shared.clone(),
-            );
+            let expected_hash = checksums
+                .as_ref()
+                .and_then(|sums| sums.get(&normalize_archive_path(&full_path)));
+            let mut hasher = expected_hash.map(|_| Sha256::new());
+            // JPEG files are checked for SOI/EOI markers across all of their chunks
+            let mut jpeg_data = (method_str == "Jpeg").then(Vec::new);
 
             let mut chunk_status = "OK";
             for &chunk_id in chunk_ids {
-                if let Some(chunk) = chunks.get(chunk_id as usize) {
-                    if let Err(_e) =
-                        local_reader.read_chunk_data_with_volumes(chunk, &mut volume_manager)
-                    {
-                        // Log error but return FAIL string
-                        error!("Chunk {} failed verification: {}", chunk_id, _e);
+                match header.chunk(chunk_id) {
+                    Ok(chunk) => match local_reader.read_chunk_data_into(
+                        chunk,
+                        &mut volume_manager,
+                        &mut VerifySink {
+                            hasher: hasher.as_mut(),
+                            jpeg_data: jpeg_data.as_mut(),
+                        },
+                    ) {
+                        Ok(written) => {
+                            if written != chunk.decompressed_length as u64 {
+                                log::debug!(
+                                    "Chunk {} decoded to {} bytes, the header says {}",
+                                    chunk_id,
+                                    written,
+                                    chunk.decompressed_length
+                                );
+                            }
+                        }
+                        Err(DzipError::UnsupportedCompression(flags)) => {
+                            // Not corruption: we just lack the decoder
+                            warn!(
+                                "Chunk {} uses an unsupported codec (flags {:#06x})",
+                                chunk_id, flags
+                            );
+                            if chunk_status == "OK" {
+                                chunk_status = "UNSUPP";
+                            }
+                        }
+                        Err(_e) => {
+                            // Log error but return FAIL string
+                            error!("Chunk {} failed verification: {}", chunk_id, _e);
+                            chunk_status = "FAIL";
+                        }
+                    },
+                    Err(e) => {
+                        error!("{}", e);
                         chunk_status = "FAIL";
                     }
-                } else {
-                    chunk_status = "FAIL";
                 }
             }
+
+            if let (Some(expected), Some(hasher)) = (expected_hash, hasher) {
+                let actual = format!("{:x}", hasher.finalize());
+                if chunk_status == "OK" && actual != *expected {
+                    error!(
+                        "{}: SHA-256 mismatch (expected {}, got {})",
+                        full_path, expected, actual
+                    );
+                    chunk_status = "HASH_FAIL";
+                }
+            } else if checksums.is_some() {
+                warn!("{}: no entry in checksums file", full_path);
+            }
+
+            if let Some(jpeg_data) = jpeg_data
+                && chunk_status == "OK"
+                && !dzip_core::reader::has_jpeg_markers(&jpeg_data)
+            {
+                warn!(
+                    "{}: JPEG chunk data lacks SOI/EOI markers, it may use an unsupported encoding",
+                    full_path
+                );
+                chunk_status = "BAD_JPEG";
+            }
+            if chunk_status == "OK"
+                && let Err(e) = header.file_directory(i)
+            {
+                error!("{}", e);
+                chunk_status = "BAD_DIR";
+            }
+            // Decoded fine, but flagged for the runtime to buffer whole
+            if chunk_status == "OK"
+                && file_chunks
+                    .iter()
+                    .any(|c| (c.flags & CHUNK_RANDOMACCESS) != 0)
+            {
+                chunk_status = "RANDOM_ACCESS";
+            }
             let status = chunk_status;
 
-            for &cid in chunk_ids {
-                let chunk = &chunks[cid as usize];
+            for chunk in &file_chunks {
                 size += chunk.decompressed_length;
-                packed += chunk.compressed_length;
             }
 
-            Ok(format!(
-                "{:<5} | {:<7} | {:<10} | {:<10} | {:<8} | {}",
-                i, status, size, packed, method_str, full_path
-            ))
+            Ok(VerifyRow {
+                status,
+                line: format!(
+                    "{:<5} | {:<9} | {:<10} | {:<10} | {:<8} | {}",
+                    i, status, size, packed, method_str, full_path
+                ),
+            })
         })
-        .collect::<Result<Vec<String>>>()?;
+        .collect::<Result<Vec<VerifyRow>>>()?;
+
+    for row in &results {
+        println!("{}", row.line);
+    }
+    if map.is_empty() {
+        println!("(archive contains no files)");
+    }
+
+    // OK, FAIL and UNSUPP are always listed, the rarer statuses only if they occur
+    let summary: Vec<String> = [
+        "OK",
+        "FAIL",
+        "UNSUPP",
+        "HASH_FAIL",
+        "BAD_JPEG",
+        "BAD_DIR",
+        "RANDOM_ACCESS",
+    ]
+    .iter()
+    .enumerate()
+    .filter_map(|(i, status)| {
+        let count = results.iter().filter(|row| row.status == *status).count();
+        (i < 3 || count > 0).then(|| format!("{} {}", count, status))
+    })
+    .collect();
+    println!();
+    let scope = if sampled.is_some() {
+        "sample of"
+    } else {
+        "first"
+    };
+    if sampled.is_some() || results.len() < map.len() {
+        println!(
+            "Summary: {} ({} {} of {} files)",
+            summary.join(", "),
+            scope,
+            results.len(),
+            map.len()
+        );
+    } else {
+        println!("Summary: {}", summary.join(", "));
+    }
 
-    for line in results {
-        println!("{}", line);
+    if options.explain {
+        println!();
+        println!("{:<5} | {:<8} | Flags", "Chunk", "Decoder");
+        println!("{:-<5}-+-{:-<8}-+-{:-<20}", "", "", "");
+        for (i, chunk) in chunks.iter().enumerate() {
+            println!(
+                "{:<5} | {:<8} | {:#06x} {}",
+                i,
+                dzip_core::reader::decoder_name(chunk.flags).unwrap_or("(none)"),
+                chunk.flags,
+                dzip_core::format::flag_names(chunk.flags).join("|")
+            );
+        }
+    }
+
+    if !layout_issues.is_empty() {
+        println!();
+        println!("Chunk layout issues:");
+        for issue in &layout_issues {
+            let level = if issue.is_error() { "ERROR" } else { "WARN" };
+            println!("  {:<5} {}", level, issue);
+        }
     }
 
     Ok(())
@@ -1,13 +1,25 @@
-use anyhow::{Context, Result};
-use dzip_core::CompressionMethod;
+use anyhow::{Context, Result, bail};
+use dzip_core::{BrotliOptions, CompressionMethod, FormatVersion, LzmaOptions, XorKey};
+use log::warn;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
+/// Schema version written to new configs. Bump it (and extend
+/// `DzipConfig::migrate`) whenever a change alters what an existing config means.
+pub const CONFIG_VERSION: u32 = 1;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DzipConfig {
+    /// Schema version; configs written before versioning was introduced are 0.
+    #[serde(default)]
+    pub config_version: u32,
     pub archives: Vec<String>,
     pub base_dir: PathBuf,
     pub files: Vec<FileEntry>,
+    /// Directories (relative paths, `/` or `\` separated) to record in the archive
+    /// even though no file lives directly in them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub empty_dirs: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub options: Option<GlobalOptions>,
 }
@@ -16,12 +28,49 @@ pub struct DzipConfig {
 pub struct FileEntry {
     pub path: PathBuf,
     pub archive_file_index: u16,
-    pub compression: CompressionMethod,
+    /// Falls back to `options.default_method`, then Copy, when unset
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub compression: Option<Compression>,
     #[serde(skip_serializing_if = "String::is_empty", default)]
     pub modifiers: String, // e.g., "to 25%"
+    /// Directory string written to the archive verbatim (separators included).
+    /// When unset, it is derived from the parent of `path`.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub directory: Option<String>,
+    /// Encoder settings for `compression = "Lzma"` (e.g. `lzma = { dict_size = 65536 }`)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub lzma: Option<LzmaOptions>,
+    /// Encoder settings for `compression = "BROTLI"` (e.g. `brotli = { quality = 9 }`)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub brotli: Option<BrotliOptions>,
+    /// Pack order: lower values are written first (unset counts as 0), ties keep
+    /// config order
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub priority: Option<i32>,
+    /// Chunk id of the file under `pack --chunk-ids config`; ignored otherwise
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub chunk_id: Option<u16>,
+}
+
+/// How a file is compressed: a method (`compression = "Zlib"`), or the chunk
+/// flags to set (`compression = ["COMBUF", "ZLIB"]`) for combinations such as
+/// a zlib stream inside a combuf.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Compression {
+    Method(CompressionMethod),
+    Flags(Vec<String>),
 }
 
+impl From<CompressionMethod> for Compression {
+    fn from(method: CompressionMethod) -> Self {
+        Compression::Method(method)
+    }
+}
+
+/// Archive-wide settings; omitted keys take their default values.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct GlobalOptions {
     pub method: String,
     pub max_mem_usage: i32,
@@ -36,6 +85,15 @@ pub struct GlobalOptions {
     pub ref_offset_table_size: u8,
     pub ref_offset_tables: u8,
     pub big_min_match: u8,
+    /// Compression for files that do not set their own
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub default_method: Option<CompressionMethod>,
+    /// Hex key the chunk data is XORed with after compression (obfuscation only)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub xor_key: Option<XorKey>,
+    /// Version byte of the archive settings (0 if unset)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub format_version: Option<u8>,
 }
 
 impl Default for GlobalOptions {
@@ -54,21 +112,256 @@ impl Default for GlobalOptions {
             ref_offset_table_size: 7,
             ref_offset_tables: 3,
             big_min_match: 15,
+            default_method: None,
+            xor_key: None,
+            format_version: None,
         }
     }
 }
 
+impl DzipConfig {
+    /// Brings a config written by an older version up to `CONFIG_VERSION`.
+    ///
+    /// Fields added since then already hold their serde defaults; this is where
+    /// a step goes if a later version changes the meaning of an existing field.
+    /// Configs from a newer version are rejected rather than half understood.
+    pub fn migrate(mut self) -> Result<Self> {
+        if self.config_version > CONFIG_VERSION {
+            bail!(
+                "Config version {} is newer than the supported version {}; update dzip-cli",
+                self.config_version,
+                CONFIG_VERSION
+            );
+        }
+        if self.config_version < CONFIG_VERSION {
+            warn!(
+                "Config version {} is outdated, migrating to version {}",
+                self.config_version, CONFIG_VERSION
+            );
+            // Version 1 only introduced `config_version` itself
+            self.config_version = CONFIG_VERSION;
+        }
+        Ok(self)
+    }
+
+    /// Encoder and wrapper flags (COMBUF, RANDOMACCESS) for `entry`: its own
+    /// compression, else the archive's `default_method`, else Copy.
+    pub fn encoding(&self, entry: &FileEntry) -> dzip_core::Result<(CompressionMethod, u16)> {
+        match &entry.compression {
+            Some(Compression::Method(method)) => Ok((*method, 0)),
+            Some(Compression::Flags(names)) => {
+                dzip_core::writer::method_for_flags(dzip_core::format::encode_flags(names)?)
+            }
+            None => Ok((
+                self.options
+                    .as_ref()
+                    .and_then(|options| options.default_method)
+                    .unwrap_or(CompressionMethod::Copy),
+                0,
+            )),
+        }
+    }
+
+    /// Archive format version to write: `options.format_version`, else V0.
+    pub fn format_version(&self) -> dzip_core::Result<FormatVersion> {
+        match self
+            .options
+            .as_ref()
+            .and_then(|options| options.format_version)
+        {
+            Some(version) => FormatVersion::try_from(version),
+            None => Ok(FormatVersion::default()),
+        }
+    }
+
+    /// Compression method used for `entry` (see `encoding`); Copy if its flags
+    /// are invalid, which `validate` reports.
+    pub fn compression(&self, entry: &FileEntry) -> CompressionMethod {
+        self.encoding(entry)
+            .map(|(method, _)| method)
+            .unwrap_or(CompressionMethod::Copy)
+    }
+
+    /// Checks that every file references a volume listed in `archives`, and that
+    /// the auxiliary volume names are valid.
+    ///
+    /// Volumes (other than volume 0, which always holds the header) that no file
+    /// references are reported as warnings, since they will be written out empty.
+    pub fn validate(&self) -> Result<()> {
+        if self.archives.is_empty() {
+            bail!("No archives specified");
+        }
+        // Auxiliary volume names end up in the header, where readers reject them
+        // unless they are distinct bare file names
+        dzip_core::reader::check_volume_names(&self.archives[1..])?;
+        // Every version the reader understands can also be written
+        self.format_version().context("options.format_version")?;
+
+        let mut referenced = vec![false; self.archives.len()];
+        for entry in &self.files {
+            let idx = entry.archive_file_index as usize;
+            match referenced.get_mut(idx) {
+                Some(used) => *used = true,
+                None => bail!(
+                    "File '{}' references archive volume {}, but only {} volume(s) are defined",
+                    entry.path.display(),
+                    entry.archive_file_index,
+                    self.archives.len()
+                ),
+            }
+        }
+
+        for entry in &self.files {
+            self.encoding(entry)
+                .with_context(|| format!("File '{}'", entry.path.display()))?;
+        }
+
+        for entry in &self.files {
+            let Some(lzma) = &entry.lzma else { continue };
+            let compression = self.compression(entry);
+            if compression != CompressionMethod::Lzma {
+                warn!(
+                    "File '{}' has LZMA options but uses {:?}; they are ignored",
+                    entry.path.display(),
+                    compression
+                );
+            }
+            lzma.validate()
+                .with_context(|| format!("File '{}'", entry.path.display()))?;
+        }
+
+        for entry in &self.files {
+            let Some(brotli) = &entry.brotli else {
+                continue;
+            };
+            let compression = self.compression(entry);
+            if compression != CompressionMethod::Brotli {
+                warn!(
+                    "File '{}' has Brotli options but uses {:?}; they are ignored",
+                    entry.path.display(),
+                    compression
+                );
+            }
+            brotli
+                .validate()
+                .with_context(|| format!("File '{}'", entry.path.display()))?;
+        }
+
+        for (idx, used) in referenced.iter().enumerate().skip(1) {
+            if !used {
+                warn!(
+                    "Archive volume {} ('{}') is not referenced by any file",
+                    idx, self.archives[idx]
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
 pub fn parse_config(path: &Path) -> Result<DzipConfig> {
+    let config = parse_config_unchecked(path)?;
+    config.validate()?;
+    Ok(config)
+}
+
+/// Builds a config from a newline-delimited list of source paths, relative to
+/// the base directory. Blank lines and lines starting with `#` are skipped.
+///
+/// Every file goes to volume 0, `<list stem>.dz`, and is compressed with
+/// `method` (Copy if unset).
+pub fn parse_file_list(path: &Path, method: Option<CompressionMethod>) -> Result<DzipConfig> {
+    let content = read_text(path)?;
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "archive".to_string());
+
+    let mut files = Vec::new();
+    for (line_no, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let path = dzip_core::path::resolve_file_path(line)
+            .with_context(|| format!("Line {}: '{}'", line_no + 1, line))?;
+        files.push(FileEntry {
+            path,
+            archive_file_index: 0,
+            compression: None,
+            modifiers: String::new(),
+            directory: None,
+            lzma: None,
+            brotli: None,
+            priority: None,
+            chunk_id: None,
+        });
+    }
+
+    let config = DzipConfig {
+        config_version: CONFIG_VERSION,
+        archives: vec![format!("{}.dz", stem)],
+        base_dir: PathBuf::from("."),
+        files,
+        empty_dirs: Vec::new(),
+        options: Some(GlobalOptions {
+            default_method: method,
+            ..GlobalOptions::default()
+        }),
+    };
+    config.validate()?;
+    Ok(config)
+}
+
+/// Reads a text file, dropping the UTF-8 byte order mark that Windows editors
+/// tend to save at the start.
+pub fn read_text(path: &Path) -> Result<String> {
     let content = std::fs::read_to_string(path)?;
+    Ok(match content.strip_prefix('\u{feff}') {
+        Some(rest) => rest.to_string(),
+        None => content,
+    })
+}
+
+/// Reports a TOML error as `path:line:column: message`, without the excerpt
+/// the `toml` crate prints over several lines.
+fn toml_error(path: &Path, content: &str, e: toml::de::Error) -> anyhow::Error {
+    let Some(span) = e.span() else {
+        return anyhow::anyhow!("{}: {}", path.display(), e.message());
+    };
+    let before = &content[..span.start.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    anyhow::anyhow!(
+        "{}:{}:{}: {}",
+        path.display(),
+        line,
+        column,
+        e.message().trim_end()
+    )
+}
+
+fn parse_config_unchecked(path: &Path) -> Result<DzipConfig> {
+    let content = read_text(path)?;
 
     if path.extension().is_some_and(|ext| ext == "toml") {
-        return Ok(toml::from_str(&content)?);
+        let mut config: DzipConfig =
+            toml::from_str(&content).map_err(|e| toml_error(path, &content, e))?;
+        // Paths use `/` or `\` depending on where the config was written (see
+        // `unpack --normalize-separators`); both work as separators on Windows
+        for entry in &mut config.files {
+            entry.path = PathBuf::from(entry.path.to_string_lossy().replace('\\', "/"));
+        }
+        return config.migrate();
     }
 
     let mut config = DzipConfig {
+        config_version: CONFIG_VERSION,
         archives: Vec::new(),
         base_dir: PathBuf::from("."),
         files: Vec::new(),
+        empty_dirs: Vec::new(),
         options: Some(GlobalOptions::default()),
     };
 
@@ -97,7 +390,7 @@ pub fn parse_config(path: &Path) -> Result<DzipConfig> {
             "file" => {
                 // file <path> <index> <algo> [modifiers...]
                 if parts.len() >= 4 {
-                    let path = dzip_core::path::resolve_relative_path(parts[1])
+                    let path = dzip_core::path::resolve_file_path(parts[1])
                         .context("Failed to resolve file path")?;
                     let idx = parts[2]
                         .parse::<u16>()
@@ -115,72 +408,111 @@ pub fn parse_config(path: &Path) -> Result<DzipConfig> {
                     config.files.push(FileEntry {
                         path,
                         archive_file_index: idx,
-                        compression: algo,
+                        compression: Some(algo.into()),
                         modifiers,
+                        directory: None,
+                        lzma: None,
+                        brotli: None,
+                        priority: None,
+                        chunk_id: None,
                     });
                 }
             }
             "options" => {
                 if parts.len() > 1 {
-                    config.options.as_mut().unwrap().method = parts[1].to_string();
+                    config
+                        .options
+                        .get_or_insert_with(GlobalOptions::default)
+                        .method = parts[1].to_string();
                 }
             }
             "max_mem_usage" => {
                 if parts.len() > 1 {
-                    config.options.as_mut().unwrap().max_mem_usage = parts[1].parse().unwrap_or(-1);
+                    config
+                        .options
+                        .get_or_insert_with(GlobalOptions::default)
+                        .max_mem_usage = parts[1].parse().unwrap_or(-1);
                 }
             }
             "use_combuf" => {
                 if parts.len() > 1 {
-                    config.options.as_mut().unwrap().use_combuf = parts[1] == "1";
+                    config
+                        .options
+                        .get_or_insert_with(GlobalOptions::default)
+                        .use_combuf = parts[1] == "1";
                 }
             }
             "preprocess" => {
                 if parts.len() > 1 {
-                    config.options.as_mut().unwrap().preprocess = parts[1] == "1";
+                    config
+                        .options
+                        .get_or_insert_with(GlobalOptions::default)
+                        .preprocess = parts[1] == "1";
                 }
             }
             "winsize" => {
                 if parts.len() > 1 {
-                    config.options.as_mut().unwrap().win_size = parts[1].parse().unwrap_or(16);
+                    config
+                        .options
+                        .get_or_insert_with(GlobalOptions::default)
+                        .win_size = parts[1].parse().unwrap_or(16);
                 }
             }
             // Parse remaining specific options based on file
             key => {
+                if parts.len() < 2 {
+                    continue;
+                }
                 // Simple parser for other keys mapping directly to struct fields if names match loosely
                 // For now, implementing manually for known fields to correspond to DerbhCLI.txt
                 match key.to_lowercase().as_str() {
                     "offsettablesize" => {
-                        config.options.as_mut().unwrap().offset_table_size =
-                            parts[1].parse().unwrap_or(8)
+                        config
+                            .options
+                            .get_or_insert_with(GlobalOptions::default)
+                            .offset_table_size = parts[1].parse().unwrap_or(8)
                     }
                     "offsettables" => {
-                        config.options.as_mut().unwrap().offset_tables =
-                            parts[1].parse().unwrap_or(3)
+                        config
+                            .options
+                            .get_or_insert_with(GlobalOptions::default)
+                            .offset_tables = parts[1].parse().unwrap_or(3)
                     }
                     "offsetcontexts" => {
-                        config.options.as_mut().unwrap().offset_contexts =
-                            parts[1].parse().unwrap_or(3)
+                        config
+                            .options
+                            .get_or_insert_with(GlobalOptions::default)
+                            .offset_contexts = parts[1].parse().unwrap_or(3)
                     }
                     "reflengthtablesize" => {
-                        config.options.as_mut().unwrap().ref_length_table_size =
-                            parts[1].parse().unwrap_or(7)
+                        config
+                            .options
+                            .get_or_insert_with(GlobalOptions::default)
+                            .ref_length_table_size = parts[1].parse().unwrap_or(7)
                     }
                     "reflengthtables" => {
-                        config.options.as_mut().unwrap().ref_length_tables =
-                            parts[1].parse().unwrap_or(1)
+                        config
+                            .options
+                            .get_or_insert_with(GlobalOptions::default)
+                            .ref_length_tables = parts[1].parse().unwrap_or(1)
                     }
                     "refoffsettablesize" => {
-                        config.options.as_mut().unwrap().ref_offset_table_size =
-                            parts[1].parse().unwrap_or(7)
+                        config
+                            .options
+                            .get_or_insert_with(GlobalOptions::default)
+                            .ref_offset_table_size = parts[1].parse().unwrap_or(7)
                     }
                     "refoffsettables" => {
-                        config.options.as_mut().unwrap().ref_offset_tables =
-                            parts[1].parse().unwrap_or(3)
+                        config
+                            .options
+                            .get_or_insert_with(GlobalOptions::default)
+                            .ref_offset_tables = parts[1].parse().unwrap_or(3)
                     }
                     "bigminmatch" => {
-                        config.options.as_mut().unwrap().big_min_match =
-                            parts[1].parse().unwrap_or(15)
+                        config
+                            .options
+                            .get_or_insert_with(GlobalOptions::default)
+                            .big_min_match = parts[1].parse().unwrap_or(15)
                     }
                     _ => {
                         // Unknown option, for now just ignore or log
@@ -193,3 +525,255 @@ pub fn parse_config(path: &Path) -> Result<DzipConfig> {
 
     Ok(config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, archive_file_index: u16) -> FileEntry {
+        FileEntry {
+            path: PathBuf::from(path),
+            archive_file_index,
+            compression: Some(CompressionMethod::Copy.into()),
+            modifiers: String::new(),
+            directory: None,
+            lzma: None,
+            brotli: None,
+            priority: None,
+            chunk_id: None,
+        }
+    }
+
+    fn config(archives: &[&str], files: Vec<FileEntry>) -> DzipConfig {
+        DzipConfig {
+            config_version: CONFIG_VERSION,
+            archives: archives.iter().map(|s| s.to_string()).collect(),
+            base_dir: PathBuf::from("."),
+            files,
+            empty_dirs: Vec::new(),
+            options: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_ok() {
+        let cfg = config(&["a.dz", "a1.dz"], vec![entry("x", 0), entry("y", 1)]);
+        assert!(cfg.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_missing_volume() {
+        let cfg = config(&["a.dz"], vec![entry("x", 0), entry("dir/y", 2)]);
+        let err = cfg.validate().unwrap_err().to_string();
+        assert!(err.contains("dir/y"));
+        assert!(err.contains("volume 2"));
+    }
+
+    #[test]
+    fn test_validate_no_archives() {
+        let cfg = config(&[], vec![entry("x", 0)]);
+        assert!(cfg.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_volume_names() {
+        let cfg = config(&["a.dz", "out/a1.dz"], vec![entry("x", 0), entry("y", 1)]);
+        let err = cfg.validate().unwrap_err().to_string();
+        assert!(err.contains("volume 1"), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_unused_volume_is_not_fatal() {
+        let cfg = config(&["a.dz", "a1.dz"], vec![entry("x", 0)]);
+        assert!(cfg.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_format_version() {
+        let mut cfg = config(&["a.dz"], vec![entry("x", 0)]);
+        assert_eq!(cfg.format_version().unwrap(), FormatVersion::V0);
+        cfg.options = Some(GlobalOptions {
+            format_version: Some(0),
+            ..Default::default()
+        });
+        assert!(cfg.validate().is_ok());
+
+        cfg.options = Some(GlobalOptions {
+            format_version: Some(3),
+            ..Default::default()
+        });
+        let err = format!("{:#}", cfg.validate().unwrap_err());
+        assert!(err.contains("format_version"), "{}", err);
+        assert!(err.contains("Unsupported version: 3"), "{}", err);
+    }
+
+    #[test]
+    fn test_default_method() {
+        let cfg: DzipConfig = toml::from_str(
+            r#"archives = ["a.dz"]
+base_dir = "."
+
+[[files]]
+path = "x"
+archive_file_index = 0
+
+[[files]]
+path = "y"
+archive_file_index = 0
+compression = "Bzip"
+
+[options]
+default_method = "Zlib"
+"#,
+        )
+        .unwrap();
+        assert_eq!(cfg.compression(&cfg.files[0]), CompressionMethod::Zlib);
+        assert_eq!(cfg.compression(&cfg.files[1]), CompressionMethod::Bzip);
+
+        // Without a default, files fall back to Copy
+        let cfg = config(
+            &["a.dz"],
+            vec![FileEntry {
+                compression: None,
+                ..entry("x", 0)
+            }],
+        );
+        assert_eq!(cfg.compression(&cfg.files[0]), CompressionMethod::Copy);
+
+        let err = toml::from_str::<DzipConfig>(
+            "archives = [\"a.dz\"]\nbase_dir = \".\"\nfiles = []\n[options]\ndefault_method = \"Fast\"\n",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Fast"), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_lzma_options() {
+        let mut lzma_entry = entry("x", 0);
+        lzma_entry.compression = Some(CompressionMethod::Lzma.into());
+        lzma_entry.lzma = Some(LzmaOptions { dict_size: 1 << 16 });
+        let mut cfg = config(&["a.dz"], vec![lzma_entry]);
+        assert!(cfg.validate().is_ok());
+
+        cfg.files[0].lzma.as_mut().unwrap().dict_size = 1024;
+        let err = format!("{:#}", cfg.validate().unwrap_err());
+        assert!(err.contains("File 'x'") && err.contains("1024"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_lzma_options() {
+        let cfg: DzipConfig = toml::from_str(
+            r#"archives = ["a.dz"]
+base_dir = "."
+
+[[files]]
+path = "x"
+archive_file_index = 0
+compression = "Lzma"
+lzma = { dict_size = 65536 }
+"#,
+        )
+        .unwrap();
+        let lzma = cfg.files[0].lzma.unwrap();
+        assert_eq!(lzma.dict_size, 65536);
+
+        // The encoder's lc/lp/pb are fixed, so they are not accepted
+        let err = toml::from_str::<DzipConfig>(
+            r#"archives = ["a.dz"]
+base_dir = "."
+
+[[files]]
+path = "x"
+archive_file_index = 0
+compression = "Lzma"
+lzma = { dict_size = 65536, lc = 4 }
+"#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("unknown field `lc`"), "{}", err);
+    }
+
+    #[test]
+    fn test_compression_flags() {
+        let cfg: DzipConfig = toml::from_str(
+            r#"archives = ["a.dz"]
+base_dir = "."
+
+[[files]]
+path = "x"
+archive_file_index = 0
+compression = ["COMBUF", "zlib"]
+
+[[files]]
+path = "y"
+archive_file_index = 0
+compression = "Bzip"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            cfg.encoding(&cfg.files[0]).unwrap(),
+            (CompressionMethod::Zlib, dzip_core::format::CHUNK_COMBUF)
+        );
+        assert_eq!(
+            cfg.encoding(&cfg.files[1]).unwrap(),
+            (CompressionMethod::Bzip, 0)
+        );
+        assert!(cfg.validate().is_ok());
+
+        let mut cfg = cfg;
+        cfg.files[0].compression = Some(Compression::Flags(vec!["ZLIB".into(), "BZIP".into()]));
+        let err = format!("{:#}", cfg.validate().unwrap_err());
+        assert!(
+            err.contains("File 'x'") && err.contains("more than one codec"),
+            "{}",
+            err
+        );
+        cfg.files[0].compression = Some(Compression::Flags(vec!["COMBUF".into(), "FAST".into()]));
+        let err = format!("{:#}", cfg.validate().unwrap_err());
+        assert!(err.contains("FAST"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_file_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let list = dir.path().join("assets.txt");
+        std::fs::write(
+            &list,
+            "# generated\nImages/logo.bmp\n\n  Data\\text.txt  \n",
+        )
+        .unwrap();
+
+        let cfg = parse_file_list(&list, Some(CompressionMethod::Lzma)).unwrap();
+        assert_eq!(cfg.archives, ["assets.dz"]);
+        let paths: Vec<_> = cfg.files.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(
+            paths,
+            [
+                Path::new("Images").join("logo.bmp"),
+                Path::new("Data").join("text.txt")
+            ]
+        );
+        assert_eq!(cfg.compression(&cfg.files[0]), CompressionMethod::Lzma);
+
+        std::fs::write(&list, "ok.txt\n../escape.txt\n").unwrap();
+        let err = format!("{:#}", parse_file_list(&list, None).unwrap_err());
+        assert!(err.contains("Line 2"), "{}", err);
+    }
+
+    #[test]
+    fn test_config_version() {
+        let old: DzipConfig =
+            toml::from_str("archives = [\"a.dz\"]\nbase_dir = \".\"\nfiles = []\n").unwrap();
+        assert_eq!(old.config_version, 0);
+        assert_eq!(old.migrate().unwrap().config_version, CONFIG_VERSION);
+
+        let mut future = config(&["a.dz"], Vec::new());
+        future.config_version = CONFIG_VERSION + 1;
+        let err = future.migrate().unwrap_err().to_string();
+        assert!(err.contains("newer than the supported version"), "{}", err);
+
+        let written = toml::to_string(&config(&["a.dz"], Vec::new())).unwrap();
+        assert!(written.starts_with(&format!("config_version = {}", CONFIG_VERSION)));
+    }
+}
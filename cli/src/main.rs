@@ -12,6 +12,19 @@ struct Cli {
     #[arg(short, long, global = true)]
     verbose: bool,
 
+    /// Only print warnings and errors, without progress bars
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Draw progress bars even when stderr is not a terminal
+    /// (by default they only appear on a terminal)
+    #[arg(long, global = true, conflicts_with_all = ["quiet", "no_progress"])]
+    progress: bool,
+
+    /// Never draw progress bars
+    #[arg(long, global = true)]
+    no_progress: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -25,38 +38,194 @@ enum Commands {
         /// The output directory
         #[arg(short, long, default_value = ".")]
         output: String,
+        #[command(flatten)]
+        options: commands::unpack::UnpackOptions,
     },
     /// Pack a directory into a dzip file
     Pack {
-        /// The configuration file to pack (toml)
-        input: String,
+        /// The configuration file to pack (toml); not needed with --file-list
+        // Not a clap conflict with --file-list: clap skips the `requires` of
+        // --method when the missing --file-list conflicts with a present argument
+        #[arg(
+            required_unless_present_any = ["file_list", "batch"],
+            conflicts_with = "batch"
+        )]
+        input: Option<String>,
         /// The output directory
         #[arg(short, long, default_value = ".")]
         output: String,
+        #[command(flatten)]
+        options: commands::pack::PackOptions,
+    },
+    /// Re-compress an existing archive with a different compression method
+    Repack {
+        /// The dzip file to repack
+        input: String,
+        /// The new archive file (auxiliary volumes are written next to it)
+        output: String,
+        #[command(flatten)]
+        options: commands::repack::RepackOptions,
+    },
+    /// Rename files and directories inside an archive, rewriting only the header
+    Rename {
+        /// The dzip file to rename files in
+        input: String,
+        /// The new archive file (auxiliary volumes are copied next to it)
+        output: String,
+        #[command(flatten)]
+        options: commands::rename::RenameOptions,
+    },
+    /// Rebuild the chunk table of a damaged archive by scanning its data
+    Repair {
+        /// The damaged dzip file
+        input: String,
+        /// The repaired archive file
+        output: String,
+        #[command(flatten)]
+        options: commands::repair::RepairOptions,
+    },
+    /// List the chunk table of an archive without decompressing
+    Chunks {
+        /// Input archive file
+        input: String,
+        #[command(flatten)]
+        options: commands::chunks::ChunksOptions,
+    },
+    /// Dump the parsed header of an archive, as stored
+    Meta {
+        /// Input archive file
+        input: String,
+        #[command(flatten)]
+        options: commands::meta::MetaOptions,
+    },
+    /// Print the directory hierarchy of an archive, with file sizes
+    Tree {
+        /// Input archive file
+        input: String,
+        #[command(flatten)]
+        options: commands::tree::TreeOptions,
     },
     /// Verify and list archive contents
     Verify {
         /// Input archive file
         input: String,
+        #[command(flatten)]
+        options: commands::verify::VerifyOptions,
+    },
+    /// Print a SHA-256 or XXH3 hash of an archive and, optionally, its volumes
+    Hash {
+        /// Input archive file
+        input: String,
+        #[command(flatten)]
+        options: commands::hash::HashOptions,
+    },
+    /// Measure compression ratio and throughput of each codec on a file
+    Bench {
+        /// File to compress
+        input: String,
+        #[command(flatten)]
+        options: commands::bench::BenchOptions,
+    },
+    /// Pack and unpack a generated file tree with every codec to check the build
+    Selftest {
+        #[command(flatten)]
+        options: commands::selftest::SelftestOptions,
     },
 }
 
-fn main() -> Result<()> {
+fn main() -> std::process::ExitCode {
     let cli = Cli::parse();
 
-    let log_level = if cli.verbose { "debug" } else { "info" };
+    let log_level = if cli.verbose {
+        "debug"
+    } else if cli.quiet {
+        "warn"
+    } else {
+        "info"
+    };
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(log_level)).init();
+    commands::set_progress_mode(if cli.progress {
+        commands::ProgressMode::Always
+    } else if cli.no_progress {
+        commands::ProgressMode::Never
+    } else {
+        commands::ProgressMode::Auto
+    });
+
+    // Errors are printed with their message rather than their debug form
+    match run(&cli) {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::ExitCode::FAILURE
+        }
+    }
+}
 
+fn run(cli: &Cli) -> Result<()> {
     match &cli.command {
-        Commands::Unpack { input, output } => {
-            commands::unpack::unpack_archive(input, output)?;
+        Commands::Unpack {
+            input,
+            output,
+            options,
+        } => {
+            commands::unpack::unpack_archive(input, output, options)?;
+        }
+        Commands::Pack {
+            input,
+            output,
+            options,
+        } => {
+            if !options.batch.is_empty() {
+                commands::pack::pack_batch(&options.batch, output, options)?;
+            } else {
+                if let Some(input) = input {
+                    info!("Packing from config {} to output dir {}", input, output);
+                }
+                commands::pack::pack_archive(input.as_deref(), output, options)?;
+            }
+        }
+        Commands::Repack {
+            input,
+            output,
+            options,
+        } => {
+            commands::repack::repack_archive(input, output, options)?;
+        }
+        Commands::Rename {
+            input,
+            output,
+            options,
+        } => {
+            commands::rename::rename_archive(input, output, options)?;
+        }
+        Commands::Repair {
+            input,
+            output,
+            options,
+        } => {
+            commands::repair::repair_archive(input, output, options)?;
+        }
+        Commands::Chunks { input, options } => {
+            commands::chunks::list_chunks(input, options)?;
+        }
+        Commands::Meta { input, options } => {
+            commands::meta::dump_meta(input, options)?;
+        }
+        Commands::Tree { input, options } => {
+            commands::tree::print_tree(input, options)?;
+        }
+        Commands::Verify { input, options } => {
+            commands::verify::verify_archive(input, options)?;
+        }
+        Commands::Hash { input, options } => {
+            commands::hash::hash_archive(input, options)?;
         }
-        Commands::Pack { input, output } => {
-            info!("Packing from config {} to output dir {}", input, output);
-            commands::pack::pack_archive(input, output)?;
+        Commands::Bench { input, options } => {
+            commands::bench::bench_codecs(input, options)?;
         }
-        Commands::Verify { input } => {
-            commands::verify::verify_archive(input)?;
+        Commands::Selftest { options } => {
+            commands::selftest::run_selftest(options)?;
         }
     }
 
@@ -0,0 +1,32 @@
+mod common;
+
+use common::dzip;
+
+#[test]
+fn test_bench_json() {
+    let tmp = tempfile::tempdir().unwrap();
+    let input = tmp.path().join("input.txt");
+    std::fs::write(&input, "benchmark input ".repeat(500)).unwrap();
+
+    let out = dzip()
+        .arg("bench")
+        .arg(&input)
+        .args(["--methods", "copy,zlib,bzip,dz", "--json"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let rows: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    let rows = rows.as_array().unwrap();
+
+    // DZ has no encoder and is skipped; the rest are sorted by ratio
+    let methods: Vec<_> = rows.iter().map(|r| r["method"].as_str().unwrap()).collect();
+    assert_eq!(methods.len(), 3);
+    assert_eq!(methods[2], "Copy");
+    assert_eq!(rows[2]["ratio"], 1.0);
+    assert!(rows[0]["ratio"].as_f64().unwrap() < 0.1);
+    assert_eq!(rows[0]["input_bytes"], 8000);
+}
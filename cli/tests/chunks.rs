@@ -0,0 +1,129 @@
+mod common;
+
+use common::{dzip, test_data};
+
+#[test]
+fn test_chunks_json() {
+    let out = dzip()
+        .arg("chunks")
+        .arg(test_data("ExampleSplitArchive/testnew.dz"))
+        .arg("--json")
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let rows: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    let rows = rows.as_array().unwrap();
+    assert_eq!(rows.len(), 8);
+
+    assert_eq!(rows[3]["volume"], 1);
+    assert_eq!(rows[3]["flag_names"], serde_json::json!(["BZIP"]));
+    // Lengths are reported after size correction
+    assert_eq!(rows[3]["compressed_length"], 357);
+    assert_eq!(rows[6]["flag_names"], serde_json::json!(["ZERO"]));
+}
+
+#[test]
+fn test_chunks_sort() {
+    let rows = |sort: &str| -> Vec<serde_json::Value> {
+        let out = dzip()
+            .arg("chunks")
+            .arg(test_data("ExampleSplitArchive/testnew.dz"))
+            .args(["--json", "--sort", sort])
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        serde_json::from_slice(&out.stdout).unwrap()
+    };
+
+    let by_size = rows("size");
+    assert_eq!(by_size.len(), 8);
+    let sizes: Vec<u64> = by_size
+        .iter()
+        .map(|row| row["compressed_length"].as_u64().unwrap())
+        .collect();
+    assert!(sizes.windows(2).all(|w| w[0] >= w[1]), "{:?}", sizes);
+
+    let by_offset = rows("offset");
+    let locations: Vec<(u64, u64)> = by_offset
+        .iter()
+        .map(|row| {
+            (
+                row["volume"].as_u64().unwrap(),
+                row["offset"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert!(
+        locations.windows(2).all(|w| w[0] <= w[1]),
+        "{:?}",
+        locations
+    );
+
+    assert_eq!(rows("path").len(), 8);
+
+    // --limit keeps the first rows of the sorted table
+    let out = dzip()
+        .arg("chunks")
+        .arg(test_data("ExampleSplitArchive/testnew.dz"))
+        .args(["--json", "--sort", "size", "--limit", "3"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let limited: Vec<serde_json::Value> = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(limited, by_size[..3]);
+}
+
+#[test]
+fn test_flag_names() {
+    use dzip_core::format::*;
+    assert_eq!(flag_names(CHUNK_COMBUF | CHUNK_DZ), vec!["COMBUF", "DZ"]);
+    assert_eq!(
+        flag_names(CHUNK_ZLIB | 0x2 | 0x8000),
+        vec!["ZLIB", "0x2", "0x8000"]
+    );
+    assert!(flag_names(0).is_empty());
+}
+
+#[test]
+fn test_chunks_list_volumes() {
+    let out = dzip()
+        .arg("chunks")
+        .arg(test_data("ExampleSplitArchive/testnew.dz"))
+        .args(["--list-volumes", "--json"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let volumes: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    let volumes = volumes.as_array().unwrap();
+    assert_eq!(volumes.len(), 4);
+
+    assert_eq!(volumes[0]["name"], "testnew.dz");
+    assert_eq!(volumes[0]["files"], serde_json::json!(["Image16b.bmp"]));
+    assert_eq!(volumes[1]["name"], "testnew1.dz");
+    assert_eq!(volumes[1]["chunks"], 5);
+    assert_eq!(
+        volumes[1]["files"],
+        serde_json::json!([
+            "BMP\\Image16.bmp",
+            "BMP\\Image4.bmp",
+            "TXT\\Text1.txt",
+            "TXT\\Text3.txt"
+        ])
+    );
+    assert_eq!(volumes[3]["files"], serde_json::json!(["BMP\\Image8.bmp"]));
+
+    let out = dzip()
+        .arg("chunks")
+        .arg(test_data("ExampleSplitArchive/testnew.dz"))
+        .arg("--list-volumes")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        stdout.contains(
+            "Volume 3: testnew3.dz (1259 bytes in 1 chunks, 1 files)\n  BMP\\Image8.bmp\n"
+        ),
+        "{}",
+        stdout
+    );
+}
@@ -0,0 +1,131 @@
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+pub fn dzip() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_dzip-cli"))
+}
+
+pub fn test_data(rel: &str) -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("../test_data");
+    path.push(rel);
+    path
+}
+
+/// Runs `dzip-cli unpack <input> -o <output> [args...]` and asserts it succeeded.
+pub fn unpack(input: &Path, output: &Path, args: &[&str]) -> Output {
+    let out = dzip()
+        .arg("unpack")
+        .arg(input)
+        .arg("-o")
+        .arg(output)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "unpack failed: {}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    out
+}
+
+/// Runs `dzip-cli pack <config> -o <output> [args...]` and asserts it succeeded.
+pub fn pack(config: &Path, output: &Path, args: &[&str]) -> Output {
+    let out = dzip()
+        .arg("pack")
+        .arg(config)
+        .arg("-o")
+        .arg(output)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "pack failed: {}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    out
+}
+
+/// Lists all regular files below `dir` as `/`-separated relative paths, sorted.
+pub fn list_files(dir: &Path) -> Vec<String> {
+    fn walk(base: &Path, dir: &Path, out: &mut Vec<String>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                walk(base, &path, out);
+            } else {
+                let rel = path.strip_prefix(base).unwrap();
+                let parts: Vec<_> = rel.iter().map(|p| p.to_string_lossy()).collect();
+                out.push(parts.join("/"));
+            }
+        }
+    }
+    let mut files = Vec::new();
+    walk(dir, dir, &mut files);
+    files.sort();
+    files
+}
+
+/// Writes a single-volume archive with one chunk per file, all in the root directory.
+///
+/// `files` holds `(name, chunk flags, stored bytes)`; chunk lengths are taken from the
+/// stored bytes. `adjust` may modify the chunk table before the header is written.
+pub fn write_archive(
+    path: &Path,
+    files: &[(&str, u16, &[u8])],
+    adjust: impl Fn(&mut [dzip_core::Chunk]),
+) {
+    use dzip_core::format::*;
+    use dzip_core::writer::DzipWriter;
+    use std::io::Cursor;
+
+    let write_header = |data_start: u32| {
+        let mut chunks = Vec::new();
+        let mut offset = data_start;
+        for (_, flags, data) in files {
+            chunks.push(Chunk {
+                offset,
+                compressed_length: data.len() as u32,
+                decompressed_length: data.len() as u32,
+                flags: *flags,
+                file: 0,
+            });
+            offset += data.len() as u32;
+        }
+        adjust(&mut chunks);
+
+        let mut header = Vec::new();
+        let mut writer = DzipWriter::new(Cursor::new(&mut header));
+        writer
+            .write_archive_settings(&ArchiveSettings {
+                header: DTRZ_MAGIC,
+                num_user_files: files.len() as u16,
+                num_directories: 1,
+                version: 0,
+            })
+            .unwrap();
+        let names: Vec<String> = files.iter().map(|(name, _, _)| name.to_string()).collect();
+        writer.write_strings(&names).unwrap();
+        let map: Vec<(u16, Vec<u16>)> = (0..files.len()).map(|i| (0, vec![i as u16])).collect();
+        writer.write_file_chunk_map(&map).unwrap();
+        writer
+            .write_chunk_settings(&ChunkSettings {
+                num_archive_files: 1,
+                num_chunks: chunks.len() as u16,
+            })
+            .unwrap();
+        writer.write_chunks(&chunks).unwrap();
+        header
+    };
+
+    let header_len = write_header(0).len() as u32;
+    let mut archive = write_header(header_len);
+    for (_, _, data) in files {
+        archive.extend_from_slice(data);
+    }
+    std::fs::write(path, archive).unwrap();
+}
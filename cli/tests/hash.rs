@@ -0,0 +1,37 @@
+mod common;
+
+use common::{dzip, test_data};
+use sha2::{Digest, Sha256};
+
+#[test]
+fn test_hash_archive_and_volumes() {
+    let archive = test_data("ExampleSplitArchive/testnew.dz");
+    let out = dzip().arg("hash").arg(&archive).output().unwrap();
+    assert!(out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let expected = format!("{:x}", Sha256::digest(std::fs::read(&archive).unwrap()));
+    assert_eq!(
+        stdout.trim_end(),
+        format!("{}  {}", expected, archive.display())
+    );
+
+    let out = dzip()
+        .arg("hash")
+        .arg(&archive)
+        .args(["--algo", "xxh3", "--volumes"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines.len(), 4, "{}", stdout);
+    for (line, name) in
+        lines
+            .iter()
+            .zip(["testnew.dz", "testnew1.dz", "testnew2.dz", "testnew3.dz"])
+    {
+        let (digest, path) = line.split_once("  ").unwrap();
+        assert_eq!(digest.len(), 16);
+        assert!(path.ends_with(name), "{}", line);
+    }
+}
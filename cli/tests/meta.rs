@@ -0,0 +1,66 @@
+mod common;
+
+use common::{dzip, test_data};
+
+fn meta_json(input: &std::path::Path) -> serde_json::Value {
+    let out = dzip()
+        .arg("meta")
+        .arg(input)
+        .arg("--json")
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    serde_json::from_slice(&out.stdout).unwrap()
+}
+
+#[test]
+fn test_meta_json() {
+    let meta = meta_json(&test_data("ExampleSplitArchive/testnew.dz"));
+    assert_eq!(meta["settings"]["header"], 0x5A525444);
+    assert_eq!(meta["settings"]["num_user_files"], 7);
+    assert_eq!(meta["strings"].as_array().unwrap().len(), 9);
+    assert_eq!(meta["strings"][7], "BMP");
+    assert_eq!(
+        meta["map"][1],
+        serde_json::json!({ "dir_id": 1, "chunk_ids": [1, 2] })
+    );
+    assert_eq!(meta["chunk_settings"]["num_chunks"], 8);
+    // Chunk records are raw: the placeholder length is not corrected
+    assert_eq!(
+        meta["chunks"][3],
+        serde_json::json!({
+            "offset": 0,
+            "compressed_length": 2166,
+            "decompressed_length": 2166,
+            "flags": 0x10,
+            "file": 1
+        })
+    );
+    assert_eq!(
+        meta["volume_files"],
+        serde_json::json!(["testnew1.dz", "testnew2.dz", "testnew3.dz"])
+    );
+    assert_eq!(meta["range_settings"], serde_json::Value::Null);
+    assert_eq!(meta["endianness"], "little");
+    assert_eq!(meta["archive_offset"], 0);
+}
+
+#[test]
+fn test_meta_after_stub() {
+    let tmp = tempfile::tempdir().unwrap();
+    let mut data = vec![0u8; 512];
+    data.extend_from_slice(&std::fs::read(test_data("ExampleSingleArchive/test1.dz")).unwrap());
+    let input = tmp.path().join("test1.exe");
+    std::fs::write(&input, &data).unwrap();
+
+    let meta = meta_json(&input);
+    assert_eq!(meta["archive_offset"], 512);
+    assert_eq!(
+        meta["chunks"],
+        meta_json(&test_data("ExampleSingleArchive/test1.dz"))["chunks"]
+    );
+}
@@ -0,0 +1,1293 @@
+mod common;
+
+use common::pack;
+
+#[test]
+fn test_pack_stats_json() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::write(src.join("a.txt"), "compressible ".repeat(200)).unwrap();
+    std::fs::write(src.join("b.bin"), b"raw bytes").unwrap();
+
+    let config = src.join("test.toml");
+    std::fs::write(
+        &config,
+        r#"archives = ["test.dz"]
+base_dir = "."
+
+[[files]]
+path = "a.txt"
+archive_file_index = 0
+compression = "Zlib"
+
+[[files]]
+path = "b.bin"
+archive_file_index = 0
+compression = "Copy"
+"#,
+    )
+    .unwrap();
+
+    let out = pack(&config, &tmp.path().join("out"), &["--stats-json"]);
+    let stats: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+
+    assert_eq!(stats["files"], 2);
+    assert_eq!(stats["input_bytes"], 2600 + 9);
+    assert_eq!(stats["codecs"]["Copy"]["output_bytes"], 9);
+    assert_eq!(stats["codecs"]["Zlib"]["files"], 1);
+    assert!(stats["ratio"].as_f64().unwrap() < 1.0);
+}
+
+#[test]
+fn test_pack_xz_roundtrip() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(&src).unwrap();
+    let contents = "xz framed ".repeat(100);
+    std::fs::write(src.join("a.txt"), &contents).unwrap();
+
+    let config = src.join("test.toml");
+    std::fs::write(
+        &config,
+        r#"archives = ["test.dz"]
+base_dir = "."
+
+[[files]]
+path = "a.txt"
+archive_file_index = 0
+compression = "XZ"
+"#,
+    )
+    .unwrap();
+
+    let packed = tmp.path().join("packed");
+    pack(&config, &packed, &[]);
+    let out = tmp.path().join("out");
+    common::unpack(&packed.join("test.dz"), &out, &[]);
+
+    assert_eq!(
+        std::fs::read_to_string(out.join("a.txt")).unwrap(),
+        contents
+    );
+    let config = std::fs::read_to_string(out.join("test.toml")).unwrap();
+    assert!(config.contains("compression = \"XZ\""), "{}", config);
+}
+
+#[test]
+fn test_pack_brotli_roundtrip() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(&src).unwrap();
+    let contents = "menu.start = Start game\nmenu.quit = Quit\n".repeat(100);
+    std::fs::write(src.join("best.txt"), &contents).unwrap();
+    std::fs::write(src.join("fast.txt"), &contents).unwrap();
+
+    let config = src.join("test.toml");
+    std::fs::write(
+        &config,
+        r#"archives = ["test.dz"]
+base_dir = "."
+
+[[files]]
+path = "best.txt"
+archive_file_index = 0
+compression = "BROTLI"
+
+[[files]]
+path = "fast.txt"
+archive_file_index = 0
+compression = "BROTLI"
+brotli = { quality = 1 }
+"#,
+    )
+    .unwrap();
+
+    for streaming in [false, true] {
+        let packed = tmp.path().join(format!("packed-{}", streaming));
+        pack(
+            &config,
+            &packed,
+            if streaming { &["--streaming"] } else { &[] },
+        );
+        let out = tmp.path().join(format!("out-{}", streaming));
+        common::unpack(&packed.join("test.dz"), &out, &[]);
+
+        for name in ["best.txt", "fast.txt"] {
+            assert_eq!(std::fs::read_to_string(out.join(name)).unwrap(), contents);
+        }
+        let config = std::fs::read_to_string(out.join("test.toml")).unwrap();
+        assert!(config.contains("compression = \"BROTLI\""), "{}", config);
+    }
+
+    std::fs::write(
+        &config,
+        std::fs::read_to_string(&config)
+            .unwrap()
+            .replace("quality = 1", "quality = 12"),
+    )
+    .unwrap();
+    let out = common::dzip()
+        .arg("pack")
+        .arg(&config)
+        .arg("-o")
+        .arg(tmp.path().join("invalid"))
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Brotli quality 12"), "{}", stderr);
+}
+
+#[test]
+fn test_pack_combined_flags() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(&src).unwrap();
+    let contents = "combuf wrapped ".repeat(100);
+    std::fs::write(src.join("a.txt"), &contents).unwrap();
+
+    let config = src.join("test.toml");
+    std::fs::write(
+        &config,
+        r#"archives = ["test.dz"]
+base_dir = "."
+
+[[files]]
+path = "a.txt"
+archive_file_index = 0
+compression = ["COMBUF", "ZLIB"]
+"#,
+    )
+    .unwrap();
+
+    for args in [&[][..], &["--streaming"][..]] {
+        let packed = tmp.path().join("packed");
+        pack(&config, &packed, args);
+        let out = tmp.path().join("out");
+        common::unpack(&packed.join("test.dz"), &out, &[]);
+
+        assert_eq!(
+            std::fs::read_to_string(out.join("a.txt")).unwrap(),
+            contents
+        );
+        // The flags survive a round trip through the generated config
+        let config = std::fs::read_to_string(out.join("test.toml")).unwrap();
+        let flags: String = config.split_whitespace().collect();
+        assert!(
+            flags.contains("compression=[\"COMBUF\",\"ZLIB\",]"),
+            "{}",
+            config
+        );
+        std::fs::remove_dir_all(&packed).unwrap();
+        std::fs::remove_dir_all(&out).unwrap();
+    }
+}
+
+/// Writes five 400-byte files to `src` and a config that packs them into `game.dz`.
+fn split_config(src: &std::path::Path) -> std::path::PathBuf {
+    std::fs::create_dir_all(src).unwrap();
+    let mut config = String::from("archives = [\"game.dz\"]\nbase_dir = \".\"\n");
+    for i in 0..5 {
+        std::fs::write(src.join(format!("{}.bin", i)), vec![i as u8; 400]).unwrap();
+        config.push_str(&format!(
+            "\n[[files]]\npath = \"{}.bin\"\narchive_file_index = 0\ncompression = \"Copy\"\n",
+            i
+        ));
+    }
+    let config_path = src.join("game.toml");
+    std::fs::write(&config_path, config).unwrap();
+    config_path
+}
+
+#[test]
+fn test_pack_split_size() {
+    let tmp = tempfile::tempdir().unwrap();
+    let config_path = split_config(&tmp.path().join("src"));
+
+    let packed = tmp.path().join("packed");
+    pack(&config_path, &packed, &["--split-size", "1000"]);
+    // Volume 0 also holds the header, which leaves room for two files
+    let sizes: Vec<(String, u64)> = common::list_files(&packed)
+        .into_iter()
+        .map(|name| {
+            let size = std::fs::metadata(packed.join(&name)).unwrap().len();
+            (name, size)
+        })
+        .collect();
+    assert_eq!(sizes.len(), 3, "{:?}", sizes);
+    assert_eq!(sizes[0], ("game.001".to_string(), 800));
+    assert_eq!(sizes[1], ("game.002".to_string(), 400));
+    assert!(sizes[2].0 == "game.dz" && (800..=1000).contains(&sizes[2].1));
+
+    let out = tmp.path().join("out");
+    common::unpack(&packed.join("game.dz"), &out, &[]);
+    for i in 0..5u8 {
+        assert_eq!(
+            std::fs::read(out.join(format!("{}.bin", i))).unwrap(),
+            vec![i; 400]
+        );
+    }
+    let config = std::fs::read_to_string(out.join("game.toml")).unwrap();
+    assert!(config.contains("\"game.002\""), "{}", config);
+}
+
+#[test]
+fn test_pack_split_size_header_crc() {
+    let tmp = tempfile::tempdir().unwrap();
+    let config_path = split_config(&tmp.path().join("src"));
+
+    // A split size that exactly fits the header and two files, without the CRC
+    let plain = tmp.path().join("plain");
+    pack(&config_path, &plain, &["--split-size", "1000"]);
+    let budget = std::fs::metadata(plain.join("game.dz")).unwrap().len();
+
+    let packed = tmp.path().join("packed");
+    let split_size = budget.to_string();
+    pack(
+        &config_path,
+        &packed,
+        &["--split-size", &split_size, "--header-crc"],
+    );
+    // The 8-byte trailer no longer fits, so the second file moves out of volume 0
+    let main_len = std::fs::metadata(packed.join("game.dz")).unwrap().len();
+    assert_eq!(main_len, budget - 400 + 8);
+
+    let out = tmp.path().join("out");
+    common::unpack(&packed.join("game.dz"), &out, &[]);
+    for i in 0..5u8 {
+        assert_eq!(
+            std::fs::read(out.join(format!("{}.bin", i))).unwrap(),
+            vec![i; 400]
+        );
+    }
+}
+
+#[test]
+fn test_pack_empty_dirs() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(src.join("data")).unwrap();
+    std::fs::write(src.join("data/a.txt"), b"contents").unwrap();
+
+    let config = src.join("test.toml");
+    std::fs::write(
+        &config,
+        r#"archives = ["test.dz"]
+base_dir = "."
+empty_dirs = ["saves", "data", "cache\\levels"]
+
+[[files]]
+path = "data/a.txt"
+archive_file_index = 0
+compression = "Copy"
+"#,
+    )
+    .unwrap();
+
+    let packed = tmp.path().join("packed");
+    pack(&config, &packed, &[]);
+    let out = tmp.path().join("out");
+    common::unpack(&packed.join("test.dz"), &out, &[]);
+
+    assert!(out.join("saves").is_dir());
+    assert!(out.join("cache/levels").is_dir());
+    assert_eq!(std::fs::read(out.join("data/a.txt")).unwrap(), b"contents");
+    // "data" holds a file, so it is not an empty directory
+    let config = std::fs::read_to_string(out.join("test.toml")).unwrap();
+    let config: toml::Value = toml::from_str(&config).unwrap();
+    assert_eq!(
+        config["empty_dirs"],
+        toml::Value::from(vec!["saves", "cache/levels"])
+    );
+}
+
+#[test]
+fn test_pack_header_crc() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::write(src.join("a.txt"), "checked ".repeat(50)).unwrap();
+    let config = src.join("test.toml");
+    std::fs::write(
+        &config,
+        r#"archives = ["test.dz"]
+base_dir = "."
+
+[[files]]
+path = "a.txt"
+archive_file_index = 0
+compression = "Zlib"
+"#,
+    )
+    .unwrap();
+
+    let packed = tmp.path().join("packed");
+    pack(&config, &packed, &["--header-crc"]);
+    let archive = packed.join("test.dz");
+    let verify = |path: &std::path::Path| common::dzip().arg("verify").arg(path).output().unwrap();
+    let out = verify(&archive);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(out.status.success());
+    assert!(stdout.contains("(valid)"), "{}", stdout);
+    // The trailer is not mistaken for chunk data
+    assert!(!stdout.contains("Chunk layout issues"), "{}", stdout);
+
+    // Corrupt the compressed length of the only chunk
+    let mut data = std::fs::read(&archive).unwrap();
+    // (header: settings 9, "a.txt" 6, map 6, chunk settings 4, chunk 16)
+    let header_len = 9 + 6 + 6 + 4 + 16;
+    data[header_len - 10] ^= 0xff;
+    let corrupt = packed.join("corrupt.dz");
+    std::fs::write(&corrupt, data).unwrap();
+    let out = verify(&corrupt);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Header corrupt"));
+}
+
+#[test]
+fn test_pack_base_dir_override() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("data");
+    let conf_dir = tmp.path().join("configs");
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::create_dir_all(&conf_dir).unwrap();
+    std::fs::write(src.join("a.txt"), b"from data dir").unwrap();
+
+    let config = conf_dir.join("test.toml");
+    std::fs::write(
+        &config,
+        r#"archives = ["test.dz"]
+base_dir = "does-not-exist"
+
+[[files]]
+path = "a.txt"
+archive_file_index = 0
+compression = "Copy"
+"#,
+    )
+    .unwrap();
+
+    let out_dir = tmp.path().join("out");
+    let base_dir = src.to_str().unwrap();
+    pack(&config, &out_dir, &["--base-dir", base_dir]);
+    let archive = std::fs::read(out_dir.join("test.dz")).unwrap();
+    assert!(archive.ends_with(b"from data dir"));
+}
+
+#[test]
+fn test_pack_first_chunk_follows_header() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(src.join("sub/dir")).unwrap();
+    std::fs::write(src.join("a.txt"), b"root file").unwrap();
+    std::fs::write(src.join("sub/dir/b.bin"), b"nested").unwrap();
+
+    let config = src.join("test.toml");
+    std::fs::write(
+        &config,
+        r#"archives = ["test.dz", "test.d01"]
+base_dir = "."
+
+[[files]]
+path = "sub/dir/b.bin"
+archive_file_index = 1
+compression = "Copy"
+
+[[files]]
+path = "a.txt"
+archive_file_index = 0
+compression = "Zlib"
+"#,
+    )
+    .unwrap();
+
+    let packed = tmp.path().join("packed");
+    pack(&config, &packed, &[]);
+    let file = std::fs::File::open(packed.join("test.dz")).unwrap();
+    let header = dzip_core::reader::DzipReader::new(file)
+        .read_header()
+        .unwrap();
+
+    let header_size = dzip_core::compute_header_size(&dzip_core::ArchivePlan {
+        strings: &header.strings,
+        num_files: header.map.len(),
+        num_chunks: header.chunks.len(),
+        volume_files: &header.volume_files,
+        range_settings: header.range_settings.is_some(),
+    });
+    let first = header.chunks.iter().find(|c| c.file == 0).unwrap();
+    assert_eq!(first.offset as u64, header_size);
+}
+
+#[test]
+fn test_pack_merge_case() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(src.join("Textures")).unwrap();
+    std::fs::create_dir_all(src.join("textures")).unwrap();
+    std::fs::write(src.join("Textures/a.png"), b"a").unwrap();
+    std::fs::write(src.join("textures/b.png"), b"b").unwrap();
+
+    let config = src.join("test.toml");
+    std::fs::write(
+        &config,
+        r#"archives = ["test.dz"]
+base_dir = "."
+
+[[files]]
+path = "Textures/a.png"
+archive_file_index = 0
+compression = "Copy"
+
+[[files]]
+path = "textures/b.png"
+archive_file_index = 0
+compression = "Copy"
+"#,
+    )
+    .unwrap();
+
+    let read_dirs = |dir: &std::path::Path| {
+        let file = std::fs::File::open(dir.join("test.dz")).unwrap();
+        let header = dzip_core::reader::DzipReader::new(file)
+            .read_header()
+            .unwrap();
+        (1..header.settings.num_directories)
+            .map(|id| header.directory(id).unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    let plain = tmp.path().join("plain");
+    pack(&config, &plain, &[]);
+    assert_eq!(read_dirs(&plain), ["Textures", "textures"]);
+
+    let merged = tmp.path().join("merged");
+    let out = pack(&config, &merged, &["--merge-case"]);
+    assert_eq!(read_dirs(&merged), ["Textures"]);
+    assert!(
+        String::from_utf8_lossy(&out.stderr)
+            .contains("Merging directory 'textures' into 'Textures'")
+    );
+
+    let unpacked = tmp.path().join("out");
+    common::unpack(&merged.join("test.dz"), &unpacked, &[]);
+    assert_eq!(
+        common::list_files(&unpacked.join("Textures")),
+        ["a.png", "b.png"]
+    );
+}
+
+#[test]
+fn test_pack_streaming_matches_buffered() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(src.join("sub")).unwrap();
+    std::fs::write(src.join("a.txt"), "streamed ".repeat(500)).unwrap();
+    std::fs::write(src.join("sub/b.bin"), [7u8; 4096]).unwrap();
+    std::fs::write(src.join("sub/c.txt"), "lzma ".repeat(300)).unwrap();
+
+    let config = src.join("test.toml");
+    std::fs::write(
+        &config,
+        r#"archives = ["test.dz", "test.d01"]
+base_dir = "."
+
+[[files]]
+path = "a.txt"
+archive_file_index = 0
+compression = "Zlib"
+
+[[files]]
+path = "sub/b.bin"
+archive_file_index = 1
+compression = "Bzip"
+
+[[files]]
+path = "sub/c.txt"
+archive_file_index = 0
+compression = "Lzma"
+lzma = { dict_size = 65536 }
+"#,
+    )
+    .unwrap();
+
+    let buffered = tmp.path().join("buffered");
+    let streamed = tmp.path().join("streamed");
+    pack(&config, &buffered, &[]);
+    pack(&config, &streamed, &["--streaming"]);
+    for name in ["test.dz", "test.d01"] {
+        assert_eq!(
+            std::fs::read(streamed.join(name)).unwrap(),
+            std::fs::read(buffered.join(name)).unwrap(),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_pack_priority_order_matches_streaming() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(src.join("sub")).unwrap();
+    std::fs::write(src.join("a.txt"), "first in config ".repeat(50)).unwrap();
+    std::fs::write(src.join("sub/b.bin"), [3u8; 2048]).unwrap();
+    std::fs::write(src.join("sub/c.txt"), "packed first ".repeat(80)).unwrap();
+
+    let config = src.join("test.toml");
+    std::fs::write(
+        &config,
+        r#"archives = ["test.dz", "test.d01"]
+base_dir = "."
+
+[[files]]
+path = "a.txt"
+archive_file_index = 0
+compression = "Zlib"
+
+[[files]]
+path = "sub/b.bin"
+archive_file_index = 1
+compression = "Copy"
+
+[[files]]
+path = "sub/c.txt"
+archive_file_index = 0
+compression = "Bzip"
+priority = -1
+"#,
+    )
+    .unwrap();
+
+    let buffered = tmp.path().join("buffered");
+    let streamed = tmp.path().join("streamed");
+    pack(&config, &buffered, &[]);
+    pack(&config, &streamed, &["--streaming"]);
+    for name in ["test.dz", "test.d01"] {
+        assert_eq!(
+            std::fs::read(streamed.join(name)).unwrap(),
+            std::fs::read(buffered.join(name)).unwrap(),
+            "{}",
+            name
+        );
+    }
+
+    // Chunk ids follow the priority order, and offsets grow with the id in each volume
+    let header =
+        dzip_core::reader::DzipReader::new(std::fs::File::open(buffered.join("test.dz")).unwrap())
+            .read_header()
+            .unwrap();
+    let names: Vec<String> = (0..header.map.len())
+        .map(|i| header.file_path(i).unwrap().replace('\\', "/"))
+        .collect();
+    assert_eq!(names, ["sub/c.txt", "a.txt", "sub/b.bin"]);
+    for (i, (_, chunk_ids)) in header.map.iter().enumerate() {
+        assert_eq!(chunk_ids, &[i as u16]);
+    }
+    for volume in 0..2 {
+        let offsets: Vec<u32> = header
+            .chunks
+            .iter()
+            .filter(|c| c.file == volume)
+            .map(|c| c.offset)
+            .collect();
+        assert!(offsets.windows(2).all(|w| w[0] < w[1]), "{:?}", offsets);
+    }
+}
+
+#[test]
+fn test_pack_output_name() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::write(src.join("a.txt"), "main").unwrap();
+    std::fs::write(src.join("b.txt"), "aux").unwrap();
+
+    let config = src.join("test.toml");
+    std::fs::write(
+        &config,
+        r#"archives = ["test.dz", "test.d01"]
+base_dir = "."
+
+[[files]]
+path = "a.txt"
+archive_file_index = 0
+compression = "Copy"
+
+[[files]]
+path = "b.txt"
+archive_file_index = 1
+compression = "Copy"
+"#,
+    )
+    .unwrap();
+
+    let packed = tmp.path().join("packed");
+    pack(&config, &packed, &["--output-name", "renamed.dz"]);
+    assert!(!packed.join("test.dz").exists());
+    let out = tmp.path().join("out");
+    common::unpack(&packed.join("renamed.dz"), &out, &[]);
+    assert_eq!(std::fs::read_to_string(out.join("b.txt")).unwrap(), "aux");
+    // Only the auxiliary volume is listed in the header
+    let unpacked = std::fs::read_to_string(out.join("renamed.toml")).unwrap();
+    assert!(unpacked.contains("\"test.d01\""), "{}", unpacked);
+
+    for name in ["sub/renamed.dz", "test.d01"] {
+        let output = common::dzip()
+            .arg("pack")
+            .arg(&config)
+            .args(["-o", packed.to_str().unwrap(), "--output-name", name])
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "{}", name);
+    }
+}
+
+#[test]
+fn test_pack_is_reproducible() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    let mut files = String::new();
+    for (i, dir) in ["z", "a/b", "m", "a", "z/y", "b", "a/b"].iter().enumerate() {
+        std::fs::create_dir_all(src.join(dir)).unwrap();
+        let path = format!("{}/f{}.txt", dir, i);
+        std::fs::write(src.join(&path), format!("file {} ", i).repeat(50)).unwrap();
+        files.push_str(&format!(
+            "\n[[files]]\npath = \"{}\"\narchive_file_index = 0\ncompression = \"{}\"\n",
+            path,
+            ["Zlib", "Gzip", "Bzip", "Lzma", "Xz", "Copy", "Zlib"][i]
+        ));
+    }
+
+    let config = src.join("test.toml");
+    std::fs::write(
+        &config,
+        format!(
+            "archives = [\"test.dz\"]\nbase_dir = \".\"\nempty_dirs = [\"q\", \"c/d\"]\n{}",
+            files
+        ),
+    )
+    .unwrap();
+
+    let first = tmp.path().join("first");
+    let second = tmp.path().join("second");
+    pack(&config, &first, &[]);
+    pack(&config, &second, &[]);
+    assert_eq!(
+        std::fs::read(first.join("test.dz")).unwrap(),
+        std::fs::read(second.join("test.dz")).unwrap()
+    );
+
+    // A bounded compression pool changes the scheduling, not the output
+    let bounded = tmp.path().join("bounded");
+    pack(&config, &bounded, &["--compression-threads", "1"]);
+    assert_eq!(
+        std::fs::read(first.join("test.dz")).unwrap(),
+        std::fs::read(bounded.join("test.dz")).unwrap()
+    );
+
+    // --reproducible checks every codec output and produces the same bytes
+    let checked = tmp.path().join("checked");
+    pack(&config, &checked, &["--reproducible"]);
+    assert_eq!(
+        std::fs::read(first.join("test.dz")).unwrap(),
+        std::fs::read(checked.join("test.dz")).unwrap()
+    );
+    let output = common::dzip()
+        .arg("pack")
+        .arg(&config)
+        .args(["--reproducible", "--streaming"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_pack_empty_config() {
+    let tmp = tempfile::tempdir().unwrap();
+    let config = tmp.path().join("empty.toml");
+    std::fs::write(
+        &config,
+        "archives = [\"empty.dz\"]\nbase_dir = \".\"\nfiles = []\n",
+    )
+    .unwrap();
+
+    let packed = tmp.path().join("packed");
+    let output = pack(&config, &packed, &["--stats"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Config lists no files"));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Packed 0 files"));
+
+    let out = tmp.path().join("out");
+    common::unpack(&packed.join("empty.dz"), &out, &[]);
+    let unpacked = std::fs::read_to_string(out.join("empty.toml")).unwrap();
+    assert!(unpacked.contains("files = []"), "{}", unpacked);
+}
+
+#[test]
+fn test_pack_file_list() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(src.join("Data")).unwrap();
+    std::fs::write(src.join("a.txt"), "listed ".repeat(50)).unwrap();
+    std::fs::write(src.join("Data/b.txt"), "also listed").unwrap();
+    std::fs::write(src.join("unlisted.txt"), "skipped").unwrap();
+    let list = src.join("assets.txt");
+    std::fs::write(&list, "# from the build\na.txt\n\nData/b.txt\n").unwrap();
+
+    let packed = tmp.path().join("packed");
+    let out = common::dzip()
+        .arg("pack")
+        .arg("--file-list")
+        .arg(&list)
+        .args(["--method", "zlib", "-o"])
+        .arg(&packed)
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "pack failed: {}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let out = tmp.path().join("out");
+    common::unpack(&packed.join("assets.dz"), &out, &[]);
+    assert_eq!(
+        common::list_files(&out),
+        ["Data/b.txt", "a.txt", "assets.toml"]
+    );
+    assert_eq!(
+        std::fs::read_to_string(out.join("Data/b.txt")).unwrap(),
+        "also listed"
+    );
+    let config = std::fs::read_to_string(out.join("assets.toml")).unwrap();
+    assert!(config.contains("compression = \"Zlib\""), "{}", config);
+
+    // --method only applies to --file-list
+    let out = common::dzip()
+        .arg("pack")
+        .arg(out.join("assets.toml"))
+        .args(["--method", "zlib"])
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains("the following required arguments were not provided")
+            && stderr.contains("--file-list <PATH>"),
+        "{}",
+        stderr
+    );
+
+    // A config and --file-list exclude each other
+    let out = common::dzip()
+        .arg("pack")
+        .arg(tmp.path().join("out/assets.toml"))
+        .arg("--file-list")
+        .arg(&list)
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains("cannot be combined with --file-list"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_pack_config_bom_and_syntax_error() {
+    let tmp = tempfile::tempdir().unwrap();
+    std::fs::write(tmp.path().join("a.txt"), "saved on Windows").unwrap();
+    // Byte order mark and CRLF line endings, as Notepad saves them
+    let config = tmp.path().join("bom.toml");
+    std::fs::write(
+        &config,
+        "\u{feff}archives = [\"bom.dz\"]\r\nbase_dir = \".\"\r\n\
+         files = [{ path = \"a.txt\", archive_file_index = 0, compression = \"Copy\" }]\r\n",
+    )
+    .unwrap();
+    pack(&config, &tmp.path().join("packed"), &[]);
+
+    let list = tmp.path().join("list.txt");
+    std::fs::write(&list, "\u{feff}a.txt\r\n").unwrap();
+    let out = common::dzip()
+        .arg("pack")
+        .arg("--file-list")
+        .arg(&list)
+        .arg("-o")
+        .arg(tmp.path().join("listed"))
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "pack failed: {}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    // Syntax errors name the line and column
+    let config = tmp.path().join("bad.toml");
+    std::fs::write(&config, "archives = [\"bad.dz\"\nbase_dir = \".\"\n").unwrap();
+    let out = common::dzip()
+        .arg("pack")
+        .arg(&config)
+        .arg("-o")
+        .arg(tmp.path().join("bad"))
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains("bad.toml:2:1: missing comma between array elements"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_pack_priority_order() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(&src).unwrap();
+    for name in ["cold.bin", "plain.bin", "hot.bin", "warm.bin"] {
+        std::fs::write(src.join(name), name.repeat(10)).unwrap();
+    }
+
+    let config = src.join("test.toml");
+    std::fs::write(
+        &config,
+        r#"archives = ["test.dz"]
+base_dir = "."
+
+[[files]]
+path = "cold.bin"
+archive_file_index = 0
+priority = 10
+
+[[files]]
+path = "plain.bin"
+archive_file_index = 0
+
+[[files]]
+path = "hot.bin"
+archive_file_index = 0
+priority = -5
+
+[[files]]
+path = "warm.bin"
+archive_file_index = 0
+"#,
+    )
+    .unwrap();
+
+    let packed = tmp.path().join("packed");
+    pack(&config, &packed, &[]);
+    let file = std::fs::File::open(packed.join("test.dz")).unwrap();
+    let header = dzip_core::reader::DzipReader::new(file)
+        .read_header()
+        .unwrap();
+
+    // Lowest priority first; unset counts as 0 and keeps config order
+    assert_eq!(
+        header.strings,
+        ["hot.bin", "plain.bin", "warm.bin", "cold.bin"]
+    );
+    let offsets: Vec<u32> = header
+        .map
+        .iter()
+        .map(|(_, ids)| header.chunks[ids[0] as usize].offset)
+        .collect();
+    assert!(offsets.is_sorted(), "{:?}", offsets);
+}
+
+#[test]
+fn test_pack_batch() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::write(src.join("a.txt"), "first").unwrap();
+    std::fs::write(src.join("b.txt"), "second").unwrap();
+    let write_config = |name: &str, archive: &str, file: &str| {
+        let path = src.join(name);
+        std::fs::write(
+            &path,
+            format!(
+                "archives = [\"{}\"]\nbase_dir = \".\"\n\n[[files]]\npath = \"{}\"\narchive_file_index = 0\n",
+                archive, file
+            ),
+        )
+        .unwrap();
+        path
+    };
+    let configs = [
+        write_config("one.toml", "one.dz", "a.txt"),
+        write_config("broken.toml", "broken.dz", "missing.txt"),
+        write_config("two.toml", "two.dz", "b.txt"),
+        write_config("again.toml", "one.dz", "b.txt"),
+    ];
+
+    let packed = tmp.path().join("packed");
+    let out = common::dzip()
+        .arg("pack")
+        .arg("--batch")
+        .args(&configs)
+        .arg("-o")
+        .arg(&packed)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(!out.status.success());
+    assert!(
+        stderr.contains("2 of 4 configs failed to pack"),
+        "{}",
+        stderr
+    );
+    let line = |config: &std::path::Path| {
+        let name = config.display().to_string();
+        stdout
+            .lines()
+            .find(|line| line.starts_with(&name))
+            .unwrap_or_else(|| panic!("no line for {}: {}", name, stdout))
+            .to_string()
+    };
+    assert!(line(&configs[0]).ends_with("| OK"), "{}", stdout);
+    assert!(line(&configs[1]).contains("| FAIL"), "{}", stdout);
+    assert!(line(&configs[2]).ends_with("| OK"), "{}", stdout);
+    assert!(line(&configs[3]).contains("writes one.dz"), "{}", stdout);
+
+    // The failures did not stop the other configs
+    let out = tmp.path().join("out");
+    common::unpack(&packed.join("one.dz"), &out, &[]);
+    assert_eq!(std::fs::read_to_string(out.join("a.txt")).unwrap(), "first");
+    common::unpack(&packed.join("two.dz"), &out, &[]);
+    assert_eq!(
+        std::fs::read_to_string(out.join("b.txt")).unwrap(),
+        "second"
+    );
+}
+
+#[test]
+fn test_pack_xor_key() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::write(src.join("a.txt"), "obfuscated ".repeat(100)).unwrap();
+    std::fs::write(src.join("b.txt"), "stored").unwrap();
+    let config = src.join("test.toml");
+    std::fs::write(
+        &config,
+        r#"archives = ["test.dz"]
+base_dir = "."
+
+[[files]]
+path = "a.txt"
+archive_file_index = 0
+compression = "Zlib"
+
+[[files]]
+path = "b.txt"
+archive_file_index = 0
+"#,
+    )
+    .unwrap();
+
+    let packed = tmp.path().join("packed");
+    pack(&config, &packed, &["--xor-key", "5a3c"]);
+    let streamed = tmp.path().join("streamed");
+    pack(&config, &streamed, &["--xor-key", "5a3c", "--streaming"]);
+    let archive = std::fs::read(packed.join("test.dz")).unwrap();
+    assert_eq!(std::fs::read(streamed.join("test.dz")).unwrap(), archive);
+    assert!(!archive.windows(6).any(|w| w == b"stored"));
+
+    // Without the key the zlib chunk cannot be decoded
+    let out = common::dzip()
+        .arg("verify")
+        .arg(packed.join("test.dz"))
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("1 OK, 1 FAIL"), "{}", stdout);
+    let out = common::dzip()
+        .arg("verify")
+        .arg(packed.join("test.dz"))
+        .args(["--xor-key", "5a3c"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("2 OK, 0 FAIL"), "{}", stdout);
+
+    let out = tmp.path().join("out");
+    common::unpack(&packed.join("test.dz"), &out, &["--xor-key", "5a3c"]);
+    assert_eq!(
+        std::fs::read_to_string(out.join("a.txt")).unwrap(),
+        "obfuscated ".repeat(100)
+    );
+    assert_eq!(
+        std::fs::read_to_string(out.join("b.txt")).unwrap(),
+        "stored"
+    );
+
+    // The generated config keeps the key, so repacking obfuscates again
+    let unpacked = std::fs::read_to_string(out.join("test.toml")).unwrap();
+    assert!(unpacked.contains("xor_key = \"5a3c\""), "{}", unpacked);
+    let repacked = tmp.path().join("repacked");
+    pack(&out.join("test.toml"), &repacked, &[]);
+    assert_eq!(std::fs::read(repacked.join("test.dz")).unwrap(), archive);
+}
+
+#[test]
+fn test_pack_min_ratio() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(&src).unwrap();
+    // xorshift noise does not compress
+    let mut state = 0x2545_F491_u32;
+    let noise: Vec<u8> = (0..8192)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+    std::fs::write(src.join("noise.bin"), &noise).unwrap();
+    std::fs::write(src.join("text.txt"), "compressible ".repeat(200)).unwrap();
+    let config = src.join("test.toml");
+    std::fs::write(
+        &config,
+        r#"archives = ["test.dz"]
+base_dir = "."
+
+[[files]]
+path = "noise.bin"
+archive_file_index = 0
+compression = "Lzma"
+
+[[files]]
+path = "text.txt"
+archive_file_index = 0
+compression = "Lzma"
+"#,
+    )
+    .unwrap();
+
+    let packed = tmp.path().join("packed");
+    let output = pack(&config, &packed, &["--min-ratio", "0.1"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Stored 1 file(s) uncompressed"),
+        "{}",
+        stderr
+    );
+
+    let out = common::dzip()
+        .arg("chunks")
+        .arg(packed.join("test.dz"))
+        .arg("--json")
+        .output()
+        .unwrap();
+    let rows: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(rows[0]["flag_names"], serde_json::json!(["COPYCOMP"]));
+    assert_eq!(rows[0]["compressed_length"], 8192);
+    assert_eq!(rows[1]["flag_names"], serde_json::json!(["LZMA"]));
+
+    let out = tmp.path().join("out");
+    common::unpack(&packed.join("test.dz"), &out, &[]);
+    assert_eq!(std::fs::read(out.join("noise.bin")).unwrap(), noise);
+
+    let output = common::dzip()
+        .arg("pack")
+        .arg(&config)
+        .args(["--min-ratio", "1.5"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_pack_keep_going() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(src.join("sub")).unwrap();
+    std::fs::write(src.join("a.txt"), "first").unwrap();
+    std::fs::write(src.join("sub/c.txt"), "third").unwrap();
+
+    let config = src.join("test.toml");
+    std::fs::write(
+        &config,
+        r#"archives = ["test.dz"]
+base_dir = "."
+
+[[files]]
+path = "a.txt"
+archive_file_index = 0
+compression = "Zlib"
+
+[[files]]
+path = "gone/b.bin"
+archive_file_index = 0
+compression = "Copy"
+
+[[files]]
+path = "sub/c.txt"
+archive_file_index = 0
+compression = "Copy"
+"#,
+    )
+    .unwrap();
+
+    let run = |out: &std::path::Path, args: &[&str]| {
+        common::dzip()
+            .arg("pack")
+            .arg(&config)
+            .arg("-o")
+            .arg(out)
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let out = run(&tmp.path().join("strict"), &[]);
+    assert!(!out.status.success());
+    assert!(!tmp.path().join("strict/test.dz").exists());
+
+    // The readable files are packed, and the run still fails naming the rest
+    let packed = tmp.path().join("packed");
+    let out = run(&packed, &["--keep-going"]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains("Skipped 1 file(s) that could not be packed: gone/b.bin"),
+        "{}",
+        stderr
+    );
+
+    let unpacked = tmp.path().join("unpacked");
+    common::unpack(&packed.join("test.dz"), &unpacked, &[]);
+    assert_eq!(
+        common::list_files(&unpacked),
+        ["a.txt", "sub/c.txt", "test.toml"]
+    );
+    assert_eq!(
+        std::fs::read_to_string(unpacked.join("sub/c.txt")).unwrap(),
+        "third"
+    );
+    // The directory of the skipped file is not in the string table
+    let header =
+        dzip_core::reader::DzipReader::new(std::fs::File::open(packed.join("test.dz")).unwrap())
+            .read_header()
+            .unwrap();
+    assert_eq!(header.strings, ["a.txt", "c.txt", "sub"]);
+}
+
+#[test]
+fn test_pack_count_limits() {
+    let tmp = tempfile::tempdir().unwrap();
+    let pack_list = |name: &str, paths: &mut dyn Iterator<Item = String>| {
+        let list = tmp.path().join(name);
+        std::fs::write(&list, paths.collect::<Vec<_>>().join("\n")).unwrap();
+        let out = common::dzip()
+            .arg("pack")
+            .arg("--file-list")
+            .arg(&list)
+            .arg("-o")
+            .arg(tmp.path().join("out"))
+            .output()
+            .unwrap();
+        assert!(!out.status.success());
+        String::from_utf8_lossy(&out.stderr).to_string()
+    };
+
+    // Rejected up front, before any file is read
+    let stderr = pack_list("files.txt", &mut (0..=65535).map(|i| format!("{}.bin", i)));
+    assert!(
+        stderr.contains("65536 files exceed the format's limit of 65535; split the files"),
+        "{}",
+        stderr
+    );
+    let stderr = pack_list("dirs.txt", &mut (0..65535).map(|i| format!("d{}/a.bin", i)));
+    assert!(
+        stderr.contains("65536 directories (counting the root) exceed"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_pack_chunk_ids_from_config() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(&src).unwrap();
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        std::fs::write(src.join(name), name.repeat(50)).unwrap();
+    }
+    let write_config = |ids: [&str; 3]| {
+        let config = src.join("test.toml");
+        let mut toml = String::from("archives = [\"test.dz\"]\nbase_dir = \".\"\n");
+        for (name, id) in ["a.txt", "b.txt", "c.txt"].iter().zip(ids) {
+            toml.push_str(&format!(
+                "\n[[files]]\npath = \"{}\"\narchive_file_index = 0\ncompression = \"Zlib\"\n{}\n",
+                name, id
+            ));
+        }
+        std::fs::write(&config, toml).unwrap();
+        config
+    };
+    let read_map = |path: &std::path::Path| {
+        dzip_core::reader::DzipReader::new(std::fs::File::open(path).unwrap())
+            .read_header()
+            .unwrap()
+            .map
+    };
+
+    let config = write_config(["chunk_id = 2", "chunk_id = 0", "chunk_id = 1"]);
+    let packed = tmp.path().join("packed");
+    pack(&config, &packed, &["--chunk-ids", "config"]);
+    let map = read_map(&packed.join("test.dz"));
+    let ids: Vec<&[u16]> = map.iter().map(|(_, ids)| ids.as_slice()).collect();
+    assert_eq!(ids, [&[2], &[0], &[1]]);
+    let out = tmp.path().join("out");
+    common::unpack(&packed.join("test.dz"), &out, &[]);
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        assert_eq!(
+            std::fs::read_to_string(out.join(name)).unwrap(),
+            name.repeat(50)
+        );
+    }
+
+    // The default numbers the files in order, whatever the config says
+    let auto = tmp.path().join("auto");
+    pack(&config, &auto, &[]);
+    let map = read_map(&auto.join("test.dz"));
+    let ids: Vec<&[u16]> = map.iter().map(|(_, ids)| ids.as_slice()).collect();
+    assert_eq!(ids, [&[0], &[1], &[2]]);
+
+    for (ids, message) in [
+        (
+            ["chunk_id = 0", "", "chunk_id = 1"],
+            "b.txt has no chunk_id",
+        ),
+        (
+            ["chunk_id = 0", "chunk_id = 1", "chunk_id = 0"],
+            "a.txt and c.txt both have chunk_id 0",
+        ),
+        (
+            ["chunk_id = 0", "chunk_id = 1", "chunk_id = 3"],
+            "chunk_id 3 of c.txt is out of range",
+        ),
+    ] {
+        let config = write_config(ids);
+        let out = common::dzip()
+            .arg("pack")
+            .arg(&config)
+            .arg("-o")
+            .arg(tmp.path().join("invalid"))
+            .args(["--chunk-ids", "config"])
+            .output()
+            .unwrap();
+        assert!(!out.status.success());
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(stderr.contains(message), "{}", stderr);
+    }
+}
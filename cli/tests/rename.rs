@@ -0,0 +1,129 @@
+mod common;
+
+use common::{dzip, list_files, pack, test_data, unpack};
+use std::path::Path;
+
+fn rename(input: &Path, output: &Path, map: &str) -> std::process::Output {
+    std::fs::create_dir_all(output.parent().unwrap()).unwrap();
+    let map_path = output.with_extension("txt");
+    std::fs::write(&map_path, map).unwrap();
+    dzip()
+        .arg("rename")
+        .arg(input)
+        .arg(output)
+        .arg("--map")
+        .arg(&map_path)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_rename_split_archive() {
+    let tmp = tempfile::tempdir().unwrap();
+    let input = test_data("ExampleSplitArchive/testnew.dz");
+    let output = tmp.path().join("renamed/renamed.dz");
+    let out = rename(
+        &input,
+        &output,
+        "# localization swap\nTXT = Texts\nBMP\\Image4.bmp=Image4.bmp\n",
+    );
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    // Only the header changes; the auxiliary volumes are copied as they are
+    for volume in ["testnew1.dz", "testnew2.dz", "testnew3.dz"] {
+        assert_eq!(
+            std::fs::read(tmp.path().join("renamed").join(volume)).unwrap(),
+            std::fs::read(test_data("ExampleSplitArchive").join(volume)).unwrap()
+        );
+    }
+
+    let original = tmp.path().join("original");
+    unpack(&input, &original, &[]);
+    let renamed = tmp.path().join("out");
+    unpack(&output, &renamed, &[]);
+    assert_eq!(
+        list_files(&renamed),
+        [
+            "BMP/Image16.bmp",
+            "BMP/Image8.bmp",
+            "Image16b.bmp",
+            "Image4.bmp",
+            "Texts/Text1.txt",
+            "Texts/Text2.txt",
+            "Texts/Text3.txt",
+            "renamed.toml"
+        ]
+    );
+    for (old, new) in [
+        ("BMP/Image4.bmp", "Image4.bmp"),
+        ("TXT/Text3.txt", "Texts/Text3.txt"),
+        ("BMP/Image8.bmp", "BMP/Image8.bmp"),
+    ] {
+        assert_eq!(
+            std::fs::read(original.join(old)).unwrap(),
+            std::fs::read(renamed.join(new)).unwrap()
+        );
+    }
+    let verify = dzip().arg("verify").arg(&output).output().unwrap();
+    assert!(verify.status.success());
+}
+
+#[test]
+fn test_rename_rejects_collisions_and_unknown_paths() {
+    let tmp = tempfile::tempdir().unwrap();
+    let input = test_data("ExampleSplitArchive/testnew.dz");
+    for (map, message) in [
+        (
+            "BMP/Image8.bmp=Image16b.bmp",
+            "the same path 'Image16b.bmp'",
+        ),
+        ("Image16b.bmp=TXT", "a directory of other files"),
+        ("Missing.bmp=Other.bmp", "Missing.bmp (rename map line 1)"),
+        ("TXT=../TXT", "'..'"),
+        ("TXT", "expected 'old_path=new_path'"),
+    ] {
+        let output = tmp.path().join("renamed.dz");
+        let out = rename(&input, &output, map);
+        assert!(!out.status.success(), "{}", map);
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(stderr.contains(message), "{}: {}", map, stderr);
+        assert!(!output.exists(), "{}", map);
+    }
+
+    // Directories kept only because they are empty count as taken too
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::write(src.join("a.txt"), b"contents").unwrap();
+    let config = src.join("test.toml");
+    std::fs::write(
+        &config,
+        r#"archives = ["test.dz"]
+base_dir = "."
+empty_dirs = ["saves", "cache\\levels"]
+
+[[files]]
+path = "a.txt"
+archive_file_index = 0
+compression = "Copy"
+"#,
+    )
+    .unwrap();
+    let packed = tmp.path().join("packed");
+    pack(&config, &packed, &[]);
+    for map in ["a.txt=saves", "a.txt=cache"] {
+        let output = tmp.path().join("renamed_empty.dz");
+        let out = rename(&packed.join("test.dz"), &output, map);
+        assert!(!out.status.success(), "{}", map);
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(
+            stderr.contains("the empty directory"),
+            "{}: {}",
+            map,
+            stderr
+        );
+        assert!(!output.exists(), "{}", map);
+    }
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use common::{dzip, list_files, test_data, unpack};
+use dzip_core::format::{CHUNK_LZMA, CHUNK_ZERO, Chunk};
+use dzip_core::reader::{DzipReader, correct_chunk_sizes};
+use dzip_core::volume::FileSystemVolumeManager;
+use std::fs::File;
+use std::path::Path;
+
+fn repack(input: &Path, output: &Path, args: &[&str]) {
+    let out = dzip()
+        .arg("repack")
+        .arg(input)
+        .arg(output)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "repack failed: {}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+}
+
+/// Reads the chunk table and the stored (undecoded) bytes of every chunk.
+fn read_chunks(path: &Path) -> Vec<(Chunk, Vec<u8>)> {
+    let mut reader = DzipReader::new(File::open(path).unwrap());
+    let settings = reader.read_archive_settings().unwrap();
+    reader
+        .read_strings((settings.num_user_files + settings.num_directories - 1) as usize)
+        .unwrap();
+    reader
+        .read_file_chunk_map(settings.num_user_files as usize)
+        .unwrap();
+    let chunk_settings = reader.read_chunk_settings().unwrap();
+    let mut chunks = reader
+        .read_chunks(chunk_settings.num_chunks as usize)
+        .unwrap();
+    let volume_files = reader
+        .read_strings((chunk_settings.num_archive_files - 1) as usize)
+        .unwrap();
+
+    let base_dir = path.parent().unwrap();
+    let sizes = std::iter::once(path.to_path_buf())
+        .chain(volume_files.iter().map(|name| base_dir.join(name)))
+        .enumerate()
+        .map(|(i, p)| (i as u16, std::fs::metadata(p).unwrap().len()))
+        .collect();
+    correct_chunk_sizes(&mut chunks, &sizes);
+
+    let mut volumes = FileSystemVolumeManager::new(base_dir.to_path_buf(), volume_files);
+    chunks
+        .into_iter()
+        .map(|chunk| {
+            let data = reader
+                .read_raw_chunk_data_with_volumes(&chunk, &mut volumes)
+                .unwrap();
+            (chunk, data)
+        })
+        .collect()
+}
+
+#[test]
+fn test_repack_split_archive_to_lzma() {
+    let input = test_data("ExampleSplitArchive/testnew.dz");
+    let tmp = tempfile::tempdir().unwrap();
+    let repacked = tmp.path().join("repacked/out.dz");
+    repack(&input, &repacked, &["--method", "lzma"]);
+
+    // Volume layout is preserved under the original auxiliary names
+    for name in ["testnew1.dz", "testnew2.dz", "testnew3.dz"] {
+        assert!(repacked.with_file_name(name).exists());
+    }
+    for (chunk, _) in read_chunks(&repacked) {
+        assert!(chunk.flags & (CHUNK_LZMA | CHUNK_ZERO) != 0);
+    }
+
+    let original = tmp.path().join("original");
+    let converted = tmp.path().join("converted");
+    unpack(&input, &original, &[]);
+    unpack(&repacked, &converted, &[]);
+
+    let files: Vec<_> = list_files(&original)
+        .into_iter()
+        .filter(|f| !f.ends_with(".toml"))
+        .collect();
+    assert!(!files.is_empty());
+    for file in &files {
+        assert_eq!(
+            std::fs::read(original.join(file)).unwrap(),
+            std::fs::read(converted.join(file)).unwrap(),
+            "{} differs after repack",
+            file
+        );
+    }
+}
+
+#[test]
+fn test_repack_keeps_chunks_in_target_format() {
+    let input = test_data("ExampleSplitArchive/testnew.dz");
+    let tmp = tempfile::tempdir().unwrap();
+
+    let kept = tmp.path().join("kept/out.dz");
+    repack(&input, &kept, &["--method", "zlib"]);
+    let forced = tmp.path().join("forced/out.dz");
+    repack(&input, &forced, &["--method", "zlib", "--force"]);
+
+    let original = read_chunks(&input);
+    let kept = read_chunks(&kept);
+    let forced = read_chunks(&forced);
+    // Chunk 0 is zlib in the source archive; its stored bytes are copied unless forced
+    assert_eq!(kept[0].0.flags, original[0].0.flags);
+    assert_eq!(kept[0].1, original[0].1);
+    assert_ne!(forced[0].1, original[0].1);
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::{dzip, list_files, unpack, write_archive};
+use dzip_core::{CompressionMethod, compress_data};
+
+const SAMPLE: &[u8] = b"Recovered by scanning the data region for codec stream headers.";
+
+#[test]
+fn test_repair_rebuilds_chunk_table() {
+    let dir = tempfile::tempdir().unwrap();
+    let (zlib, zlib_data) = compress_data(SAMPLE, CompressionMethod::Zlib).unwrap();
+    let (bzip, bzip_data) = compress_data(&SAMPLE[..20], CompressionMethod::Bzip).unwrap();
+    let damaged = dir.path().join("damaged.dz");
+    write_archive(
+        &damaged,
+        &[("a.txt", zlib, &zlib_data), ("b.txt", bzip, &bzip_data)],
+        |chunks| {
+            for chunk in chunks {
+                chunk.offset = 0xDEAD;
+                chunk.compressed_length = 1;
+            }
+        },
+    );
+
+    let repaired = dir.path().join("repaired.dz");
+    let out = dzip()
+        .arg("repair")
+        .arg(&damaged)
+        .arg(&repaired)
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "repair failed: {}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let out_dir = dir.path().join("out");
+    unpack(&repaired, &out_dir, &[]);
+    let root = out_dir;
+    assert_eq!(list_files(&root), ["a.txt", "b.txt", "repaired.toml"]);
+    assert_eq!(std::fs::read(root.join("a.txt")).unwrap(), SAMPLE);
+    assert_eq!(std::fs::read(root.join("b.txt")).unwrap(), &SAMPLE[..20]);
+}
+
+#[test]
+fn test_repair_numbers_files_without_string_table() {
+    let dir = tempfile::tempdir().unwrap();
+    let (zlib, zlib_data) = compress_data(SAMPLE, CompressionMethod::Zlib).unwrap();
+    let damaged = dir.path().join("damaged.dz");
+    write_archive(&damaged, &[("a.txt", zlib, &zlib_data)], |_| {});
+    // Wipe everything after the magic, including the string table
+    let mut bytes = std::fs::read(&damaged).unwrap();
+    let header_len = bytes.len() - zlib_data.len();
+    bytes[4..header_len].fill(0xFF);
+    std::fs::write(&damaged, bytes).unwrap();
+
+    let repaired = dir.path().join("repaired.dz");
+    let out = dzip()
+        .arg("repair")
+        .arg(&damaged)
+        .arg(&repaired)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+
+    let out_dir = dir.path().join("out");
+    unpack(&repaired, &out_dir, &[]);
+    let root = out_dir;
+    assert_eq!(list_files(&root), ["chunk_0000.bin", "repaired.toml"]);
+    assert_eq!(std::fs::read(root.join("chunk_0000.bin")).unwrap(), SAMPLE);
+}
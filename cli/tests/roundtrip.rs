@@ -0,0 +1,239 @@
+mod common;
+
+use common::{pack, test_data, unpack};
+use dzip_core::format::ArchiveSettings;
+use dzip_core::reader::DzipReader;
+use std::fs::File;
+use std::path::Path;
+
+/// Reads the archive settings, string table and file/chunk map of an archive.
+fn read_header(path: &Path) -> (ArchiveSettings, Vec<String>, Vec<u16>) {
+    let mut reader = DzipReader::new(File::open(path).expect("Failed to open archive"));
+    let settings = reader.read_archive_settings().unwrap();
+    let strings = reader
+        .read_strings((settings.num_user_files + settings.num_directories - 1) as usize)
+        .unwrap();
+    let dir_ids = reader
+        .read_file_chunk_map(settings.num_user_files as usize)
+        .unwrap()
+        .into_iter()
+        .map(|(dir_id, _)| dir_id)
+        .collect();
+    (settings, strings, dir_ids)
+}
+
+#[test]
+fn test_preserve_separators_roundtrip() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(src.join("Data/Sub")).unwrap();
+    std::fs::write(src.join("Data/Sub/a.txt"), b"nested file").unwrap();
+    std::fs::write(src.join("root.txt"), b"root file").unwrap();
+
+    let config = src.join("test.toml");
+    std::fs::write(
+        &config,
+        r#"archives = ["test.dz"]
+base_dir = "."
+
+[[files]]
+path = "Data/Sub/a.txt"
+archive_file_index = 0
+compression = "Zlib"
+directory = "Data/Sub"
+
+[[files]]
+path = "root.txt"
+archive_file_index = 0
+compression = "Copy"
+"#,
+    )
+    .unwrap();
+
+    let first = tmp.path().join("first");
+    pack(&config, &first, &[]);
+
+    let extracted = tmp.path().join("extracted");
+    unpack(
+        &first.join("test.dz"),
+        &extracted,
+        &["--preserve-separators"],
+    );
+
+    let second = tmp.path().join("second");
+    pack(&extracted.join("test.toml"), &second, &[]);
+
+    let original = read_header(&first.join("test.dz"));
+    let repacked = read_header(&second.join("test.dz"));
+    assert!(original.1.contains(&"Data/Sub".to_string()));
+    assert_eq!(original, repacked);
+}
+
+#[test]
+fn test_split_archive_header_roundtrip() {
+    let input = test_data("ExampleSplitArchive/testnew.dz");
+    let tmp = tempfile::tempdir().unwrap();
+
+    let extracted = tmp.path().join("extracted");
+    unpack(&input, &extracted, &["--preserve-separators"]);
+
+    let repacked = tmp.path().join("repacked");
+    pack(&extracted.join("testnew.toml"), &repacked, &[]);
+
+    assert_eq!(
+        read_header(&input),
+        read_header(&repacked.join("testnew.dz"))
+    );
+}
+
+#[test]
+fn test_normalize_separators_roundtrip() {
+    let input = test_data("ExampleSingleArchive/test1.dz");
+    let tmp = tempfile::tempdir().unwrap();
+    let config_paths = |dir: &Path| -> Vec<String> {
+        let config = std::fs::read_to_string(dir.join("test1.toml")).unwrap();
+        let config: toml::Table = toml::from_str(&config).unwrap();
+        config["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|file| file["path"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let mut headers = Vec::new();
+    for (style, separator) in [
+        ("forward", "/"),
+        ("back", "\\"),
+        ("native", std::path::MAIN_SEPARATOR_STR),
+    ] {
+        let extracted = tmp.path().join(style);
+        unpack(&input, &extracted, &["--normalize-separators", style]);
+        let paths = config_paths(&extracted);
+        assert!(
+            paths.contains(&format!("TXT{}Text1.txt", separator)),
+            "{}: {:?}",
+            style,
+            paths
+        );
+        // Extracted files always use the OS separator
+        assert!(extracted.join("TXT").join("Text1.txt").is_file());
+
+        let repacked = tmp.path().join(format!("{}-repacked", style));
+        pack(&extracted.join("test1.toml"), &repacked, &[]);
+        headers.push(read_header(&repacked.join("test1.dz")));
+    }
+    // Every style packs back into the same directory strings
+    assert!(headers.iter().all(|header| *header == headers[0]));
+    assert!(headers[0].1.contains(&"TXT".to_string()));
+}
+
+#[test]
+fn test_unknown_flags_roundtrip() {
+    use dzip_core::format::{CHUNK_COPYCOMP, CHUNK_ZLIB};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let input = tmp.path().join("vendor.dz");
+    let (_, zlib) = dzip_core::compress_data(
+        b"vendor data ".repeat(20).as_slice(),
+        dzip_core::CompressionMethod::Zlib,
+    )
+    .unwrap();
+    common::write_archive(
+        &input,
+        &[
+            ("a.bin", CHUNK_ZLIB | 0x4000, &zlib),
+            ("b.bin", CHUNK_COPYCOMP | 0x8000, b"stored"),
+        ],
+        |chunks| chunks[0].decompressed_length = 240,
+    );
+
+    let out = tmp.path().join("out");
+    unpack(&input, &out, &[]);
+    assert_eq!(
+        std::fs::read(out.join("a.bin")).unwrap(),
+        b"vendor data ".repeat(20)
+    );
+    let config = std::fs::read_to_string(out.join("vendor.toml")).unwrap();
+    assert!(config.contains("0x4000"), "{}", config);
+    assert!(config.contains("0x8000"), "{}", config);
+
+    let packed = tmp.path().join("packed");
+    pack(&out.join("vendor.toml"), &packed, &[]);
+    let chunks = |path: &Path| {
+        let mut reader = DzipReader::new(File::open(path).unwrap());
+        reader.read_header().unwrap().chunks
+    };
+    let flags: Vec<u16> = chunks(&packed.join("vendor.dz"))
+        .iter()
+        .map(|c| c.flags)
+        .collect();
+    assert_eq!(flags, [CHUNK_ZLIB | 0x4000, CHUNK_COPYCOMP | 0x8000]);
+}
+
+#[test]
+fn test_trailing_separator_roundtrip() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(src.join("textures")).unwrap();
+    std::fs::write(src.join("textures/a.bmp"), b"first").unwrap();
+    std::fs::write(src.join("textures/b.bmp"), b"second").unwrap();
+    let config = src.join("test.toml");
+    std::fs::write(
+        &config,
+        r#"archives = ["test.dz"]
+base_dir = "."
+
+[[files]]
+path = "textures/a.bmp"
+archive_file_index = 0
+directory = "textures\\"
+
+[[files]]
+path = "textures/b.bmp"
+archive_file_index = 0
+"#,
+    )
+    .unwrap();
+
+    // By default both spellings are the same directory
+    let normalized = tmp.path().join("normalized");
+    pack(&config, &normalized, &[]);
+    let (_, strings, dir_ids) = read_header(&normalized.join("test.dz"));
+    assert_eq!(strings, ["a.bmp", "b.bmp", "textures"]);
+    assert_eq!(dir_ids, [1, 1]);
+
+    // An archive that stores the trailing separator, as some tools write them
+    let verbatim = tmp.path().join("verbatim");
+    pack(&config, &verbatim, &["--keep-trailing-separators"]);
+    let original = read_header(&verbatim.join("test.dz"));
+    assert_eq!(original.1, ["a.bmp", "b.bmp", "textures\\", "textures"]);
+
+    let extracted = tmp.path().join("extracted");
+    unpack(
+        &verbatim.join("test.dz"),
+        &extracted,
+        &["--preserve-separators"],
+    );
+    let generated = std::fs::read_to_string(extracted.join("test.toml")).unwrap();
+    assert!(!generated.contains("textures\\\\"), "{}", generated);
+    assert_eq!(
+        std::fs::read(extracted.join("textures/a.bmp")).unwrap(),
+        b"first"
+    );
+
+    // Keeping them on both sides reproduces the archive's strings
+    let kept = tmp.path().join("kept");
+    unpack(
+        &verbatim.join("test.dz"),
+        &kept,
+        &["--preserve-separators", "--keep-trailing-separators"],
+    );
+    let repacked = tmp.path().join("repacked");
+    pack(
+        &kept.join("test.toml"),
+        &repacked,
+        &["--keep-trailing-separators"],
+    );
+    assert_eq!(read_header(&repacked.join("test.dz")), original);
+}
@@ -0,0 +1,23 @@
+mod common;
+
+use common::dzip;
+
+#[test]
+fn test_selftest_passes() {
+    let out = dzip().arg("selftest").output().unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        out.status.success(),
+        "{}\n{}",
+        stdout,
+        String::from_utf8_lossy(&out.stderr)
+    );
+    for codec in ["Copy", "Zlib", "Gzip", "Bzip", "Lzma", "Xz", "Brotli"] {
+        assert!(
+            stdout.contains(&format!("{:<8} | PASS", codec)),
+            "{}",
+            stdout
+        );
+    }
+    assert!(stdout.contains("All 7 codecs passed."));
+}
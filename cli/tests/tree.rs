@@ -0,0 +1,50 @@
+mod common;
+
+use common::{dzip, test_data};
+
+fn tree(args: &[&str]) -> String {
+    let out = dzip()
+        .arg("tree")
+        .arg(test_data("ExampleSplitArchive/testnew.dz"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    String::from_utf8(out.stdout).unwrap()
+}
+
+#[test]
+fn test_tree() {
+    assert_eq!(
+        tree(&[]),
+        "\
+testnew.dz (7 files, 44432 bytes)
+├── BMP/ (3 files, 32024 bytes)
+│   ├── Image16.bmp (24684 bytes)
+│   ├── Image4.bmp (2166 bytes)
+│   └── Image8.bmp (5174 bytes)
+├── Image16b.bmp (12342 bytes)
+└── TXT/ (3 files, 66 bytes)
+    ├── Text1.txt (22 bytes)
+    ├── Text2.txt (22 bytes)
+    └── Text3.txt (22 bytes)
+"
+    );
+}
+
+#[test]
+fn test_tree_depth() {
+    assert_eq!(
+        tree(&["--depth", "1"]),
+        "\
+testnew.dz (7 files, 44432 bytes)
+├── BMP/ (3 files, 32024 bytes)
+├── Image16b.bmp (12342 bytes)
+└── TXT/ (3 files, 66 bytes)
+"
+    );
+}
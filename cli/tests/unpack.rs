@@ -0,0 +1,1017 @@
+mod common;
+
+use common::{dzip, list_files, test_data, unpack};
+use std::collections::BTreeMap;
+use std::io::Read;
+
+#[test]
+fn test_unpack_include_exclude() {
+    let tmp = tempfile::tempdir().unwrap();
+    let out = tmp.path().join("out");
+    let output = unpack(
+        &test_data("ExampleSplitArchive/testnew.dz"),
+        &out,
+        &["--include", "*.bmp", "--exclude", "BMP/Image1*"],
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Unpacked 3 files"), "{}", stderr);
+    assert!(stderr.contains("3 complete, 0 with failed chunks, 4 skipped"));
+
+    assert_eq!(
+        list_files(&out),
+        vec![
+            "BMP/Image4.bmp",
+            "BMP/Image8.bmp",
+            "Image16b.bmp",
+            "testnew.toml"
+        ]
+    );
+
+    let config = std::fs::read_to_string(out.join("testnew.toml")).unwrap();
+    assert!(config.contains("BMP/Image4.bmp"));
+    assert!(!config.contains("Image16.bmp"));
+    assert!(!config.contains("Text1.txt"));
+}
+
+#[test]
+fn test_unpack_method_filter() {
+    let tmp = tempfile::tempdir().unwrap();
+    let out = tmp.path().join("out");
+    unpack(
+        &test_data("ExampleSplitArchive/testnew.dz"),
+        &out,
+        &["--method", "bzip", "--method", "copy"],
+    );
+    assert_eq!(
+        list_files(&out),
+        vec!["BMP/Image4.bmp", "TXT/Text1.txt", "testnew.toml"]
+    );
+
+    // Path filters apply as well
+    let out = tmp.path().join("combined");
+    unpack(
+        &test_data("ExampleSplitArchive/testnew.dz"),
+        &out,
+        &["--method", "zlib", "--include", "TXT/*"],
+    );
+    assert_eq!(list_files(&out), vec!["TXT/Text3.txt", "testnew.toml"]);
+}
+
+/// Packs an archive holding two different files that both extract to `d/one.txt`.
+fn pack_colliding_archive(dir: &std::path::Path) -> std::path::PathBuf {
+    let src = dir.join("src");
+    std::fs::create_dir_all(src.join("d")).unwrap();
+    std::fs::write(src.join("one.txt"), b"first").unwrap();
+    std::fs::write(src.join("d/one.txt"), b"second").unwrap();
+    let config = src.join("dup.toml");
+    std::fs::write(
+        &config,
+        r#"archives = ["dup.dz"]
+base_dir = "."
+
+[[files]]
+path = "one.txt"
+archive_file_index = 0
+compression = "Copy"
+directory = "d"
+
+[[files]]
+path = "d/one.txt"
+archive_file_index = 0
+compression = "Copy"
+"#,
+    )
+    .unwrap();
+    let packed = dir.join("packed");
+    common::pack(&config, &packed, &[]);
+    packed.join("dup.dz")
+}
+
+#[test]
+fn test_unpack_on_collision() {
+    let tmp = tempfile::tempdir().unwrap();
+    let archive = pack_colliding_archive(tmp.path());
+
+    let out = tmp.path().join("overwrite");
+    unpack(&archive, &out, &[]);
+    assert_eq!(std::fs::read(out.join("d/one.txt")).unwrap(), b"second");
+
+    let out = tmp.path().join("skip");
+    unpack(&archive, &out, &["--on-collision", "skip"]);
+    assert_eq!(std::fs::read(out.join("d/one.txt")).unwrap(), b"first");
+    assert_eq!(list_files(&out), vec!["d/one.txt", "dup.toml"]);
+
+    let out = tmp.path().join("rename");
+    unpack(&archive, &out, &["--on-collision", "rename"]);
+    assert_eq!(std::fs::read(out.join("d/one.txt")).unwrap(), b"first");
+    assert_eq!(std::fs::read(out.join("d/one_1.txt")).unwrap(), b"second");
+}
+
+#[test]
+fn test_unpack_file_directory_collision() {
+    let tmp = tempfile::tempdir().unwrap();
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::write(src.join("foo"), b"file").unwrap();
+    std::fs::write(src.join("bar.txt"), b"nested").unwrap();
+    // `foo` is a file, and also the directory of `foo/bar.txt`
+    let config = src.join("clash.toml");
+    std::fs::write(
+        &config,
+        r#"archives = ["clash.dz"]
+base_dir = "."
+
+[[files]]
+path = "foo"
+archive_file_index = 0
+compression = "Copy"
+
+[[files]]
+path = "bar.txt"
+archive_file_index = 0
+compression = "Copy"
+directory = "foo"
+"#,
+    )
+    .unwrap();
+    let packed = tmp.path().join("packed");
+    common::pack(&config, &packed, &[]);
+    let archive = packed.join("clash.dz");
+
+    let out = tmp.path().join("overwrite");
+    let output = unpack(&archive, &out, &[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("File 0 ('foo') is also the directory of file 1"),
+        "{}",
+        stderr
+    );
+    assert_eq!(list_files(&out), vec!["clash.toml", "foo/bar.txt"]);
+
+    let out = tmp.path().join("skip");
+    unpack(&archive, &out, &["--on-collision", "skip"]);
+    assert_eq!(list_files(&out), vec!["clash.toml", "foo"]);
+    assert_eq!(std::fs::read(out.join("foo")).unwrap(), b"file");
+
+    let out = tmp.path().join("rename");
+    unpack(&archive, &out, &["--on-collision", "rename"]);
+    assert_eq!(std::fs::read(out.join("foo_1")).unwrap(), b"file");
+    assert_eq!(std::fs::read(out.join("foo/bar.txt")).unwrap(), b"nested");
+
+    let output = dzip()
+        .args(["unpack", "--on-collision", "abort"])
+        .arg(&archive)
+        .arg("-o")
+        .arg(tmp.path().join("abort"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(!tmp.path().join("abort/foo").exists());
+}
+
+#[test]
+fn test_unpack_zero_chunk_file() {
+    use dzip_core::format::*;
+    use dzip_core::writer::DzipWriter;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let archive = tmp.path().join("empty_file.dz");
+    {
+        let file = std::fs::File::create(&archive).unwrap();
+        let mut writer = DzipWriter::new(file);
+        writer
+            .write_archive_settings(&ArchiveSettings {
+                header: DTRZ_MAGIC,
+                num_user_files: 1,
+                num_directories: 1,
+                version: 0,
+            })
+            .unwrap();
+        writer.write_strings(&["empty.bin".to_string()]).unwrap();
+        writer.write_file_chunk_map(&[(0, vec![])]).unwrap();
+        writer
+            .write_chunk_settings(&ChunkSettings {
+                num_archive_files: 1,
+                num_chunks: 0,
+            })
+            .unwrap();
+    }
+
+    let out = tmp.path().join("out");
+    unpack(&archive, &out, &[]);
+    assert_eq!(std::fs::read(out.join("empty.bin")).unwrap(), b"");
+    let config = std::fs::read_to_string(out.join("empty_file.toml")).unwrap();
+    assert!(config.contains("compression = \"Copy\""));
+}
+
+#[test]
+fn test_unpack_resume() {
+    let input = test_data("ExampleSplitArchive/testnew.dz");
+    let tmp = tempfile::tempdir().unwrap();
+    let out = tmp.path().join("out");
+    unpack(&input, &out, &[]);
+    let text1 = std::fs::read(out.join("TXT/Text1.txt")).unwrap();
+    let image = std::fs::read(out.join("BMP/Image4.bmp")).unwrap();
+
+    // An interrupted run leaves a truncated file; a tampered file keeps its size
+    std::fs::write(out.join("BMP/Image4.bmp"), &image[..100]).unwrap();
+    let tampered = vec![b'x'; text1.len()];
+    std::fs::write(out.join("TXT/Text1.txt"), &tampered).unwrap();
+
+    let output = unpack(&input, &out, &["--resume"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("6 file(s) were already extracted"),
+        "{}",
+        stderr
+    );
+    assert_eq!(std::fs::read(out.join("BMP/Image4.bmp")).unwrap(), image);
+    assert_eq!(std::fs::read(out.join("TXT/Text1.txt")).unwrap(), tampered);
+
+    unpack(&input, &out, &["--resume", "--verify-existing"]);
+    assert_eq!(std::fs::read(out.join("TXT/Text1.txt")).unwrap(), text1);
+}
+
+#[test]
+fn test_unpack_overwrite_never() {
+    let input = test_data("ExampleSplitArchive/testnew.dz");
+    let tmp = tempfile::tempdir().unwrap();
+    let out = tmp.path().join("out");
+    unpack(&input, &out, &[]);
+    let image = std::fs::read(out.join("BMP/Image4.bmp")).unwrap();
+
+    std::fs::write(out.join("TXT/Text1.txt"), b"local edit").unwrap();
+    std::fs::remove_dir_all(out.join("BMP")).unwrap();
+
+    let output = unpack(&input, &out, &["--overwrite", "never"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("existing file(s) were not overwritten"),
+        "{}",
+        stderr
+    );
+    assert_eq!(
+        std::fs::read(out.join("TXT/Text1.txt")).unwrap(),
+        b"local edit"
+    );
+    // Missing files are still extracted
+    assert_eq!(std::fs::read(out.join("BMP/Image4.bmp")).unwrap(), image);
+
+    unpack(&input, &out, &["--overwrite", "always"]);
+    assert_ne!(
+        std::fs::read(out.join("TXT/Text1.txt")).unwrap(),
+        b"local edit"
+    );
+}
+
+#[test]
+fn test_unpack_buffer_size() {
+    let input = test_data("ExampleSplitArchive/testnew.dz");
+    let tmp = tempfile::tempdir().unwrap();
+    let out = tmp.path().join("out");
+    unpack(&input, &out, &["--buffer-size", "512"]);
+    assert_eq!(
+        std::fs::read(out.join("Image16b.bmp")).unwrap(),
+        std::fs::read(test_data("DerbhExampleFiles/Image16b.bmp")).unwrap()
+    );
+
+    for invalid in ["1000", "256", "1G", "abc"] {
+        let output = common::dzip()
+            .arg("unpack")
+            .arg(&input)
+            .arg("-o")
+            .arg(&out)
+            .args(["--buffer-size", invalid])
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "{} was accepted", invalid);
+    }
+}
+
+#[test]
+fn test_unpack_quiet() {
+    let input = test_data("ExampleSplitArchive/testnew.dz");
+    let tmp = tempfile::tempdir().unwrap();
+    let output = unpack(&input, &tmp.path().join("out"), &["--quiet"]);
+    assert!(
+        output.stderr.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(output.stdout.is_empty());
+
+    let output = common::dzip()
+        .args(["--quiet", "--verbose", "verify"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unpack_no_follow_symlinks() {
+    let input = test_data("ExampleSplitArchive/testnew.dz");
+    let tmp = tempfile::tempdir().unwrap();
+    let out = tmp.path().join("out");
+    let outside = tmp.path().join("outside");
+    std::fs::create_dir_all(&out).unwrap();
+    std::fs::create_dir_all(&outside).unwrap();
+
+    // A symlinked directory inside the output is refused
+    std::os::unix::fs::symlink(&outside, out.join("TXT")).unwrap();
+    let output = common::dzip()
+        .arg("unpack")
+        .arg(&input)
+        .arg("-o")
+        .arg(&out)
+        .arg("--no-follow-symlinks")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("symlink"));
+    assert!(list_files(&outside).is_empty());
+
+    // So is a symlink in place of the file itself
+    std::fs::remove_file(out.join("TXT")).unwrap();
+    std::fs::create_dir_all(out.join("TXT")).unwrap();
+    let target = outside.join("target.txt");
+    std::fs::write(&target, b"keep").unwrap();
+    std::os::unix::fs::symlink(&target, out.join("TXT/Text1.txt")).unwrap();
+    let output = common::dzip()
+        .arg("unpack")
+        .arg(&input)
+        .arg("-o")
+        .arg(&out)
+        .arg("--no-follow-symlinks")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert_eq!(std::fs::read(&target).unwrap(), b"keep");
+}
+
+#[test]
+fn test_unpack_recover_truncated() {
+    let tmp = tempfile::tempdir().unwrap();
+    let full = std::fs::read(test_data("ExampleSingleArchive/test1.dz")).unwrap();
+    // Cut the download short after the first chunk (offset 121, 1141 bytes)
+    let input = tmp.path().join("test1.dz");
+    std::fs::write(&input, &full[..1300]).unwrap();
+
+    let out = tmp.path().join("out");
+    let output = dzip()
+        .arg("unpack")
+        .arg(&input)
+        .arg("-o")
+        .arg(&out)
+        .arg("--recover")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Cannot recover 'TXT/Text1.txt'"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("2 file(s) could not be recovered"),
+        "{}",
+        stderr
+    );
+    assert_eq!(list_files(&out), vec!["Image16b.bmp", "test1.toml"]);
+
+    let reference = tmp.path().join("reference");
+    unpack(&test_data("ExampleSingleArchive/test1.dz"), &reference, &[]);
+    assert_eq!(
+        std::fs::read(out.join("Image16b.bmp")).unwrap(),
+        std::fs::read(reference.join("Image16b.bmp")).unwrap()
+    );
+    let config = std::fs::read_to_string(out.join("test1.toml")).unwrap();
+    assert!(!config.contains("Text1.txt"));
+}
+
+#[test]
+fn test_unpack_recover_truncated_placeholder_chunk() {
+    use dzip_core::format::{CHUNK_COPYCOMP, CHUNK_ZLIB};
+    let tmp = tempfile::tempdir().unwrap();
+    let text = "placeholder ".repeat(200);
+    let (_, zlib) =
+        dzip_core::compress_data(text.as_bytes(), dzip_core::CompressionMethod::Zlib).unwrap();
+    let input = tmp.path().join("cut.dz");
+    common::write_archive(
+        &input,
+        &[
+            ("a.txt", CHUNK_COPYCOMP, b"complete"),
+            ("b.txt", CHUNK_ZLIB, &zlib),
+        ],
+        // Both lengths of the last chunk hold the decompressed size
+        |chunks| {
+            chunks[1].compressed_length = text.len() as u32;
+            chunks[1].decompressed_length = text.len() as u32;
+        },
+    );
+    let full = std::fs::read(&input).unwrap();
+    // Cut the volume halfway into the last chunk's stream
+    std::fs::write(&input, &full[..full.len() - zlib.len() / 2]).unwrap();
+
+    let out = tmp.path().join("out");
+    let output = dzip()
+        .arg("unpack")
+        .arg(&input)
+        .arg("-o")
+        .arg(&out)
+        .arg("--recover")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Cannot recover 'b.txt': 1 of 1 chunk(s) could not be decoded"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("1 file(s) could not be recovered"),
+        "{}",
+        stderr
+    );
+    assert_eq!(list_files(&out), vec!["a.txt", "cut.toml"]);
+    assert_eq!(std::fs::read(out.join("a.txt")).unwrap(), b"complete");
+}
+
+#[test]
+fn test_unpack_volume_size_report() {
+    let tmp = tempfile::tempdir().unwrap();
+    let full = std::fs::read(test_data("ExampleSingleArchive/test1.dz")).unwrap();
+
+    let out = tmp.path().join("out");
+    let output = unpack(
+        &test_data("ExampleSingleArchive/test1.dz"),
+        &out,
+        &["--volume-size-report"],
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Volume 0 (test1.dz): 1641 bytes, chunk data ends at 1641"),
+        "{}",
+        stderr
+    );
+
+    let padded = tmp.path().join("padded.dz");
+    std::fs::write(&padded, [full.as_slice(), &[0; 16]].concat()).unwrap();
+    let output = unpack(
+        &padded,
+        &tmp.path().join("padded"),
+        &["--volume-size-report"],
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Volume 0 (padded.dz) has 16 bytes after its last chunk"),
+        "{}",
+        stderr
+    );
+
+    let truncated = tmp.path().join("truncated.dz");
+    std::fs::write(&truncated, &full[..1300]).unwrap();
+    let output = dzip()
+        .arg("unpack")
+        .arg(&truncated)
+        .arg("-o")
+        .arg(tmp.path().join("truncated"))
+        .args(["--volume-size-report", "--recover"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(
+            "Volume 0 (truncated.dz) is truncated: 1300 bytes, but its chunk data ends at 1641"
+        ),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_unpack_range_setting_override() {
+    use dzip_core::format::*;
+    let tmp = tempfile::tempdir().unwrap();
+    let input = tmp.path().join("dz.dz");
+    let mut header = ArchiveHeader {
+        settings: ArchiveSettings {
+            header: DTRZ_MAGIC,
+            num_user_files: 1,
+            num_directories: 1,
+            version: 0,
+        },
+        strings: vec!["a.bin".to_string()],
+        map: vec![(0, vec![0])],
+        chunk_settings: ChunkSettings {
+            num_archive_files: 1,
+            num_chunks: 1,
+        },
+        chunks: vec![Chunk {
+            offset: 0,
+            compressed_length: 4,
+            decompressed_length: 8,
+            flags: CHUNK_DZ,
+            file: 0,
+        }],
+        volume_files: Vec::new(),
+        range_settings: Some(RangeSettings {
+            win_size: 16,
+            offset_contexts: 3,
+            ..RangeSettings::default()
+        }),
+    };
+    let mut data = std::io::Cursor::new(Vec::new());
+    dzip_core::writer::DzipWriter::new(&mut data)
+        .write_header(&header)
+        .unwrap();
+    header.chunks[0].offset = data.get_ref().len() as u32;
+    data.set_position(0);
+    dzip_core::writer::DzipWriter::new(&mut data)
+        .write_header(&header)
+        .unwrap();
+    let mut data = data.into_inner();
+    data.extend_from_slice(b"dz!!");
+    std::fs::write(&input, data).unwrap();
+
+    let out = tmp.path().join("out");
+    let output = unpack(
+        &input,
+        &out,
+        &[
+            "--range-setting",
+            "win_size=20",
+            "--range-setting",
+            "big_min_match=9",
+        ],
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("win_size: 20"), "{}", stderr);
+    let config = std::fs::read_to_string(out.join("dz.toml")).unwrap();
+    assert!(config.contains("win_size = 20"), "{}", config);
+    assert!(config.contains("offset_contexts = 3"), "{}", config);
+    assert!(config.contains("big_min_match = 9"), "{}", config);
+
+    let output = common::dzip()
+        .arg("unpack")
+        .arg(&input)
+        .args(["-o", out.to_str().unwrap(), "--range-setting", "window=20"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown range setting 'window'"));
+}
+
+#[test]
+fn test_unpack_atomic() {
+    let input = test_data("ExampleSplitArchive/testnew.dz");
+    let tmp = tempfile::tempdir().unwrap();
+    let plain = tmp.path().join("plain");
+    let atomic = tmp.path().join("atomic");
+    unpack(&input, &plain, &[]);
+    unpack(&input, &atomic, &["--atomic"]);
+    assert_eq!(common::list_files(&atomic), common::list_files(&plain));
+    assert_eq!(
+        std::fs::read(atomic.join("BMP/Image4.bmp")).unwrap(),
+        std::fs::read(plain.join("BMP/Image4.bmp")).unwrap()
+    );
+}
+
+#[test]
+fn test_unpack_to_archive() {
+    let input = test_data("ExampleSplitArchive/testnew.dz");
+    let tmp = tempfile::tempdir().unwrap();
+    let reference = tmp.path().join("reference");
+    unpack(&input, &reference, &[]);
+    let expected: BTreeMap<String, Vec<u8>> = list_files(&reference)
+        .into_iter()
+        .filter(|name| name != "testnew.toml")
+        .map(|name| {
+            let data = std::fs::read(reference.join(&name)).unwrap();
+            (name, data)
+        })
+        .collect();
+
+    let out = tmp.path().join("out");
+    let tar_path = tmp.path().join("files.tar");
+    unpack(&input, &out, &["--to-archive", tar_path.to_str().unwrap()]);
+    let mut tarred = BTreeMap::new();
+    let mut archive = tar::Archive::new(std::fs::File::open(&tar_path).unwrap());
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        if entry.header().entry_type() != tar::EntryType::Regular {
+            continue;
+        }
+        let name = entry.path().unwrap().to_string_lossy().to_string();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).unwrap();
+        tarred.insert(name, data);
+    }
+    assert_eq!(tarred, expected);
+
+    let zip_path = tmp.path().join("files.zip");
+    unpack(&input, &out, &["--to-archive", zip_path.to_str().unwrap()]);
+    let mut zipped = BTreeMap::new();
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(&zip_path).unwrap()).unwrap();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).unwrap();
+        if file.is_dir() {
+            continue;
+        }
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).unwrap();
+        zipped.insert(file.name().to_string(), data);
+    }
+    assert_eq!(zipped, expected);
+    // Only the config lands in the output directory
+    assert_eq!(list_files(&out), ["testnew.toml"]);
+
+    let output = common::dzip()
+        .arg("unpack")
+        .arg(&input)
+        .args(["-o", out.to_str().unwrap(), "--to-archive", "files.7z"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_unpack_config_only() {
+    let input = test_data("ExampleSplitArchive/testnew.dz");
+    let tmp = tempfile::tempdir().unwrap();
+    let full = tmp.path().join("full");
+    let config_only = tmp.path().join("config_only");
+    unpack(&input, &full, &[]);
+    unpack(&input, &config_only, &["--config-only"]);
+
+    assert_eq!(list_files(&config_only), ["testnew.toml"]);
+    assert_eq!(
+        std::fs::read_to_string(config_only.join("testnew.toml")).unwrap(),
+        std::fs::read_to_string(full.join("testnew.toml")).unwrap()
+    );
+}
+
+#[test]
+fn test_unpack_header_only_archive() {
+    let tmp = tempfile::tempdir().unwrap();
+    let input = tmp.path().join("empty.dz");
+    common::write_archive(&input, &[], |_| {});
+
+    let out = tmp.path().join("out");
+    unpack(&input, &out, &[]);
+    assert_eq!(list_files(&out), ["empty.toml"]);
+
+    let output = common::dzip().arg("verify").arg(&input).output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("(archive contains no files)"));
+
+    // Repacking the generated config reproduces the archive
+    let packed = tmp.path().join("packed");
+    common::pack(&out.join("empty.toml"), &packed, &[]);
+    assert_eq!(
+        std::fs::read(packed.join("empty.dz")).unwrap(),
+        std::fs::read(&input).unwrap()
+    );
+}
+
+#[test]
+fn test_unpack_encoding() {
+    let tmp = tempfile::tempdir().unwrap();
+    let archive = tmp.path().join("sjis.dz");
+    common::write_archive(
+        &archive,
+        &[("abcd.txt", dzip_core::format::CHUNK_COPYCOMP, b"data")],
+        |_| {},
+    );
+    // "日本.txt" in Shift_JIS has the same length as the placeholder name
+    let mut bytes = std::fs::read(&archive).unwrap();
+    let pos = bytes.windows(4).position(|w| w == b"abcd").unwrap();
+    bytes[pos..pos + 4].copy_from_slice(&[0x93, 0xFA, 0x96, 0x7B]);
+    std::fs::write(&archive, bytes).unwrap();
+
+    let out = tmp.path().join("out");
+    let failed = common::dzip()
+        .arg("unpack")
+        .arg(&archive)
+        .arg("-o")
+        .arg(&out)
+        .output()
+        .unwrap();
+    assert!(!failed.status.success());
+
+    unpack(&archive, &out, &["--encoding", "shift_jis"]);
+    assert_eq!(std::fs::read(out.join("日本.txt")).unwrap(), b"data");
+    let config = std::fs::read_to_string(out.join("sjis.toml")).unwrap();
+    assert!(config.contains("日本.txt"), "{}", config);
+}
+
+#[test]
+fn test_unpack_mmap() {
+    let tmp = tempfile::tempdir().unwrap();
+    let archive = test_data("ExampleSplitArchive/testnew.dz");
+    let plain = tmp.path().join("plain");
+    let mapped = tmp.path().join("mapped");
+    unpack(&archive, &plain, &[]);
+    unpack(&archive, &mapped, &["--mmap"]);
+
+    let files = list_files(&plain);
+    assert_eq!(files, list_files(&mapped));
+    for file in files {
+        assert_eq!(
+            std::fs::read(plain.join(&file)).unwrap(),
+            std::fs::read(mapped.join(&file)).unwrap(),
+            "{}",
+            file
+        );
+    }
+}
+
+#[test]
+fn test_unpack_cache_shared_chunks() {
+    use dzip_core::format::*;
+    let tmp = tempfile::tempdir().unwrap();
+    let input = tmp.path().join("shared.dz");
+    let shared = "shared text ".repeat(40);
+    let (flags, compressed) =
+        dzip_core::compress_data(shared.as_bytes(), dzip_core::CompressionMethod::Zlib).unwrap();
+    // Files in two directories point at chunk 0; the last one also has its own chunk
+    let mut header = ArchiveHeader {
+        settings: ArchiveSettings {
+            header: DTRZ_MAGIC,
+            num_user_files: 3,
+            num_directories: 3,
+            version: 0,
+        },
+        strings: ["one.txt", "two.txt", "both.txt", "A", "B"]
+            .map(String::from)
+            .to_vec(),
+        map: vec![(1, vec![0]), (2, vec![0]), (0, vec![0, 1])],
+        chunk_settings: ChunkSettings {
+            num_archive_files: 1,
+            num_chunks: 2,
+        },
+        chunks: vec![
+            Chunk {
+                offset: 0,
+                compressed_length: compressed.len() as u32,
+                decompressed_length: shared.len() as u32,
+                flags,
+                file: 0,
+            },
+            Chunk {
+                offset: 0,
+                compressed_length: 4,
+                decompressed_length: 4,
+                flags: CHUNK_COPYCOMP,
+                file: 0,
+            },
+        ],
+        volume_files: Vec::new(),
+        range_settings: None,
+    };
+    // compute_header_size assumes one chunk per file, so measure the header instead
+    let mut data = std::io::Cursor::new(Vec::new());
+    dzip_core::writer::DzipWriter::new(&mut data)
+        .write_header(&header)
+        .unwrap();
+    let header_len = data.get_ref().len() as u32;
+    header.chunks[0].offset = header_len;
+    header.chunks[1].offset = header_len + compressed.len() as u32;
+    data.set_position(0);
+    dzip_core::writer::DzipWriter::new(&mut data)
+        .write_header(&header)
+        .unwrap();
+    let mut data = data.into_inner();
+    data.extend_from_slice(&compressed);
+    data.extend_from_slice(b"tail");
+    std::fs::write(&input, data).unwrap();
+
+    let out = tmp.path().join("out");
+    let output = unpack(&input, &out, &["--cache-shared-chunks"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Caching 1 shared chunk(s)"), "{}", stderr);
+    assert_eq!(
+        std::fs::read_to_string(out.join("A/one.txt")).unwrap(),
+        shared
+    );
+    assert_eq!(
+        std::fs::read_to_string(out.join("B/two.txt")).unwrap(),
+        shared
+    );
+    assert_eq!(
+        std::fs::read_to_string(out.join("both.txt")).unwrap(),
+        format!("{}tail", shared)
+    );
+
+    // A cache too small for the chunk falls back to decoding it for every file
+    let small = tmp.path().join("small");
+    unpack(&input, &small, &["--cache-shared-chunks=16"]);
+    for file in ["A/one.txt", "B/two.txt", "both.txt"] {
+        assert_eq!(
+            std::fs::read(out.join(file)).unwrap(),
+            std::fs::read(small.join(file)).unwrap()
+        );
+    }
+}
+
+#[test]
+fn test_unpack_root_level_files() {
+    use dzip_core::format::*;
+    let tmp = tempfile::tempdir().unwrap();
+    let input = tmp.path().join("rootfiles.dz");
+    // Directory 1 is "." and directory 2 is "\", both spellings of the root
+    let header = |data_start: u32| ArchiveHeader {
+        settings: ArchiveSettings {
+            header: DTRZ_MAGIC,
+            num_user_files: 2,
+            num_directories: 3,
+            version: 0,
+        },
+        strings: ["top.txt", "dot.txt", ".", "\\"].map(String::from).to_vec(),
+        map: vec![(0, vec![0]), (1, vec![1])],
+        chunk_settings: ChunkSettings {
+            num_archive_files: 1,
+            num_chunks: 2,
+        },
+        chunks: (0..2)
+            .map(|i| Chunk {
+                offset: data_start + i * 3,
+                compressed_length: 3,
+                decompressed_length: 3,
+                flags: CHUNK_COPYCOMP,
+                file: 0,
+            })
+            .collect(),
+        volume_files: Vec::new(),
+        range_settings: None,
+    };
+    let mut data = std::io::Cursor::new(Vec::new());
+    dzip_core::writer::DzipWriter::new(&mut data)
+        .write_header(&header(0))
+        .unwrap();
+    let data_start = data.get_ref().len() as u32;
+    data.set_position(0);
+    dzip_core::writer::DzipWriter::new(&mut data)
+        .write_header(&header(data_start))
+        .unwrap();
+    let mut data = data.into_inner();
+    data.extend_from_slice(b"topdot");
+    std::fs::write(&input, data).unwrap();
+
+    let out = tmp.path().join("out");
+    unpack(&input, &out, &[]);
+    assert_eq!(
+        list_files(&out),
+        vec!["dot.txt", "rootfiles.toml", "top.txt"]
+    );
+    assert_eq!(std::fs::read(out.join("top.txt")).unwrap(), b"top");
+    assert_eq!(std::fs::read(out.join("dot.txt")).unwrap(), b"dot");
+    // The unused root spelling is not recorded as an empty directory
+    let config = std::fs::read_to_string(out.join("rootfiles.toml")).unwrap();
+    assert!(!config.contains("empty_dirs = [\""), "{}", config);
+}
+
+#[test]
+fn test_unpack_directory_id_out_of_range() {
+    use dzip_core::format::*;
+    let tmp = tempfile::tempdir().unwrap();
+    let input = tmp.path().join("baddir.dz");
+    // File 1 points at directory 5, but only the root exists
+    let header = |data_start: u32| ArchiveHeader {
+        settings: ArchiveSettings {
+            header: DTRZ_MAGIC,
+            num_user_files: 2,
+            num_directories: 1,
+            version: 0,
+        },
+        strings: ["good.txt", "lost.txt"].map(String::from).to_vec(),
+        map: vec![(0, vec![0]), (5, vec![1])],
+        chunk_settings: ChunkSettings {
+            num_archive_files: 1,
+            num_chunks: 2,
+        },
+        chunks: (0..2)
+            .map(|i| Chunk {
+                offset: data_start + i * 4,
+                compressed_length: 4,
+                decompressed_length: 4,
+                flags: CHUNK_COPYCOMP,
+                file: 0,
+            })
+            .collect(),
+        volume_files: Vec::new(),
+        range_settings: None,
+    };
+    let mut data = std::io::Cursor::new(Vec::new());
+    dzip_core::writer::DzipWriter::new(&mut data)
+        .write_header(&header(0))
+        .unwrap();
+    let data_start = data.get_ref().len() as u32;
+    data.set_position(0);
+    dzip_core::writer::DzipWriter::new(&mut data)
+        .write_header(&header(data_start))
+        .unwrap();
+    let mut data = data.into_inner();
+    data.extend_from_slice(b"goodlost");
+    std::fs::write(&input, data).unwrap();
+
+    let out = tmp.path().join("out");
+    let output = unpack(&input, &out, &[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("File 1 has directory id 5")
+            && stderr.contains("extracting it to the root"),
+        "{}",
+        stderr
+    );
+    assert_eq!(std::fs::read(out.join("lost.txt")).unwrap(), b"lost");
+
+    let output = dzip()
+        .args(["unpack", "--strict"])
+        .arg(&input)
+        .arg("-o")
+        .arg(tmp.path().join("strict"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("File 1 has directory id 5"), "{}", stderr);
+
+    let output = dzip().arg("verify").arg(&input).output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("1 OK") && stdout.contains("1 BAD_DIR"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn test_unpack_self_extracting() {
+    let tmp = tempfile::tempdir().unwrap();
+    let mut data = b"MZ stub ".repeat(100);
+    data.extend_from_slice(&std::fs::read(test_data("ExampleSingleArchive/test1.dz")).unwrap());
+    let input = tmp.path().join("test1.exe");
+    std::fs::write(&input, &data).unwrap();
+
+    let reference = tmp.path().join("reference");
+    unpack(&test_data("ExampleSingleArchive/test1.dz"), &reference, &[]);
+    let reference_files = list_files(&reference);
+
+    for args in [&[][..], &["--archive-offset", "800"], &["--mmap"]] {
+        let out = tmp.path().join("out");
+        let output = unpack(&input, &out, args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("Archive starts at offset 800"),
+            "{}",
+            stderr
+        );
+        for file in reference_files.iter().filter(|f| !f.ends_with(".toml")) {
+            assert_eq!(
+                std::fs::read(out.join(file)).unwrap(),
+                std::fs::read(reference.join(file)).unwrap(),
+                "{}",
+                file
+            );
+        }
+        std::fs::remove_dir_all(&out).unwrap();
+    }
+
+    // A wrong offset points at no archive
+    let output = dzip()
+        .args(["unpack", "--archive-offset", "799"])
+        .arg(&input)
+        .arg("-o")
+        .arg(tmp.path().join("bad"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_progress_bar_only_on_terminal() {
+    let tmp = tempfile::tempdir().unwrap();
+    let input = test_data("ExampleSingleArchive/test1.dz");
+
+    // stderr is a pipe here, so the bar stays hidden unless forced
+    let output = unpack(&input, &tmp.path().join("auto"), &[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains('\r'), "{}", stderr);
+    assert!(!stderr.contains("Unpack complete"), "{}", stderr);
+
+    let output = unpack(&input, &tmp.path().join("forced"), &["--progress"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Unpack complete"), "{}", stderr);
+
+    let output = dzip()
+        .args(["--progress", "--no-progress", "unpack"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
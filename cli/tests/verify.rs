@@ -0,0 +1,323 @@
+mod common;
+
+use common::{dzip, test_data, write_archive};
+use sha2::{Digest, Sha256};
+
+/// Returns the verify table row for `path`.
+fn row<'a>(stdout: &'a str, path: &str) -> &'a str {
+    stdout
+        .lines()
+        .find(|line| line.ends_with(&format!("| {}", path)))
+        .unwrap_or_else(|| panic!("no row for {} in:\n{}", path, stdout))
+}
+
+#[test]
+fn test_verify_checksums_file() {
+    let tmp = tempfile::tempdir().unwrap();
+    let original = std::fs::read(test_data("DerbhExampleFiles/Image16b.bmp")).unwrap();
+    let sums = tmp.path().join("SHA256SUMS");
+    std::fs::write(
+        &sums,
+        format!(
+            "{:x}  Image16b.bmp\n{}  BMP\\Image4.bmp\n",
+            Sha256::digest(&original),
+            "0".repeat(64)
+        ),
+    )
+    .unwrap();
+
+    let out = dzip()
+        .arg("verify")
+        .arg(test_data("ExampleSplitArchive/testnew.dz"))
+        .arg("--checksums-file")
+        .arg(&sums)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+
+    assert!(stdout.contains("Format version: v0 (Marmalade DZ)"));
+    assert!(row(&stdout, "Image16b.bmp").contains("| OK "));
+    assert!(row(&stdout, "BMP/Image4.bmp").contains("| HASH_FAIL "));
+    assert!(row(&stdout, "TXT/Text1.txt").contains("| OK "));
+}
+
+#[test]
+fn test_verify_jpeg_markers() {
+    use dzip_core::format::CHUNK_JPEG;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("jpeg.dz");
+    write_archive(
+        &path,
+        &[
+            (
+                "good.jpg",
+                CHUNK_JPEG,
+                &[0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, 0xFF, 0xD9],
+            ),
+            ("bad.jpg", CHUNK_JPEG, b"not a jpeg stream"),
+        ],
+        |_| {},
+    );
+
+    let out = dzip().arg("verify").arg(&path).output().unwrap();
+    assert!(out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+
+    assert!(row(&stdout, "good.jpg").contains("| OK "));
+    assert!(row(&stdout, "good.jpg").contains("| Jpeg "));
+    assert!(row(&stdout, "bad.jpg").contains("| BAD_JPEG "));
+}
+
+#[test]
+fn test_verify_reports_chunk_layout() {
+    use dzip_core::format::CHUNK_COPYCOMP;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("overlap.dz");
+    write_archive(
+        &path,
+        &[
+            ("a.txt", CHUNK_COPYCOMP, b"first file"),
+            ("b.txt", CHUNK_COPYCOMP, b"second file"),
+        ],
+        |chunks| chunks[0].compressed_length += 4,
+    );
+
+    let out = dzip().arg("verify").arg(&path).output().unwrap();
+    assert!(out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        stdout.contains("ERROR chunk 0 overlaps chunk 1 in volume 0"),
+        "{}",
+        stdout
+    );
+
+    let out = dzip()
+        .arg("unpack")
+        .arg(&path)
+        .arg("-o")
+        .arg(tmp.path().join("out"))
+        .arg("--strict-layout")
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Invalid chunk layout"));
+}
+
+#[test]
+fn test_verify_explain() {
+    let out = dzip()
+        .arg("verify")
+        .arg(test_data("ExampleSplitArchive/testnew.dz"))
+        .arg("--explain")
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let decoder_rows: Vec<&str> = stdout
+        .lines()
+        .skip_while(|line| !line.starts_with("Chunk | Decoder"))
+        .skip(2)
+        .collect();
+    assert_eq!(decoder_rows.len(), 8, "{}", stdout);
+    assert_eq!(decoder_rows[3], "3     | BZIP     | 0x0010 BZIP");
+    assert_eq!(decoder_rows[6], "6     | ZERO     | 0x0080 ZERO");
+}
+
+#[test]
+fn test_verify_sort() {
+    let out = dzip()
+        .arg("verify")
+        .arg(test_data("ExampleSplitArchive/testnew.dz"))
+        .args(["--sort", "path"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let paths: Vec<&str> = stdout
+        .lines()
+        .skip_while(|line| !line.starts_with("-----"))
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .map(|line| line.rsplit("| ").next().unwrap())
+        .collect();
+    assert!(paths.len() > 1, "{}", stdout);
+    assert!(paths.windows(2).all(|w| w[0] <= w[1]), "{:?}", paths);
+}
+
+#[test]
+fn test_verify_limit() {
+    let verify = |args: &[&str]| {
+        let out = dzip()
+            .arg("verify")
+            .arg(test_data("ExampleSplitArchive/testnew.dz"))
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        String::from_utf8(out.stdout).unwrap()
+    };
+    let rows = |stdout: &str| -> Vec<String> {
+        stdout
+            .lines()
+            .skip_while(|line| !line.starts_with("-----"))
+            .skip(1)
+            .take_while(|line| !line.is_empty())
+            .map(String::from)
+            .collect()
+    };
+
+    let all = verify(&["--sort", "size"]);
+    let limited = verify(&["--sort", "size", "--limit", "2"]);
+    assert_eq!(rows(&limited), rows(&all)[..2]);
+    assert!(
+        limited.contains("Summary: 2 OK, 0 FAIL, 0 UNSUPP (first 2 of 7 files)"),
+        "{}",
+        limited
+    );
+    // A limit past the file count checks everything
+    assert!(verify(&["--limit", "100"]).contains("Summary: 7 OK, 0 FAIL, 0 UNSUPP\n"));
+}
+
+#[test]
+fn test_verify_no_size_correction() {
+    let archive = test_data("ExampleSplitArchive/testnew.dz");
+    let out = dzip().arg("verify").arg(&archive).output().unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(row(&stdout, "Image16b.bmp").contains("| OK "));
+
+    // testnew.dz lists uncompressed sizes as compressed lengths, so reading
+    // them verbatim runs past the end of the volume
+    let out = dzip()
+        .arg("verify")
+        .arg(&archive)
+        .arg("--no-size-correction")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Size correction disabled"));
+    assert!(row(&stdout, "Image16b.bmp").contains("| FAIL "));
+    assert!(row(&stdout, "TXT/Text1.txt").contains("| OK "));
+}
+
+#[test]
+fn test_verify_repair_sizes() {
+    let tmp = tempfile::tempdir().unwrap();
+    for name in ["testnew.dz", "testnew1.dz", "testnew2.dz", "testnew3.dz"] {
+        std::fs::copy(
+            test_data(&format!("ExampleSplitArchive/{}", name)),
+            tmp.path().join(name),
+        )
+        .unwrap();
+    }
+    let fixed = tmp.path().join("fixed.dz");
+    let out = dzip()
+        .arg("verify")
+        .arg(tmp.path().join("testnew.dz"))
+        .arg("--repair-sizes")
+        .arg(&fixed)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).contains("corrected chunk length(s)"));
+
+    // The copy reads correctly without correction and has the same size
+    let out = dzip()
+        .arg("verify")
+        .arg(&fixed)
+        .arg("--no-size-correction")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    for path in ["Image16b.bmp", "BMP/Image16.bmp", "BMP/Image8.bmp"] {
+        assert!(row(&stdout, path).contains("| OK "), "{}", stdout);
+    }
+    assert_eq!(
+        std::fs::metadata(&fixed).unwrap().len(),
+        std::fs::metadata(tmp.path().join("testnew.dz"))
+            .unwrap()
+            .len()
+    );
+}
+
+#[test]
+fn test_verify_unsupported_summary() {
+    use dzip_core::format::{CHUNK_COPYCOMP, CHUNK_DZ};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("mixed.dz");
+    write_archive(
+        &path,
+        &[
+            ("a.txt", CHUNK_COPYCOMP, b"first file"),
+            ("b.txt", CHUNK_COPYCOMP, b"second file"),
+            ("level.bin", CHUNK_DZ, b"not decodable here"),
+        ],
+        |_| {},
+    );
+
+    let out = dzip().arg("verify").arg(&path).output().unwrap();
+    assert!(out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        row(&stdout, "level.bin").contains("| UNSUPP "),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Summary: 2 OK, 0 FAIL, 1 UNSUPP\n"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn test_verify_sample() {
+    let verify = |args: &[&str]| {
+        let out = dzip()
+            .arg("verify")
+            .arg(test_data("ExampleSplitArchive/testnew.dz"))
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        String::from_utf8(out.stdout).unwrap()
+    };
+    let rows = |stdout: &str| stdout.lines().filter(|l| l.contains("| OK ")).count();
+
+    let first = verify(&["--sample", "40%", "--seed", "5"]);
+    assert!(
+        first.contains("Sampling 3 of 7 files (40%, seed 5)."),
+        "{}",
+        first
+    );
+    assert!(first.contains("Summary: 3 OK, 0 FAIL, 0 UNSUPP (sample of 3 of 7 files)"));
+    assert_eq!(rows(&first), 3);
+    // The same seed checks the same files
+    assert_eq!(verify(&["--sample", "40", "--seed", "5"]), first);
+
+    let all = verify(&["--sample", "100%"]);
+    assert_eq!(rows(&all), 7);
+    // A tiny percentage still checks one file
+    assert_eq!(rows(&verify(&["--sample", "0.1%"])), 1);
+
+    for args in [
+        &["--sample", "0%"][..],
+        &["--sample", "150%"],
+        &["--seed", "5"],
+    ] {
+        let out = dzip()
+            .arg("verify")
+            .arg(test_data("ExampleSplitArchive/testnew.dz"))
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(!out.status.success(), "{:?}", args);
+    }
+}
@@ -0,0 +1,200 @@
+//! High-level access to an archive: the header, read once with chunk sizes
+//! corrected, and extraction of single files.
+//!
+//! `Archive::open` does what every caller of `DzipReader` would otherwise
+//! repeat: read the header, check the header CRC and correct the compressed
+//! lengths against the actual volume sizes (see `correct_chunk_sizes`).
+
+use crate::error::{DzipError, Result};
+use crate::format::{ArchiveHeader, Chunk, Endianness};
+use crate::path::resolve_file_path;
+use crate::progress::ProgressFn;
+use crate::reader::{DzipReader, HeaderCrc, VolumeSource, correct_chunk_sizes, locate_archive};
+use crate::source::OffsetReader;
+use crate::unpack::{ExtractStats, extract_chunks_with_progress};
+use crate::volume::FileSystemVolumeManager;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// A user file as listed by `Archive::files`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveFile<'a> {
+    /// Index in the file map
+    pub index: usize,
+    /// Path inside the archive, with the separators as stored
+    pub path: String,
+    /// Decompressed size, from the chunk table
+    pub size: u64,
+    pub chunk_ids: &'a [u16],
+}
+
+/// An open archive: volume 0 and the source of its auxiliary volumes.
+pub struct Archive<R: Read + Seek, V: VolumeSource> {
+    reader: DzipReader<R>,
+    volumes: V,
+    header: ArchiveHeader,
+    header_crc: HeaderCrc,
+}
+
+impl Archive<OffsetReader<File>, FileSystemVolumeManager> {
+    /// Opens the archive at `path`, with its auxiliary volumes next to it.
+    ///
+    /// An archive behind a self-extracting stub is found as by `locate_archive`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut file = File::open(path)?;
+        let base = locate_archive(&mut file)?;
+        let main = OffsetReader::new(file, base)?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+        Self::read(main, |header| {
+            FileSystemVolumeManager::new(base_dir.to_path_buf(), header.volume_files.clone())
+        })
+    }
+}
+
+impl<R: Read + Seek, V: VolumeSource> Archive<R, V> {
+    /// Reads the header from `main` (volume 0) and corrects the chunk sizes
+    /// against the volumes `volumes` serves. Volumes that cannot be opened are
+    /// left uncorrected; reading from them fails later.
+    ///
+    /// Fails with `HeaderCorrupt` if the archive has a header CRC that does not match.
+    pub fn from_parts(main: R, volumes: V) -> Result<Self> {
+        Self::read(main, |_| volumes)
+    }
+
+    /// `volumes` is built once the header, and with it the volume list, is known.
+    fn read(mut main: R, volumes: impl FnOnce(&ArchiveHeader) -> V) -> Result<Self> {
+        let main_size = main.seek(SeekFrom::End(0))?;
+        main.seek(SeekFrom::Start(0))?;
+        let mut reader = DzipReader::new(main);
+        let mut header = reader.read_header()?;
+        let header_crc = reader.verify_header_crc()?;
+        let mut volumes = volumes(&header);
+
+        let mut file_sizes = HashMap::new();
+        file_sizes.insert(0u16, main_size - header_crc.trailer_size());
+        for id in 1..header.chunk_settings.num_archive_files {
+            if let Ok(volume) = volumes.open_volume(id) {
+                file_sizes.insert(id, volume.seek(SeekFrom::End(0))?);
+            }
+        }
+        correct_chunk_sizes(&mut header.chunks, &file_sizes);
+        Ok(Self {
+            reader,
+            volumes,
+            header,
+            header_crc,
+        })
+    }
+
+    /// The header, with the corrected chunk sizes.
+    pub fn header(&self) -> &ArchiveHeader {
+        &self.header
+    }
+
+    pub fn header_crc(&self) -> HeaderCrc {
+        self.header_crc
+    }
+
+    /// Byte order of the header.
+    pub fn endianness(&self) -> Endianness {
+        self.reader.endianness()
+    }
+
+    /// Length of the header up to the end of the chunk table, the range a
+    /// header CRC covers.
+    pub fn chunk_table_end(&self) -> u64 {
+        self.reader
+            .chunk_table_end()
+            .expect("the header was read when the archive was opened")
+    }
+
+    /// The user files, in archive order.
+    pub fn files(&self) -> impl Iterator<Item = ArchiveFile<'_>> {
+        self.header
+            .map
+            .iter()
+            .enumerate()
+            .map(|(index, (_, chunk_ids))| ArchiveFile {
+                index,
+                path: self.header.file_path(index).unwrap_or_default(),
+                size: self.header.file_decompressed_size(index).unwrap_or(0),
+                chunk_ids,
+            })
+    }
+
+    /// The chunk table, with the corrected compressed lengths.
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.header.chunks
+    }
+
+    /// Names of the auxiliary volumes (volume 1 onwards).
+    pub fn volumes(&self) -> &[String] {
+        &self.header.volume_files
+    }
+
+    /// Decodes chunk `id`.
+    pub fn read_chunk(&mut self, id: u16) -> Result<Vec<u8>> {
+        let chunk = self.header.chunk(id)?;
+        self.reader
+            .read_chunk_data_with_volumes(chunk, &mut self.volumes)
+    }
+
+    /// Reads the stored bytes of chunk `id` without decoding them.
+    pub fn read_raw_chunk(&mut self, id: u16) -> Result<Vec<u8>> {
+        let chunk = self.header.chunk(id)?;
+        self.reader
+            .read_raw_chunk_data_with_volumes(chunk, &mut self.volumes)
+    }
+
+    /// Index of the file at `path`. Either separator matches, and `.` and
+    /// empty components are ignored, as when extracting.
+    pub fn find_file(&self, path: &str) -> Result<usize> {
+        let wanted = resolve_file_path(path)?;
+        (0..self.header.map.len())
+            .find(|&i| {
+                self.header
+                    .file_path(i)
+                    .and_then(|found| resolve_file_path(&found))
+                    .is_ok_and(|found| found == wanted)
+            })
+            .ok_or_else(|| DzipError::PathNotFound(path.to_string()))
+    }
+
+    /// Decompresses the file at `path` into `out`; see `find_file`.
+    ///
+    /// Chunks that fail to decode are skipped and counted in the returned stats,
+    /// as by `extract_chunks`.
+    pub fn extract_file(&mut self, path: &str, out: &mut dyn Write) -> Result<ExtractStats> {
+        let index = self.find_file(path)?;
+        self.extract_index(index, out)
+    }
+
+    /// Decompresses user file `index` into `out`.
+    pub fn extract_index(&mut self, index: usize, out: &mut dyn Write) -> Result<ExtractStats> {
+        self.extract_index_with_progress(index, out, None)
+    }
+
+    /// Like `extract_index`, reporting each decoded chunk to `progress`.
+    pub fn extract_index_with_progress(
+        &mut self,
+        index: usize,
+        out: &mut dyn Write,
+        progress: Option<ProgressFn<'_>>,
+    ) -> Result<ExtractStats> {
+        let (_, chunk_ids) = self.header.map.get(index).ok_or(DzipError::FileNotFound {
+            index,
+            count: self.header.map.len(),
+        })?;
+        extract_chunks_with_progress(
+            &mut self.reader,
+            &mut self.volumes,
+            &self.header.chunks,
+            chunk_ids,
+            out,
+            progress,
+        )
+    }
+}
@@ -23,6 +23,61 @@ pub enum DzipError {
 
     #[error("Failed to open volume {0}: {1}")]
     VolumeOpenError(u16, String),
+
+    #[error("Volume {volume} ({name}) is missing")]
+    MissingVolume { volume: u16, name: String },
+
+    #[error("Chunk {id} is out of range ({count} chunks in archive)")]
+    ChunkNotFound { id: u16, count: usize },
+
+    #[error("File {index} is out of range ({count} files in archive)")]
+    FileNotFound { index: usize, count: usize },
+
+    #[error("No file named {0:?} in the archive")]
+    PathNotFound(String),
+
+    #[error("File {index} has directory id {dir_id}, but the archive has {count} directories")]
+    DirectoryNotFound {
+        index: usize,
+        dir_id: u16,
+        count: u16,
+    },
+
+    #[error("Header is truncated")]
+    HeaderTruncated,
+
+    #[error("Invalid archive settings: {0}")]
+    InvalidSettings(String),
+
+    #[error("Unsafe path {path:?}: {reason}")]
+    UnsafePath { path: String, reason: &'static str },
+
+    #[error("Refusing to write through symlink '{}'", .0.display())]
+    SymlinkRefused(std::path::PathBuf),
+
+    #[error("'{}' exists and is not a directory", .0.display())]
+    NotADirectory(std::path::PathBuf),
+
+    #[error("Header corrupt: CRC is {actual:#010x}, trailer records {expected:#010x}")]
+    HeaderCorrupt { expected: u32, actual: u32 },
+
+    #[error("Invalid string table entry {index} ({name:?}): {reason}")]
+    InvalidName {
+        index: usize,
+        name: String,
+        reason: &'static str,
+    },
+
+    #[error("Invalid name for volume {volume} ({name:?}): {reason}")]
+    InvalidVolumeName {
+        volume: usize,
+        name: String,
+        reason: &'static str,
+    },
+
+    /// Catch-all for failures without a dedicated variant
+    #[error("{0}")]
+    Generic(String),
 }
 
 pub type Result<T> = std::result::Result<T, DzipError>;
@@ -14,7 +14,35 @@
 //!
 //! - File data
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+use crate::error::{DzipError, Result};
+use serde::Serialize;
+
+/// Identification 'DTRZ' as read from a little-endian archive
+pub const DTRZ_MAGIC: u32 = 0x5A525444;
+
+/// Marks the optional header CRC trailer at the very end of volume 0.
+///
+/// The trailer is this magic followed by a little-endian CRC-32 of the header
+/// bytes from the DTRZ magic through the end of the chunk table. Readers that
+/// do not know it only see a few unreferenced bytes after the chunk data.
+pub const HEADER_CRC_MAGIC: [u8; 4] = *b"DZHC";
+
+/// Size of the header CRC trailer in bytes.
+pub const HEADER_CRC_TRAILER_SIZE: u64 = 8;
+
+/// Byte order of the integers stored in an archive header.
+///
+/// Archives are little-endian, but some builds for big-endian targets wrote
+/// byte-swapped headers. The magic reads as `0x44545A52` in that case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Endianness {
+    #[default]
+    Little,
+    Big,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub struct ArchiveSettings {
     /// Identification 'DTRZ'
     pub header: u32,
@@ -27,7 +55,62 @@ pub struct ArchiveSettings {
     pub version: u8,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Archive format versions, as stored in `ArchiveSettings::version`.
+///
+/// Parsing may differ between versions, so readers reject versions they do not
+/// know instead of misreading the header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FormatVersion {
+    /// The only version defined by DZSettings.h (`DZ_VERSION 0`)
+    #[default]
+    V0,
+}
+
+impl FormatVersion {
+    pub fn as_u8(self) -> u8 {
+        match self {
+            FormatVersion::V0 => 0,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FormatVersion::V0 => "v0 (Marmalade DZ)",
+        }
+    }
+}
+
+impl TryFrom<u8> for FormatVersion {
+    type Error = DzipError;
+
+    fn try_from(version: u8) -> std::result::Result<Self, Self::Error> {
+        match version {
+            0 => Ok(FormatVersion::V0),
+            other => Err(DzipError::UnsupportedVersion(other)),
+        }
+    }
+}
+
+impl std::fmt::Display for FormatVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl ArchiveSettings {
+    /// Decodes the version byte, failing for versions this crate cannot parse.
+    pub fn format_version(&self) -> Result<FormatVersion> {
+        FormatVersion::try_from(self.version)
+    }
+
+    /// Number of entries in the string table: user file names followed by every
+    /// directory except the implicit root.
+    pub fn num_strings(&self) -> usize {
+        (self.num_user_files as usize + self.num_directories as usize).saturating_sub(1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub struct ChunkSettings {
     /// Number of files used to store this archive
     pub num_archive_files: u16,
@@ -35,7 +118,10 @@ pub struct ChunkSettings {
     pub num_chunks: u16,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Size of one chunk table entry in bytes.
+pub const CHUNK_ENTRY_SIZE: u64 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub struct Chunk {
     /// The location of the chunk in its file
     pub offset: u32,
@@ -50,6 +136,42 @@ pub struct Chunk {
     pub file: u16,
 }
 
+impl Chunk {
+    /// Whether `compressed_length` is a placeholder. Some tools store the
+    /// decompressed size in both length fields of a compressed chunk, so the
+    /// real length is only known from where the next chunk starts.
+    pub fn has_placeholder_length(&self) -> bool {
+        let is_compressed = (self.flags
+            & (CHUNK_LZMA
+                | CHUNK_ZLIB
+                | CHUNK_BZIP
+                | CHUNK_DZ
+                | CHUNK_XZ
+                | CHUNK_GZIP
+                | CHUNK_BROTLI))
+            != 0;
+        is_compressed && self.compressed_length == self.decompressed_length
+    }
+
+    /// The compressed length to read the chunk with.
+    ///
+    /// The chunk's data ends at `next_offset`, the offset of the next chunk of
+    /// the same volume, or at `file_size` for the last chunk. A placeholder
+    /// length becomes all of that space; any other length is only clamped to
+    /// it. This is the rule `correct_chunk_sizes` applies to a whole table.
+    pub fn effective_compressed_length(&self, next_offset: Option<u32>, file_size: u64) -> u32 {
+        let limit = next_offset.map_or(file_size, u64::from);
+        let available = limit
+            .saturating_sub(self.offset as u64)
+            .min(u32::MAX as u64) as u32;
+        if self.has_placeholder_length() {
+            available
+        } else {
+            self.compressed_length.min(available)
+        }
+    }
+}
+
 // Chunk flags constants
 pub const CHUNK_COMBUF: u16 = 0x1; // Set to indicate a combuf chunk.
 pub const CHUNK_DZ: u16 = 0x4; // Set to indicate a dzip chunk, for use with range decoder
@@ -61,8 +183,111 @@ pub const CHUNK_ZERO: u16 = 0x80; // Set to indicate a zerod-out chunk
 pub const CHUNK_COPYCOMP: u16 = 0x100; // Set to indicate a copy-coded (ie no compression) chunk
 pub const CHUNK_LZMA: u16 = 0x200; // Set to indicate a lzma encoded chunk
 pub const CHUNK_RANDOMACCESS: u16 = 0x400; // Set to indicate whole chunk should be buffered for random access
+// Not part of DZSettings.h: allocated above CHUNK_RANDOMACCESS, leaving 0x2 reserved for the format
+pub const CHUNK_XZ: u16 = 0x800; // Set to indicate an xz-container (LZMA2) chunk, unlike raw CHUNK_LZMA
+pub const CHUNK_GZIP: u16 = 0x1000; // Set to indicate a gzip-framed (RFC 1952) chunk, kept framed as-is
+pub const CHUNK_BROTLI: u16 = 0x2000; // Set to indicate a brotli (RFC 7932) chunk
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Chunk flag bits and their names, in bit order.
+pub const CHUNK_FLAG_NAMES: [(u16, &str); 13] = [
+    (CHUNK_COMBUF, "COMBUF"),
+    (CHUNK_DZ, "DZ"),
+    (CHUNK_ZLIB, "ZLIB"),
+    (CHUNK_BZIP, "BZIP"),
+    (CHUNK_MP3, "MP3"),
+    (CHUNK_JPEG, "JPEG"),
+    (CHUNK_ZERO, "ZERO"),
+    (CHUNK_COPYCOMP, "COPYCOMP"),
+    (CHUNK_LZMA, "LZMA"),
+    (CHUNK_RANDOMACCESS, "RANDOMACCESS"),
+    (CHUNK_XZ, "XZ"),
+    (CHUNK_GZIP, "GZIP"),
+    (CHUNK_BROTLI, "BROTLI"),
+];
+
+/// Bits that no `CHUNK_*` constant names, e.g. vendor-specific flags. Readers
+/// ignore them; `method_for_flags` keeps them, so a repack writes them back.
+pub const UNKNOWN_FLAGS: u16 = !(CHUNK_COMBUF
+    | CHUNK_DZ
+    | CHUNK_ZLIB
+    | CHUNK_BZIP
+    | CHUNK_MP3
+    | CHUNK_JPEG
+    | CHUNK_ZERO
+    | CHUNK_COPYCOMP
+    | CHUNK_LZMA
+    | CHUNK_RANDOMACCESS
+    | CHUNK_XZ
+    | CHUNK_GZIP
+    | CHUNK_BROTLI);
+
+/// Codec bits in the order they take precedence when a chunk sets several.
+///
+/// COMBUF and RANDOMACCESS are not listed: they wrap the codec set next to them.
+/// Zero chunks have no data and stored data (COPYCOMP, MP3, JPEG) is never decoded
+/// further, so they come first. DZ comes last: next to another codec it is taken
+/// as an outer marker and the other codec decodes the data, so `DZ | ZLIB` is zlib.
+pub const CODEC_PRECEDENCE: [u16; 11] = [
+    CHUNK_ZERO,
+    CHUNK_COPYCOMP,
+    CHUNK_MP3,
+    CHUNK_JPEG,
+    CHUNK_ZLIB,
+    CHUNK_BZIP,
+    CHUNK_LZMA,
+    CHUNK_XZ,
+    CHUNK_GZIP,
+    CHUNK_BROTLI,
+    CHUNK_DZ,
+];
+
+/// The codec bit that decides how a chunk with `flags` is decoded (see
+/// `CODEC_PRECEDENCE`), or `None` if no codec bit is set.
+pub fn primary_codec(flags: u16) -> Option<u16> {
+    CODEC_PRECEDENCE.into_iter().find(|&bit| (flags & bit) != 0)
+}
+
+/// Names of the flags set in `flags`. Unknown bits are listed as hex values.
+pub fn flag_names(flags: u16) -> Vec<String> {
+    let mut names = Vec::new();
+    for (bit, name) in CHUNK_FLAG_NAMES {
+        if (flags & bit) != 0 {
+            names.push(name.to_string());
+        }
+    }
+    for bit in (0..16).map(|i| 1u16 << i) {
+        if (flags & bit & UNKNOWN_FLAGS) != 0 {
+            names.push(format!("{:#x}", bit));
+        }
+    }
+    names
+}
+
+/// Parses flag names as produced by `flag_names` back into chunk flags.
+///
+/// Names are case-insensitive; bits without a name are given as hex values
+/// (e.g. `0x4000`).
+pub fn encode_flags<S: AsRef<str>>(names: &[S]) -> Result<u16> {
+    let mut flags = 0;
+    for name in names {
+        let name = name.as_ref().trim();
+        let bit = CHUNK_FLAG_NAMES
+            .iter()
+            .find(|(_, known)| known.eq_ignore_ascii_case(name))
+            .map(|(bit, _)| *bit)
+            .or_else(|| {
+                let hex = name
+                    .strip_prefix("0x")
+                    .or_else(|| name.strip_prefix("0X"))?;
+                u16::from_str_radix(hex, 16).ok()
+            })
+            .ok_or_else(|| DzipError::InvalidSettings(format!("Unknown chunk flag '{}'", name)))?;
+        flags |= bit;
+    }
+    Ok(flags)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
 pub struct RangeSettings {
     /// log2(LZ-77 window size)
     pub win_size: u8,
@@ -85,3 +310,150 @@ pub struct RangeSettings {
     /// minimum match length for external references
     pub big_min_match: u8,
 }
+
+impl RangeSettings {
+    /// Field names accepted by `set`, in header order.
+    pub const FIELDS: [&'static str; 10] = [
+        "win_size",
+        "flags",
+        "offset_table_size",
+        "offset_tables",
+        "offset_contexts",
+        "ref_length_table_size",
+        "ref_length_tables",
+        "ref_offset_table_size",
+        "ref_offset_tables",
+        "big_min_match",
+    ];
+
+    /// Sets the field named `name` (one of `FIELDS`) to `value`.
+    pub fn set(&mut self, name: &str, value: u8) -> Result<()> {
+        let field = match name {
+            "win_size" => &mut self.win_size,
+            "flags" => &mut self.flags,
+            "offset_table_size" => &mut self.offset_table_size,
+            "offset_tables" => &mut self.offset_tables,
+            "offset_contexts" => &mut self.offset_contexts,
+            "ref_length_table_size" => &mut self.ref_length_table_size,
+            "ref_length_tables" => &mut self.ref_length_tables,
+            "ref_offset_table_size" => &mut self.ref_offset_table_size,
+            "ref_offset_tables" => &mut self.ref_offset_tables,
+            "big_min_match" => &mut self.big_min_match,
+            _ => {
+                return Err(DzipError::Generic(format!(
+                    "Unknown range setting '{}' (expected one of: {})",
+                    name,
+                    Self::FIELDS.join(", ")
+                )));
+            }
+        };
+        *field = value;
+        Ok(())
+    }
+}
+
+/// Everything stored in the header of volume 0, in on-disk order.
+///
+/// Read with `DzipReader::read_header` and written with `DzipWriter::write_header`,
+/// so every command goes through the same header layout.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ArchiveHeader {
+    pub settings: ArchiveSettings,
+    /// User file names followed by the directories (without the implicit root)
+    pub strings: Vec<String>,
+    /// Directory id and chunk ids of every user file
+    #[serde(serialize_with = "serialize_file_map")]
+    pub map: Vec<(u16, Vec<u16>)>,
+    pub chunk_settings: ChunkSettings,
+    pub chunks: Vec<Chunk>,
+    /// Names of the auxiliary volumes, starting with volume 1
+    pub volume_files: Vec<String>,
+    /// Range decoder settings; only present if some chunk is `CHUNK_DZ`
+    pub range_settings: Option<RangeSettings>,
+}
+
+/// Serializes the file map as `{ dir_id, chunk_ids }` objects rather than pairs.
+fn serialize_file_map<S: serde::Serializer>(
+    map: &[(u16, Vec<u16>)],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct FileMapEntry<'a> {
+        dir_id: u16,
+        chunk_ids: &'a [u16],
+    }
+    serializer.collect_seq(map.iter().map(|(dir_id, chunk_ids)| FileMapEntry {
+        dir_id: *dir_id,
+        chunk_ids,
+    }))
+}
+
+impl ArchiveHeader {
+    /// Directory string for `dir_id`. Id 0 is the implicit root (`""`);
+    /// returns `None` for ids past the directory table.
+    pub fn directory(&self, dir_id: u16) -> Option<&str> {
+        match dir_id {
+            0 => Some(""),
+            id => self
+                .strings
+                .get(self.settings.num_user_files as usize + id as usize - 1)
+                .map(String::as_str),
+        }
+    }
+
+    /// Directory string of user file `index`. Fails with `DirectoryNotFound`
+    /// if its directory id is past the directory table, which only a corrupt
+    /// file map produces; `file_path` places such files in the root instead.
+    pub fn file_directory(&self, index: usize) -> Result<&str> {
+        let (dir_id, _) = self.map.get(index).ok_or(DzipError::FileNotFound {
+            index,
+            count: self.map.len(),
+        })?;
+        self.directory(*dir_id).ok_or(DzipError::DirectoryNotFound {
+            index,
+            dir_id: *dir_id,
+            count: self.settings.num_directories,
+        })
+    }
+
+    /// Chunk `id` of the chunk table.
+    pub fn chunk(&self, id: u16) -> Result<&Chunk> {
+        self.chunks
+            .get(id as usize)
+            .ok_or(DzipError::ChunkNotFound {
+                id,
+                count: self.chunks.len(),
+            })
+    }
+
+    /// Archive path (directory and file name, `\`-separated) of user file `index`.
+    /// Files with an out-of-range directory id are placed in the root; an
+    /// `index` past the file map fails with `FileNotFound`.
+    pub fn file_path(&self, index: usize) -> Result<String> {
+        let ((dir_id, _), name) =
+            self.map
+                .get(index)
+                .zip(self.strings.get(index))
+                .ok_or(DzipError::FileNotFound {
+                    index,
+                    count: self.map.len(),
+                })?;
+        let dir = self.directory(*dir_id).unwrap_or("");
+        Ok(crate::path::join_archive_path(dir, name))
+    }
+
+    /// Decompressed size of user file `index`: the sum of its chunks'
+    /// `decompressed_length`, taken from the tables without reading any data.
+    ///
+    /// Decompressed lengths are what the header records, so this is exact with or
+    /// without `correct_chunk_sizes` (which only fixes compressed lengths).
+    pub fn file_decompressed_size(&self, index: usize) -> Result<u64> {
+        let (_, chunk_ids) = self.map.get(index).ok_or(DzipError::FileNotFound {
+            index,
+            count: self.map.len(),
+        })?;
+        chunk_ids.iter().try_fold(0u64, |size, &id| {
+            Ok(size + self.chunk(id)?.decompressed_length as u64)
+        })
+    }
+}
@@ -1,13 +1,26 @@
+pub mod archive;
 pub mod error;
 pub mod format;
 pub mod path;
+pub mod progress;
 pub mod reader;
+pub mod repair;
+pub mod sink;
+pub mod source;
+pub mod unpack;
+pub mod validate;
 pub mod volume;
 pub mod writer;
+pub mod xor;
 
+pub use archive::{Archive, ArchiveFile};
 pub use error::{DzipError, Result};
-pub use format::{ArchiveSettings, Chunk, ChunkSettings, RangeSettings};
-pub use writer::{CompressionMethod, compress_data};
+pub use format::{ArchiveSettings, Chunk, ChunkSettings, Endianness, FormatVersion, RangeSettings};
+pub use writer::{
+    ArchivePlan, BrotliOptions, CompressionMethod, LzmaOptions, StreamedChunk, compress_data,
+    compress_into, compress_stream, compress_with_flags, compute_header_size,
+};
+pub use xor::XorKey;
 
 // #[cfg(test)]
 // mod tests;
@@ -16,18 +16,18 @@ pub fn sanitize_path(path: &Path) -> Result<PathBuf> {
             }
             Component::CurDir => {}
             Component::ParentDir => {
-                return Err(DzipError::Io(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    "Invalid path component '..' (Zip Slip prevention)",
-                )));
+                return Err(DzipError::UnsafePath {
+                    path: path.display().to_string(),
+                    reason: "contains '..' (Zip Slip prevention)",
+                });
             }
         }
     }
     if clean_path.as_os_str().is_empty() {
-        return Err(DzipError::Io(std::io::Error::new(
-            std::io::ErrorKind::InvalidInput,
-            "Empty path after sanitization",
-        )));
+        return Err(DzipError::UnsafePath {
+            path: path.display().to_string(),
+            reason: "empty after sanitization",
+        });
     }
     Ok(clean_path)
 }
@@ -56,9 +56,31 @@ pub fn from_archive_format(path_str: &str) -> Result<PathBuf> {
     sanitize_path(path)
 }
 
+/// Join an archive directory string and a file name with the archive separator (`\\`).
+/// An empty directory denotes the root; a directory already ending in a separator is not doubled.
+pub fn join_archive_path(dir: &str, file_name: &str) -> String {
+    let mut full = String::with_capacity(dir.len() + file_name.len() + 1);
+    full.push_str(dir);
+    if !full.is_empty() && !full.ends_with('/') && !full.ends_with('\\') {
+        full.push('\\');
+    }
+    full.push_str(file_name);
+    full
+}
+
+/// Removes separators (`\` or `/`) from the end of an archive directory string,
+/// so that `textures\` and `textures` name the same directory. A directory of
+/// separators only becomes the root (`""`).
+pub fn trim_trailing_separators(dir: &str) -> &str {
+    dir.trim_end_matches(['/', '\\'])
+}
+
 /// Resolve a relative path from a string that might contain mixed separators (Internet/Windows style).
 /// This splits the path by both `/` and `\` and reconstructs it using the system's native separator.
 /// It also performs sanitization (Zip Slip prevention).
+///
+/// The root (`""`, `"."`, `"\\"`, ...) resolves to an empty path, so callers can
+/// skip it instead of creating `"."` below the output directory.
 pub fn resolve_relative_path(path_str: &str) -> Result<PathBuf> {
     let mut clean_path = PathBuf::new();
 
@@ -70,10 +92,10 @@ pub fn resolve_relative_path(path_str: &str) -> Result<PathBuf> {
             continue;
         }
         if part == ".." {
-            return Err(DzipError::Io(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "Invalid path component '..' (Zip Slip prevention)",
-            )));
+            return Err(DzipError::UnsafePath {
+                path: path_str.to_string(),
+                reason: "contains '..' (Zip Slip prevention)",
+            });
         }
 
         // Filter out Windows drive prefixes if any (e.g. "C:")
@@ -82,24 +104,31 @@ pub fn resolve_relative_path(path_str: &str) -> Result<PathBuf> {
         if part.contains(':') {
             // For safety, just treat as invalid or skip?
             // Let's treat as invalid for now to be safe.
-            return Err(DzipError::Io(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "Invalid path component containing ':'",
-            )));
+            return Err(DzipError::UnsafePath {
+                path: path_str.to_string(),
+                reason: "component contains ':'",
+            });
         }
 
         clean_path.push(part);
     }
 
-    if clean_path.as_os_str().is_empty() {
-        // If empty, it means it's the root directory or just "."
-        // Return mostly empty path (current dir)
-        return Ok(PathBuf::from("."));
-    }
-
     Ok(clean_path)
 }
 
+/// Like `resolve_relative_path`, for a file. The path must end in a file name, so
+/// it can resolve neither to the root nor to its own directory (`dir\.`).
+pub fn resolve_file_path(path_str: &str) -> Result<PathBuf> {
+    let name = path_str.rsplit(['/', '\\']).next().unwrap_or_default();
+    if name.is_empty() || name == "." {
+        return Err(DzipError::UnsafePath {
+            path: path_str.to_string(),
+            reason: "does not end in a file name",
+        });
+    }
+    resolve_relative_path(path_str)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -140,9 +169,66 @@ mod tests {
         assert_eq!(resolved, expected);
     }
 
+    #[test]
+    fn test_resolve_relative_path_root() {
+        for root in ["", ".", "\\", "/", "./.", ".\\"] {
+            assert_eq!(
+                resolve_relative_path(root).unwrap(),
+                PathBuf::new(),
+                "{:?}",
+                root
+            );
+        }
+        assert_eq!(
+            resolve_relative_path(".\\dir\\").unwrap(),
+            PathBuf::from("dir")
+        );
+    }
+
+    #[test]
+    fn test_resolve_file_path() {
+        assert_eq!(
+            resolve_file_path("file.txt").unwrap(),
+            PathBuf::from("file.txt")
+        );
+        assert_eq!(
+            resolve_file_path(".\\file.txt").unwrap(),
+            PathBuf::from("file.txt")
+        );
+        for invalid in ["", ".", "dir\\", "dir/.", "\\"] {
+            assert!(
+                matches!(
+                    resolve_file_path(invalid),
+                    Err(DzipError::UnsafePath { .. })
+                ),
+                "{:?}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_join_archive_path() {
+        assert_eq!(join_archive_path("", "file.txt"), "file.txt");
+        assert_eq!(join_archive_path("dir", "file.txt"), "dir\\file.txt");
+        assert_eq!(join_archive_path("dir/", "file.txt"), "dir/file.txt");
+    }
+
+    #[test]
+    fn test_trim_trailing_separators() {
+        assert_eq!(trim_trailing_separators("textures\\"), "textures");
+        assert_eq!(trim_trailing_separators("a\\b/\\"), "a\\b");
+        assert_eq!(trim_trailing_separators("a\\b"), "a\\b");
+        assert_eq!(trim_trailing_separators("\\"), "");
+        assert_eq!(trim_trailing_separators(""), "");
+    }
+
     #[test]
     fn test_resolve_relative_path_zip_slip() {
         let p = "folder\\../file.txt";
-        assert!(resolve_relative_path(p).is_err());
+        assert!(matches!(
+            resolve_relative_path(p),
+            Err(DzipError::UnsafePath { .. })
+        ));
     }
 }
@@ -0,0 +1,24 @@
+use crate::unpack::ExtractStats;
+use std::path::Path;
+
+/// Progress notifications from the extraction functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressEvent<'a> {
+    /// Extraction of the file at `index` in the archive is about to start
+    FileStarted { index: usize, path: &'a Path },
+    /// The file at `index` has been written
+    FileCompleted {
+        index: usize,
+        path: &'a Path,
+        stats: ExtractStats,
+    },
+    /// A chunk was decoded and `bytes` decompressed bytes were written
+    ChunkDecompressed { chunk: u16, bytes: u64 },
+}
+
+/// Callback receiving `ProgressEvent`s.
+///
+/// Files may be extracted in parallel, so the callback can be invoked from
+/// several threads at once and must be `Sync`. Events of different files may
+/// interleave; events of one file arrive in order on the thread extracting it.
+pub type ProgressFn<'a> = &'a (dyn Fn(ProgressEvent<'_>) + Sync);
@@ -1,40 +1,231 @@
 use crate::error::{DzipError, Result};
 use crate::format::*;
-use byteorder::{LittleEndian, ReadBytesExt};
-use std::io::{BufRead, BufReader, Read, Seek};
+use crate::source::OffsetReader;
+use crate::writer::Counting;
+use crate::xor::XorKey;
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+
+pub use encoding_rs::Encoding;
+
+/// Buffer size used for reading archives and writing extracted files unless overridden.
+pub const DEFAULT_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Internal buffer of the brotli decoder.
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+/// Outcome of `DzipReader::verify_header_crc`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderCrc {
+    /// The archive has no header CRC trailer
+    Absent,
+    /// The trailer is present and matches the header
+    Valid(u32),
+}
+
+impl HeaderCrc {
+    /// Bytes the trailer occupies at the end of volume 0. Subtract this from the
+    /// volume size before correcting chunk sizes or checking the layout.
+    pub fn trailer_size(self) -> u64 {
+        match self {
+            HeaderCrc::Absent => 0,
+            HeaderCrc::Valid(_) => HEADER_CRC_TRAILER_SIZE,
+        }
+    }
+}
 
 pub struct DzipReader<R: Read + Seek> {
     reader: BufReader<R>,
+    endianness: Endianness,
+    format_version: FormatVersion,
+    chunk_table_end: Option<u64>,
+    volume_names: Vec<String>,
+    encoding: Option<&'static Encoding>,
+    xor_key: Option<XorKey>,
 }
 
 impl<R: Read + Seek> DzipReader<R> {
     pub fn new(reader: R) -> Self {
+        Self::with_buffer_size(reader, DEFAULT_BUFFER_SIZE)
+    }
+
+    /// Creates a reader whose internal buffer holds `capacity` bytes.
+    pub fn with_buffer_size(reader: R, capacity: usize) -> Self {
         Self {
-            reader: BufReader::new(reader),
+            reader: BufReader::with_capacity(capacity, reader),
+            endianness: Endianness::Little,
+            format_version: FormatVersion::default(),
+            chunk_table_end: None,
+            volume_names: Vec::new(),
+            encoding: None,
+            xor_key: None,
         }
     }
 
+    /// Decodes the string table and volume names with `encoding` instead of
+    /// UTF-8, for archives written with a legacy code page such as Shift_JIS.
+    /// Bytes that are invalid in `encoding` become U+FFFD.
+    pub fn set_encoding(&mut self, encoding: &'static Encoding) {
+        self.encoding = Some(encoding);
+    }
+
+    /// Undoes XOR obfuscation of the chunk data (see `crate::xor`) before
+    /// decoding. Raw chunk data is still returned as stored.
+    pub fn set_xor_key(&mut self, key: XorKey) {
+        self.xor_key = Some(key);
+    }
+
+    /// Byte order of the archive header.
+    /// Detected by `read_archive_settings`; little-endian until then.
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    /// Format version of the archive.
+    /// Detected by `read_archive_settings`; `V0` until then.
+    pub fn format_version(&self) -> FormatVersion {
+        self.format_version
+    }
+
+    /// Offset of the end of the chunk table, i.e. the length covered by the
+    /// header CRC. Known once `read_chunks` has run.
+    pub fn chunk_table_end(&self) -> Option<u64> {
+        self.chunk_table_end
+    }
+
+    /// File names of the auxiliary volumes, starting with volume 1, as listed
+    /// after the chunk table. Known once `read_file_list` (or `read_header`)
+    /// has run; empty for a single-volume archive.
+    pub fn volume_names(&self) -> &[String] {
+        &self.volume_names
+    }
+
+    /// Checks the optional header CRC trailer (see `HEADER_CRC_MAGIC`).
+    ///
+    /// Call this after `read_chunks` or `read_header`; the position is restored
+    /// afterwards. Fails with `HeaderCorrupt` if the trailer does not match, so
+    /// that a damaged chunk table is reported before its offsets are used.
+    pub fn verify_header_crc(&mut self) -> Result<HeaderCrc> {
+        let position = self.reader.stream_position()?;
+        let header_end = self.chunk_table_end.unwrap_or(position);
+        let file_len = self.reader.seek(SeekFrom::End(0))?;
+        let result = self.check_header_crc(header_end, file_len);
+        self.reader.seek(SeekFrom::Start(position))?;
+        result
+    }
+
+    /// Reads the whole header of volume 0, from the archive settings through the
+    /// range settings (read only if some chunk is `CHUNK_DZ`).
+    ///
+    /// Chunk sizes are returned as stored; see `correct_chunk_sizes`.
+    pub fn read_header(&mut self) -> Result<ArchiveHeader> {
+        self.read_header_fields().map_err(|e| match e {
+            DzipError::Io(io) if io.kind() == std::io::ErrorKind::UnexpectedEof => {
+                DzipError::HeaderTruncated
+            }
+            e => e,
+        })
+    }
+
+    fn read_header_fields(&mut self) -> Result<ArchiveHeader> {
+        let settings = self.read_archive_settings()?;
+        let strings = self.read_strings(settings.num_strings())?;
+        check_string_table(&strings, settings.num_user_files as usize)?;
+        let map = self.read_file_chunk_map(settings.num_user_files as usize)?;
+        let chunk_settings = self.read_chunk_settings()?;
+        let chunks = self.read_chunks(chunk_settings.num_chunks as usize)?;
+        let volume_files =
+            self.read_file_list(chunk_settings.num_archive_files.saturating_sub(1) as usize)?;
+        check_volume_names(&volume_files)?;
+        let range_settings = if chunks.iter().any(|c| (c.flags & CHUNK_DZ) != 0) {
+            Some(self.read_global_settings()?)
+        } else {
+            None
+        };
+        Ok(ArchiveHeader {
+            settings,
+            strings,
+            map,
+            chunk_settings,
+            chunks,
+            volume_files,
+            range_settings,
+        })
+    }
+
+    fn check_header_crc(&mut self, header_end: u64, file_len: u64) -> Result<HeaderCrc> {
+        if file_len < header_end + HEADER_CRC_TRAILER_SIZE {
+            return Ok(HeaderCrc::Absent);
+        }
+        self.reader
+            .seek(SeekFrom::Start(file_len - HEADER_CRC_TRAILER_SIZE))?;
+        let mut magic = [0u8; 4];
+        self.reader.read_exact(&mut magic)?;
+        if magic != HEADER_CRC_MAGIC {
+            return Ok(HeaderCrc::Absent);
+        }
+        let expected = self.reader.read_u32::<LittleEndian>()?;
+
+        self.reader.seek(SeekFrom::Start(0))?;
+        let actual = crc32(&mut (&mut self.reader).take(header_end))?;
+        if actual != expected {
+            return Err(DzipError::HeaderCorrupt { expected, actual });
+        }
+        Ok(HeaderCrc::Valid(actual))
+    }
+
+    fn read_u16(&mut self) -> Result<u16> {
+        Ok(match self.endianness {
+            Endianness::Little => self.reader.read_u16::<LittleEndian>()?,
+            Endianness::Big => self.reader.read_u16::<BigEndian>()?,
+        })
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        Ok(match self.endianness {
+            Endianness::Little => self.reader.read_u32::<LittleEndian>()?,
+            Endianness::Big => self.reader.read_u32::<BigEndian>()?,
+        })
+    }
+
     pub fn read_archive_settings(&mut self) -> Result<ArchiveSettings> {
         log::debug!(
             "Reading archive settings at offset {}",
             self.reader.stream_position().unwrap_or(0)
         );
+        // 'DTRZ' in little endian (ZRTD); byte-swapped for big-endian archives
         let header = self.reader.read_u32::<LittleEndian>()?;
-        if header != 0x5A525444 {
-            // 'DTRZ' in little endian (ZRTD)
+        self.endianness = if header == DTRZ_MAGIC {
+            Endianness::Little
+        } else if header == DTRZ_MAGIC.swap_bytes() {
+            log::debug!("Detected big-endian archive header");
+            Endianness::Big
+        } else {
             return Err(DzipError::InvalidHeader);
-        }
+        };
 
-        let num_user_files = self.reader.read_u16::<LittleEndian>()?;
-        let num_directories = self.reader.read_u16::<LittleEndian>()?;
+        let num_user_files = self.read_u16()?;
+        let num_directories = self.read_u16()?;
         let version = self.reader.read_u8()?;
 
-        Ok(ArchiveSettings {
-            header,
+        // The root directory is always counted, so a valid header has at least one
+        if num_directories == 0 {
+            return Err(DzipError::InvalidSettings(format!(
+                "num_directories is 0 (num_user_files = {}); \
+                 the root directory must always be counted",
+                num_user_files
+            )));
+        }
+
+        let settings = ArchiveSettings {
+            header: DTRZ_MAGIC,
             num_user_files,
             num_directories,
             version,
-        })
+        };
+        // Later versions may add header fields, so never guess at their layout
+        self.format_version = settings.format_version()?;
+        Ok(settings)
     }
 
     pub fn read_strings(&mut self, count: usize) -> Result<Vec<String>> {
@@ -43,6 +234,14 @@ impl<R: Read + Seek> DzipReader<R> {
             count,
             self.reader.stream_position().unwrap_or(0)
         );
+        // Files and directories are both u16 counts, so nothing larger can be valid
+        if count > 2 * u16::MAX as usize {
+            return Err(DzipError::InvalidSettings(format!(
+                "string table count {} exceeds the maximum of {}",
+                count,
+                2 * u16::MAX as usize
+            )));
+        }
         let mut strings = Vec::with_capacity(count);
         for _ in 0..count {
             let s = self.read_null_terminated_string()?;
@@ -58,7 +257,18 @@ impl<R: Read + Seek> DzipReader<R> {
         if bytes.last() == Some(&0) {
             bytes.pop();
         }
-        Ok(String::from_utf8(bytes)?)
+        let Some(encoding) = self.encoding else {
+            return Ok(String::from_utf8(bytes)?);
+        };
+        let (text, had_errors) = encoding.decode_without_bom_handling(&bytes);
+        if had_errors {
+            log::warn!(
+                "String {:?} is not valid {}; invalid bytes were replaced",
+                text,
+                encoding.name()
+            );
+        }
+        Ok(text.into_owned())
     }
 
     /// Reads the User-File to Chunk-And-Directory list.
@@ -67,10 +277,10 @@ impl<R: Read + Seek> DzipReader<R> {
         log::debug!("Reading file chunk map for {} files", num_files);
         let mut map = Vec::with_capacity(num_files);
         for _ in 0..num_files {
-            let dir_id = self.reader.read_u16::<LittleEndian>()?;
+            let dir_id = self.read_u16()?;
             let mut chunks = Vec::new();
             loop {
-                let chunk_id = self.reader.read_u16::<LittleEndian>()?;
+                let chunk_id = self.read_u16()?;
                 if chunk_id == 0xFFFF {
                     break;
                 }
@@ -82,8 +292,8 @@ impl<R: Read + Seek> DzipReader<R> {
     }
 
     pub fn read_chunk_settings(&mut self) -> Result<ChunkSettings> {
-        let num_archive_files = self.reader.read_u16::<LittleEndian>()?;
-        let num_chunks = self.reader.read_u16::<LittleEndian>()?;
+        let num_archive_files = self.read_u16()?;
+        let num_chunks = self.read_u16()?;
         Ok(ChunkSettings {
             num_archive_files,
             num_chunks,
@@ -98,11 +308,11 @@ impl<R: Read + Seek> DzipReader<R> {
         );
         let mut chunks = Vec::with_capacity(count);
         for _ in 0..count {
-            let offset = self.reader.read_u32::<LittleEndian>()?;
-            let compressed_length = self.reader.read_u32::<LittleEndian>()?;
-            let decompressed_length = self.reader.read_u32::<LittleEndian>()?;
-            let flags = self.reader.read_u16::<LittleEndian>()?;
-            let file = self.reader.read_u16::<LittleEndian>()?;
+            let offset = self.read_u32()?;
+            let compressed_length = self.read_u32()?;
+            let decompressed_length = self.read_u32()?;
+            let flags = self.read_u16()?;
+            let file = self.read_u16()?;
             chunks.push(Chunk {
                 offset,
                 compressed_length,
@@ -111,6 +321,7 @@ impl<R: Read + Seek> DzipReader<R> {
                 file,
             });
         }
+        self.chunk_table_end = Some(self.reader.stream_position()?);
         Ok(chunks)
     }
 
@@ -145,6 +356,7 @@ impl<R: Read + Seek> DzipReader<R> {
         for _ in 0..num_archive_files {
             files.push(self.read_null_terminated_string()?);
         }
+        self.volume_names = files.clone();
         Ok(files)
     }
 
@@ -153,7 +365,7 @@ impl<R: Read + Seek> DzipReader<R> {
     }
 
     pub fn read_chunk_data(&mut self, chunk: &Chunk) -> Result<Vec<u8>> {
-        Self::decompress_chunk_data(&mut self.reader, chunk)
+        Self::decompress_chunk_data(&mut self.reader, chunk, self.xor_key.as_ref())
     }
 
     pub fn read_chunk_data_with_volumes(
@@ -162,14 +374,160 @@ impl<R: Read + Seek> DzipReader<R> {
         volume_source: &mut dyn VolumeSource,
     ) -> Result<Vec<u8>> {
         if chunk.file == 0 {
-            Self::decompress_chunk_data(&mut self.reader, chunk)
+            Self::decompress_chunk_data(&mut self.reader, chunk, self.xor_key.as_ref())
         } else {
             let reader = volume_source.open_volume(chunk.file)?;
-            Self::decompress_chunk_data(reader, chunk)
+            Self::decompress_chunk_data(reader, chunk, self.xor_key.as_ref())
+        }
+    }
+
+    /// Reads the stored (still compressed) bytes of a chunk without decoding them.
+    /// Zero chunks have no stored data and yield an empty buffer. The XOR key is
+    /// not applied.
+    pub fn read_raw_chunk_data_with_volumes(
+        &mut self,
+        chunk: &Chunk,
+        volume_source: &mut dyn VolumeSource,
+    ) -> Result<Vec<u8>> {
+        if (chunk.flags & CHUNK_ZERO) != 0 {
+            return Ok(Vec::new());
+        }
+        let reader: &mut dyn ReadSeek = if chunk.file == 0 {
+            &mut self.reader
+        } else {
+            volume_source.open_volume(chunk.file)?
+        };
+        reader.seek(std::io::SeekFrom::Start(chunk.offset as u64))?;
+        let mut buffer = vec![0u8; chunk.compressed_length as usize];
+        reader.read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Like `read_chunk_data_with_volumes`, but writes the decoded bytes to
+    /// `out` as they are produced instead of collecting them, and returns how
+    /// many were written.
+    ///
+    /// Zero and stored chunks and zlib, gzip, bzip2, xz and Brotli streams are
+    /// decoded through fixed-size buffers, so memory does not grow with the
+    /// chunk. LZMA, JPEG, combuf and random-access chunks, chunks that may need
+    /// the equal-length fallback to raw data, and chunks read with an XOR key
+    /// are decoded in memory as by `read_chunk_data`. If decoding fails, part
+    /// of the chunk may already have been written.
+    pub fn read_chunk_data_into(
+        &mut self,
+        chunk: &Chunk,
+        volume_source: &mut dyn VolumeSource,
+        out: &mut dyn Write,
+    ) -> Result<u64> {
+        let reader: &mut dyn ReadSeek = if chunk.file == 0 {
+            &mut self.reader
+        } else {
+            volume_source.open_volume(chunk.file)?
+        };
+        Self::decompress_chunk_into(reader, chunk, self.xor_key.as_ref(), out)
+    }
+
+    fn decompress_chunk_into(
+        reader: &mut dyn ReadSeek,
+        chunk: &Chunk,
+        xor_key: Option<&XorKey>,
+        out: &mut dyn Write,
+    ) -> Result<u64> {
+        if (chunk.flags & CHUNK_ZERO) != 0 {
+            let mut zeros = std::io::repeat(0).take(chunk.decompressed_length as u64);
+            return Ok(std::io::copy(&mut zeros, out)?);
+        }
+        let equal_lengths = chunk.compressed_length == chunk.decompressed_length;
+        let decoder = match select_decoder(chunk.flags) {
+            _ if xor_key.is_some() || (chunk.flags & (CHUNK_COMBUF | CHUNK_RANDOMACCESS)) != 0 => {
+                None
+            }
+            Some(Decoder::Zlib | Decoder::Bzip) if equal_lengths => None,
+            Some(
+                decoder @ (Decoder::Copy
+                | Decoder::Zlib
+                | Decoder::Bzip
+                | Decoder::Xz
+                | Decoder::Gzip
+                | Decoder::Brotli),
+            ) => Some(decoder),
+            _ => None,
+        };
+        let Some(decoder) = decoder else {
+            let data = Self::decompress_chunk_data(reader, chunk, xor_key)?;
+            out.write_all(&data)?;
+            return Ok(data.len() as u64);
+        };
+
+        reader.seek(SeekFrom::Start(chunk.offset as u64))?;
+        let mut input = BufReader::new(reader.take(chunk.compressed_length as u64));
+        let written = Self::decode_stream(decoder, &mut input, chunk, out)?;
+        // `read_chunk_data` reads stored chunks with `read_exact`
+        if decoder == Decoder::Copy && written < chunk.compressed_length as u64 {
+            return Err(DzipError::Io(std::io::ErrorKind::UnexpectedEof.into()));
         }
+        Ok(written)
     }
 
-    fn decompress_chunk_data(reader: &mut dyn ReadSeek, chunk: &Chunk) -> Result<Vec<u8>> {
+    /// Decodes a chunk stream with one of the codecs that can stream (stored,
+    /// zlib, bzip2, xz, gzip and Brotli) from `input` to `out`, and returns how
+    /// many bytes were written. Shared by `decode_buffer` and
+    /// `read_chunk_data_into`.
+    ///
+    /// A zlib chunk that holds a gzip stream is decoded as gzip, and may lack
+    /// the gzip footer once the whole chunk is out.
+    fn decode_stream(
+        decoder: Decoder,
+        input: &mut dyn BufRead,
+        chunk: &Chunk,
+        out: &mut dyn Write,
+    ) -> Result<u64> {
+        let mut counted = Counting::new(out);
+        let gzip_in_zlib = decoder == Decoder::Zlib && input.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+        let result = match decoder {
+            Decoder::Copy => std::io::copy(input, &mut counted).map(drop),
+            Decoder::Zlib if gzip_in_zlib => {
+                let mut decoder = flate2::bufread::GzDecoder::new(input);
+                std::io::copy(&mut decoder, &mut counted).map(drop)
+            }
+            Decoder::Zlib => {
+                let mut decoder = flate2::bufread::ZlibDecoder::new(input);
+                std::io::copy(&mut decoder, &mut counted).map(drop)
+            }
+            Decoder::Bzip => {
+                let mut decoder = bzip2::bufread::BzDecoder::new(input);
+                std::io::copy(&mut decoder, &mut counted).map(drop)
+            }
+            Decoder::Xz => {
+                let mut input = input;
+                lzma_rs::xz_decompress(&mut input, &mut counted).map_err(std::io::Error::other)
+            }
+            Decoder::Gzip => {
+                let mut decoder = flate2::bufread::GzDecoder::new(input);
+                std::io::copy(&mut decoder, &mut counted).map(drop)
+            }
+            Decoder::Brotli => {
+                let mut decoder = brotli::Decompressor::new(input, BROTLI_BUFFER_SIZE);
+                std::io::copy(&mut decoder, &mut counted).map(drop)
+            }
+            Decoder::Jpeg | Decoder::Lzma => {
+                return Err(DzipError::UnsupportedCompression(chunk.flags));
+            }
+        };
+        match result {
+            Ok(()) => Ok(counted.count),
+            Err(_) if gzip_in_zlib && counted.count == chunk.decompressed_length as u64 => {
+                Ok(counted.count)
+            }
+            Err(e) => Err(DzipError::Io(e)),
+        }
+    }
+
+    fn decompress_chunk_data(
+        reader: &mut dyn ReadSeek,
+        chunk: &Chunk,
+        xor_key: Option<&XorKey>,
+    ) -> Result<Vec<u8>> {
         log::trace!(
             "Decompressing Chunk: offset={}, comp={}, decomp={}, flags={:x}",
             chunk.offset,
@@ -188,24 +546,43 @@ impl<R: Read + Seek> DzipReader<R> {
         // Read compressed data
         let mut buffer = vec![0u8; chunk.compressed_length as usize];
         reader.read_exact(&mut buffer)?;
+        if let Some(key) = xor_key {
+            key.apply(&mut buffer);
+        }
+
+        Self::decode_buffer(buffer, chunk)
+    }
 
-        // If explicitly flagged as copy encoded, or no compression flags set?
-        // Actually, let's just check flags.
-        // User confirmed: CHUNK_MP3 and CHUNK_JPEG are equivalent to CHUNK_COPYCOMP
-        if (chunk.flags & (CHUNK_COPYCOMP | CHUNK_MP3 | CHUNK_JPEG)) != 0 {
+    /// Decodes the compressed bytes of a chunk according to its flags.
+    fn decode_buffer(buffer: Vec<u8>, chunk: &Chunk) -> Result<Vec<u8>> {
+        // Combuf chunks wrap an inner codec, so unwrap them before anything else.
+        if (chunk.flags & CHUNK_COMBUF) != 0 {
+            return Self::decode_combuf(buffer, chunk);
+        }
+        if (chunk.flags & CHUNK_RANDOMACCESS) != 0 {
+            return Self::decode_random_access(buffer, chunk);
+        }
+
+        // Copy and MP3 chunks are stored as-is
+        let decoder = select_decoder(chunk.flags);
+        if decoder == Some(Decoder::Copy) {
             return Ok(buffer);
         }
 
-        // Handle RandomAccess chunks (usually stored uncompressed if no other compression flag is set)
-        if (chunk.flags & CHUNK_RANDOMACCESS) != 0 {
-            // Check if any actual compression flag is ALSO set.
-            // If LZMA/ZLIB/BZIP/DZ are NOT set, then it's just raw data with a type hint.
-            if (chunk.flags & (CHUNK_LZMA | CHUNK_ZLIB | CHUNK_BZIP | CHUNK_DZ)) == 0 {
-                return Ok(buffer);
+        // JPEG chunks hold the stored JPEG stream. A file may span several chunks,
+        // so missing markers on a single chunk are only worth a debug note here;
+        // `has_jpeg_markers` lets callers check the whole file.
+        if decoder == Some(Decoder::Jpeg) {
+            if !buffer.starts_with(&JPEG_SOI) {
+                log::debug!(
+                    "JPEG chunk at offset {} does not start with an SOI marker",
+                    chunk.offset
+                );
             }
+            return Ok(buffer);
         }
 
-        if (chunk.flags & CHUNK_ZLIB) != 0 {
+        if decoder == Some(Decoder::Zlib) {
             // Heuristic for "Equal Lengths" Quirk:
             if chunk.compressed_length == chunk.decompressed_length {
                 // Typical Zlib header starts with 0x78 (Deflate, 32k win).
@@ -218,34 +595,24 @@ impl<R: Read + Seek> DzipReader<R> {
                 }
             }
 
-            // Check for GZIP header (0x1f 0x8b)
-            if buffer.len() >= 2 && buffer[0] == 0x1f && buffer[1] == 0x8b {
-                let mut decoder = flate2::read::GzDecoder::new(&buffer[..]);
-                let mut decompressed = Vec::with_capacity(chunk.decompressed_length as usize);
-                match std::io::Read::read_to_end(&mut decoder, &mut decompressed) {
-                    Ok(_) => return Ok(decompressed),
-                    Err(e) => {
-                        // If we extracted the full expected length, ignore the error (likely missing footer)
-                        if decompressed.len() == chunk.decompressed_length as usize {
-                            return Ok(decompressed);
-                        }
-                        return Err(DzipError::Io(e));
-                    }
-                }
-            }
-
-            let mut decoder = flate2::read::ZlibDecoder::new(&buffer[..]);
+            // A gzip stream (0x1f 0x8b) is decoded as such by `decode_stream`
+            let is_gzip = buffer.starts_with(&[0x1f, 0x8b]);
             let mut decompressed = Vec::with_capacity(chunk.decompressed_length as usize);
-            match std::io::Read::read_to_end(&mut decoder, &mut decompressed) {
-                Ok(_) => return Ok(decompressed),
-                Err(_) if chunk.compressed_length == chunk.decompressed_length => {
-                    return Ok(buffer);
+            return match Self::decode_stream(
+                Decoder::Zlib,
+                &mut &buffer[..],
+                chunk,
+                &mut decompressed,
+            ) {
+                Ok(_) => Ok(decompressed),
+                Err(_) if !is_gzip && chunk.compressed_length == chunk.decompressed_length => {
+                    Ok(buffer)
                 }
-                Err(e) => return Err(DzipError::Io(e)),
-            }
+                Err(e) => Err(e),
+            };
         }
 
-        if (chunk.flags & CHUNK_BZIP) != 0 {
+        if decoder == Some(Decoder::Bzip) {
             // Heuristic for "Equal Lengths" Quirk:
             if chunk.compressed_length == chunk.decompressed_length {
                 // Bzip2 header must start with "BZh".
@@ -254,18 +621,20 @@ impl<R: Read + Seek> DzipReader<R> {
                 }
             }
 
-            let mut decoder = bzip2::read::BzDecoder::new(&buffer[..]);
             let mut decompressed = Vec::with_capacity(chunk.decompressed_length as usize);
-            match std::io::Read::read_to_end(&mut decoder, &mut decompressed) {
-                Ok(_) => return Ok(decompressed),
-                Err(_) if chunk.compressed_length == chunk.decompressed_length => {
-                    return Ok(buffer);
-                }
-                Err(e) => return Err(DzipError::Io(e)),
-            }
+            return match Self::decode_stream(
+                Decoder::Bzip,
+                &mut &buffer[..],
+                chunk,
+                &mut decompressed,
+            ) {
+                Ok(_) => Ok(decompressed),
+                Err(_) if chunk.compressed_length == chunk.decompressed_length => Ok(buffer),
+                Err(e) => Err(e),
+            };
         }
 
-        if (chunk.flags & CHUNK_LZMA) != 0 {
+        if decoder == Some(Decoder::Lzma) {
             // Heuristic for "Equal Lengths" Quirk ambiguity:
             if chunk.compressed_length == chunk.decompressed_length
                 && (buffer.is_empty() || buffer[0] != 0x5d)
@@ -301,9 +670,321 @@ impl<R: Read + Seek> DzipReader<R> {
             }
         }
 
+        if let Some(decoder @ (Decoder::Xz | Decoder::Gzip | Decoder::Brotli)) = decoder {
+            let mut decompressed = Vec::with_capacity(chunk.decompressed_length as usize);
+            Self::decode_stream(decoder, &mut &buffer[..], chunk, &mut decompressed)?;
+            return Ok(decompressed);
+        }
+
         // TODO: Implement other decompression methods (e.g. CHUNK_DZ)
         Err(DzipError::UnsupportedCompression(chunk.flags))
     }
+
+    /// Decodes a combuf chunk by delegating to its inner codec.
+    ///
+    /// The inner codec is taken from the remaining flag bits (e.g. `0x5` is COMBUF|DZ).
+    /// If no codec bit accompanies COMBUF, the inner stream header is sniffed instead.
+    fn decode_combuf(buffer: Vec<u8>, chunk: &Chunk) -> Result<Vec<u8>> {
+        let mut inner = *chunk;
+        inner.flags &= !CHUNK_COMBUF;
+        if (inner.flags & CODEC_FLAGS) == 0 {
+            let detected = detect_stream_flags(&buffer)
+                .ok_or(DzipError::UnsupportedCompression(chunk.flags))?;
+            log::trace!(
+                "Combuf chunk inner stream detected as flags={:#x}",
+                detected
+            );
+            inner.flags |= detected;
+        }
+
+        match Self::decode_buffer(buffer, &inner) {
+            Err(DzipError::UnsupportedCompression(_)) => {
+                Err(DzipError::UnsupportedCompression(chunk.flags))
+            }
+            result => result,
+        }
+    }
+
+    /// Decodes a random-access chunk.
+    ///
+    /// RANDOMACCESS only asks the runtime to buffer the whole decoded chunk so
+    /// it can seek within it; the chunk data carries no index of its own. With a
+    /// codec bit the chunk decodes with that codec. Without one it is stored,
+    /// unless its lengths differ: then the inner stream is sniffed as for combuf
+    /// rather than handing back compressed bytes as the file's contents.
+    fn decode_random_access(buffer: Vec<u8>, chunk: &Chunk) -> Result<Vec<u8>> {
+        let mut inner = *chunk;
+        inner.flags &= !CHUNK_RANDOMACCESS;
+        if (inner.flags & CODEC_FLAGS) == 0 {
+            if buffer.len() == chunk.decompressed_length as usize {
+                return Ok(buffer);
+            }
+            let detected = detect_stream_flags(&buffer)
+                .ok_or(DzipError::UnsupportedCompression(chunk.flags))?;
+            log::trace!(
+                "Random-access chunk inner stream detected as flags={:#x}",
+                detected
+            );
+            inner.flags |= detected;
+        }
+
+        match Self::decode_buffer(buffer, &inner) {
+            Err(DzipError::UnsupportedCompression(_)) => {
+                Err(DzipError::UnsupportedCompression(chunk.flags))
+            }
+            result => result,
+        }
+    }
+}
+
+/// Computes the CRC-32 of everything `reader` yields.
+pub(crate) fn crc32(reader: &mut impl Read) -> Result<u32> {
+    let mut crc = flate2::Crc::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            return Ok(crc.sum());
+        }
+        crc.update(&buf[..n]);
+    }
+}
+
+/// How far into a file `locate_archive` looks for the archive header.
+pub const SFX_SCAN_LIMIT: u64 = 16 * 1024 * 1024;
+
+/// How much of the file `locate_archive` reads at a time while scanning.
+pub const SFX_SCAN_BLOCK: usize = 64 * 1024;
+
+/// Finds where the archive starts in `reader`: 0 for a plain archive, or the
+/// offset of the archive after a self-extracting stub.
+///
+/// If the magic is not at offset 0, the first `SFX_SCAN_LIMIT` bytes are
+/// searched for it block by block, and the first occurrence that starts a
+/// readable header is taken. Returns 0 if there is none, so that reading the
+/// header reports the usual error. Wrap the reader in `OffsetReader` to read
+/// the archive found.
+pub fn locate_archive<R: Read + Seek>(mut reader: R) -> Result<u64> {
+    let le = DTRZ_MAGIC.to_le_bytes();
+    let be = DTRZ_MAGIC.to_be_bytes();
+    reader.seek(SeekFrom::Start(0))?;
+    let mut block = Vec::with_capacity(SFX_SCAN_BLOCK);
+    (&mut reader)
+        .take(le.len() as u64)
+        .read_to_end(&mut block)?;
+    if block == le || block == be {
+        return Ok(0);
+    }
+
+    // Consecutive blocks overlap by one byte less than the magic, so a magic
+    // split across two blocks is still seen whole in the second one
+    let overlap = le.len() - 1;
+    let mut start = 1;
+    while start < SFX_SCAN_LIMIT {
+        let len = (SFX_SCAN_BLOCK as u64).min(SFX_SCAN_LIMIT - start);
+        reader.seek(SeekFrom::Start(start))?;
+        block.clear();
+        (&mut reader).take(len).read_to_end(&mut block)?;
+        if block.len() < le.len() {
+            break;
+        }
+
+        for (pos, window) in block.windows(le.len()).enumerate() {
+            if window != le && window != be {
+                continue;
+            }
+            let base = start + pos as u64;
+            let mut archive = DzipReader::new(OffsetReader::new(&mut reader, base)?);
+            match archive.read_header() {
+                Ok(_) => return Ok(base),
+                Err(e) => log::debug!("Magic at offset {} does not start an archive: {}", base, e),
+            }
+        }
+        if (block.len() as u64) < len {
+            break;
+        }
+        start += (block.len() - overlap) as u64;
+    }
+    Ok(0)
+}
+
+/// Looks up a string encoding by its WHATWG label, e.g. `shift_jis`, `latin1`
+/// or `gbk` (case-insensitive).
+pub fn string_encoding(label: &str) -> Result<&'static Encoding> {
+    Encoding::for_label(label.trim().as_bytes())
+        .ok_or_else(|| DzipError::InvalidSettings(format!("Unknown string encoding '{}'", label)))
+}
+
+/// Rejects string table entries that cannot name anything on disk: control
+/// characters anywhere, and separators in the file names (the first
+/// `num_user_files` entries), which belong in the directory entries instead.
+/// A corrupt table fails here with the offending index rather than producing
+/// odd file names during extraction.
+pub fn check_string_table(strings: &[String], num_user_files: usize) -> Result<()> {
+    for (index, name) in strings.iter().enumerate() {
+        let reason = if name.chars().any(char::is_control) {
+            "contains control characters"
+        } else if index < num_user_files && name.contains(['/', '\\']) {
+            "file name contains a path separator"
+        } else {
+            continue;
+        };
+        return Err(DzipError::InvalidName {
+            index,
+            name: name.clone(),
+            reason,
+        });
+    }
+    Ok(())
+}
+
+/// Rejects auxiliary volume names that cannot be resolved next to the main
+/// volume: empty names, `.`/`..`, path separators or control characters, and
+/// names listed twice. Errors name the volume by its index in the chunk table
+/// (the first auxiliary volume is 1).
+pub fn check_volume_names(names: &[String]) -> Result<()> {
+    let mut seen = std::collections::HashSet::new();
+    for (i, name) in names.iter().enumerate() {
+        let reason = if name.is_empty() {
+            "name is empty"
+        } else if name == "." || name == ".." {
+            "not a file name"
+        } else if name.contains(['/', '\\']) {
+            "name contains a path separator"
+        } else if name.chars().any(char::is_control) {
+            "contains control characters"
+        } else if !seen.insert(name.as_str()) {
+            "name is listed more than once"
+        } else {
+            continue;
+        };
+        return Err(DzipError::InvalidVolumeName {
+            volume: i + 1,
+            name: name.clone(),
+            reason,
+        });
+    }
+    Ok(())
+}
+
+/// Decoder chosen for a chunk's flags (see `select_decoder`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Decoder {
+    Copy,
+    Jpeg,
+    Zlib,
+    Bzip,
+    Lzma,
+    Xz,
+    Gzip,
+    Brotli,
+}
+
+impl Decoder {
+    fn name(self) -> &'static str {
+        match self {
+            Decoder::Copy => "COPY",
+            Decoder::Jpeg => "JPEG",
+            Decoder::Zlib => "ZLIB",
+            Decoder::Bzip => "BZIP",
+            Decoder::Lzma => "LZMA",
+            Decoder::Xz => "XZ",
+            Decoder::Gzip => "GZIP",
+            Decoder::Brotli => "BROTLI",
+        }
+    }
+}
+
+/// Picks the decoder for a (non-combuf, non-zero) chunk. Several codec bits
+/// resolve by `CODEC_PRECEDENCE`; `None` means no supported decoder (e.g. DZ).
+///
+/// Only flags that mark stored data select `Copy`. Anything else without a
+/// decoder, including unknown bits on their own, must stay `None` so the chunk
+/// fails with `UnsupportedCompression` instead of being passed off as raw data.
+fn select_decoder(flags: u16) -> Option<Decoder> {
+    match primary_codec(flags) {
+        // CHUNK_MP3 is stored like CHUNK_COPYCOMP
+        Some(CHUNK_COPYCOMP | CHUNK_MP3) => Some(Decoder::Copy),
+        Some(CHUNK_JPEG) => Some(Decoder::Jpeg),
+        Some(CHUNK_ZLIB) => Some(Decoder::Zlib),
+        Some(CHUNK_BZIP) => Some(Decoder::Bzip),
+        Some(CHUNK_LZMA) => Some(Decoder::Lzma),
+        Some(CHUNK_XZ) => Some(Decoder::Xz),
+        Some(CHUNK_GZIP) => Some(Decoder::Gzip),
+        Some(CHUNK_BROTLI) => Some(Decoder::Brotli),
+        // RandomAccess without a codec bit is raw data with a buffering hint
+        None if (flags & CHUNK_RANDOMACCESS) != 0 => Some(Decoder::Copy),
+        _ => None,
+    }
+}
+
+/// Returns the name of the decoder that a chunk with `flags` is read with,
+/// without reading or decoding anything.
+///
+/// This follows the same priority as decoding, so it shows which codec wins
+/// when a chunk has several codec bits set (e.g. `DZ | ZLIB` decodes as ZLIB).
+/// Zero chunks give `"ZERO"`; combuf chunks give their inner decoder, or
+/// `"COMBUF"` when the inner codec is sniffed from the data. Returns `None` if
+/// the flags select no supported decoder. Data-dependent fallbacks, such as
+/// treating an equal-length chunk without a valid stream header as raw, are not
+/// reflected.
+pub fn decoder_name(flags: u16) -> Option<&'static str> {
+    if (flags & CHUNK_ZERO) != 0 {
+        return Some("ZERO");
+    }
+    if (flags & CHUNK_COMBUF) != 0 {
+        let inner = flags & !CHUNK_COMBUF;
+        if (inner & CODEC_FLAGS) == 0 {
+            return Some("COMBUF");
+        }
+        return decoder_name(inner);
+    }
+    select_decoder(flags).map(Decoder::name)
+}
+
+/// Flags that select how a chunk's data is encoded.
+const CODEC_FLAGS: u16 = CHUNK_DZ
+    | CHUNK_ZLIB
+    | CHUNK_BZIP
+    | CHUNK_MP3
+    | CHUNK_JPEG
+    | CHUNK_ZERO
+    | CHUNK_COPYCOMP
+    | CHUNK_LZMA
+    | CHUNK_XZ
+    | CHUNK_GZIP
+    | CHUNK_BROTLI;
+
+/// Guesses the codec flag of a compressed stream from its leading bytes.
+///
+/// Recognizes gzip/zlib (`CHUNK_ZLIB`), bzip2 (`CHUNK_BZIP`), xz (`CHUNK_XZ`)
+/// and LZMA-alone (`CHUNK_LZMA`) headers. Returns `None` if the data does not look like any of them.
+pub fn detect_stream_flags(data: &[u8]) -> Option<u16> {
+    match data {
+        [0x1f, 0x8b, ..] => Some(CHUNK_ZLIB),
+        [cmf, flg, ..]
+            if (cmf & 0x0F) == 0x08 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0 =>
+        {
+            Some(CHUNK_ZLIB)
+        }
+        [b'B', b'Z', b'h', ..] => Some(CHUNK_BZIP),
+        [0xFD, b'7', b'z', b'X', b'Z', 0x00, ..] => Some(CHUNK_XZ),
+        [0x5d, ..] => Some(CHUNK_LZMA),
+        _ => None,
+    }
+}
+
+/// JPEG start-of-image marker
+pub const JPEG_SOI: [u8; 2] = [0xFF, 0xD8];
+/// JPEG end-of-image marker
+pub const JPEG_EOI: [u8; 2] = [0xFF, 0xD9];
+
+/// Checks that `data` is a plain JPEG stream, i.e. starts with SOI and ends with EOI.
+///
+/// Marmalade stores JPEG chunks untransformed; data without these markers was
+/// encoded with something this crate cannot decode.
+pub fn has_jpeg_markers(data: &[u8]) -> bool {
+    data.len() >= 4 && data.starts_with(&JPEG_SOI) && data.ends_with(&JPEG_EOI)
 }
 
 pub trait ReadSeek: Read + Seek {}
@@ -314,10 +995,18 @@ pub trait VolumeSource {
     fn open_volume(&mut self, id: u16) -> Result<&mut dyn ReadSeek>;
 }
 
+impl<V: VolumeSource + ?Sized> VolumeSource for &mut V {
+    fn open_volume(&mut self, id: u16) -> Result<&mut dyn ReadSeek> {
+        (**self).open_volume(id)
+    }
+}
+
 /// Corrects chunk sizes based on actual file boundaries.
 ///
 /// Some archives (like testnew.dz) have incorrect compressed_length headers (e.g., listing uncompressed size).
-/// This function clamps compressed lengths to the available space between chunks or EOF.
+/// This function applies `Chunk::effective_compressed_length` to every chunk, with the
+/// next chunk of the same volume (by offset) or the volume size as the limit.
+/// Volumes are independent, so they are processed in parallel.
 ///
 /// # Arguments
 /// * `chunks` - The list of chunks to correct.
@@ -326,62 +1015,62 @@ pub fn correct_chunk_sizes(
     chunks: &mut [crate::format::Chunk],
     file_sizes: &std::collections::HashMap<u16, u64>,
 ) {
-    use crate::format::*;
+    use rayon::prelude::*;
+
     let mut chunks_by_file: std::collections::HashMap<u16, Vec<usize>> =
         std::collections::HashMap::new();
     for (i, chunk) in chunks.iter().enumerate() {
         chunks_by_file.entry(chunk.file).or_default().push(i);
     }
 
-    for (file_id, mut indices) in chunks_by_file {
-        indices.sort_by_key(|&i| chunks[i].offset);
+    let shared: &[crate::format::Chunk] = chunks;
+    let corrections: Vec<(usize, u32)> = chunks_by_file
+        .into_par_iter()
+        .flat_map_iter(|(file_id, indices)| {
+            let file_size = *file_sizes.get(&file_id).unwrap_or(&0);
+            volume_corrections(shared, indices, file_size)
+        })
+        .collect();
 
-        let file_size = *file_sizes.get(&file_id).unwrap_or(&0);
+    for (idx, compressed_length) in corrections {
+        chunks[idx].compressed_length = compressed_length;
+    }
+}
 
-        for i in 0..indices.len() {
-            let idx = indices[i];
-            let chunk_offset = chunks[idx].offset as u64;
+/// Computes the corrected compressed lengths for the chunks of one volume.
+///
+/// `indices` lists the volume's chunks in table order; the stable sort keeps
+/// chunks sharing an offset in that order. Returns `(chunk index, new length)`
+/// for every chunk whose length changes.
+fn volume_corrections(
+    chunks: &[crate::format::Chunk],
+    mut indices: Vec<usize>,
+    file_size: u64,
+) -> Vec<(usize, u32)> {
+    indices.sort_by_key(|&i| chunks[i].offset);
 
-            // Determine the limit (end of region)
-            let limit = if i + 1 < indices.len() {
-                chunks[indices[i + 1]].offset as u64
+    let mut corrections = Vec::new();
+    for (pos, &idx) in indices.iter().enumerate() {
+        let chunk = &chunks[idx];
+        let next_offset = indices.get(pos + 1).map(|&next| chunks[next].offset);
+        let corrected = chunk.effective_compressed_length(next_offset, file_size);
+        if corrected != chunk.compressed_length {
+            let kind = if chunk.has_placeholder_length() {
+                "Equal-Size Chunk"
             } else {
-                file_size
+                "Chunk"
             };
-
-            let available = limit.saturating_sub(chunk_offset);
-
-            // If header claims more than available, clamp it.
-            // BMS Logic: If SIZE == ZSIZE (equal lengths) for compressed chunks, it means
-            // the size is unknown/placeholder, so we SHOULD use the available size (next offset - current).
-            let is_compressed =
-                (chunks[idx].flags & (CHUNK_LZMA | CHUNK_ZLIB | CHUNK_BZIP | CHUNK_DZ)) != 0;
-            let equal_sizes = chunks[idx].compressed_length == chunks[idx].decompressed_length;
-
-            if is_compressed && equal_sizes {
-                // Always update to available size (whether larger or smaller)
-                if chunks[idx].compressed_length != available as u32 {
-                    log::debug!(
-                        "Correcting Equal-Size Chunk {} from {} to {} (File {}, Offset {})",
-                        idx,
-                        chunks[idx].compressed_length,
-                        available,
-                        chunks[idx].file,
-                        chunk_offset
-                    );
-                    chunks[idx].compressed_length = available as u32;
-                }
-            } else if (chunks[idx].compressed_length as u64) > available {
-                log::debug!(
-                    "Correcting Chunk {} size from {} to {} (File {}, Offset {})",
-                    idx,
-                    chunks[idx].compressed_length,
-                    available,
-                    chunks[idx].file,
-                    chunk_offset
-                );
-                chunks[idx].compressed_length = available as u32;
-            }
+            log::debug!(
+                "Correcting {} {} size from {} to {} (File {}, Offset {})",
+                kind,
+                idx,
+                chunk.compressed_length,
+                corrected,
+                chunk.file,
+                chunk.offset
+            );
+            corrections.push((idx, corrected));
         }
     }
+    corrections
 }
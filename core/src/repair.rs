@@ -0,0 +1,111 @@
+//! Recovery of chunk boundaries from the raw data of a damaged archive.
+//!
+//! Compressed chunks start with recognizable stream headers (see
+//! `detect_stream_flags`). A candidate is only accepted if it decodes to the
+//! end of its stream, which rules out signature bytes that merely occur inside
+//! other data; the decoder also tells how many bytes the stream occupies.
+
+use crate::format::*;
+use crate::reader::detect_stream_flags;
+use std::io::Read;
+
+/// Locates the codec streams in `data`, starting at `start`, and returns one
+/// chunk per stream.
+///
+/// Bytes between two streams, and after the last one, become `CHUNK_COPYCOMP`
+/// chunks so that no data is dropped; so do the bytes between `start` and the
+/// first stream. Offsets are relative to `data` and every chunk is in volume 0.
+pub fn scan_chunks(data: &[u8], start: usize) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let mut gap_start = start;
+    let mut pos = start;
+    while pos < data.len() {
+        let Some((flags, consumed, decoded)) = decode_stream_at(&data[pos..]) else {
+            pos += 1;
+            continue;
+        };
+        if pos > gap_start {
+            chunks.push(stored_chunk(gap_start, pos - gap_start));
+        }
+        chunks.push(Chunk {
+            offset: pos as u32,
+            compressed_length: consumed as u32,
+            decompressed_length: decoded as u32,
+            flags,
+            file: 0,
+        });
+        pos += consumed;
+        gap_start = pos;
+    }
+    if data.len() > gap_start {
+        chunks.push(stored_chunk(gap_start, data.len() - gap_start));
+    }
+    chunks
+}
+
+fn stored_chunk(offset: usize, len: usize) -> Chunk {
+    Chunk {
+        offset: offset as u32,
+        compressed_length: len as u32,
+        decompressed_length: len as u32,
+        flags: CHUNK_COPYCOMP,
+        file: 0,
+    }
+}
+
+/// Decodes the stream starting at `data[0]`, if there is one.
+/// Returns its chunk flags, the bytes it occupies and its decoded length.
+fn decode_stream_at(data: &[u8]) -> Option<(u16, usize, usize)> {
+    let mut output = Vec::new();
+    let (flags, consumed) = match detect_stream_flags(data)? {
+        CHUNK_ZLIB if data.starts_with(&[0x1f, 0x8b]) => {
+            let mut decoder = flate2::bufread::GzDecoder::new(data);
+            decoder.read_to_end(&mut output).ok()?;
+            // gzip framing is what `CompressionMethod::Zlib` writes
+            (CHUNK_ZLIB, data.len() - decoder.into_inner().len())
+        }
+        CHUNK_ZLIB => {
+            let mut decoder = flate2::bufread::ZlibDecoder::new(data);
+            decoder.read_to_end(&mut output).ok()?;
+            (CHUNK_ZLIB, decoder.total_in() as usize)
+        }
+        CHUNK_BZIP => {
+            let mut decoder = bzip2::bufread::BzDecoder::new(data);
+            decoder.read_to_end(&mut output).ok()?;
+            (CHUNK_BZIP, decoder.total_in() as usize)
+        }
+        CHUNK_LZMA => {
+            let consumed = decode_exact(data, &mut output, |input, output| {
+                lzma_rs::lzma_decompress(input, output).is_ok()
+            })?;
+            (CHUNK_LZMA, consumed)
+        }
+        CHUNK_XZ => {
+            let consumed = decode_exact(data, &mut output, |input, output| {
+                lzma_rs::xz_decompress(input, output).is_ok()
+            })?;
+            (CHUNK_XZ, consumed)
+        }
+        _ => return None,
+    };
+    // Empty streams are almost always signature bytes that happened to decode
+    if consumed == 0 || output.is_empty() {
+        return None;
+    }
+    u32::try_from(output.len()).ok()?;
+    Some((flags, consumed, output.len()))
+}
+
+/// lzma-rs fails on bytes after the end of a stream, but stops reading where the
+/// stream ends. Decode once to find that point, then again up to it to check that
+/// the stream is complete.
+fn decode_exact(
+    data: &[u8],
+    output: &mut Vec<u8>,
+    decode: impl Fn(&mut &[u8], &mut Vec<u8>) -> bool,
+) -> Option<usize> {
+    let mut input = data;
+    decode(&mut input, &mut Vec::new());
+    let consumed = data.len() - input.len();
+    decode(&mut &data[..consumed], output).then_some(consumed)
+}
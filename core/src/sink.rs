@@ -0,0 +1,482 @@
+use crate::error::{DzipError, Result};
+use crate::reader::DEFAULT_BUFFER_SIZE;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Destination for extracted files.
+///
+/// Paths are relative to the extraction root and already sanitized.
+/// Implementations must be `Sync`, since files may be extracted in parallel.
+pub trait UnpackSink: Sync {
+    /// Create a directory (and any missing parents).
+    fn create_dir_all(&self, path: &Path) -> Result<()>;
+
+    /// Create (or truncate) a file and return a writer for its contents.
+    fn create_file(&self, path: &Path) -> Result<Box<dyn SinkFile + '_>>;
+}
+
+/// A file being written to an `UnpackSink`.
+pub trait SinkFile: Write + Send {
+    /// Completes the file, reporting any error writing it out. Sinks that only
+    /// show whole files (atomic renames, archive entries) publish it here, not
+    /// on `flush`; a file dropped without `finish` is discarded by them.
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+impl SinkFile for BufWriter<File> {
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.flush()?;
+        Ok(())
+    }
+}
+
+/// Suffix of the temporary file an atomic `FileSystemSink` writes to.
+pub const PARTIAL_SUFFIX: &str = ".partial";
+
+/// A sink that writes extracted files below a directory on disk.
+///
+/// Files are written through a buffer; callers should `finish` the returned
+/// writer so that write errors are not lost when it is dropped.
+pub struct FileSystemSink {
+    root: PathBuf,
+    buffer_size: usize,
+    follow_symlinks: bool,
+    atomic: bool,
+    keep_partial: bool,
+}
+
+impl FileSystemSink {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self::with_buffer_size(root, DEFAULT_BUFFER_SIZE)
+    }
+
+    /// Creates a sink whose file writers buffer `buffer_size` bytes.
+    pub fn with_buffer_size(root: impl Into<PathBuf>, buffer_size: usize) -> Self {
+        Self {
+            root: root.into(),
+            buffer_size,
+            follow_symlinks: true,
+            atomic: false,
+            keep_partial: false,
+        }
+    }
+
+    /// Sets whether symlinks below the root may be followed (the default).
+    ///
+    /// When disabled, every directory is created one component at a time and
+    /// checked to be a real directory, and files are opened without following a
+    /// symlink in their last component (`O_NOFOLLOW` on Unix). A symlink planted
+    /// inside the output directory, e.g. by another process racing the unpack,
+    /// then fails the write instead of redirecting it outside the root.
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.follow_symlinks = follow;
+        self
+    }
+
+    /// Sets whether files appear at their final path only once complete.
+    ///
+    /// Data is written to `<name>.partial` next to the target and renamed into
+    /// place when the writer is finished. A writer dropped without `finish`
+    /// (e.g. after a failed chunk read) removes its partial file, so an
+    /// interrupted extraction never leaves a truncated file under the real name.
+    pub fn atomic(mut self, atomic: bool) -> Self {
+        self.atomic = atomic;
+        self
+    }
+
+    /// With `atomic`, keeps the partial file of an unfinished write for
+    /// diagnosis instead of removing it.
+    pub fn keep_partial(mut self, keep: bool) -> Self {
+        self.keep_partial = keep;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn open_file(&self, path: &Path) -> Result<File> {
+        if self.follow_symlinks {
+            Ok(File::create(self.root.join(path))?)
+        } else {
+            self.open_nofollow(path)
+        }
+    }
+
+    /// Creates `path` below the root one component at a time, refusing symlinks.
+    fn create_dir_all_nofollow(&self, path: &Path) -> Result<()> {
+        let mut dir = self.root.clone();
+        for component in path.components() {
+            dir.push(component);
+            match std::fs::create_dir(&dir) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e.into()),
+            }
+            let metadata = std::fs::symlink_metadata(&dir)?;
+            if metadata.file_type().is_symlink() {
+                return Err(DzipError::SymlinkRefused(dir));
+            }
+            if !metadata.is_dir() {
+                return Err(DzipError::NotADirectory(dir));
+            }
+        }
+        Ok(())
+    }
+
+    fn open_nofollow(&self, path: &Path) -> Result<File> {
+        if let Some(parent) = path.parent() {
+            self.create_dir_all_nofollow(parent)?;
+        }
+        let full_path = self.root.join(path);
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.custom_flags(libc::O_NOFOLLOW);
+        }
+        #[cfg(not(unix))]
+        if std::fs::symlink_metadata(&full_path).is_ok_and(|m| m.file_type().is_symlink()) {
+            return Err(DzipError::SymlinkRefused(full_path));
+        }
+        options.open(&full_path).map_err(|e| {
+            // O_NOFOLLOW reports a symlink in the last component as ELOOP
+            #[cfg(unix)]
+            if e.raw_os_error() == Some(libc::ELOOP) {
+                return DzipError::SymlinkRefused(full_path);
+            }
+            e.into()
+        })
+    }
+}
+
+impl UnpackSink for FileSystemSink {
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        if !self.follow_symlinks {
+            return self.create_dir_all_nofollow(path);
+        }
+        std::fs::create_dir_all(self.root.join(path))?;
+        Ok(())
+    }
+
+    fn create_file(&self, path: &Path) -> Result<Box<dyn SinkFile + '_>> {
+        if !self.atomic {
+            let file = self.open_file(path)?;
+            return Ok(Box::new(BufWriter::with_capacity(self.buffer_size, file)));
+        }
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(PARTIAL_SUFFIX);
+        let partial = PathBuf::from(partial);
+        let file = self.open_file(&partial)?;
+        Ok(Box::new(PartialFile {
+            writer: Some(BufWriter::with_capacity(self.buffer_size, file)),
+            partial: self.root.join(partial),
+            target: self.root.join(path),
+            committed: false,
+            keep: self.keep_partial,
+        }))
+    }
+}
+
+/// Writer handed out by an atomic `FileSystemSink`; renames its partial file to
+/// the target on `finish`, and removes it if dropped before that.
+struct PartialFile {
+    writer: Option<BufWriter<File>>,
+    partial: PathBuf,
+    target: PathBuf,
+    committed: bool,
+    keep: bool,
+}
+
+impl Write for PartialFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer.as_mut().expect("writer is open").write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.as_mut().expect("writer is open").flush()
+    }
+}
+
+impl SinkFile for PartialFile {
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.flush()?;
+        // Close the file before renaming it, which fails on Windows otherwise
+        drop(self.writer.take());
+        std::fs::rename(&self.partial, &self.target)?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        // Close the file first, removing an open file fails on Windows
+        drop(self.writer.take());
+        if !self.committed && !self.keep {
+            let _ = std::fs::remove_file(&self.partial);
+        }
+    }
+}
+
+/// A sink that collects extracted files in memory, keyed by their relative path
+/// with `/` separators.
+///
+/// A file's contents are stored once its writer is dropped.
+#[derive(Default)]
+pub struct MemorySink {
+    files: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl MemorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the contents of an extracted file, if present.
+    pub fn get(&self, path: &str) -> Option<Vec<u8>> {
+        self.files.lock().unwrap().get(path).cloned()
+    }
+
+    /// Consumes the sink and returns all extracted files.
+    pub fn into_files(self) -> HashMap<String, Vec<u8>> {
+        self.files.into_inner().unwrap()
+    }
+}
+
+/// Relative path with `/` separators, as used for in-memory and archive entries.
+fn entry_name(path: &Path) -> String {
+    path.iter()
+        .map(|c| c.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+impl UnpackSink for MemorySink {
+    fn create_dir_all(&self, _path: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    fn create_file(&self, path: &Path) -> Result<Box<dyn SinkFile + '_>> {
+        Ok(Box::new(MemoryFile {
+            sink: self,
+            key: entry_name(path),
+            data: Vec::new(),
+        }))
+    }
+}
+
+/// Writer handed out by `MemorySink`; stores its buffer into the sink on drop.
+struct MemoryFile<'a> {
+    sink: &'a MemorySink,
+    key: String,
+    data: Vec<u8>,
+}
+
+impl Write for MemoryFile<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SinkFile for MemoryFile<'_> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for MemoryFile<'_> {
+    fn drop(&mut self) {
+        let data = std::mem::take(&mut self.data);
+        let key = std::mem::take(&mut self.key);
+        self.sink.files.lock().unwrap().insert(key, data);
+    }
+}
+
+/// Sinks that store each file as one entry of an output archive.
+trait AppendEntry: Sync {
+    fn append_entry(&self, name: &str, data: &[u8]) -> std::io::Result<()>;
+}
+
+/// Writer handed out by archive sinks. Archive entries need their size up front,
+/// so the file is buffered and appended when the writer is finished; a writer
+/// dropped without `finish` adds nothing.
+struct BufferedEntry<'a> {
+    sink: &'a dyn AppendEntry,
+    name: String,
+    data: Vec<u8>,
+}
+
+impl Write for BufferedEntry<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SinkFile for BufferedEntry<'_> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        self.sink.append_entry(&self.name, &self.data)?;
+        Ok(())
+    }
+}
+
+/// A sink that writes extracted files as entries of a tar archive.
+///
+/// Entries are appended one at a time as files complete, so decompression can
+/// stay parallel; the entry order then follows completion order. Files are
+/// buffered in memory until finished. Call `finish` to complete the archive.
+pub struct TarSink<W: Write + Send> {
+    builder: Mutex<tar::Builder<W>>,
+    dirs: Mutex<HashSet<String>>,
+}
+
+impl<W: Write + Send> TarSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            builder: Mutex::new(tar::Builder::new(writer)),
+            dirs: Mutex::default(),
+        }
+    }
+
+    /// Writes the end-of-archive marker and returns the underlying writer.
+    pub fn finish(self) -> Result<W> {
+        Ok(self.builder.into_inner().unwrap().into_inner()?)
+    }
+
+    fn header(entry_type: tar::EntryType, mode: u32, size: u64) -> tar::Header {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_mode(mode);
+        header.set_size(size);
+        header.set_mtime(0);
+        header
+    }
+}
+
+impl<W: Write + Send> AppendEntry for TarSink<W> {
+    fn append_entry(&self, name: &str, data: &[u8]) -> std::io::Result<()> {
+        let mut header = Self::header(tar::EntryType::Regular, 0o644, data.len() as u64);
+        self.builder
+            .lock()
+            .unwrap()
+            .append_data(&mut header, name, data)
+    }
+}
+
+impl<W: Write + Send> UnpackSink for TarSink<W> {
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        let name = entry_name(path);
+        if name.is_empty() || self.dirs.lock().unwrap().contains(&name) {
+            return Ok(());
+        }
+        // Parents first, so every directory entry precedes its children
+        if let Some(parent) = path.parent() {
+            self.create_dir_all(parent)?;
+        }
+        if !self.dirs.lock().unwrap().insert(name.clone()) {
+            return Ok(());
+        }
+        let mut header = Self::header(tar::EntryType::Directory, 0o755, 0);
+        self.builder.lock().unwrap().append_data(
+            &mut header,
+            format!("{}/", name),
+            std::io::empty(),
+        )?;
+        Ok(())
+    }
+
+    fn create_file(&self, path: &Path) -> Result<Box<dyn SinkFile + '_>> {
+        Ok(Box::new(BufferedEntry {
+            sink: self,
+            name: entry_name(path),
+            data: Vec::new(),
+        }))
+    }
+}
+
+/// A sink that writes extracted files as deflate-compressed entries of a zip archive.
+///
+/// Like `TarSink`, entries are appended in completion order and files are
+/// buffered until finished. Call `finish` to write the central directory.
+pub struct ZipSink<W: Write + Seek + Send> {
+    writer: Mutex<zip::ZipWriter<W>>,
+    dirs: Mutex<HashSet<String>>,
+}
+
+impl<W: Write + Seek + Send> ZipSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(zip::ZipWriter::new(writer)),
+            dirs: Mutex::default(),
+        }
+    }
+
+    /// Writes the central directory and returns the underlying writer.
+    pub fn finish(self) -> Result<W> {
+        self.writer
+            .into_inner()
+            .unwrap()
+            .finish()
+            .map_err(|e| std::io::Error::other(e).into())
+    }
+
+    fn options(mode: u32) -> zip::write::SimpleFileOptions {
+        zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .unix_permissions(mode)
+    }
+}
+
+impl<W: Write + Seek + Send> AppendEntry for ZipSink<W> {
+    fn append_entry(&self, name: &str, data: &[u8]) -> std::io::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        writer
+            .start_file(name, Self::options(0o644))
+            .map_err(std::io::Error::other)?;
+        writer.write_all(data)
+    }
+}
+
+impl<W: Write + Seek + Send> UnpackSink for ZipSink<W> {
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        let name = entry_name(path);
+        if name.is_empty() || self.dirs.lock().unwrap().contains(&name) {
+            return Ok(());
+        }
+        // Parents first, so every directory entry precedes its children
+        if let Some(parent) = path.parent() {
+            self.create_dir_all(parent)?;
+        }
+        if !self.dirs.lock().unwrap().insert(name.clone()) {
+            return Ok(());
+        }
+        self.writer
+            .lock()
+            .unwrap()
+            .add_directory(name, Self::options(0o755))
+            .map_err(std::io::Error::other)?;
+        Ok(())
+    }
+
+    fn create_file(&self, path: &Path) -> Result<Box<dyn SinkFile + '_>> {
+        Ok(Box::new(BufferedEntry {
+            sink: self,
+            name: entry_name(path),
+            data: Vec::new(),
+        }))
+    }
+}
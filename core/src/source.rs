@@ -0,0 +1,218 @@
+use crate::error::Result;
+use crate::reader::ReadSeek;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// A seekable archive volume that can be read from several threads at once.
+///
+/// Every call to `reader` returns an independent reader with its own position,
+/// so parallel extraction can share one source instead of reopening the file
+/// for each entry. Byte buffers (`Vec<u8>`, `&[u8]`, `Arc<[u8]>`, ...) implement
+/// this directly, which covers embedded or downloaded archives.
+pub trait ArchiveSource: Sync {
+    fn reader(&self) -> Result<Box<dyn ReadSeek + Send + '_>>;
+}
+
+impl<T: AsRef<[u8]> + Sync> ArchiveSource for T {
+    fn reader(&self) -> Result<Box<dyn ReadSeek + Send + '_>> {
+        Ok(Box::new(Cursor::new(self.as_ref())))
+    }
+}
+
+/// A file opened once and read with positional I/O, so its handle can be
+/// shared between threads without the readers disturbing each other.
+///
+/// Opened with `open_mapped`, the file is memory-mapped instead and reads are
+/// served from the mapping, without a system call per read.
+pub struct SharedFile {
+    file: File,
+    /// Where the archive starts in the file (see `with_base`)
+    base: u64,
+    len: u64,
+    map: Option<memmap2::Mmap>,
+}
+
+impl SharedFile {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        Ok(Self {
+            file,
+            base: 0,
+            len,
+            map: None,
+        })
+    }
+
+    /// Serves the file from offset `base` on, for an archive appended to a
+    /// self-extracting stub. Readers see the archive start at position 0.
+    pub fn with_base(mut self, base: u64) -> Result<Self> {
+        let total = self.base + self.len;
+        if base > total {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "archive offset {} is past the end of the file ({} bytes)",
+                    base, total
+                ),
+            )
+            .into());
+        }
+        self.base = base;
+        self.len = total - base;
+        Ok(self)
+    }
+
+    /// Like `open`, but memory-maps the file. Falls back to positional I/O if
+    /// the file cannot be mapped (e.g. it exceeds the address space on 32-bit
+    /// targets).
+    ///
+    /// The file must not be modified while it is mapped; archives are only read,
+    /// so this holds unless another process rewrites them during extraction.
+    pub fn open_mapped(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut shared = Self::open(path)?;
+        // SAFETY: the mapping is read-only and lives no longer than `shared`;
+        // see the note above about concurrent modification.
+        match unsafe { memmap2::Mmap::map(&shared.file) } {
+            Ok(map) => shared.map = Some(map),
+            Err(e) => log::warn!(
+                "Cannot memory-map {} ({}); using file I/O",
+                path.display(),
+                e
+            ),
+        }
+        Ok(shared)
+    }
+
+    /// Whether reads are served from a memory mapping.
+    pub fn is_mapped(&self) -> bool {
+        self.map.is_some()
+    }
+
+    /// Length of the archive, i.e. of the file after `base`.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl ArchiveSource for SharedFile {
+    fn reader(&self) -> Result<Box<dyn ReadSeek + Send + '_>> {
+        if let Some(map) = &self.map {
+            return Ok(Box::new(Cursor::new(&map[self.base as usize..])));
+        }
+        Ok(Box::new(SharedFileReader {
+            file: &self.file,
+            base: self.base,
+            len: self.len,
+            pos: 0,
+        }))
+    }
+}
+
+/// Reader over a `SharedFile` that tracks its own position.
+struct SharedFileReader<'a> {
+    file: &'a File,
+    base: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl Read for SharedFileReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        #[cfg(unix)]
+        let n = std::os::unix::fs::FileExt::read_at(self.file, buf, self.base + self.pos)?;
+        #[cfg(windows)]
+        let n = std::os::windows::fs::FileExt::seek_read(self.file, buf, self.base + self.pos)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for SharedFileReader<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+        match new_pos {
+            Some(new_pos) => {
+                self.pos = new_pos;
+                Ok(new_pos)
+            }
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+/// Reader over the part of `inner` that starts at `base`, for an archive
+/// appended to a self-extracting stub. Positions are relative to `base`, so
+/// header and chunk offsets can be used unchanged.
+pub struct OffsetReader<R> {
+    inner: R,
+    base: u64,
+}
+
+impl<R: Seek> OffsetReader<R> {
+    pub fn new(mut inner: R, base: u64) -> std::io::Result<Self> {
+        inner.seek(SeekFrom::Start(base))?;
+        Ok(Self { inner, base })
+    }
+
+    /// Offset of the archive in the underlying reader.
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for OffsetReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+/// Writes pass through as well, so a header can be patched in place.
+impl<R: Write> Write for OffsetReader<R> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<R: Seek> Seek for OffsetReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(offset) => {
+                SeekFrom::Start(self.base.checked_add(offset).ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidInput, "seek offset overflows")
+                })?)
+            }
+            pos => pos,
+        };
+        let new_pos = self.inner.seek(pos)?;
+        if new_pos < self.base {
+            self.inner.seek(SeekFrom::Start(self.base))?;
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to before the start of the archive",
+            ));
+        }
+        Ok(new_pos - self.base)
+    }
+}
@@ -0,0 +1,299 @@
+use crate::archive::Archive;
+use crate::error::{DzipError, Result};
+use crate::format::Chunk;
+use crate::path::resolve_file_path;
+use crate::progress::{ProgressEvent, ProgressFn};
+use crate::reader::{DzipReader, VolumeSource};
+use crate::sink::UnpackSink;
+use std::collections::HashMap;
+use std::io::{Read, Seek, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Outcome of extracting a single file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ExtractStats {
+    /// Decompressed bytes written to the output
+    pub bytes_written: u64,
+    /// Chunks that could not be decoded and were left out of the output
+    pub chunks_failed: usize,
+}
+
+/// Summary of an unpack run.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct UnpackReport {
+    /// Relative paths of the written files, in archive order
+    pub files: Vec<PathBuf>,
+    /// Files written with every chunk decoded
+    pub files_ok: usize,
+    /// Files written with at least one chunk missing
+    pub files_partial: usize,
+    /// Files not written at all (filtered out or skipped on collision)
+    pub files_skipped: usize,
+    /// Files left in place because a previous run already extracted them
+    pub files_existing: usize,
+    /// Files not written because some of their chunks are missing from the volumes
+    pub files_missing: usize,
+    /// Chunks that could not be decoded, across all files
+    pub chunks_failed: usize,
+    /// Decompressed bytes written, across all files
+    pub bytes_written: u64,
+}
+
+impl UnpackReport {
+    /// Records a written file.
+    pub fn add_file(&mut self, path: PathBuf, stats: ExtractStats) {
+        if stats.chunks_failed == 0 {
+            self.files_ok += 1;
+        } else {
+            self.files_partial += 1;
+        }
+        self.chunks_failed += stats.chunks_failed;
+        self.bytes_written += stats.bytes_written;
+        self.files.push(path);
+    }
+
+    /// Records a file that was already extracted and left untouched.
+    pub fn add_existing(&mut self, path: PathBuf) {
+        self.files_existing += 1;
+        self.files.push(path);
+    }
+
+    /// Returns true if every written file was extracted completely.
+    pub fn is_complete(&self) -> bool {
+        self.files_partial == 0
+    }
+}
+
+/// Decompressed chunks that more than one file references, so that archives
+/// sharing chunks between files decode each shared chunk only once.
+///
+/// The cache is safe to use from several extraction threads. An entry is dropped
+/// once every file referencing it has read it, and chunks that would push the
+/// cache past its capacity are decoded without being cached. Two threads missing
+/// the same chunk at the same time may both decode it.
+#[derive(Debug)]
+pub struct ChunkCache {
+    capacity: u64,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    /// Reads still expected per shared chunk
+    remaining: HashMap<u16, usize>,
+    entries: HashMap<u16, Arc<Vec<u8>>>,
+    bytes: u64,
+}
+
+impl ChunkCache {
+    /// Creates a cache for the files about to be extracted, given their chunk ids.
+    /// Only chunks referenced more than once are cached, using at most `capacity`
+    /// bytes of decompressed data.
+    pub fn new<'a>(files: impl IntoIterator<Item = &'a [u16]>, capacity: u64) -> Self {
+        let mut remaining = HashMap::new();
+        for chunk_ids in files {
+            for &id in chunk_ids {
+                *remaining.entry(id).or_insert(0usize) += 1;
+            }
+        }
+        remaining.retain(|_, count| *count > 1);
+        Self {
+            capacity,
+            state: Mutex::new(CacheState {
+                remaining,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Number of distinct chunks referenced by more than one file.
+    pub fn shared_chunks(&self) -> usize {
+        self.lock().remaining.len()
+    }
+
+    /// Returns chunk `id`, decoding it with `decode` unless it is cached.
+    pub fn get_or_decode(
+        &self,
+        id: u16,
+        decode: impl FnOnce() -> Result<Vec<u8>>,
+    ) -> Result<Arc<Vec<u8>>> {
+        {
+            let mut state = self.lock();
+            if !state.remaining.contains_key(&id) {
+                drop(state);
+                return decode().map(Arc::new);
+            }
+            if let Some(data) = state.entries.get(&id).cloned() {
+                state.consume(id);
+                return Ok(data);
+            }
+        }
+
+        // Decode without holding the lock so other chunks are not held up
+        let data = Arc::new(decode()?);
+        let mut state = self.lock();
+        if state.bytes + data.len() as u64 <= self.capacity && !state.entries.contains_key(&id) {
+            state.bytes += data.len() as u64;
+            state.entries.insert(id, Arc::clone(&data));
+        }
+        state.consume(id);
+        Ok(data)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        // The state stays consistent even if a decoder panicked elsewhere
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl CacheState {
+    /// Counts one read of `id` and evicts it after the last expected one.
+    fn consume(&mut self, id: u16) {
+        let Some(count) = self.remaining.get_mut(&id) else {
+            return;
+        };
+        *count -= 1;
+        if *count == 0 {
+            self.remaining.remove(&id);
+            if let Some(data) = self.entries.remove(&id) {
+                self.bytes -= data.len() as u64;
+            }
+        }
+    }
+}
+
+/// Decompresses the chunks of one file in order and writes them to `out`.
+///
+/// Chunks that fail to decode (including unsupported compression) are logged and
+/// skipped so the rest of the file is still written.
+pub fn extract_chunks<R: Read + Seek>(
+    reader: &mut DzipReader<R>,
+    volumes: &mut dyn VolumeSource,
+    chunks: &[Chunk],
+    chunk_ids: &[u16],
+    out: &mut dyn Write,
+) -> Result<ExtractStats> {
+    extract_chunks_with_progress(reader, volumes, chunks, chunk_ids, out, None)
+}
+
+/// Like `extract_chunks`, reporting `ChunkDecompressed` for every chunk written.
+pub fn extract_chunks_with_progress<R: Read + Seek>(
+    reader: &mut DzipReader<R>,
+    volumes: &mut dyn VolumeSource,
+    chunks: &[Chunk],
+    chunk_ids: &[u16],
+    out: &mut dyn Write,
+    progress: Option<ProgressFn<'_>>,
+) -> Result<ExtractStats> {
+    extract_chunks_cached(reader, volumes, chunks, chunk_ids, out, progress, None)
+}
+
+/// Like `extract_chunks_with_progress`, serving shared chunks from `cache`.
+pub fn extract_chunks_cached<R: Read + Seek>(
+    reader: &mut DzipReader<R>,
+    volumes: &mut dyn VolumeSource,
+    chunks: &[Chunk],
+    chunk_ids: &[u16],
+    out: &mut dyn Write,
+    progress: Option<ProgressFn<'_>>,
+    cache: Option<&ChunkCache>,
+) -> Result<ExtractStats> {
+    let mut stats = ExtractStats::default();
+    for &chunk_id in chunk_ids {
+        let Some(chunk) = chunks.get(chunk_id as usize) else {
+            let e = DzipError::ChunkNotFound {
+                id: chunk_id,
+                count: chunks.len(),
+            };
+            log::error!("{}", e);
+            stats.chunks_failed += 1;
+            continue;
+        };
+        let data = match cache {
+            Some(cache) => cache.get_or_decode(chunk_id, || {
+                reader.read_chunk_data_with_volumes(chunk, volumes)
+            }),
+            None => reader
+                .read_chunk_data_with_volumes(chunk, volumes)
+                .map(Arc::new),
+        };
+        match data {
+            Ok(data) => {
+                out.write_all(&data)?;
+                stats.bytes_written += data.len() as u64;
+                if let Some(progress) = progress {
+                    progress(ProgressEvent::ChunkDecompressed {
+                        chunk: chunk_id,
+                        bytes: data.len() as u64,
+                    });
+                }
+            }
+            Err(DzipError::UnsupportedCompression(flags)) => {
+                log::warn!(
+                    "Skipping chunk {} due to unsupported compression (flags: {:#x})",
+                    chunk_id,
+                    flags
+                );
+                stats.chunks_failed += 1;
+            }
+            Err(e) => {
+                log::error!("Error extracting chunk {}: {}", chunk_id, e);
+                stats.chunks_failed += 1;
+            }
+        }
+    }
+    Ok(stats)
+}
+
+/// Extracts every file of an archive into `sink`, one file at a time.
+///
+/// `main` is volume 0; `volumes` serves the auxiliary volumes listed in its header.
+/// Chunk sizes are corrected against the actual volume sizes before extraction.
+/// Returns a report listing the extracted relative paths in archive order.
+pub fn unpack_to_sink<R: Read + Seek>(
+    main: R,
+    volumes: &mut dyn VolumeSource,
+    sink: &dyn UnpackSink,
+) -> Result<UnpackReport> {
+    unpack_to_sink_with_progress(main, volumes, sink, None)
+}
+
+/// Like `unpack_to_sink`, reporting the start and end of every file and each
+/// decoded chunk to `progress`.
+pub fn unpack_to_sink_with_progress<R: Read + Seek>(
+    main: R,
+    volumes: &mut dyn VolumeSource,
+    sink: &dyn UnpackSink,
+    progress: Option<ProgressFn<'_>>,
+) -> Result<UnpackReport> {
+    let mut archive = Archive::from_parts(main, volumes)?;
+
+    let mut report = UnpackReport::default();
+    for i in 0..archive.header().map.len() {
+        let relative_path = resolve_file_path(&archive.header().file_path(i)?)?;
+
+        if let Some(parent) = relative_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            sink.create_dir_all(parent)?;
+        }
+        if let Some(progress) = progress {
+            progress(ProgressEvent::FileStarted {
+                index: i,
+                path: &relative_path,
+            });
+        }
+        let mut out = sink.create_file(&relative_path)?;
+        let stats = archive.extract_index_with_progress(i, &mut out, progress)?;
+        out.finish()?;
+        if let Some(progress) = progress {
+            progress(ProgressEvent::FileCompleted {
+                index: i,
+                path: &relative_path,
+                stats,
+            });
+        }
+        report.add_file(relative_path, stats);
+    }
+
+    Ok(report)
+}
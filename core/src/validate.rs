@@ -0,0 +1,249 @@
+use crate::format::{CHUNK_ZERO, Chunk};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+/// A problem with where a chunk's data lives inside its volume.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayoutIssue {
+    /// The data of `chunk` runs into the data of `next`, or a chunk of unknown
+    /// length starts at the same offset as `next`
+    Overlap {
+        file: u16,
+        chunk: usize,
+        next: usize,
+    },
+    /// `size` unreferenced bytes lie between the end of `chunk` and the start of `next`
+    Gap {
+        file: u16,
+        chunk: usize,
+        next: usize,
+        size: u64,
+    },
+    /// The data of `chunk` starts or ends beyond the end of its volume
+    OutOfBounds {
+        file: u16,
+        chunk: usize,
+        end: u64,
+        volume_size: u64,
+    },
+}
+
+impl LayoutIssue {
+    /// Overlaps and out-of-bounds chunks cannot be read correctly; gaps are only suspicious.
+    pub fn is_error(&self) -> bool {
+        !matches!(self, LayoutIssue::Gap { .. })
+    }
+}
+
+impl fmt::Display for LayoutIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutIssue::Overlap { file, chunk, next } => write!(
+                f,
+                "chunk {} overlaps chunk {} in volume {}",
+                chunk, next, file
+            ),
+            LayoutIssue::Gap {
+                file,
+                chunk,
+                next,
+                size,
+            } => write!(
+                f,
+                "{} unused bytes between chunk {} and chunk {} in volume {}",
+                size, chunk, next, file
+            ),
+            LayoutIssue::OutOfBounds {
+                file,
+                chunk,
+                end,
+                volume_size,
+            } => write!(
+                f,
+                "chunk {} ends at {} beyond the end of volume {} ({} bytes)",
+                chunk, end, file, volume_size
+            ),
+        }
+    }
+}
+
+/// Checks that the chunks of each volume are laid out back to back without overlapping.
+///
+/// Run this on the chunk table as read, before `correct_chunk_sizes` clamps the
+/// lengths. Compressed chunks whose two lengths are equal carry a placeholder
+/// `compressed_length` (see `correct_chunk_sizes`), so only their start offset is
+/// checked. Zero chunks have no data and are ignored. Volumes missing from
+/// `file_sizes` are not bounds-checked.
+pub fn check_chunk_layout(chunks: &[Chunk], file_sizes: &HashMap<u16, u64>) -> Vec<LayoutIssue> {
+    let mut chunks_by_file: HashMap<u16, Vec<usize>> = HashMap::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        if (chunk.flags & CHUNK_ZERO) == 0 {
+            chunks_by_file.entry(chunk.file).or_default().push(i);
+        }
+    }
+    let mut files: Vec<_> = chunks_by_file.into_iter().collect();
+    files.sort_by_key(|(file, _)| *file);
+
+    let mut issues = Vec::new();
+    for (file, mut indices) in files {
+        // Empty chunks sort before a chunk starting at the same offset
+        indices.sort_by_key(|&i| (chunks[i].offset, chunks[i].compressed_length, i));
+        let volume_size = file_sizes.get(&file).copied();
+
+        for (pos, &idx) in indices.iter().enumerate() {
+            let chunk = &chunks[idx];
+            let start = chunk.offset as u64;
+            let end = if chunk.has_placeholder_length() {
+                None
+            } else {
+                Some(start + chunk.compressed_length as u64)
+            };
+
+            if let Some(volume_size) = volume_size {
+                let last_byte = end.unwrap_or(start);
+                if start > volume_size || last_byte > volume_size {
+                    issues.push(LayoutIssue::OutOfBounds {
+                        file,
+                        chunk: idx,
+                        end: last_byte,
+                        volume_size,
+                    });
+                }
+            }
+
+            let Some(&next) = indices.get(pos + 1) else {
+                continue;
+            };
+            let next_start = chunks[next].offset as u64;
+            match end {
+                None if next_start == start => issues.push(LayoutIssue::Overlap {
+                    file,
+                    chunk: idx,
+                    next,
+                }),
+                Some(end) if end > next_start => issues.push(LayoutIssue::Overlap {
+                    file,
+                    chunk: idx,
+                    next,
+                }),
+                Some(end) if end < next_start => issues.push(LayoutIssue::Gap {
+                    file,
+                    chunk: idx,
+                    next,
+                    size: next_start - end,
+                }),
+                _ => {}
+            }
+        }
+    }
+    issues
+}
+
+/// Returns the chunks whose data is not fully present, e.g. in a truncated download.
+///
+/// A chunk is missing if its volume is absent from `file_sizes` or its data ends
+/// beyond the end of the volume. Like `check_chunk_layout`, this must run on the
+/// chunk table as read: `correct_chunk_sizes` clamps lengths to the volume size
+/// and would hide the truncation. A chunk with a placeholder length is taken to
+/// end where the next chunk of its volume starts; the last one is missing if it
+/// starts at or past the end of the volume, while a cut inside it only shows
+/// when it fails to decode. Zero chunks are never missing.
+pub fn missing_chunks(chunks: &[Chunk], file_sizes: &HashMap<u16, u64>) -> BTreeSet<usize> {
+    let mut chunks_by_file: HashMap<u16, Vec<usize>> = HashMap::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        if (chunk.flags & CHUNK_ZERO) == 0 {
+            chunks_by_file.entry(chunk.file).or_default().push(i);
+        }
+    }
+
+    let mut missing = BTreeSet::new();
+    for (file, mut indices) in chunks_by_file {
+        let Some(&volume_size) = file_sizes.get(&file) else {
+            missing.extend(indices);
+            continue;
+        };
+        indices.sort_by_key(|&i| (chunks[i].offset, chunks[i].compressed_length, i));
+        for (pos, &idx) in indices.iter().enumerate() {
+            let chunk = &chunks[idx];
+            let start = chunk.offset as u64;
+            let is_missing = if chunk.has_placeholder_length() {
+                match indices.get(pos + 1) {
+                    Some(&next) => chunks[next].offset as u64 > volume_size,
+                    None => start >= volume_size,
+                }
+            } else {
+                start + chunk.compressed_length as u64 > volume_size
+            };
+            if is_missing {
+                missing.insert(idx);
+            }
+        }
+    }
+    missing
+}
+
+/// How the size of a volume on disk compares to the end of its chunk data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeSizeStatus {
+    Ok,
+    /// The volume ends before the data of its last chunk
+    Truncated,
+    /// Bytes follow the data of the last chunk, e.g. a volume from another version
+    ExtraData,
+    /// The volume could not be found
+    Missing,
+}
+
+/// Size of one volume against the data its chunks reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolumeSize {
+    pub file: u16,
+    /// Size on disk, if the volume exists
+    pub actual: Option<u64>,
+    /// Largest `offset + compressed_length` of the volume's chunks
+    pub expected: u64,
+    /// False if the last chunk has a placeholder length, so `expected` is only
+    /// where its data starts and extra data cannot be detected
+    pub exact: bool,
+}
+
+impl VolumeSize {
+    pub fn status(&self) -> VolumeSizeStatus {
+        match self.actual {
+            None => VolumeSizeStatus::Missing,
+            Some(actual) if actual < self.expected => VolumeSizeStatus::Truncated,
+            Some(actual) if actual > self.expected && self.exact => VolumeSizeStatus::ExtraData,
+            Some(_) => VolumeSizeStatus::Ok,
+        }
+    }
+}
+
+/// Compares the size of every volume that holds chunk data with the end of its
+/// last chunk, to catch truncated or mismatched volumes before extraction.
+///
+/// `file_sizes` is the same map as for `check_chunk_layout` (volume 0 without the
+/// header CRC trailer), and like it this must run before `correct_chunk_sizes`.
+/// Zero chunks have no data and are ignored. Results are sorted by volume.
+pub fn check_volume_sizes(chunks: &[Chunk], file_sizes: &HashMap<u16, u64>) -> Vec<VolumeSize> {
+    // Per volume: the furthest end, and whether a placeholder chunk reaches it
+    let mut ends: BTreeMap<u16, (u64, bool)> = BTreeMap::new();
+    for chunk in chunks.iter().filter(|c| (c.flags & CHUNK_ZERO) == 0) {
+        let placeholder = chunk.has_placeholder_length();
+        let end = if placeholder {
+            chunk.offset as u64
+        } else {
+            chunk.offset as u64 + chunk.compressed_length as u64
+        };
+        // A placeholder chunk starting where another chunk ends comes after it
+        let entry = ends.entry(chunk.file).or_insert((end, placeholder));
+        *entry = (*entry).max((end, placeholder));
+    }
+    ends.into_iter()
+        .map(|(file, (expected, placeholder))| VolumeSize {
+            file,
+            actual: file_sizes.get(&file).copied(),
+            expected,
+            exact: !placeholder,
+        })
+        .collect()
+}
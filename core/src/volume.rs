@@ -1,9 +1,11 @@
 use crate::error::{DzipError, Result};
 use crate::reader::{ReadSeek, VolumeSource};
+use crate::source::{ArchiveSource, SharedFile};
 use std::collections::HashMap;
 use std::collections::hash_map::Entry;
 use std::fs::File;
-use std::path::PathBuf;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
 
 /// A volume manager that reads volumes from the filesystem using a base directory and a file list.
 pub struct FileSystemVolumeManager {
@@ -51,10 +53,133 @@ impl VolumeSource for FileSystemVolumeManager {
                 let file_name = &self.file_list[list_index];
                 let path = self.base_dir.join(file_name);
                 log::debug!("Opening volume {}: {}", id, path.display());
-                let file =
-                    File::open(&path).map_err(|e| DzipError::VolumeOpenError(id, e.to_string()))?;
+                let file = File::open(&path).map_err(|e| open_error(id, file_name, &e.into()))?;
                 Ok(e.insert(file))
             }
         }
     }
 }
+
+/// Error for auxiliary volume `id` (named `name`) that could not be opened.
+fn open_error(id: u16, name: &str, e: &DzipError) -> DzipError {
+    match e {
+        DzipError::Io(io) if io.kind() == std::io::ErrorKind::NotFound => {
+            DzipError::MissingVolume {
+                volume: id,
+                name: name.to_string(),
+            }
+        }
+        e => DzipError::VolumeOpenError(id, e.to_string()),
+    }
+}
+
+/// A volume source serving auxiliary volumes from memory.
+///
+/// Useful for archives that were downloaded or embedded rather than stored on disk.
+#[derive(Default)]
+pub struct MemoryVolumeManager {
+    volumes: HashMap<u16, Cursor<Vec<u8>>>,
+}
+
+impl MemoryVolumeManager {
+    /// Creates a manager from auxiliary volume contents, in file list order (Volume 1, Volume 2, ...).
+    pub fn new(volumes: Vec<Vec<u8>>) -> Self {
+        let mut manager = Self::default();
+        for (i, data) in volumes.into_iter().enumerate() {
+            manager.insert((i + 1) as u16, data);
+        }
+        manager
+    }
+
+    /// Adds or replaces the contents of a volume.
+    pub fn insert(&mut self, id: u16, data: Vec<u8>) {
+        self.volumes.insert(id, Cursor::new(data));
+    }
+}
+
+impl VolumeSource for MemoryVolumeManager {
+    fn open_volume(&mut self, id: u16) -> Result<&mut dyn ReadSeek> {
+        match self.volumes.get_mut(&id) {
+            Some(cursor) => Ok(cursor),
+            None => Err(DzipError::VolumeNotFound(id)),
+        }
+    }
+}
+
+/// Auxiliary volumes opened once and shared between threads.
+///
+/// Volumes that could not be opened are remembered and reported when a chunk
+/// from them is requested, like `FileSystemVolumeManager` does.
+pub struct SharedVolumes<S> {
+    /// Each volume, or its name and why it could not be opened
+    volumes: Vec<std::result::Result<S, (String, DzipError)>>,
+}
+
+impl<S: ArchiveSource> SharedVolumes<S> {
+    /// Creates a set from auxiliary volume sources, in file list order (Volume 1, Volume 2, ...).
+    pub fn new(volumes: Vec<S>) -> Self {
+        Self {
+            volumes: volumes.into_iter().map(Ok).collect(),
+        }
+    }
+
+    /// Returns a per-thread volume source reading from the shared volumes.
+    pub fn readers(&self) -> SharedVolumeReaders<'_, S> {
+        SharedVolumeReaders {
+            volumes: self,
+            readers: HashMap::new(),
+        }
+    }
+}
+
+impl SharedVolumes<SharedFile> {
+    /// Opens every volume in `file_list` below `base_dir`.
+    pub fn open(base_dir: &Path, file_list: &[String]) -> Self {
+        Self::open_with(base_dir, file_list, |path| SharedFile::open(path))
+    }
+
+    /// Like `open`, memory-mapping each volume (see `SharedFile::open_mapped`).
+    pub fn open_mapped(base_dir: &Path, file_list: &[String]) -> Self {
+        Self::open_with(base_dir, file_list, |path| SharedFile::open_mapped(path))
+    }
+
+    fn open_with(
+        base_dir: &Path,
+        file_list: &[String],
+        open: fn(&Path) -> Result<SharedFile>,
+    ) -> Self {
+        let volumes = file_list
+            .iter()
+            .map(|name| {
+                let path = base_dir.join(name);
+                log::debug!("Opening shared volume: {}", path.display());
+                open(&path).map_err(|e| (name.clone(), e))
+            })
+            .collect();
+        Self { volumes }
+    }
+}
+
+/// Volume source handed out by `SharedVolumes::readers`.
+pub struct SharedVolumeReaders<'a, S> {
+    volumes: &'a SharedVolumes<S>,
+    readers: HashMap<u16, Box<dyn ReadSeek + Send + 'a>>,
+}
+
+impl<S: ArchiveSource> VolumeSource for SharedVolumeReaders<'_, S> {
+    fn open_volume(&mut self, id: u16) -> Result<&mut dyn ReadSeek> {
+        let volume = id
+            .checked_sub(1)
+            .and_then(|index| self.volumes.volumes.get(index as usize))
+            .ok_or(DzipError::VolumeNotFound(id))?;
+        match self.readers.entry(id) {
+            Entry::Occupied(e) => Ok(e.into_mut().as_mut()),
+            Entry::Vacant(e) => {
+                let source = volume
+                    .as_ref()
+                    .map_err(|(name, e)| open_error(id, name, e))?;
+                Ok(e.insert(source.reader()?).as_mut())
+            }
+        }
+    }
+}
@@ -1,28 +1,100 @@
 use crate::DzipError;
 use crate::error::Result;
 use crate::format::*;
-use byteorder::{LittleEndian, WriteBytesExt};
+use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
 use log::warn;
 use serde::{Deserialize, Serialize};
-use std::io::{Seek, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::str::FromStr;
 
+/// Appends the header CRC trailer (see `HEADER_CRC_MAGIC`) to volume 0.
+///
+/// `header_len` is the length of the header from the DTRZ magic through the end
+/// of the chunk table. Call this once the header and all chunk data of the
+/// volume are written. Returns the CRC.
+pub fn append_header_crc<F: Read + Write + Seek>(file: &mut F, header_len: u64) -> Result<u32> {
+    file.seek(SeekFrom::Start(0))?;
+    let crc = crate::reader::crc32(&mut Read::take(&mut *file, header_len))?;
+    file.seek(SeekFrom::End(0))?;
+    file.write_all(&HEADER_CRC_MAGIC)?;
+    file.write_u32::<LittleEndian>(crc)?;
+    Ok(crc)
+}
+
+/// Counts and names that determine the size of an archive header, known before
+/// any chunk data is compressed.
+///
+/// Every chunk is assumed to belong to exactly one file, as `pack` writes them.
+#[derive(Debug, Clone, Copy)]
+pub struct ArchivePlan<'a> {
+    /// User file names followed by the directories (without the implicit root)
+    pub strings: &'a [String],
+    pub num_files: usize,
+    pub num_chunks: usize,
+    /// Names of the auxiliary volumes, starting with volume 1
+    pub volume_files: &'a [String],
+    /// Whether range settings follow the volume list (archives with DZ chunks)
+    pub range_settings: bool,
+}
+
+/// Size in bytes of the header `DzipWriter::write_header` produces for `plan`,
+/// i.e. the offset of the first chunk in volume 0.
+pub fn compute_header_size(plan: &ArchivePlan) -> u64 {
+    let strings_size =
+        |strings: &[String]| -> u64 { strings.iter().map(|s| s.len() as u64 + 1).sum() };
+    // magic, file count, directory count, version
+    let settings = 4 + 2 + 2 + 1;
+    // per file: dir id and 0xFFFF terminator; per chunk: its id
+    let map = plan.num_files as u64 * 4 + plan.num_chunks as u64 * 2;
+    // archive file count and chunk count
+    let chunk_settings = 2 + 2;
+    let chunks = plan.num_chunks as u64 * CHUNK_ENTRY_SIZE;
+    let range_settings = if plan.range_settings { 10 } else { 0 };
+    settings
+        + strings_size(plan.strings)
+        + map
+        + chunk_settings
+        + chunks
+        + strings_size(plan.volume_files)
+        + range_settings
+}
+
 pub struct DzipWriter<W: Write + Seek> {
     writer: W,
+    endianness: Endianness,
 }
 
 impl<W: Write + Seek> DzipWriter<W> {
     pub fn new(writer: W) -> Self {
-        Self { writer }
+        Self::with_endianness(writer, Endianness::Little)
+    }
+
+    /// Creates a writer that stores header integers in the given byte order.
+    pub fn with_endianness(writer: W, endianness: Endianness) -> Self {
+        Self { writer, endianness }
+    }
+
+    fn write_u16(&mut self, n: u16) -> Result<()> {
+        match self.endianness {
+            Endianness::Little => self.writer.write_u16::<LittleEndian>(n)?,
+            Endianness::Big => self.writer.write_u16::<BigEndian>(n)?,
+        }
+        Ok(())
+    }
+
+    fn write_u32(&mut self, n: u32) -> Result<()> {
+        match self.endianness {
+            Endianness::Little => self.writer.write_u32::<LittleEndian>(n)?,
+            Endianness::Big => self.writer.write_u32::<BigEndian>(n)?,
+        }
+        Ok(())
     }
 
     pub fn write_archive_settings(&mut self, settings: &ArchiveSettings) -> Result<()> {
         log::debug!("Writing archive settings: {:?}", settings);
-        self.writer.write_u32::<LittleEndian>(settings.header)?; // Should be 0x5A525444
-        self.writer
-            .write_u16::<LittleEndian>(settings.num_user_files)?;
-        self.writer
-            .write_u16::<LittleEndian>(settings.num_directories)?;
+        self.write_u32(settings.header)?; // Should be 0x5A525444
+        self.write_u16(settings.num_user_files)?;
+        self.write_u16(settings.num_directories)?;
         self.writer.write_u8(settings.version)?;
         Ok(())
     }
@@ -37,32 +109,43 @@ impl<W: Write + Seek> DzipWriter<W> {
 
     pub fn write_file_chunk_map(&mut self, map: &[(u16, Vec<u16>)]) -> Result<()> {
         for (dir_id, chunks) in map {
-            self.writer.write_u16::<LittleEndian>(*dir_id)?;
+            self.write_u16(*dir_id)?;
             for &chunk_id in chunks {
-                self.writer.write_u16::<LittleEndian>(chunk_id)?;
+                self.write_u16(chunk_id)?;
             }
-            self.writer.write_u16::<LittleEndian>(0xFFFF)?; // Terminator
+            self.write_u16(0xFFFF)?; // Terminator
         }
         Ok(())
     }
 
     pub fn write_chunk_settings(&mut self, settings: &ChunkSettings) -> Result<()> {
-        self.writer
-            .write_u16::<LittleEndian>(settings.num_archive_files)?;
-        self.writer.write_u16::<LittleEndian>(settings.num_chunks)?;
+        self.write_u16(settings.num_archive_files)?;
+        self.write_u16(settings.num_chunks)?;
         Ok(())
     }
 
     pub fn write_chunks(&mut self, chunks: &[Chunk]) -> Result<()> {
         log::debug!("Writing {} chunks", chunks.len());
         for chunk in chunks {
-            self.writer.write_u32::<LittleEndian>(chunk.offset)?;
-            self.writer
-                .write_u32::<LittleEndian>(chunk.compressed_length)?;
-            self.writer
-                .write_u32::<LittleEndian>(chunk.decompressed_length)?;
-            self.writer.write_u16::<LittleEndian>(chunk.flags)?;
-            self.writer.write_u16::<LittleEndian>(chunk.file)?;
+            self.write_u32(chunk.offset)?;
+            self.write_u32(chunk.compressed_length)?;
+            self.write_u32(chunk.decompressed_length)?;
+            self.write_u16(chunk.flags)?;
+            self.write_u16(chunk.file)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the whole header in the layout `DzipReader::read_header` expects.
+    pub fn write_header(&mut self, header: &ArchiveHeader) -> Result<()> {
+        self.write_archive_settings(&header.settings)?;
+        self.write_strings(&header.strings)?;
+        self.write_file_chunk_map(&header.map)?;
+        self.write_chunk_settings(&header.chunk_settings)?;
+        self.write_chunks(&header.chunks)?;
+        self.write_strings(&header.volume_files)?;
+        if let Some(range_settings) = &header.range_settings {
+            self.write_global_settings(range_settings)?;
         }
         Ok(())
     }
@@ -94,6 +177,42 @@ pub enum CompressionMethod {
     Lzma,
     Combuf,
     RandomAccess,
+    /// xz container (LZMA2), stored under `CHUNK_XZ`
+    #[serde(rename = "XZ", alias = "Xz")]
+    Xz,
+    /// gzip-framed deflate, stored under `CHUNK_GZIP`
+    #[serde(rename = "GZIP", alias = "Gzip")]
+    Gzip,
+    /// Brotli, stored under `CHUNK_BROTLI`
+    #[serde(rename = "BROTLI", alias = "Brotli")]
+    Brotli,
+}
+
+impl CompressionMethod {
+    /// Determines the method that best describes a chunk's flags.
+    ///
+    /// Several codec bits resolve by `CODEC_PRECEDENCE`, the same order the
+    /// reader decodes with. The wrappers only count without a codec bit.
+    /// Returns `None` if no known compression flag is set.
+    pub fn from_flags(flags: u16) -> Option<Self> {
+        let method = match primary_codec(flags) {
+            Some(CHUNK_ZERO) => CompressionMethod::Zero,
+            Some(CHUNK_COPYCOMP) => CompressionMethod::Copy,
+            Some(CHUNK_MP3) => CompressionMethod::Mp3,
+            Some(CHUNK_JPEG) => CompressionMethod::Jpeg,
+            Some(CHUNK_ZLIB) => CompressionMethod::Zlib,
+            Some(CHUNK_BZIP) => CompressionMethod::Bzip,
+            Some(CHUNK_LZMA) => CompressionMethod::Lzma,
+            Some(CHUNK_XZ) => CompressionMethod::Xz,
+            Some(CHUNK_GZIP) => CompressionMethod::Gzip,
+            Some(CHUNK_BROTLI) => CompressionMethod::Brotli,
+            Some(_) => CompressionMethod::Dz,
+            None if (flags & CHUNK_COMBUF) != 0 => CompressionMethod::Combuf,
+            None if (flags & CHUNK_RANDOMACCESS) != 0 => CompressionMethod::RandomAccess,
+            None => return None,
+        };
+        Some(method)
+    }
 }
 
 impl FromStr for CompressionMethod {
@@ -109,6 +228,9 @@ impl FromStr for CompressionMethod {
             "mp3" => Ok(CompressionMethod::Mp3),
             "jpeg" | "jpg" => Ok(CompressionMethod::Jpeg),
             "lzma" => Ok(CompressionMethod::Lzma),
+            "xz" => Ok(CompressionMethod::Xz),
+            "gzip" | "gz" => Ok(CompressionMethod::Gzip),
+            "brotli" | "br" => Ok(CompressionMethod::Brotli),
             "combuf" => Ok(CompressionMethod::Combuf),
             "randomaccess" => Ok(CompressionMethod::RandomAccess),
             _ => Err(DzipError::Io(std::io::Error::other(format!(
@@ -119,35 +241,334 @@ impl FromStr for CompressionMethod {
     }
 }
 
-pub fn compress_data(data: &[u8], method: CompressionMethod) -> Result<(u16, Vec<u8>)> {
-    match method {
-        CompressionMethod::Copy => Ok((CHUNK_COPYCOMP, data.to_vec())),
-        CompressionMethod::Zero => Ok((CHUNK_ZERO, Vec::new())), // Zero chunk has 0 compressed size
-        CompressionMethod::Zlib => {
+/// Encoder settings for LZMA chunks.
+///
+/// LZMA streams carry their parameters in their own 13-byte header, so they
+/// need no space in the archive header (`RangeSettings` only applies to DZ).
+/// The bundled encoder always codes with `lc = 3`, `lp = 0`, `pb = 2`, so only
+/// the dictionary size is configurable. The encoder emits literals only, so
+/// `dict_size` does not change the compressed size, but it is the window a
+/// decoder allocates, which matters on memory-constrained targets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LzmaOptions {
+    /// Dictionary size in bytes (at least 4096)
+    pub dict_size: u32,
+}
+
+impl Default for LzmaOptions {
+    fn default() -> Self {
+        Self {
+            dict_size: 8 * 1024 * 1024,
+        }
+    }
+}
+
+impl LzmaOptions {
+    /// Smallest dictionary the LZMA format allows.
+    pub const MIN_DICT_SIZE: u32 = 4096;
+
+    /// Checks that the encoder can produce a stream with these parameters.
+    pub fn validate(&self) -> Result<()> {
+        if self.dict_size < Self::MIN_DICT_SIZE {
+            return Err(DzipError::Generic(format!(
+                "LZMA dictionary size {} is below the minimum of {} bytes",
+                self.dict_size,
+                Self::MIN_DICT_SIZE
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Compresses `data` into an LZMA-alone stream (as stored under `CHUNK_LZMA`).
+pub fn compress_lzma(data: &[u8], options: &LzmaOptions) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    compress_stream(
+        &mut &data[..],
+        &mut output,
+        CompressionMethod::Lzma,
+        options,
+        &BrotliOptions::default(),
+    )?;
+    Ok(output)
+}
+
+/// Encoder settings for Brotli chunks.
+///
+/// Brotli streams need no parameters to decode, so these only trade packing
+/// time for size and never affect the archive header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BrotliOptions {
+    /// Compression level, 0 (fastest) to 11 (smallest)
+    pub quality: u32,
+}
+
+impl Default for BrotliOptions {
+    fn default() -> Self {
+        Self {
+            quality: Self::MAX_QUALITY,
+        }
+    }
+}
+
+impl BrotliOptions {
+    /// Highest (slowest, smallest) quality level.
+    pub const MAX_QUALITY: u32 = 11;
+    /// Base-2 log of the sliding window; the encoder's default.
+    const WINDOW_BITS: i32 = 22;
+
+    /// Checks that the quality level is one the encoder supports.
+    pub fn validate(&self) -> Result<()> {
+        if self.quality > Self::MAX_QUALITY {
+            return Err(DzipError::Generic(format!(
+                "Brotli quality {} is out of range (0 to {})",
+                self.quality,
+                Self::MAX_QUALITY
+            )));
+        }
+        Ok(())
+    }
+
+    /// Encodes everything `reader` yields into `writer` as one Brotli stream.
+    fn compress(&self, mut reader: impl Read, mut writer: impl Write) -> Result<()> {
+        self.validate()?;
+        let params = brotli::enc::BrotliEncoderParams {
+            quality: self.quality as i32,
+            lgwin: Self::WINDOW_BITS,
+            ..Default::default()
+        };
+        brotli::BrotliCompress(&mut reader, &mut writer, &params)?;
+        Ok(())
+    }
+}
+
+/// Compresses `data` into a Brotli stream (as stored under `CHUNK_BROTLI`).
+pub fn compress_brotli(data: &[u8], options: &BrotliOptions) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    options.compress(data, &mut output)?;
+    Ok(output)
+}
+
+/// Sizes of a chunk written by `compress_stream`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamedChunk {
+    /// Chunk flags for the codec that was used
+    pub flags: u16,
+    /// Bytes read from the input (the decompressed length)
+    pub input_len: u64,
+    /// Bytes written to the output (the compressed length)
+    pub output_len: u64,
+}
+
+/// Counts the bytes passing through a reader or writer. A writer can also
+/// overwrite four bytes at a fixed stream position on the way through, which
+/// patches the dictionary size into an LZMA header that is already streaming out.
+pub(crate) struct Counting<T> {
+    inner: T,
+    pub(crate) count: u64,
+    patch: Option<(u64, [u8; 4])>,
+}
+
+impl<T> Counting<T> {
+    pub(crate) fn new(inner: T) -> Self {
+        Self {
+            inner,
+            count: 0,
+            patch: None,
+        }
+    }
+}
+
+impl<R: Read> Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+impl<W: Write> Write for Counting<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let range = self.count..self.count + buf.len() as u64;
+        let n = match self.patch {
+            Some((at, bytes)) if range.start < at + 4 && at < range.end => {
+                let mut patched = buf.to_vec();
+                for (pos, byte) in (at..).zip(bytes) {
+                    if range.contains(&pos) {
+                        patched[(pos - range.start) as usize] = byte;
+                    }
+                }
+                self.inner.write(&patched)?
+            }
+            _ => self.inner.write(buf)?,
+        };
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Compresses everything `reader` yields straight into `writer`, so memory use
+/// is bounded by the codec's own buffers rather than the input size.
+///
+/// This is the one place each codec is driven from; `compress_data` and
+/// `compress_lzma` run it over a buffer, and `compress_brotli` shares the
+/// encoder settings. The lengths for the chunk table are only known once it
+/// returns.
+pub fn compress_stream(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    method: CompressionMethod,
+    lzma: &LzmaOptions,
+    brotli: &BrotliOptions,
+) -> Result<StreamedChunk> {
+    let mut input = std::io::BufReader::new(Counting::new(reader));
+    let mut output = Counting::new(writer);
+    let flags = match method {
+        CompressionMethod::Zero => {
+            std::io::copy(&mut input, &mut std::io::sink())?;
+            CHUNK_ZERO
+        }
+        CompressionMethod::Zlib | CompressionMethod::Gzip => {
             use flate2::Compression;
             use flate2::write::GzEncoder;
-            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
-            encoder.write_all(data).map_err(DzipError::Io)?;
-            Ok((CHUNK_ZLIB, encoder.finish().map_err(DzipError::Io)?))
+            let mut encoder = GzEncoder::new(&mut output, Compression::default());
+            std::io::copy(&mut input, &mut encoder)?;
+            encoder.finish()?;
+            if method == CompressionMethod::Zlib {
+                CHUNK_ZLIB
+            } else {
+                CHUNK_GZIP
+            }
         }
         CompressionMethod::Bzip => {
             use bzip2::Compression;
             use bzip2::write::BzEncoder;
-            let mut encoder = BzEncoder::new(Vec::new(), Compression::default());
-            encoder.write_all(data).map_err(DzipError::Io)?;
-            Ok((CHUNK_BZIP, encoder.finish().map_err(DzipError::Io)?))
+            let mut encoder = BzEncoder::new(&mut output, Compression::default());
+            std::io::copy(&mut input, &mut encoder)?;
+            encoder.finish()?;
+            CHUNK_BZIP
         }
         CompressionMethod::Lzma => {
-            // lzma-rs
-            let mut output = Vec::new();
-            lzma_rs::lzma_compress(&mut std::io::Cursor::new(data), &mut output)
+            lzma.validate()?;
+            // Header: properties byte, then the little-endian dictionary size
+            output.patch = Some((1, lzma.dict_size.to_le_bytes()));
+            lzma_rs::lzma_compress(&mut input, &mut output)
                 .map_err(|e| DzipError::Io(std::io::Error::other(e)))?;
-            Ok((CHUNK_LZMA, output))
+            CHUNK_LZMA
+        }
+        CompressionMethod::Xz => {
+            lzma_rs::xz_compress(&mut input, &mut output)?;
+            CHUNK_XZ
+        }
+        CompressionMethod::Brotli => {
+            brotli.compress(&mut input, &mut output)?;
+            CHUNK_BROTLI
+        }
+        CompressionMethod::Copy => {
+            std::io::copy(&mut input, &mut output)?;
+            CHUNK_COPYCOMP
         }
         // Fallback to Copy for unsupported types
         _ => {
             warn!("Unsupported compression {:?}, using Copy", method);
-            Ok((CHUNK_COPYCOMP, data.to_vec()))
+            std::io::copy(&mut input, &mut output)?;
+            CHUNK_COPYCOMP
         }
+    };
+    Ok(StreamedChunk {
+        flags,
+        input_len: input.into_inner().count,
+        output_len: output.count,
+    })
+}
+
+/// `compress_stream` with the default encoder settings: the streaming
+/// counterpart of `compress_data`. Returns the chunk flags and the number of
+/// bytes written to `writer`.
+pub fn compress_into(
+    mut reader: impl Read,
+    mut writer: impl Write,
+    method: CompressionMethod,
+) -> Result<(u16, u64)> {
+    let streamed = compress_stream(
+        &mut reader,
+        &mut writer,
+        method,
+        &LzmaOptions::default(),
+        &BrotliOptions::default(),
+    )?;
+    Ok((streamed.flags, streamed.output_len))
+}
+
+/// Chunk flags that only change how the runtime buffers a chunk; the data is
+/// the stream of the codec flag set next to them.
+pub const WRAPPER_FLAGS: u16 = CHUNK_COMBUF | CHUNK_RANDOMACCESS;
+
+/// Splits chunk flags into the encoder for their codec bit and the wrapper bits
+/// to keep on top of the encoder's flags. Unknown bits (`UNKNOWN_FLAGS`) are
+/// kept along with the wrappers, so vendor-specific flags survive a repack.
+///
+/// Without a codec bit the data is stored (RANDOMACCESS alone), except that
+/// COMBUF needs one: readers would otherwise have to guess the inner stream.
+/// Fails for several codec bits, or for codecs without an encoder (e.g. DZ).
+pub fn method_for_flags(flags: u16) -> Result<(CompressionMethod, u16)> {
+    let wrappers = flags & (WRAPPER_FLAGS | UNKNOWN_FLAGS);
+    let codec = flags & !(WRAPPER_FLAGS | UNKNOWN_FLAGS);
+    let invalid = |reason: &str| {
+        DzipError::InvalidSettings(format!(
+            "Chunk flags {} {}",
+            flag_names(flags).join("|"),
+            reason
+        ))
+    };
+    if codec.count_ones() > 1 {
+        return Err(invalid("set more than one codec"));
+    }
+    if codec == 0 {
+        if (wrappers & CHUNK_COMBUF) != 0 {
+            return Err(invalid("need a codec inside COMBUF"));
+        }
+        return Ok((CompressionMethod::Copy, wrappers));
+    }
+    match CompressionMethod::from_flags(codec) {
+        Some(
+            method @ (CompressionMethod::Zlib
+            | CompressionMethod::Bzip
+            | CompressionMethod::Copy
+            | CompressionMethod::Zero
+            | CompressionMethod::Lzma
+            | CompressionMethod::Xz
+            | CompressionMethod::Gzip
+            | CompressionMethod::Brotli),
+        ) => Ok((method, wrappers)),
+        _ => Err(invalid("use a codec without an encoder")),
     }
 }
+
+/// Compresses `data` for a chunk with the given flags (see `method_for_flags`),
+/// e.g. `CHUNK_COMBUF | CHUNK_ZLIB` for a zlib stream inside a combuf.
+pub fn compress_with_flags(data: &[u8], flags: u16) -> Result<(u16, Vec<u8>)> {
+    let (method, wrappers) = method_for_flags(flags)?;
+    let (codec_flags, output) = compress_data(data, method)?;
+    Ok((wrappers | codec_flags, output))
+}
+
+/// Compresses `data` in memory: `compress_stream` with the default encoder
+/// settings, writing to a `Vec`.
+pub fn compress_data(data: &[u8], method: CompressionMethod) -> Result<(u16, Vec<u8>)> {
+    let mut output = Vec::new();
+    let streamed = compress_stream(
+        &mut &data[..],
+        &mut output,
+        method,
+        &LzmaOptions::default(),
+        &BrotliOptions::default(),
+    )?;
+    Ok((streamed.flags, output))
+}
@@ -0,0 +1,122 @@
+//! XOR obfuscation of chunk data, as used by some shipped games.
+//!
+//! Such archives store each chunk's (compressed) bytes XORed with a short
+//! repeating key that restarts at every chunk. The header is not obfuscated.
+//! This is obfuscation, not encryption: anyone with the key, or with a few
+//! known plaintext bytes such as a zlib header, can undo it.
+
+use crate::error::{DzipError, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+/// A non-empty repeating XOR key, written as hex (e.g. `"5a3c"`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct XorKey(Vec<u8>);
+
+impl XorKey {
+    pub fn new(key: Vec<u8>) -> Result<Self> {
+        if key.is_empty() {
+            return Err(DzipError::InvalidSettings(
+                "XOR key must not be empty".to_string(),
+            ));
+        }
+        Ok(Self(key))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// XORs `data` in place, starting at the beginning of the key. Applying
+    /// it twice restores the data.
+    pub fn apply(&self, data: &mut [u8]) {
+        self.apply_at(data, 0);
+    }
+
+    /// Like `apply`, for bytes that start `pos` bytes into the chunk.
+    pub fn apply_at(&self, data: &mut [u8], pos: u64) {
+        let start = (pos % self.0.len() as u64) as usize;
+        for (byte, key) in data.iter_mut().zip(self.0.iter().cycle().skip(start)) {
+            *byte ^= key;
+        }
+    }
+
+    /// Wraps `inner` so that everything written to it is XORed, starting at
+    /// the beginning of the key. Use one writer per chunk.
+    pub fn writer<W: Write>(&self, inner: W) -> XorWriter<'_, W> {
+        XorWriter {
+            inner,
+            key: self,
+            pos: 0,
+        }
+    }
+}
+
+impl FromStr for XorKey {
+    type Err = DzipError;
+
+    /// Parses hex digits, with an optional `0x` prefix.
+    fn from_str(s: &str) -> Result<Self> {
+        let hex = s
+            .strip_prefix("0x")
+            .or_else(|| s.strip_prefix("0X"))
+            .unwrap_or(s);
+        let invalid = || DzipError::InvalidSettings(format!("Invalid XOR key '{}'", s));
+        if !hex.len().is_multiple_of(2) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let key = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid()))
+            .collect::<Result<Vec<u8>>>()?;
+        Self::new(key)
+    }
+}
+
+impl fmt::Display for XorKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for XorKey {
+    type Error = DzipError;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<XorKey> for String {
+    fn from(key: XorKey) -> Self {
+        key.to_string()
+    }
+}
+
+/// Writer returned by `XorKey::writer`.
+pub struct XorWriter<'a, W: Write> {
+    inner: W,
+    key: &'a XorKey,
+    pos: u64,
+}
+
+impl<W: Write> Write for XorWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut data = buf.to_vec();
+        self.key.apply_at(&mut data, self.pos);
+        // Only count what the inner writer took, so the key stays aligned
+        let written = self.inner.write(&data)?;
+        self.pos += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,124 @@
+use dzip_core::format::*;
+use dzip_core::reader::correct_chunk_sizes;
+use std::collections::HashMap;
+
+/// Straightforward single-threaded version of the correction rules.
+fn reference(chunks: &mut [Chunk], file_sizes: &HashMap<u16, u64>) {
+    let original = chunks.to_vec();
+    for (idx, chunk) in chunks.iter_mut().enumerate() {
+        let mut same_file: Vec<usize> = (0..original.len())
+            .filter(|&i| original[i].file == chunk.file)
+            .collect();
+        same_file.sort_by_key(|&i| original[i].offset);
+        let pos = same_file.iter().position(|&i| i == idx).unwrap();
+        let limit = match same_file.get(pos + 1) {
+            Some(&next) => original[next].offset as u64,
+            None => *file_sizes.get(&chunk.file).unwrap_or(&0),
+        };
+        let available = limit.saturating_sub(chunk.offset as u64) as u32;
+        let is_compressed = (chunk.flags & (CHUNK_ZLIB | CHUNK_LZMA)) != 0;
+        if (is_compressed && chunk.compressed_length == chunk.decompressed_length)
+            || chunk.compressed_length > available
+        {
+            chunk.compressed_length = available;
+        }
+    }
+}
+
+/// Builds `count` chunks spread round-robin over `volumes` volumes, with a mix
+/// of correct, placeholder and oversized lengths and some shared offsets.
+fn synthetic_chunks(count: usize, volumes: u16) -> (Vec<Chunk>, HashMap<u16, u64>) {
+    let mut next_offset = vec![0u32; volumes as usize];
+    let mut chunks = Vec::with_capacity(count);
+    for i in 0..count {
+        let file = (i % volumes as usize) as u16;
+        let size = 16 + (i as u32 * 7919) % 500;
+        let offset = next_offset[file as usize];
+        // Every 97th chunk repeats the previous offset of its volume
+        if i % 97 != 0 {
+            next_offset[file as usize] += size;
+        }
+        let (compressed_length, decompressed_length, flags) = match i % 3 {
+            0 => (size, size * 3, CHUNK_ZLIB),
+            1 => (size * 4, size * 4, CHUNK_LZMA),
+            _ => (size + 10, size + 10, CHUNK_COPYCOMP),
+        };
+        chunks.push(Chunk {
+            offset,
+            compressed_length,
+            decompressed_length,
+            flags,
+            file,
+        });
+    }
+    let file_sizes = next_offset
+        .iter()
+        .enumerate()
+        .map(|(file, &end)| (file as u16, end as u64))
+        .collect();
+    (chunks, file_sizes)
+}
+
+#[test]
+fn test_correct_chunk_sizes_matches_reference() {
+    let (chunks, file_sizes) = synthetic_chunks(3_000, 7);
+    let mut expected = chunks.clone();
+    reference(&mut expected, &file_sizes);
+
+    let mut actual = chunks;
+    correct_chunk_sizes(&mut actual, &file_sizes);
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_correct_chunk_sizes_large_archive_is_deterministic() {
+    let (chunks, file_sizes) = synthetic_chunks(50_000, 40);
+
+    let mut first = chunks.clone();
+    correct_chunk_sizes(&mut first, &file_sizes);
+
+    for _ in 0..3 {
+        let mut again = chunks.clone();
+        correct_chunk_sizes(&mut again, &file_sizes);
+        assert_eq!(again, first);
+    }
+}
+
+#[test]
+fn test_effective_compressed_length() {
+    let chunk = |compressed_length, decompressed_length, flags| Chunk {
+        offset: 100,
+        compressed_length,
+        decompressed_length,
+        flags,
+        file: 0,
+    };
+
+    // A consistent length is kept, an oversized one is clamped
+    assert_eq!(
+        chunk(30, 80, CHUNK_ZLIB).effective_compressed_length(Some(150), 1000),
+        30
+    );
+    assert_eq!(
+        chunk(80, 200, CHUNK_ZLIB).effective_compressed_length(Some(150), 1000),
+        50
+    );
+    assert_eq!(
+        chunk(80, 80, CHUNK_COPYCOMP).effective_compressed_length(None, 120),
+        20
+    );
+
+    // A placeholder takes all the space up to the next chunk or the end of the volume
+    let placeholder = chunk(40, 40, CHUNK_LZMA);
+    assert!(placeholder.has_placeholder_length());
+    assert!(!chunk(40, 40, CHUNK_COPYCOMP).has_placeholder_length());
+    assert_eq!(placeholder.effective_compressed_length(Some(150), 1000), 50);
+    assert_eq!(placeholder.effective_compressed_length(None, 300), 200);
+
+    // Nothing is left past the limit
+    assert_eq!(placeholder.effective_compressed_length(Some(90), 1000), 0);
+    assert_eq!(
+        chunk(30, 80, CHUNK_ZLIB).effective_compressed_length(None, 50),
+        0
+    );
+}
@@ -0,0 +1,475 @@
+use dzip_core::format::*;
+use dzip_core::reader::{DzipReader, decoder_name, has_jpeg_markers};
+use dzip_core::volume::MemoryVolumeManager;
+use dzip_core::writer::{compress_brotli, compress_lzma};
+use dzip_core::{
+    BrotliOptions, CompressionMethod, DzipError, LzmaOptions, XorKey, compress_data, compress_into,
+    compress_stream, compress_with_flags,
+};
+use std::io::Cursor;
+
+const SAMPLE: &[u8] =
+    b"The quick brown fox jumps over the lazy dog. The quick brown fox jumps again.";
+
+/// Reads `data` back as a single chunk stored at offset 0 with the given flags.
+fn decode(data: &[u8], flags: u16, decompressed_length: usize) -> dzip_core::Result<Vec<u8>> {
+    let chunk = Chunk {
+        offset: 0,
+        compressed_length: data.len() as u32,
+        decompressed_length: decompressed_length as u32,
+        flags,
+        file: 0,
+    };
+    DzipReader::new(Cursor::new(data)).read_chunk_data(&chunk)
+}
+
+#[test]
+fn test_combuf_with_inner_flag() {
+    let (_, compressed) = compress_data(SAMPLE, CompressionMethod::Bzip).unwrap();
+    let data = decode(&compressed, CHUNK_COMBUF | CHUNK_BZIP, SAMPLE.len()).unwrap();
+    assert_eq!(data, SAMPLE);
+}
+
+#[test]
+fn test_combuf_detects_inner_stream() {
+    for method in [
+        CompressionMethod::Zlib,
+        CompressionMethod::Bzip,
+        CompressionMethod::Lzma,
+    ] {
+        let (_, compressed) = compress_data(SAMPLE, method).unwrap();
+        let data = decode(&compressed, CHUNK_COMBUF, SAMPLE.len()).unwrap();
+        assert_eq!(data, SAMPLE, "inner method {:?}", method);
+    }
+}
+
+#[test]
+fn test_combuf_unsupported_inner() {
+    let err = decode(SAMPLE, CHUNK_COMBUF | CHUNK_DZ, SAMPLE.len()).unwrap_err();
+    assert!(matches!(err, DzipError::UnsupportedCompression(0x5)));
+
+    let err = decode(SAMPLE, CHUNK_COMBUF, SAMPLE.len()).unwrap_err();
+    assert!(matches!(err, DzipError::UnsupportedCompression(0x1)));
+}
+
+#[test]
+fn test_jpeg_passthrough() {
+    let jpeg = [0xFF, 0xD8, 0x01, 0x02, 0xFF, 0xD9];
+    assert_eq!(decode(&jpeg, CHUNK_JPEG, jpeg.len()).unwrap(), jpeg);
+    assert!(has_jpeg_markers(&jpeg));
+
+    // Stored as-is even without markers; only the marker check flags it
+    assert_eq!(decode(SAMPLE, CHUNK_JPEG, SAMPLE.len()).unwrap(), SAMPLE);
+    assert!(!has_jpeg_markers(SAMPLE));
+    assert!(!has_jpeg_markers(&[0xFF, 0xD8, 0xD9]));
+}
+
+#[test]
+fn test_xz_roundtrip() {
+    let (flags, compressed) = compress_data(SAMPLE, CompressionMethod::Xz).unwrap();
+    assert_eq!(flags, CHUNK_XZ);
+    assert_eq!(
+        dzip_core::reader::detect_stream_flags(&compressed),
+        Some(CHUNK_XZ)
+    );
+    assert_eq!(
+        CompressionMethod::from_flags(flags),
+        Some(CompressionMethod::Xz)
+    );
+    assert_eq!(decode(&compressed, CHUNK_XZ, SAMPLE.len()).unwrap(), SAMPLE);
+    assert_eq!(
+        decode(&compressed, CHUNK_COMBUF, SAMPLE.len()).unwrap(),
+        SAMPLE
+    );
+    assert_eq!(
+        "XZ".parse::<CompressionMethod>().unwrap(),
+        CompressionMethod::Xz
+    );
+}
+
+#[test]
+fn test_gzip_roundtrip() {
+    assert_eq!(CHUNK_GZIP & CHUNK_ZLIB, 0);
+    let (flags, compressed) = compress_data(SAMPLE, CompressionMethod::Gzip).unwrap();
+    assert_eq!(flags, CHUNK_GZIP);
+    assert!(compressed.starts_with(&[0x1f, 0x8b]));
+    assert_eq!(
+        CompressionMethod::from_flags(flags),
+        Some(CompressionMethod::Gzip)
+    );
+    assert_eq!(
+        decode(&compressed, CHUNK_GZIP, SAMPLE.len()).unwrap(),
+        SAMPLE
+    );
+    assert_eq!(decoder_name(CHUNK_GZIP), Some("GZIP"));
+    assert_eq!(flag_names(CHUNK_GZIP), ["GZIP"]);
+    assert_eq!(
+        "GZIP".parse::<CompressionMethod>().unwrap(),
+        CompressionMethod::Gzip
+    );
+
+    // A Zlib chunk is tagged ZLIB but carries the same gzip stream; GZIP only
+    // differs in the tag, and does not take the deflate data without the
+    // 10-byte gzip header
+    let (flags, zlib) = compress_data(SAMPLE, CompressionMethod::Zlib).unwrap();
+    assert_eq!((flags, &zlib), (CHUNK_ZLIB, &compressed));
+    assert!(decode(&zlib[10..], CHUNK_GZIP, SAMPLE.len()).is_err());
+}
+
+#[test]
+fn test_brotli_roundtrip() {
+    // Every named flag is a distinct single bit
+    let mut seen = 0u16;
+    for (bit, name) in CHUNK_FLAG_NAMES {
+        assert_eq!(bit.count_ones(), 1, "{}", name);
+        assert_eq!(seen & bit, 0, "{} overlaps another flag", name);
+        seen |= bit;
+    }
+
+    let (flags, compressed) = compress_data(SAMPLE, CompressionMethod::Brotli).unwrap();
+    assert_eq!(flags, CHUNK_BROTLI);
+    assert_eq!(
+        CompressionMethod::from_flags(flags),
+        Some(CompressionMethod::Brotli)
+    );
+    assert_eq!(
+        decode(&compressed, CHUNK_BROTLI, SAMPLE.len()).unwrap(),
+        SAMPLE
+    );
+    assert_eq!(
+        decode(&compressed, CHUNK_COMBUF | CHUNK_BROTLI, SAMPLE.len()).unwrap(),
+        SAMPLE
+    );
+    assert_eq!(decoder_name(CHUNK_BROTLI), Some("BROTLI"));
+    assert_eq!(flag_names(CHUNK_BROTLI), ["BROTLI"]);
+    assert_eq!(encode_flags(&["brotli"]).unwrap(), CHUNK_BROTLI);
+    assert_eq!(
+        "BROTLI".parse::<CompressionMethod>().unwrap(),
+        CompressionMethod::Brotli
+    );
+
+    // Lower qualities trade size for speed and still decode
+    let text = SAMPLE.repeat(50);
+    let fast = compress_brotli(&text, &BrotliOptions { quality: 0 }).unwrap();
+    assert_eq!(decode(&fast, CHUNK_BROTLI, text.len()).unwrap(), text);
+    let best = compress_brotli(&text, &BrotliOptions::default()).unwrap();
+    assert!(best.len() <= fast.len());
+    assert!(compress_brotli(SAMPLE, &BrotliOptions { quality: 12 }).is_err());
+}
+
+#[test]
+fn test_decoder_name_priority() {
+    assert_eq!(decoder_name(CHUNK_ZLIB), Some("ZLIB"));
+    assert_eq!(decoder_name(CHUNK_DZ | CHUNK_ZLIB), Some("ZLIB"));
+    assert_eq!(decoder_name(CHUNK_COPYCOMP | CHUNK_LZMA), Some("COPY"));
+    assert_eq!(decoder_name(CHUNK_MP3), Some("COPY"));
+    assert_eq!(decoder_name(CHUNK_RANDOMACCESS), Some("COPY"));
+    assert_eq!(decoder_name(CHUNK_RANDOMACCESS | CHUNK_BZIP), Some("BZIP"));
+    assert_eq!(decoder_name(CHUNK_ZERO | CHUNK_ZLIB), Some("ZERO"));
+    assert_eq!(decoder_name(CHUNK_COMBUF | CHUNK_XZ), Some("XZ"));
+    assert_eq!(decoder_name(CHUNK_COMBUF), Some("COMBUF"));
+    assert_eq!(decoder_name(CHUNK_DZ), None);
+    assert_eq!(decoder_name(0), None);
+
+    // Decoding follows the same choice: DZ|ZLIB data is read as zlib
+    let (_, compressed) = compress_data(SAMPLE, CompressionMethod::Zlib).unwrap();
+    assert_eq!(
+        decode(&compressed, CHUNK_DZ | CHUNK_ZLIB, SAMPLE.len()).unwrap(),
+        SAMPLE
+    );
+}
+
+#[test]
+fn test_combined_flags_follow_codec_precedence() {
+    // The method recorded for a chunk must be the codec it is decoded with
+    let decoded_as = |method| match method {
+        CompressionMethod::Copy | CompressionMethod::Mp3 => Some("COPY"),
+        CompressionMethod::Jpeg => Some("JPEG"),
+        CompressionMethod::Zero => Some("ZERO"),
+        CompressionMethod::Zlib => Some("ZLIB"),
+        CompressionMethod::Bzip => Some("BZIP"),
+        CompressionMethod::Lzma => Some("LZMA"),
+        CompressionMethod::Xz => Some("XZ"),
+        CompressionMethod::Gzip => Some("GZIP"),
+        CompressionMethod::Brotli => Some("BROTLI"),
+        _ => None,
+    };
+    for (i, &first) in CODEC_PRECEDENCE.iter().enumerate() {
+        for &second in &CODEC_PRECEDENCE[i..] {
+            for wrapper in [0, CHUNK_RANDOMACCESS] {
+                let flags = first | second | wrapper;
+                assert_eq!(primary_codec(flags), Some(first), "{:#x}", flags);
+                let method = CompressionMethod::from_flags(flags).unwrap();
+                assert_eq!(decoded_as(method), decoder_name(flags), "{:#x}", flags);
+                assert_eq!(encode_flags(&flag_names(flags)).unwrap(), flags);
+            }
+        }
+    }
+    assert_eq!(
+        CompressionMethod::from_flags(CHUNK_COPYCOMP | CHUNK_ZLIB),
+        Some(CompressionMethod::Copy)
+    );
+    assert_eq!(
+        CompressionMethod::from_flags(CHUNK_DZ | CHUNK_ZLIB),
+        Some(CompressionMethod::Zlib)
+    );
+    assert_eq!(
+        CompressionMethod::from_flags(CHUNK_COMBUF | CHUNK_DZ),
+        Some(CompressionMethod::Dz)
+    );
+    assert_eq!(
+        CompressionMethod::from_flags(CHUNK_COMBUF),
+        Some(CompressionMethod::Combuf)
+    );
+    assert_eq!(primary_codec(CHUNK_COMBUF | CHUNK_RANDOMACCESS), None);
+}
+
+#[test]
+fn test_random_access_chunks() {
+    // Stored data comes back as-is, a codec bit decodes with that codec
+    assert_eq!(
+        decode(SAMPLE, CHUNK_RANDOMACCESS, SAMPLE.len()).unwrap(),
+        SAMPLE
+    );
+    let (_, lzma) = compress_data(SAMPLE, CompressionMethod::Lzma).unwrap();
+    assert_eq!(
+        decode(&lzma, CHUNK_RANDOMACCESS | CHUNK_LZMA, SAMPLE.len()).unwrap(),
+        SAMPLE
+    );
+
+    // Without a codec bit, a compressed stream is detected instead of copied
+    let (_, zlib) = compress_data(SAMPLE, CompressionMethod::Zlib).unwrap();
+    assert_eq!(
+        decode(&zlib, CHUNK_RANDOMACCESS, SAMPLE.len()).unwrap(),
+        SAMPLE
+    );
+}
+
+#[test]
+fn test_compress_stream_matches_compress_data() {
+    for method in [
+        CompressionMethod::Copy,
+        CompressionMethod::Zero,
+        CompressionMethod::Zlib,
+        CompressionMethod::Gzip,
+        CompressionMethod::Bzip,
+        CompressionMethod::Lzma,
+        CompressionMethod::Xz,
+        CompressionMethod::Brotli,
+    ] {
+        let mut output = Vec::new();
+        let streamed = compress_stream(
+            &mut &SAMPLE[..],
+            &mut output,
+            method,
+            &LzmaOptions::default(),
+            &BrotliOptions::default(),
+        )
+        .unwrap();
+        let (flags, expected) = compress_data(SAMPLE, method).unwrap();
+        assert_eq!(output, expected, "{:?}", method);
+        assert_eq!(streamed.flags, flags);
+        assert_eq!(streamed.input_len, SAMPLE.len() as u64);
+        assert_eq!(streamed.output_len, expected.len() as u64);
+
+        let mut output = Vec::new();
+        let (into_flags, written) = compress_into(SAMPLE, &mut output, method).unwrap();
+        assert_eq!(output, expected, "{:?}", method);
+        assert_eq!((into_flags, written), (flags, expected.len() as u64));
+    }
+
+    // The dictionary size is patched into the header as it streams out
+    let options = LzmaOptions { dict_size: 1 << 16 };
+    let mut output = Vec::new();
+    compress_stream(
+        &mut &SAMPLE[..],
+        &mut output,
+        CompressionMethod::Lzma,
+        &options,
+        &BrotliOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(output, compress_lzma(SAMPLE, &options).unwrap());
+}
+
+#[test]
+fn test_unimplemented_flags_are_not_copied() {
+    // Compressed data must not come back as if it were stored
+    let (_, compressed) = compress_data(SAMPLE, CompressionMethod::Lzma).unwrap();
+    for flags in [
+        0,
+        CHUNK_DZ,
+        CHUNK_DZ | CHUNK_RANDOMACCESS,
+        CHUNK_COMBUF | CHUNK_DZ,
+        0x4000,
+        0x4000 | CHUNK_RANDOMACCESS | CHUNK_DZ,
+    ] {
+        let err = decode(&compressed, flags, SAMPLE.len()).unwrap_err();
+        assert!(
+            matches!(err, DzipError::UnsupportedCompression(f) if f == flags),
+            "flags {:#x}: {:?}",
+            flags,
+            err
+        );
+    }
+}
+
+#[test]
+fn test_lzma_dict_size() {
+    let options = LzmaOptions {
+        dict_size: 0x0001_2345,
+    };
+    let compressed = compress_lzma(SAMPLE, &options).unwrap();
+    // Properties (lc=3, lp=0, pb=2), little-endian dictionary size, unknown size
+    assert_eq!(
+        &compressed[..13],
+        &[
+            0x5d, 0x45, 0x23, 0x01, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff
+        ]
+    );
+    assert_eq!(
+        decode(&compressed, CHUNK_LZMA, SAMPLE.len()).unwrap(),
+        SAMPLE
+    );
+
+    // The default matches plain compress_data, with an 8 MiB dictionary
+    let (_, default) = compress_data(SAMPLE, CompressionMethod::Lzma).unwrap();
+    assert_eq!(&default[1..5], &[0x00, 0x00, 0x80, 0x00]);
+    assert_eq!(
+        compress_lzma(SAMPLE, &LzmaOptions::default()).unwrap(),
+        default
+    );
+
+    assert!(compress_lzma(SAMPLE, &LzmaOptions { dict_size: 1024 }).is_err());
+}
+
+#[test]
+fn test_compress_with_flags() {
+    let (flags, compressed) = compress_with_flags(SAMPLE, CHUNK_COMBUF | CHUNK_ZLIB).unwrap();
+    assert_eq!(flags, CHUNK_COMBUF | CHUNK_ZLIB);
+    assert_eq!(decode(&compressed, flags, SAMPLE.len()).unwrap(), SAMPLE);
+
+    // RANDOMACCESS alone stores the data
+    let (flags, stored) = compress_with_flags(SAMPLE, CHUNK_RANDOMACCESS).unwrap();
+    assert_eq!(flags, CHUNK_RANDOMACCESS | CHUNK_COPYCOMP);
+    assert_eq!(stored, SAMPLE);
+
+    // Unknown bits are kept, and the chunk still decodes by its codec bit
+    assert_eq!(UNKNOWN_FLAGS, 0x2 | 0x4000 | 0x8000);
+    let (flags, compressed) = compress_with_flags(SAMPLE, CHUNK_ZLIB | 0x4000).unwrap();
+    assert_eq!(flags, CHUNK_ZLIB | 0x4000);
+    assert_eq!(decode(&compressed, flags, SAMPLE.len()).unwrap(), SAMPLE);
+    let (flags, _) = compress_with_flags(SAMPLE, 0x8000).unwrap();
+    assert_eq!(flags, CHUNK_COPYCOMP | 0x8000);
+    assert_eq!(flag_names(0x8000 | CHUNK_LZMA), ["LZMA", "0x8000"]);
+
+    for flags in [
+        CHUNK_COMBUF,
+        CHUNK_ZLIB | CHUNK_BZIP,
+        CHUNK_DZ,
+        CHUNK_DZ | 0x4000,
+    ] {
+        assert!(
+            matches!(
+                compress_with_flags(SAMPLE, flags),
+                Err(DzipError::InvalidSettings(_))
+            ),
+            "{:#x}",
+            flags
+        );
+    }
+    assert_eq!(
+        encode_flags(&["combuf", "ZLIB", "0x4000"]).unwrap(),
+        CHUNK_COMBUF | CHUNK_ZLIB | 0x4000
+    );
+    assert!(encode_flags(&["FAST"]).is_err());
+}
+
+#[test]
+fn test_xor_key() {
+    let key: XorKey = "0x5A3c".parse().unwrap();
+    assert_eq!(key.as_bytes(), [0x5a, 0x3c]);
+    assert_eq!(key.to_string(), "5a3c");
+    for invalid in ["", "0x", "abc", "zz", "aéb", "+f"] {
+        assert!(invalid.parse::<XorKey>().is_err(), "{:?}", invalid);
+    }
+
+    let mut data = SAMPLE.to_vec();
+    key.apply(&mut data);
+    assert_ne!(data, SAMPLE);
+    // The key restarts per chunk, and apply_at continues it mid-chunk
+    let mut tail = SAMPLE[3..].to_vec();
+    key.apply_at(&mut tail, 3);
+    assert_eq!(tail, data[3..]);
+    let mut written = Vec::new();
+    let mut writer = key.writer(&mut written);
+    std::io::Write::write_all(&mut writer, &SAMPLE[..5]).unwrap();
+    std::io::Write::write_all(&mut writer, &SAMPLE[5..]).unwrap();
+    assert_eq!(written, data);
+
+    // The reader undoes the key before decoding
+    let (flags, mut compressed) = compress_data(SAMPLE, CompressionMethod::Zlib).unwrap();
+    key.apply(&mut compressed);
+    let chunk = Chunk {
+        offset: 0,
+        compressed_length: compressed.len() as u32,
+        decompressed_length: SAMPLE.len() as u32,
+        flags,
+        file: 0,
+    };
+    let mut reader = DzipReader::new(Cursor::new(&compressed));
+    assert!(reader.read_chunk_data(&chunk).is_err());
+    reader.set_xor_key(key);
+    assert_eq!(reader.read_chunk_data(&chunk).unwrap(), SAMPLE);
+}
+
+#[test]
+fn test_read_chunk_data_into_matches_read_chunk_data() {
+    let input = SAMPLE.repeat(500);
+    let mut volumes = MemoryVolumeManager::default();
+    for method in [
+        CompressionMethod::Copy,
+        CompressionMethod::Zero,
+        CompressionMethod::Zlib,
+        CompressionMethod::Gzip,
+        CompressionMethod::Bzip,
+        CompressionMethod::Lzma,
+        CompressionMethod::Xz,
+        CompressionMethod::Brotli,
+    ] {
+        let (flags, compressed) = compress_data(&input, method).unwrap();
+        // Behind a few bytes of padding, to check the seek
+        let mut data = vec![0xAA; 5];
+        data.extend_from_slice(&compressed);
+        let chunk = Chunk {
+            offset: 5,
+            compressed_length: compressed.len() as u32,
+            decompressed_length: input.len() as u32,
+            flags,
+            file: 0,
+        };
+        let mut reader = DzipReader::new(Cursor::new(&data));
+        let mut out = Vec::new();
+        let written = reader
+            .read_chunk_data_into(&chunk, &mut volumes, &mut out)
+            .unwrap();
+        assert_eq!(written, input.len() as u64, "{:?}", method);
+        assert_eq!(out, reader.read_chunk_data(&chunk).unwrap(), "{:?}", method);
+    }
+
+    // A stored chunk cut short fails, as with read_chunk_data
+    let chunk = Chunk {
+        offset: 0,
+        compressed_length: SAMPLE.len() as u32 + 1,
+        decompressed_length: SAMPLE.len() as u32 + 1,
+        flags: CHUNK_COPYCOMP,
+        file: 0,
+    };
+    let mut reader = DzipReader::new(Cursor::new(SAMPLE));
+    assert!(
+        reader
+            .read_chunk_data_into(&chunk, &mut volumes, &mut std::io::sink())
+            .is_err()
+    );
+    assert!(reader.read_chunk_data(&chunk).is_err());
+}
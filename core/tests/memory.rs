@@ -0,0 +1,133 @@
+use dzip_core::progress::ProgressEvent;
+use dzip_core::sink::MemorySink;
+use dzip_core::unpack::{unpack_to_sink, unpack_to_sink_with_progress};
+use dzip_core::volume::MemoryVolumeManager;
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+fn test_data(rel: &str) -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("../test_data");
+    path.push(rel);
+    path
+}
+
+#[test]
+fn test_unpack_split_archive_in_memory() {
+    let main = std::fs::read(test_data("ExampleSplitArchive/testnew.dz")).unwrap();
+    let volumes = ["testnew1.dz", "testnew2.dz", "testnew3.dz"]
+        .iter()
+        .map(|name| std::fs::read(test_data(&format!("ExampleSplitArchive/{}", name))).unwrap())
+        .collect();
+    let mut volumes = MemoryVolumeManager::new(volumes);
+    let sink = MemorySink::new();
+
+    let report = unpack_to_sink(Cursor::new(main), &mut volumes, &sink).unwrap();
+    assert_eq!(report.files.len(), 7);
+    assert_eq!(report.files_ok, 7);
+    assert_eq!(report.chunks_failed, 0);
+    assert!(report.is_complete());
+
+    let files = sink.into_files();
+    assert_eq!(files.len(), 7);
+    for name in [
+        "Image16b.bmp",
+        "BMP/Image4.bmp",
+        "BMP/Image8.bmp",
+        "TXT/Text1.txt",
+        "TXT/Text3.txt",
+    ] {
+        let original = std::fs::read(test_data(&format!("DerbhExampleFiles/{}", name))).unwrap();
+        assert_eq!(files[name], original, "{} differs from the original", name);
+    }
+    assert_eq!(files["TXT/Text2.txt"], vec![0u8; 22]);
+}
+
+#[test]
+fn test_unpack_missing_volume_in_memory() {
+    let main = std::fs::read(test_data("ExampleSplitArchive/testnew.dz")).unwrap();
+    let mut volumes = MemoryVolumeManager::default();
+    let sink = MemorySink::new();
+
+    // Chunks in missing volumes are skipped; files in volume 0 still extract.
+    let report = unpack_to_sink(Cursor::new(main), &mut volumes, &sink).unwrap();
+    assert!(report.files_partial > 0);
+    assert_eq!(report.files_ok + report.files_partial, 7);
+    assert!(report.chunks_failed >= report.files_partial);
+    let original = std::fs::read(test_data("DerbhExampleFiles/Image16b.bmp")).unwrap();
+    assert_eq!(sink.get("Image16b.bmp").unwrap(), original);
+    assert_eq!(sink.get("BMP/Image4.bmp").unwrap(), Vec::<u8>::new());
+}
+
+#[test]
+fn test_unpack_progress_events() {
+    let main = std::fs::read(test_data("ExampleSingleArchive/test1.dz")).unwrap();
+    let mut volumes = MemoryVolumeManager::default();
+    let sink = MemorySink::new();
+
+    let events = Mutex::new(Vec::new());
+    let on_progress = |event: ProgressEvent<'_>| {
+        let event = match event {
+            ProgressEvent::FileStarted { index, path } => {
+                format!("start {} {}", index, path.display())
+            }
+            ProgressEvent::FileCompleted { index, path, stats } => {
+                // The sink already holds the completed file
+                let key: Vec<_> = path.iter().map(|c| c.to_string_lossy()).collect();
+                assert!(sink.get(&key.join("/")).is_some());
+                format!("done {} {}", index, stats.bytes_written)
+            }
+            ProgressEvent::ChunkDecompressed { chunk, bytes } => {
+                format!("chunk {} {}", chunk, bytes)
+            }
+        };
+        events.lock().unwrap().push(event);
+    };
+    let report =
+        unpack_to_sink_with_progress(Cursor::new(main), &mut volumes, &sink, Some(&on_progress))
+            .unwrap();
+    assert_eq!(report.files_ok, 3);
+
+    let events = events.into_inner().unwrap();
+    assert_eq!(events.len(), 9, "{:?}", events);
+    assert_eq!(events[0], "start 0 Image16b.bmp");
+    assert_eq!(events[1], "chunk 0 12342");
+    assert_eq!(events[2], "done 0 12342");
+}
+
+#[test]
+fn test_chunk_cache_decodes_shared_chunks_once() {
+    use dzip_core::unpack::ChunkCache;
+
+    // Chunk 1 is shared by three files, chunk 2 by two; chunk 0 is private
+    let files: [&[u16]; 3] = [&[0, 1], &[1, 2], &[1, 2]];
+    let cache = ChunkCache::new(files, 1024);
+    assert_eq!(cache.shared_chunks(), 2);
+
+    let decodes = Mutex::new(Vec::new());
+    let decode = |id: u16| {
+        decodes.lock().unwrap().push(id);
+        Ok(vec![id as u8; 8])
+    };
+    for chunk_ids in files {
+        for &id in chunk_ids {
+            assert_eq!(
+                *cache.get_or_decode(id, || decode(id)).unwrap(),
+                [id as u8; 8]
+            );
+        }
+    }
+    assert_eq!(*decodes.lock().unwrap(), [0, 1, 2]);
+    // Every shared chunk was read by all of its files and evicted
+    assert_eq!(cache.shared_chunks(), 0);
+
+    // Chunks that do not fit are decoded on every read
+    let cache = ChunkCache::new(files, 4);
+    decodes.lock().unwrap().clear();
+    for &id in files[1] {
+        cache.get_or_decode(id, || decode(id)).unwrap();
+        cache.get_or_decode(id, || decode(id)).unwrap();
+    }
+    assert_eq!(*decodes.lock().unwrap(), [1, 1, 2, 2]);
+}
@@ -160,3 +160,64 @@ fn test_split_archive_parsing() {
         println!("Split Archive Volumes: {:?}", file_list);
     }
 }
+
+#[test]
+fn test_volume_names() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../test_data/ExampleSplitArchive/testnew.dz");
+    let mut reader = DzipReader::new(File::open(path).unwrap());
+    assert!(reader.volume_names().is_empty());
+
+    let header = reader.read_header().unwrap();
+    assert_eq!(
+        reader.volume_names(),
+        ["testnew1.dz", "testnew2.dz", "testnew3.dz"]
+    );
+    assert_eq!(reader.volume_names(), header.volume_files);
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../test_data/ExampleSingleArchive/test1.dz");
+    let mut reader = DzipReader::new(File::open(path).unwrap());
+    reader.read_header().unwrap();
+    assert!(reader.volume_names().is_empty());
+}
+
+#[test]
+fn test_archive_open() {
+    let data_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../test_data");
+    let mut archive =
+        dzip_core::Archive::open(data_dir.join("ExampleSingleArchive/test1.dz")).unwrap();
+    assert!(archive.volumes().is_empty());
+    let paths: Vec<String> = archive.files().map(|f| f.path).collect();
+    assert_eq!(paths.len(), 3);
+
+    // Either separator finds the file
+    let expected = std::fs::read(data_dir.join("DerbhExampleFiles/TXT/Text1.txt")).unwrap();
+    for path in ["TXT/Text1.txt", "TXT\\Text1.txt"] {
+        let mut out = Vec::new();
+        let stats = archive.extract_file(path, &mut out).unwrap();
+        assert_eq!(out, expected);
+        assert_eq!(stats.bytes_written, expected.len() as u64);
+        assert_eq!(stats.chunks_failed, 0);
+    }
+    assert!(matches!(
+        archive.extract_file("TXT/Missing.txt", &mut Vec::new()),
+        Err(dzip_core::DzipError::PathNotFound(_))
+    ));
+
+    // Chunks in the auxiliary volumes are read from the files next to the archive
+    let mut archive =
+        dzip_core::Archive::open(data_dir.join("ExampleSplitArchive/testnew.dz")).unwrap();
+    assert_eq!(
+        archive.volumes(),
+        ["testnew1.dz", "testnew2.dz", "testnew3.dz"]
+    );
+    let files: Vec<(usize, u64)> = archive.files().map(|f| (f.index, f.size)).collect();
+    assert_eq!(files.len(), 7);
+    for (index, size) in files {
+        let mut out = Vec::new();
+        let stats = archive.extract_index(index, &mut out).unwrap();
+        assert_eq!(stats.chunks_failed, 0);
+        assert_eq!(out.len() as u64, size);
+    }
+}
@@ -0,0 +1,38 @@
+use dzip_core::format::*;
+use dzip_core::repair::scan_chunks;
+use dzip_core::{CompressionMethod, compress_data};
+
+const SAMPLE: &[u8] =
+    b"The quick brown fox jumps over the lazy dog. The quick brown fox jumps again.";
+
+#[test]
+fn test_scan_chunks_finds_streams_and_gaps() {
+    let mut data = b"HEADER".to_vec();
+    let mut expected = Vec::new();
+    for method in [
+        CompressionMethod::Zlib,
+        CompressionMethod::Bzip,
+        CompressionMethod::Lzma,
+        CompressionMethod::Xz,
+    ] {
+        let (flags, compressed) = compress_data(SAMPLE, method).unwrap();
+        expected.push((data.len() as u32, compressed.len() as u32, flags));
+        data.extend_from_slice(&compressed);
+    }
+    let tail = data.len() as u32;
+    data.extend_from_slice(b"stored tail");
+
+    let chunks = scan_chunks(&data, 0);
+    assert_eq!(chunks.len(), 6);
+    assert_eq!((chunks[0].offset, chunks[0].flags), (0, CHUNK_COPYCOMP));
+    assert_eq!(chunks[0].compressed_length, 6);
+    for (chunk, &(offset, len, flags)) in chunks[1..5].iter().zip(&expected) {
+        assert_eq!(
+            (chunk.offset, chunk.compressed_length, chunk.flags),
+            (offset, len, flags)
+        );
+        assert_eq!(chunk.decompressed_length as usize, SAMPLE.len());
+    }
+    assert_eq!((chunks[5].offset, chunks[5].flags), (tail, CHUNK_COPYCOMP));
+    assert_eq!(chunks[5].compressed_length, 11);
+}
@@ -1,6 +1,7 @@
+use dzip_core::DzipError;
 use dzip_core::format::*;
-use dzip_core::reader::DzipReader;
-use dzip_core::writer::DzipWriter;
+use dzip_core::reader::{DzipReader, HeaderCrc};
+use dzip_core::writer::{ArchivePlan, DzipWriter, append_header_crc, compute_header_size};
 use std::io::Cursor;
 
 #[test]
@@ -96,3 +97,373 @@ fn test_roundtrip() {
     let read_global_settings = reader.read_global_settings().unwrap();
     assert_eq!(global_settings, read_global_settings);
 }
+
+#[test]
+fn test_big_endian_roundtrip() {
+    let payload = b"big-endian payload".to_vec();
+    let strings = vec!["file.txt".to_string()];
+    let map = vec![(0, vec![0])];
+
+    // Header: 9 + "file.txt\0" (9) + map (6) + chunk settings (4) + chunk table (16)
+    let data_offset = 9 + 9 + 6 + 4 + 16;
+    let chunks = vec![Chunk {
+        offset: data_offset,
+        compressed_length: payload.len() as u32,
+        decompressed_length: payload.len() as u32,
+        flags: CHUNK_COPYCOMP,
+        file: 0,
+    }];
+
+    let mut buffer = Vec::new();
+    {
+        let mut writer = DzipWriter::with_endianness(Cursor::new(&mut buffer), Endianness::Big);
+        writer
+            .write_archive_settings(&ArchiveSettings {
+                header: DTRZ_MAGIC,
+                num_user_files: 1,
+                num_directories: 1,
+                version: 0,
+            })
+            .unwrap();
+        writer.write_strings(&strings).unwrap();
+        writer.write_file_chunk_map(&map).unwrap();
+        writer
+            .write_chunk_settings(&ChunkSettings {
+                num_archive_files: 1,
+                num_chunks: 1,
+            })
+            .unwrap();
+        writer.write_chunks(&chunks).unwrap();
+    }
+    assert_eq!(buffer.len() as u32, data_offset);
+    assert_eq!(&buffer[0..4], b"ZRTD");
+    buffer.extend_from_slice(&payload);
+
+    let mut reader = DzipReader::new(Cursor::new(&buffer));
+    let settings = reader.read_archive_settings().unwrap();
+    assert_eq!(reader.endianness(), Endianness::Big);
+    assert_eq!(settings.header, DTRZ_MAGIC);
+    assert_eq!(settings.num_user_files, 1);
+    assert_eq!(settings.num_directories, 1);
+
+    assert_eq!(reader.read_strings(1).unwrap(), strings);
+    assert_eq!(reader.read_file_chunk_map(1).unwrap(), map);
+    let chunk_settings = reader.read_chunk_settings().unwrap();
+    assert_eq!(chunk_settings.num_chunks, 1);
+    let read_chunks = reader.read_chunks(1).unwrap();
+    assert_eq!(read_chunks, chunks);
+
+    let data = reader.read_chunk_data(&read_chunks[0]).unwrap();
+    assert_eq!(data, payload);
+}
+
+#[test]
+fn test_zero_directories_rejected() {
+    let mut buffer = Vec::new();
+    DzipWriter::new(Cursor::new(&mut buffer))
+        .write_archive_settings(&ArchiveSettings {
+            header: DTRZ_MAGIC,
+            num_user_files: 1,
+            num_directories: 0,
+            version: 0,
+        })
+        .unwrap();
+
+    let mut reader = DzipReader::new(Cursor::new(buffer));
+    match reader.read_archive_settings() {
+        Err(dzip_core::DzipError::InvalidSettings(msg)) => assert!(msg.contains("num_directories")),
+        other => panic!("expected InvalidSettings, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_unknown_version_rejected() {
+    let mut buffer = Vec::new();
+    DzipWriter::new(Cursor::new(&mut buffer))
+        .write_archive_settings(&ArchiveSettings {
+            header: DTRZ_MAGIC,
+            num_user_files: 1,
+            num_directories: 1,
+            version: 3,
+        })
+        .unwrap();
+
+    let mut reader = DzipReader::new(Cursor::new(buffer));
+    assert!(matches!(
+        reader.read_archive_settings(),
+        Err(dzip_core::DzipError::UnsupportedVersion(3))
+    ));
+    assert_eq!(FormatVersion::try_from(0).unwrap(), FormatVersion::V0);
+}
+
+/// Writes a one-file archive with 5 bytes of chunk data; returns the header length.
+fn write_small_archive(buffer: &mut Cursor<Vec<u8>>) -> u64 {
+    let mut writer = DzipWriter::new(&mut *buffer);
+    writer
+        .write_archive_settings(&ArchiveSettings {
+            header: DTRZ_MAGIC,
+            num_user_files: 1,
+            num_directories: 1,
+            version: 0,
+        })
+        .unwrap();
+    writer.write_strings(&["a.txt".to_string()]).unwrap();
+    writer.write_file_chunk_map(&[(0, vec![0])]).unwrap();
+    writer
+        .write_chunk_settings(&ChunkSettings {
+            num_archive_files: 1,
+            num_chunks: 1,
+        })
+        .unwrap();
+    let header_len = 9 + 6 + 6 + 4 + 16;
+    writer
+        .write_chunks(&[Chunk {
+            offset: header_len as u32,
+            compressed_length: 5,
+            decompressed_length: 5,
+            flags: CHUNK_COPYCOMP,
+            file: 0,
+        }])
+        .unwrap();
+    buffer.get_mut().extend_from_slice(b"hello");
+    header_len
+}
+
+fn read_header_crc(data: &[u8]) -> dzip_core::Result<HeaderCrc> {
+    let mut reader = DzipReader::new(Cursor::new(data));
+    let settings = reader.read_archive_settings().unwrap();
+    reader.read_strings(settings.num_strings()).unwrap();
+    reader.read_file_chunk_map(1).unwrap();
+    reader.read_chunk_settings().unwrap();
+    reader.read_chunks(1).unwrap();
+    let result = reader.verify_header_crc();
+    // The reader is left at the end of the chunk table
+    assert_eq!(reader.position().unwrap(), 41);
+    result
+}
+
+#[test]
+fn test_header_crc() {
+    let mut buffer = Cursor::new(Vec::new());
+    let header_len = write_small_archive(&mut buffer);
+    assert_eq!(
+        read_header_crc(buffer.get_ref()).unwrap(),
+        HeaderCrc::Absent
+    );
+
+    let crc = append_header_crc(&mut buffer, header_len).unwrap();
+    let data = buffer.into_inner();
+    assert_eq!(data.len() as u64, header_len + 5 + HEADER_CRC_TRAILER_SIZE);
+    let header_crc = read_header_crc(&data).unwrap();
+    assert_eq!(header_crc, HeaderCrc::Valid(crc));
+    assert_eq!(header_crc.trailer_size(), HEADER_CRC_TRAILER_SIZE);
+
+    // Chunk data is not covered, a flipped bit in the chunk table is
+    let mut data_changed = data.clone();
+    data_changed[header_len as usize] = b'j';
+    assert!(read_header_crc(&data_changed).is_ok());
+    let mut corrupt = data;
+    corrupt[30] ^= 0x10;
+    assert!(matches!(
+        read_header_crc(&corrupt),
+        Err(dzip_core::DzipError::HeaderCorrupt { expected, .. }) if expected == crc
+    ));
+}
+
+#[test]
+fn test_header_roundtrip() {
+    let header = ArchiveHeader {
+        settings: ArchiveSettings {
+            header: DTRZ_MAGIC,
+            num_user_files: 3,
+            num_directories: 3,
+            version: 0,
+        },
+        strings: vec![
+            "root.txt".to_string(),
+            "a.bin".to_string(),
+            "b.bin".to_string(),
+            "data".to_string(),
+            "data\\sub\\".to_string(),
+        ],
+        map: vec![(0, vec![0]), (1, vec![1, 2]), (2, vec![])],
+        chunk_settings: ChunkSettings {
+            num_archive_files: 2,
+            num_chunks: 3,
+        },
+        chunks: (0..3)
+            .map(|i| Chunk {
+                offset: i * 10,
+                compressed_length: 10,
+                decompressed_length: 10,
+                flags: if i == 2 { CHUNK_DZ } else { CHUNK_COPYCOMP },
+                file: (i == 2) as u16,
+            })
+            .collect(),
+        volume_files: vec!["archive.d01".to_string()],
+        range_settings: Some(RangeSettings {
+            win_size: 16,
+            ..RangeSettings::default()
+        }),
+    };
+
+    let mut buffer = Cursor::new(Vec::new());
+    DzipWriter::new(&mut buffer).write_header(&header).unwrap();
+    let header_len = buffer.get_ref().len() as u64;
+    let plan = ArchivePlan {
+        strings: &header.strings,
+        num_files: header.map.len(),
+        num_chunks: header.chunks.len(),
+        volume_files: &header.volume_files,
+        range_settings: true,
+    };
+    assert_eq!(compute_header_size(&plan), header_len);
+
+    let mut reader = DzipReader::new(Cursor::new(buffer.into_inner()));
+    assert_eq!(reader.read_header().unwrap(), header);
+    assert_eq!(reader.position().unwrap(), header_len);
+    // Volume list (12 bytes) and range settings (10 bytes) follow the chunk table
+    assert_eq!(reader.chunk_table_end(), Some(header_len - 22));
+    assert_eq!(reader.verify_header_crc().unwrap(), HeaderCrc::Absent);
+
+    assert_eq!(header.directory(0), Some(""));
+    assert_eq!(header.directory(2), Some("data\\sub\\"));
+    assert_eq!(header.directory(3), None);
+    assert_eq!(header.file_path(0).unwrap(), "root.txt");
+    assert_eq!(header.file_path(1).unwrap(), "data\\a.bin");
+    assert_eq!(header.file_path(2).unwrap(), "data\\sub\\b.bin");
+    assert!(matches!(
+        header.file_path(3),
+        Err(DzipError::FileNotFound { index: 3, count: 3 })
+    ));
+    assert_eq!(header.file_decompressed_size(0).unwrap(), 10);
+    assert_eq!(header.file_decompressed_size(1).unwrap(), 20);
+    assert_eq!(header.file_decompressed_size(2).unwrap(), 0);
+    assert!(matches!(
+        header.file_decompressed_size(3),
+        Err(DzipError::FileNotFound { index: 3, count: 3 })
+    ));
+}
+
+#[test]
+fn test_read_header_rejects_bad_names() {
+    let header_with = |strings: Vec<&str>| ArchiveHeader {
+        settings: ArchiveSettings {
+            header: DTRZ_MAGIC,
+            num_user_files: 2,
+            num_directories: 2,
+            version: 0,
+        },
+        strings: strings.into_iter().map(String::from).collect(),
+        map: vec![(0, vec![]), (1, vec![])],
+        chunk_settings: ChunkSettings {
+            num_archive_files: 1,
+            num_chunks: 0,
+        },
+        chunks: Vec::new(),
+        volume_files: Vec::new(),
+        range_settings: None,
+    };
+    let read = |header: &ArchiveHeader| {
+        let mut buffer = Cursor::new(Vec::new());
+        DzipWriter::new(&mut buffer).write_header(header).unwrap();
+        DzipReader::new(Cursor::new(buffer.into_inner())).read_header()
+    };
+
+    // Separators are fine in directories, mixed or not
+    assert!(read(&header_with(vec!["a.txt", "b.txt", "data\\sub/"])).is_ok());
+
+    for (strings, bad_index) in [
+        (vec!["a.txt", "b\u{1}.txt", "data"], 1),
+        (vec!["a.txt", "b.txt", "da\tta"], 2),
+        (vec!["sub\\a.txt", "b.txt", "data"], 0),
+        (vec!["a.txt", "sub/b.txt", "data"], 1),
+    ] {
+        match read(&header_with(strings)) {
+            Err(dzip_core::DzipError::InvalidName { index, .. }) => assert_eq!(index, bad_index),
+            other => panic!("expected InvalidName, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn test_read_header_rejects_bad_volume_names() {
+    let read = |volume_files: &[&str]| {
+        let header = ArchiveHeader {
+            settings: ArchiveSettings {
+                header: DTRZ_MAGIC,
+                num_user_files: 1,
+                num_directories: 1,
+                version: 0,
+            },
+            strings: vec!["a.txt".to_string()],
+            map: vec![(0, vec![])],
+            chunk_settings: ChunkSettings {
+                num_archive_files: volume_files.len() as u16 + 1,
+                num_chunks: 0,
+            },
+            chunks: Vec::new(),
+            volume_files: volume_files.iter().map(|s| s.to_string()).collect(),
+            range_settings: None,
+        };
+        let mut buffer = Cursor::new(Vec::new());
+        DzipWriter::new(&mut buffer).write_header(&header).unwrap();
+        DzipReader::new(Cursor::new(buffer.into_inner())).read_header()
+    };
+
+    assert!(read(&["game.d01", "game.d02"]).is_ok());
+    for (names, bad_volume) in [
+        (vec!["game.d01", ""], 2),
+        (vec!["../game.d01"], 1),
+        (vec!["data\\game.d01"], 1),
+        (vec![".."], 1),
+        (vec!["game.d01", "game.d02", "game.d01"], 3),
+    ] {
+        match read(&names) {
+            Err(dzip_core::DzipError::InvalidVolumeName { volume, .. }) => {
+                assert_eq!(volume, bad_volume, "{:?}", names)
+            }
+            other => panic!("expected InvalidVolumeName, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn test_read_header_truncated() {
+    let header = ArchiveHeader {
+        settings: ArchiveSettings {
+            header: DTRZ_MAGIC,
+            num_user_files: 1,
+            num_directories: 1,
+            version: 0,
+        },
+        strings: vec!["a.txt".to_string()],
+        map: vec![(0, vec![0])],
+        chunk_settings: ChunkSettings {
+            num_archive_files: 1,
+            num_chunks: 1,
+        },
+        chunks: vec![Chunk {
+            offset: 0,
+            compressed_length: 0,
+            decompressed_length: 0,
+            flags: CHUNK_COPYCOMP,
+            file: 0,
+        }],
+        volume_files: Vec::new(),
+        range_settings: None,
+    };
+    let mut buffer = Cursor::new(Vec::new());
+    DzipWriter::new(&mut buffer).write_header(&header).unwrap();
+    let mut data = buffer.into_inner();
+    data.truncate(data.len() - 3);
+
+    assert!(matches!(
+        DzipReader::new(Cursor::new(data)).read_header(),
+        Err(dzip_core::DzipError::HeaderTruncated)
+    ));
+    assert!(matches!(
+        header.chunk(1),
+        Err(dzip_core::DzipError::ChunkNotFound { id: 1, count: 1 })
+    ));
+}
@@ -0,0 +1,104 @@
+use dzip_core::sink::{FileSystemSink, TarSink, UnpackSink, ZipSink};
+use std::io::Write;
+use std::path::Path;
+
+#[test]
+fn test_atomic_sink_renames_on_finish() {
+    let tmp = tempfile::tempdir().unwrap();
+    let sink = FileSystemSink::new(tmp.path()).atomic(true);
+    sink.create_dir_all(Path::new("dir")).unwrap();
+
+    let mut out = sink.create_file(Path::new("dir/a.txt")).unwrap();
+    out.write_all(b"comp").unwrap();
+    // A flush in the middle of the file does not publish it
+    out.flush().unwrap();
+    assert!(!tmp.path().join("dir/a.txt").exists());
+    assert_eq!(
+        std::fs::read(tmp.path().join("dir/a.txt.partial")).unwrap(),
+        b"comp"
+    );
+    out.write_all(b"lete").unwrap();
+
+    out.finish().unwrap();
+    assert_eq!(
+        std::fs::read(tmp.path().join("dir/a.txt")).unwrap(),
+        b"complete"
+    );
+    assert!(!tmp.path().join("dir/a.txt.partial").exists());
+}
+
+#[test]
+fn test_atomic_sink_discards_unfinished_files() {
+    let tmp = tempfile::tempdir().unwrap();
+    std::fs::write(tmp.path().join("a.txt"), b"previous").unwrap();
+
+    let sink = FileSystemSink::new(tmp.path()).atomic(true);
+    let mut out = sink.create_file(Path::new("a.txt")).unwrap();
+    out.write_all(b"interrupted").unwrap();
+    out.flush().unwrap();
+    drop(out);
+    // The existing file is untouched and no partial file is left behind
+    assert_eq!(
+        std::fs::read(tmp.path().join("a.txt")).unwrap(),
+        b"previous"
+    );
+    assert!(!tmp.path().join("a.txt.partial").exists());
+
+    let sink = sink.keep_partial(true);
+    let mut out = sink.create_file(Path::new("b.txt")).unwrap();
+    out.write_all(b"interrupted").unwrap();
+    out.flush().unwrap();
+    drop(out);
+    assert!(!tmp.path().join("b.txt").exists());
+    assert_eq!(
+        std::fs::read(tmp.path().join("b.txt.partial")).unwrap(),
+        b"interrupted"
+    );
+}
+
+#[test]
+fn test_tar_sink_appends_entries() {
+    let sink = TarSink::new(Vec::new());
+    sink.create_dir_all(Path::new("dir/sub")).unwrap();
+    sink.create_dir_all(Path::new("dir")).unwrap();
+    let mut out = sink.create_file(Path::new("dir/sub/a.txt")).unwrap();
+    out.write_all(b"tarred").unwrap();
+    out.finish().unwrap();
+    // An entry dropped without `finish` is left out
+    let mut out = sink.create_file(Path::new("dir/b.txt")).unwrap();
+    out.write_all(b"unfinished").unwrap();
+    out.flush().unwrap();
+    drop(out);
+
+    let data = sink.finish().unwrap();
+    let mut archive = tar::Archive::new(data.as_slice());
+    let mut entries = Vec::new();
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let mut body = String::new();
+        std::io::Read::read_to_string(&mut entry, &mut body).unwrap();
+        entries.push((entry.path().unwrap().display().to_string(), body));
+    }
+    assert_eq!(
+        entries,
+        [
+            ("dir/".to_string(), String::new()),
+            ("dir/sub/".to_string(), String::new()),
+            ("dir/sub/a.txt".to_string(), "tarred".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_zip_sink_appends_entries() {
+    let sink = ZipSink::new(std::io::Cursor::new(Vec::new()));
+    let mut out = sink.create_file(Path::new("dir/a.txt")).unwrap();
+    out.write_all(b"zipped").unwrap();
+    out.finish().unwrap();
+
+    let data = sink.finish().unwrap();
+    let mut archive = zip::ZipArchive::new(data).unwrap();
+    let mut body = String::new();
+    std::io::Read::read_to_string(&mut archive.by_name("dir/a.txt").unwrap(), &mut body).unwrap();
+    assert_eq!(body, "zipped");
+}
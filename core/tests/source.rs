@@ -0,0 +1,170 @@
+use dzip_core::DzipError;
+use dzip_core::format::Chunk;
+use dzip_core::reader::{DzipReader, SFX_SCAN_BLOCK, VolumeSource, locate_archive};
+use dzip_core::source::{ArchiveSource, OffsetReader, SharedFile};
+use dzip_core::volume::SharedVolumes;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+#[test]
+fn test_shared_file_readers_are_independent() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("data.bin");
+    let data: Vec<u8> = (0..=255).collect();
+    std::fs::write(&path, &data).unwrap();
+
+    let shared = SharedFile::open(&path).unwrap();
+    assert_eq!(shared.len(), 256);
+
+    std::thread::scope(|scope| {
+        for start in [0u64, 64, 128, 192] {
+            let shared = &shared;
+            scope.spawn(move || {
+                let mut reader = shared.reader().unwrap();
+                reader.seek(SeekFrom::Start(start)).unwrap();
+                let mut buf = [0u8; 64];
+                reader.read_exact(&mut buf).unwrap();
+                assert_eq!(buf[0], start as u8);
+                assert_eq!(buf[63], start as u8 + 63);
+            });
+        }
+    });
+
+    let mut reader = shared.reader().unwrap();
+    assert_eq!(reader.seek(SeekFrom::End(-1)).unwrap(), 255);
+    assert!(reader.seek(SeekFrom::Current(-300)).is_err());
+}
+
+#[test]
+fn test_shared_file_mapped() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("data.bin");
+    let data: Vec<u8> = (0..=255).collect();
+    std::fs::write(&path, &data).unwrap();
+
+    let mapped = SharedFile::open_mapped(&path).unwrap();
+    assert!(mapped.is_mapped());
+    assert_eq!(mapped.len(), 256);
+    assert!(!SharedFile::open(&path).unwrap().is_mapped());
+
+    let mut reader = mapped.reader().unwrap();
+    reader.seek(SeekFrom::Start(200)).unwrap();
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, &data[200..]);
+    assert!(reader.seek(SeekFrom::Current(-300)).is_err());
+}
+
+#[test]
+fn test_shared_volumes_from_memory() {
+    let volumes = SharedVolumes::new(vec![b"volume one".to_vec()]);
+    let chunk = Chunk {
+        offset: 7,
+        compressed_length: 3,
+        decompressed_length: 3,
+        flags: dzip_core::format::CHUNK_COPYCOMP,
+        file: 1,
+    };
+
+    let main: &[u8] = b"";
+    let mut reader = DzipReader::new(main.reader().unwrap());
+    let mut readers = volumes.readers();
+    let data = reader
+        .read_chunk_data_with_volumes(&chunk, &mut readers)
+        .unwrap();
+    assert_eq!(data, b"one");
+
+    assert!(matches!(
+        readers.open_volume(2),
+        Err(DzipError::VolumeNotFound(2))
+    ));
+}
+
+#[test]
+fn test_shared_volumes_missing_file() {
+    let tmp = tempfile::tempdir().unwrap();
+    std::fs::write(tmp.path().join("present.d01"), b"data").unwrap();
+    let volumes = SharedVolumes::open(
+        tmp.path(),
+        &["present.d01".to_string(), "absent.d02".to_string()],
+    );
+    let mut readers = volumes.readers();
+    assert!(readers.open_volume(1).is_ok());
+    match readers.open_volume(2) {
+        Err(DzipError::MissingVolume { volume, name }) => {
+            assert_eq!((volume, name.as_str()), (2, "absent.d02"))
+        }
+        other => panic!("expected MissingVolume, got {:?}", other.err()),
+    }
+}
+
+/// A stub that contains the magic once without an archive behind it.
+fn sfx_stub() -> Vec<u8> {
+    let mut stub = b"MZ self-extractor ".repeat(40);
+    stub.extend_from_slice(b"DTRZ\0\0\0\0\0 not an archive");
+    stub
+}
+
+#[test]
+fn test_locate_archive_after_stub() {
+    let archive = std::fs::read("../test_data/ExampleSingleArchive/test1.dz").unwrap();
+    assert_eq!(locate_archive(Cursor::new(&archive)).unwrap(), 0);
+
+    let mut data = sfx_stub();
+    let base = data.len() as u64;
+    data.extend_from_slice(&archive);
+    assert_eq!(locate_archive(Cursor::new(&data)).unwrap(), base);
+
+    let mut reader = DzipReader::new(OffsetReader::new(Cursor::new(&data), base).unwrap());
+    let header = reader.read_header().unwrap();
+    let expected = DzipReader::new(Cursor::new(&archive))
+        .read_header()
+        .unwrap();
+    assert_eq!(header.chunks, expected.chunks);
+    let chunk = &header.chunks[0];
+    assert_eq!(
+        reader.read_chunk_data(chunk).unwrap(),
+        DzipReader::new(Cursor::new(&archive))
+            .read_chunk_data(chunk)
+            .unwrap()
+    );
+
+    // Positions before the archive are out of reach
+    let mut offset_reader = OffsetReader::new(Cursor::new(&data), base).unwrap();
+    assert!(offset_reader.seek(SeekFrom::Current(-1)).is_err());
+    assert_eq!(offset_reader.stream_position().unwrap(), 0);
+
+    // No archive at all leaves the base at 0
+    assert_eq!(locate_archive(Cursor::new(sfx_stub())).unwrap(), 0);
+}
+
+#[test]
+fn test_locate_archive_across_scan_blocks() {
+    let archive = std::fs::read("../test_data/ExampleSingleArchive/test1.dz").unwrap();
+    // The scan starts at offset 1, so blocks begin at 1, 1 + SFX_SCAN_BLOCK - 3, ...
+    // and a magic at SFX_SCAN_BLOCK - 1 straddles the first two blocks
+    for base in [SFX_SCAN_BLOCK - 1, SFX_SCAN_BLOCK + 7] {
+        let mut data = vec![0u8; base];
+        data.extend_from_slice(&archive);
+        assert_eq!(locate_archive(Cursor::new(&data)).unwrap(), base as u64);
+    }
+}
+
+#[test]
+fn test_shared_file_with_base() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("data.bin");
+    let data: Vec<u8> = (0..=255).collect();
+    std::fs::write(&path, &data).unwrap();
+
+    for shared in [
+        SharedFile::open(&path).unwrap(),
+        SharedFile::open_mapped(&path).unwrap(),
+    ] {
+        let shared = shared.with_base(100).unwrap();
+        assert_eq!(shared.len(), 156);
+        let mut buf = Vec::new();
+        shared.reader().unwrap().read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, &data[100..]);
+    }
+    assert!(SharedFile::open(&path).unwrap().with_base(257).is_err());
+}
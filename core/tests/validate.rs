@@ -0,0 +1,164 @@
+use dzip_core::format::*;
+use dzip_core::validate::{
+    LayoutIssue, VolumeSize, VolumeSizeStatus, check_chunk_layout, check_volume_sizes,
+    missing_chunks,
+};
+use std::collections::HashMap;
+
+fn chunk(offset: u32, length: u32, flags: u16, file: u16) -> Chunk {
+    Chunk {
+        offset,
+        compressed_length: length,
+        decompressed_length: length * 2,
+        flags,
+        file,
+    }
+}
+
+#[test]
+fn test_contiguous_layout_is_clean() {
+    let chunks = vec![
+        chunk(120, 30, CHUNK_ZLIB, 0),
+        chunk(100, 20, CHUNK_COPYCOMP, 0),
+        chunk(120, 0, CHUNK_COPYCOMP, 0),
+        chunk(0, 0, CHUNK_ZERO, 0),
+        chunk(0, 50, CHUNK_BZIP, 1),
+    ];
+    let sizes = HashMap::from([(0, 150), (1, 50)]);
+    assert!(check_chunk_layout(&chunks, &sizes).is_empty());
+}
+
+#[test]
+fn test_layout_issues() {
+    let mut placeholder = chunk(200, 0, CHUNK_ZLIB, 0);
+    placeholder.compressed_length = 500;
+    placeholder.decompressed_length = 500;
+    let chunks = vec![
+        chunk(100, 30, CHUNK_ZLIB, 0),
+        chunk(120, 30, CHUNK_ZLIB, 0),
+        chunk(160, 40, CHUNK_ZLIB, 0),
+        placeholder,
+        chunk(0, 10, CHUNK_COPYCOMP, 1),
+        chunk(0, 10, CHUNK_COPYCOMP, 1),
+        chunk(10, 100, CHUNK_COPYCOMP, 1),
+    ];
+    let sizes = HashMap::from([(0, 250), (1, 50)]);
+
+    let issues = check_chunk_layout(&chunks, &sizes);
+    assert_eq!(
+        issues,
+        vec![
+            LayoutIssue::Overlap {
+                file: 0,
+                chunk: 0,
+                next: 1
+            },
+            LayoutIssue::Gap {
+                file: 0,
+                chunk: 1,
+                next: 2,
+                size: 10
+            },
+            LayoutIssue::Overlap {
+                file: 1,
+                chunk: 4,
+                next: 5
+            },
+            LayoutIssue::OutOfBounds {
+                file: 1,
+                chunk: 6,
+                end: 110,
+                volume_size: 50
+            },
+        ]
+    );
+    assert!(issues[0].is_error());
+    assert!(!issues[1].is_error());
+}
+
+#[test]
+fn test_missing_chunks_in_truncated_volume() {
+    let mut placeholder = chunk(130, 0, CHUNK_ZLIB, 0);
+    placeholder.compressed_length = 40;
+    placeholder.decompressed_length = 40;
+    let chunks = vec![
+        chunk(100, 30, CHUNK_ZLIB, 0),
+        placeholder,
+        chunk(160, 20, CHUNK_COPYCOMP, 0),
+        chunk(0, 0, CHUNK_ZERO, 0),
+        chunk(0, 10, CHUNK_COPYCOMP, 2),
+    ];
+    // Volume 0 was cut inside the placeholder chunk; volume 2 is absent
+    let sizes = HashMap::from([(0, 150)]);
+    assert_eq!(
+        missing_chunks(&chunks, &sizes)
+            .into_iter()
+            .collect::<Vec<_>>(),
+        vec![1, 2, 4]
+    );
+
+    let sizes = HashMap::from([(0, 180), (2, 10)]);
+    assert!(missing_chunks(&chunks, &sizes).is_empty());
+
+    // A placeholder chunk at the end of its volume has no data left at all
+    let chunks = &chunks[..2];
+    let sizes = HashMap::from([(0, 130)]);
+    assert_eq!(
+        missing_chunks(chunks, &sizes)
+            .into_iter()
+            .collect::<Vec<_>>(),
+        vec![1]
+    );
+    let sizes = HashMap::from([(0, 131)]);
+    assert!(missing_chunks(chunks, &sizes).is_empty());
+}
+
+#[test]
+fn test_volume_sizes() {
+    let mut placeholder = chunk(130, 0, CHUNK_ZLIB, 2);
+    placeholder.compressed_length = 40;
+    placeholder.decompressed_length = 40;
+    let chunks = vec![
+        chunk(100, 30, CHUNK_ZLIB, 0),
+        chunk(130, 20, CHUNK_COPYCOMP, 0),
+        chunk(0, 0, CHUNK_ZERO, 0),
+        chunk(0, 50, CHUNK_BZIP, 1),
+        chunk(0, 130, CHUNK_COPYCOMP, 2),
+        placeholder,
+        chunk(0, 10, CHUNK_COPYCOMP, 3),
+    ];
+    let sizes = HashMap::from([(0, 150), (1, 60), (2, 200)]);
+    let volumes = check_volume_sizes(&chunks, &sizes);
+    assert_eq!(
+        volumes[2],
+        VolumeSize {
+            file: 2,
+            actual: Some(200),
+            expected: 130,
+            exact: false,
+        }
+    );
+    assert_eq!(
+        volumes.iter().map(VolumeSize::status).collect::<Vec<_>>(),
+        vec![
+            VolumeSizeStatus::Ok,
+            VolumeSizeStatus::ExtraData,
+            VolumeSizeStatus::Ok,
+            VolumeSizeStatus::Missing,
+        ]
+    );
+
+    let sizes = HashMap::from([(0, 140), (1, 50), (2, 120), (3, 10)]);
+    assert_eq!(
+        check_volume_sizes(&chunks, &sizes)
+            .iter()
+            .map(VolumeSize::status)
+            .collect::<Vec<_>>(),
+        vec![
+            VolumeSizeStatus::Truncated,
+            VolumeSizeStatus::Ok,
+            VolumeSizeStatus::Truncated,
+            VolumeSizeStatus::Ok,
+        ]
+    );
+}